@@ -1,9 +1,14 @@
+use errors::{AuthError, CustomError};
 use lib_config::config::configuration;
+use lib_config::session::redis::RedisService;
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use anyhow::Context;
+
+use super::permissions::{has_permissions, Permission};
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Claims {
     pub sub: String,
@@ -12,7 +17,16 @@ pub struct Claims {
     pub iat: usize,
     pub nfb: usize,
     pub sid: String,
-    pub role: Role
+    pub role: Role,
+    /// Permissions granted to admin tokens, user tokens carry none
+    #[serde(default)]
+    pub scopes: Vec<String>
+}
+
+impl Claims {
+    pub fn has_permissions(&self, required: &[Permission]) -> bool {
+        has_permissions(&self.scopes, required)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -24,7 +38,7 @@ pub enum Role {
 /******************************************/
 // Creating JWT token
 /******************************************/
-pub fn create_jwt(user_id: &str, role: Role) -> Result<(String, String), anyhow::Error> {
+pub fn create_jwt(user_id: &str, role: Role, scopes: Vec<String>) -> Result<(String, String), anyhow::Error> {
     let config = configuration::Settings::new()
         .context("Failed to get config")?;    
     let expiration_time = (Utc::now() + Duration::hours(1)).timestamp() as usize;
@@ -38,7 +52,8 @@ pub fn create_jwt(user_id: &str, role: Role) -> Result<(String, String), anyhow:
         iat: issued_at,
         nfb: not_before,
         sid: sid.clone(),
-        role
+        role,
+        scopes
     };
 
     // let secret = env::var("JWT_SECRET").expect("Jwt secret not found");
@@ -78,4 +93,73 @@ pub fn verify_jwt(token: &str) -> Result<Claims, String> {
     }
     let sid = &token_data.claims.sid;
     Ok(token_data.claims)
-}
\ No newline at end of file
+}
+
+/// Minutes a user has to enter the second factor after their password was accepted
+pub const MFA_CHALLENGE_MINUTES: i64 = 5;
+
+/// Issued instead of a session token when the account has two-factor authentication, it
+/// can't be used as a bearer token since it has another issuer and no session
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MfaChallengeClaims {
+    pub sub: String,
+    pub exp: usize,
+    pub iss: String,
+    pub iat: usize,
+    pub jti: String,
+    pub role: Role
+}
+
+/******************************************/
+// Two-factor login challenge
+/******************************************/
+pub fn create_mfa_challenge(user_id: &str, role: Role) -> Result<(String, String), anyhow::Error> {
+    let config = configuration::Settings::new()
+        .context("Failed to get config")?;
+    let issued_at = Utc::now().timestamp() as usize;
+    let jti = Uuid::new_v4().to_string();
+    let claims = MfaChallengeClaims {
+        sub: user_id.to_string(),
+        exp: (Utc::now() + Duration::minutes(MFA_CHALLENGE_MINUTES)).timestamp() as usize,
+        iss: "auth-mfa".to_string(),
+        iat: issued_at,
+        jti: jti.clone(),
+        role
+    };
+
+    let encoding_key = EncodingKey::from_secret(config.jwt.secret.as_ref());
+    let token = encode(&Header::default(), &claims, &encoding_key)
+        .context("Failed to encode mfa challenge")?;
+
+    Ok((token, jti))
+}
+
+/// Redis key holding a pending challenge, taken when its second factor is accepted so the
+/// challenge token only ever yields one session
+pub fn mfa_challenge_key(jti: &str) -> String {
+    format!("mfa_challenge:{}", jti)
+}
+
+/// Takes the pending challenge with GETDEL, a replayed or concurrently reused challenge token
+/// finds nothing left and is refused
+pub async fn consume_mfa_challenge(redis_service: &RedisService, challenge: &MfaChallengeClaims) -> Result<(), CustomError> {
+    let pending = redis_service.take_value(&mfa_challenge_key(&challenge.jti)).await?;
+    if pending.as_deref() != Some(challenge.sub.as_str()) {
+        return Err(AuthError::InvalidCredentials(anyhow::anyhow!("Challenge already used, log in again")).into());
+    }
+    Ok(())
+}
+
+pub fn verify_mfa_challenge(token: &str, role: Role) -> Result<MfaChallengeClaims, String> {
+    let config = configuration::Settings::new().expect("Failed to load configurations");
+    let decoding_key = DecodingKey::from_secret(config.jwt.secret.as_ref());
+    let mut validation = Validation::default();
+    validation.set_issuer(&["auth-mfa"]);
+    let token_data =
+        decode::<MfaChallengeClaims>(token, &decoding_key, &validation).map_err(|err| err.to_string())?;
+
+    if token_data.claims.role != role {
+        return Err("Invalid role".to_string());
+    }
+    Ok(token_data.claims)
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A single action an admin token may perform, carried in the JWT as a scope string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize, ToSchema)]
+pub enum Permission {
+    #[serde(rename = "games:read")]
+    GamesRead,
+    #[serde(rename = "games:write")]
+    GamesWrite,
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:delete")]
+    UsersDelete,
+    #[serde(rename = "users:suspend")]
+    UsersSuspend,
+    #[serde(rename = "moderation:read")]
+    ModerationRead,
+    #[serde(rename = "moderation:decide")]
+    ModerationDecide,
+    #[serde(rename = "search:maintain")]
+    SearchMaintain,
+    #[serde(rename = "roles:manage")]
+    RolesManage,
+    #[serde(rename = "analytics:read")]
+    AnalyticsRead,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 10] = [
+        Permission::GamesRead,
+        Permission::GamesWrite,
+        Permission::UsersRead,
+        Permission::UsersDelete,
+        Permission::UsersSuspend,
+        Permission::ModerationRead,
+        Permission::ModerationDecide,
+        Permission::SearchMaintain,
+        Permission::RolesManage,
+        Permission::AnalyticsRead,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::GamesRead => "games:read",
+            Permission::GamesWrite => "games:write",
+            Permission::UsersRead => "users:read",
+            Permission::UsersDelete => "users:delete",
+            Permission::UsersSuspend => "users:suspend",
+            Permission::ModerationRead => "moderation:read",
+            Permission::ModerationDecide => "moderation:decide",
+            Permission::SearchMaintain => "search:maintain",
+            Permission::RolesManage => "roles:manage",
+            Permission::AnalyticsRead => "analytics:read",
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Permission {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Permission::ALL
+            .into_iter()
+            .find(|permission| permission.as_str() == s)
+            .ok_or(format!("{} is not a known permission.", s))
+    }
+}
+
+/// True when every required permission is present in the token scopes
+pub fn has_permissions(scopes: &[String], required: &[Permission]) -> bool {
+    required
+        .iter()
+        .all(|permission| scopes.iter().any(|scope| scope == permission.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_permissions, Permission};
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn scopes_round_trip_through_strings() {
+        for permission in Permission::ALL {
+            assert_eq!(permission.as_str().parse::<Permission>(), Ok(permission));
+        }
+        assert_ok!("roles:manage".parse::<Permission>());
+        assert_err!("games:delete".parse::<Permission>());
+    }
+
+    #[test]
+    fn every_required_permission_must_be_granted() {
+        let scopes = vec!["games:read".to_string(), "games:write".to_string()];
+        assert!(has_permissions(&scopes, &[Permission::GamesRead, Permission::GamesWrite]));
+        assert!(!has_permissions(&scopes, &[Permission::GamesRead, Permission::UsersRead]));
+        assert!(has_permissions(&[], &[]));
+    }
+}
@@ -0,0 +1,44 @@
+use std::net::IpAddr;
+
+/// Address of the client behind a request. `X-Forwarded-For` is only believed when the peer is
+/// one of the `trusted_proxies` (the gateway, which overwrites the header with its own peer);
+/// anyone else could put any address there to dodge lockouts or alerts.
+pub fn client_ip(peer: Option<IpAddr>, forwarded_for: Option<&str>, trusted_proxies: &[IpAddr]) -> Option<String> {
+    let peer = peer?;
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer.to_string());
+    }
+    // The rightmost entry is the one the proxy added itself
+    let forwarded = forwarded_for
+        .and_then(|header| header.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse::<IpAddr>().ok());
+    Some(forwarded.unwrap_or(peer).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::client_ip;
+    use std::net::IpAddr;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_header_is_used_from_a_trusted_proxy() {
+        let client = client_ip(Some(ip("10.0.0.2")), Some("203.0.113.7"), &[ip("10.0.0.2")]);
+        assert_eq!(client.as_deref(), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn forwarded_header_is_ignored_from_other_peers() {
+        let client = client_ip(Some(ip("198.51.100.1")), Some("203.0.113.7"), &[ip("10.0.0.2")]);
+        assert_eq!(client.as_deref(), Some("198.51.100.1"));
+    }
+
+    #[test]
+    fn invalid_forwarded_header_falls_back_to_the_peer() {
+        let client = client_ip(Some(ip("10.0.0.2")), Some("not-an-ip"), &[ip("10.0.0.2")]);
+        assert_eq!(client.as_deref(), Some("10.0.0.2"));
+    }
+}
@@ -0,0 +1,68 @@
+/******************************************/
+// CSV export
+/******************************************/
+/// A row that can be written as one CSV record, `HEADER` names the columns in `fields` order
+pub trait CsvRecord {
+    const HEADER: &'static [&'static str];
+
+    fn fields(&self) -> Vec<String>;
+}
+
+/// Header line then one line per row, fields holding a comma, quote or line break are quoted
+pub fn to_csv<T: CsvRecord>(rows: &[T]) -> String {
+    let mut out = String::new();
+    write_record(&mut out, T::HEADER.iter().copied());
+    for row in rows {
+        let fields = row.fields();
+        write_record(&mut out, fields.iter().map(String::as_str));
+    }
+    out
+}
+
+fn write_record<'a>(out: &mut String, fields: impl Iterator<Item = &'a str>) {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_csv, CsvRecord};
+
+    struct Row(&'static str, i64);
+
+    impl CsvRecord for Row {
+        const HEADER: &'static [&'static str] = &["name", "count"];
+
+        fn fields(&self) -> Vec<String> {
+            vec![self.0.to_string(), self.1.to_string()]
+        }
+    }
+
+    #[test]
+    fn rows_follow_the_header() {
+        let csv = to_csv(&[Row("zelda", 3), Row("doom", 0)]);
+        assert_eq!(csv, "name,count\r\nzelda,3\r\ndoom,0\r\n");
+    }
+
+    #[test]
+    fn special_characters_are_quoted() {
+        let csv = to_csv(&[Row("say \"hi\", twice", 1)]);
+        assert_eq!(csv, "name,count\r\n\"say \"\"hi\"\", twice\",1\r\n");
+    }
+
+    #[test]
+    fn empty_exports_still_have_a_header() {
+        assert_eq!(to_csv::<Row>(&[]), "name,count\r\n");
+    }
+}
@@ -1,2 +1,8 @@
 pub mod auth_jwt;
 pub mod validations;
+pub mod openapi;
+pub mod mfa;
+pub mod oidc;
+pub mod pagination;
+pub mod csv;
+pub mod client_ip;
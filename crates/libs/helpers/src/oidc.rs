@@ -0,0 +1,195 @@
+use data_encoding::{BASE64, BASE64URL_NOPAD};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub use jsonwebtoken::jwk::JwkSet;
+
+/******************************************/
+// Authorization request values
+/******************************************/
+/// Random value for `state`, `nonce` or a PKCE code verifier (RFC 7636 allows 43 to 128 characters)
+pub fn random_token() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect()
+}
+
+/// `S256` code challenge sent with the authorization request for the verifier
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    BASE64URL_NOPAD.encode(&Sha256::digest(code_verifier.as_bytes()))
+}
+
+/******************************************/
+// ID token
+/******************************************/
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+    pub name: Option<String>,
+}
+
+/// Checks the signature against the provider's keys, the issuer, audience, expiry and the
+/// nonce of the authorization request
+pub fn verify_id_token(
+    id_token: &str,
+    jwks: &JwkSet,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+) -> Result<IdTokenClaims, String> {
+    let header = decode_header(id_token).map_err(|e| format!("Invalid ID token header: {}", e))?;
+    // Only asymmetric algorithms, the provider's public keys must never be used as an HMAC secret
+    if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+        return Err("ID token signed with a symmetric algorithm".to_string());
+    }
+    let jwk = match header.kid.as_deref() {
+        Some(kid) => jwks.find(kid),
+        None => jwks.keys.first(),
+    }
+    .ok_or("No provider key matches the ID token")?;
+    let key = DecodingKey::from_jwk(jwk).map_err(|e| format!("Unusable provider key: {}", e))?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+        .map_err(|e| format!("Invalid ID token: {}", e))?
+        .claims;
+
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err("ID token nonce doesn't match the authorization request".to_string());
+    }
+    Ok(claims)
+}
+
+/// SHA-256 hex of an opaque token, tokens are random enough that a fast hash is fine
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// `(client_id, client_secret)` of an `Authorization: Basic ...` header value
+pub fn parse_basic_auth(header: &str) -> Option<(String, String)> {
+    let encoded = header.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(BASE64.decode(encoded.trim().as_bytes()).ok()?).ok()?;
+    let (client_id, client_secret) = decoded.split_once(':')?;
+    Some((client_id.to_string(), client_secret.to_string()))
+}
+
+/******************************************/
+// Signing our own ID tokens
+/******************************************/
+/// Ed25519 key the authorization server signs ID tokens with, published as a JWK
+pub struct IdTokenSigner {
+    encoding_key: EncodingKey,
+    public_key: Vec<u8>,
+    kid: String,
+}
+
+/// Public part of the signing key as served from the JWKS endpoint
+#[derive(Debug, Serialize)]
+pub struct PublicJwk {
+    pub kty: &'static str,
+    pub crv: &'static str,
+    pub x: String,
+    pub kid: String,
+    pub alg: &'static str,
+    #[serde(rename = "use")]
+    pub key_use: &'static str,
+}
+
+impl IdTokenSigner {
+    /// New random key as a base64 PKCS#8 document, the format `from_pkcs8_base64` reads
+    pub fn new_pkcs8_base64() -> String {
+        let der = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).expect("Failed to generate a signing key");
+        BASE64.encode(der.as_ref())
+    }
+
+    pub fn from_pkcs8_base64(encoded: &str) -> Result<Self, String> {
+        let der = BASE64
+            .decode(encoded.trim().as_bytes())
+            .map_err(|e| format!("Signing key is not valid base64: {}", e))?;
+        Self::from_pkcs8(&der)
+    }
+
+    /// New random key, tokens it signs stop verifying once the process restarts
+    pub fn generate() -> Self {
+        Self::from_pkcs8_base64(&Self::new_pkcs8_base64()).expect("Generated signing key is valid")
+    }
+
+    fn from_pkcs8(der: &[u8]) -> Result<Self, String> {
+        let key_pair = Ed25519KeyPair::from_pkcs8(der).map_err(|e| format!("Invalid Ed25519 signing key: {}", e))?;
+        let public_key = key_pair.public_key().as_ref().to_vec();
+        let kid = hash_token(&BASE64URL_NOPAD.encode(&public_key))[..16].to_string();
+        Ok(Self {
+            encoding_key: EncodingKey::from_ed_der(der),
+            public_key,
+            kid,
+        })
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(self.kid.clone());
+        encode(&header, claims, &self.encoding_key)
+    }
+
+    pub fn public_jwk(&self) -> PublicJwk {
+        PublicJwk {
+            kty: "OKP",
+            crv: "Ed25519",
+            x: BASE64URL_NOPAD.encode(&self.public_key),
+            kid: self.kid.clone(),
+            alg: "EdDSA",
+            key_use: "sig",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pkce_challenge, IdTokenSigner};
+    use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+    use serde::{Deserialize, Serialize};
+
+    #[test]
+    fn pkce_challenge_matches_the_rfc_7636_example() {
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    #[test]
+    fn signed_tokens_verify_with_the_published_key() {
+        let signer = IdTokenSigner::generate();
+        let token = signer
+            .sign(&TestClaims { sub: "user".to_string(), exp: 4_000_000_000 })
+            .unwrap();
+
+        assert_eq!(decode_header(&token).unwrap().kid, Some(signer.public_jwk().kid));
+        let key = DecodingKey::from_ed_der(&signer.public_key);
+        let claims = decode::<TestClaims>(&token, &key, &Validation::new(Algorithm::EdDSA)).unwrap().claims;
+        assert_eq!(claims.sub, "user");
+    }
+}
@@ -0,0 +1,17 @@
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::Modify;
+
+/******************************************/
+// Registering JWT bearer security scheme
+/******************************************/
+/// Adds the `bearer_auth` scheme referenced by protected handlers
+pub struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        let mut scheme = Http::new(HttpAuthScheme::Bearer);
+        scheme.bearer_format = Some("JWT".to_string());
+        components.add_security_scheme("bearer_auth", SecurityScheme::Http(scheme));
+    }
+}
@@ -0,0 +1,47 @@
+use data_encoding::BASE64URL_NOPAD;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/******************************************/
+// Keyset pagination cursors
+/******************************************/
+/// Opaque cursor handed to clients: the sort key of the last row of a page, JSON encoded
+/// then base64url so it can travel in a query string untouched
+pub fn encode_cursor<T: Serialize>(position: &T) -> String {
+    let json = serde_json::to_vec(position).expect("cursor positions always serialize");
+    BASE64URL_NOPAD.encode(&json)
+}
+
+/// Reads a cursor produced by `encode_cursor`, `None` when it was tampered with or belongs
+/// to another listing
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> Option<T> {
+    let json = BASE64URL_NOPAD.decode(cursor.as_bytes()).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_cursor, encode_cursor};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Position {
+        Name(String, u32),
+        Count(i64, u32),
+    }
+
+    #[test]
+    fn cursors_round_trip_and_are_url_safe() {
+        let position = Position::Name("ünïcode & spaces?".to_string(), 7);
+        let cursor = encode_cursor(&position);
+
+        assert!(cursor.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(decode_cursor::<Position>(&cursor), Some(position));
+    }
+
+    #[test]
+    fn garbage_cursors_are_rejected() {
+        assert_eq!(decode_cursor::<Position>("not a cursor"), None);
+        assert_eq!(decode_cursor::<Position>(&encode_cursor(&"just a string")), None);
+    }
+}
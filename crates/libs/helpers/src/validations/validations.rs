@@ -3,6 +3,7 @@ use unicode_segmentation::UnicodeSegmentation;
 use serde::{Deserialize, Serialize};
 use argon2::{self, password_hash::SaltString};
 use errors::CustomError;
+use utoipa::ToSchema;
 
 #[derive(Debug)]
 pub struct UserName(String);
@@ -51,7 +52,62 @@ impl AsRef<str> for UserEmail {
     }
 }
 
-#[derive(Deserialize)]
+const MAX_GAME_LABELS: usize = 20;
+const MAX_GAME_LABEL_LENGTH: usize = 50;
+
+/// Tags or platforms of a game, trimmed, lowercased and without duplicates.
+/// Commas are rejected since search takes them as comma separated lists.
+#[derive(Debug)]
+pub struct GameLabels(Vec<String>);
+
+impl GameLabels {
+    pub fn parse(labels: Vec<String>, kind: &str) -> std::result::Result<GameLabels, CustomError> {
+        let mut parsed: Vec<String> = Vec::with_capacity(labels.len());
+        for label in labels {
+            let label = label.trim().to_lowercase();
+            let is_too_long = label.graphemes(true).count() > MAX_GAME_LABEL_LENGTH;
+            if label.is_empty() || is_too_long || label.contains(',') {
+                return Err(CustomError::ValidationError(format!("{:?} is not a valid {}.", label, kind)));
+            }
+            if !parsed.contains(&label) {
+                parsed.push(label);
+            }
+        }
+
+        if parsed.len() > MAX_GAME_LABELS {
+            return Err(CustomError::ValidationError(format!("A game can't have more than {} {}s.", MAX_GAME_LABELS, kind)));
+        }
+        Ok(Self(parsed))
+    }
+
+    pub fn into_inner(self) -> Vec<String> {
+        self.0
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageUrl(String);
+
+impl ImageUrl {
+    pub fn parse(s: String) -> std::result::Result<ImageUrl, CustomError> {
+        let url_regex = Regex::new(r"^https?://[^\s/?#]+\.[^\s/?#]+(/\S*)?$")
+        .map_err(|e| CustomError::ValidationError(format!("Invalid regex: {}", e)))?;
+
+        if s.len() <= 2048 && url_regex.is_match(&s) {
+            Ok(Self(s))
+        } else {
+            Err(CustomError::ValidationError(format!("{} is not a valid image url.", s)))
+        }
+    }
+}
+
+impl AsRef<str> for ImageUrl {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
 pub struct CreateUserBody {
     pub username: String,
     pub password: String,
@@ -64,7 +120,7 @@ impl CreateUserBody {
         Ok((user_name, user_email))
     }
 }
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct UpdateUserBody {
     pub username: String,
     pub email: String,
@@ -78,7 +134,7 @@ impl UpdateUserBody {
 }
 
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct LoginUserBody {
     pub email: String,
     pub password: String,
@@ -115,7 +171,7 @@ pub fn check_password_strength(password: &str) -> Result<(), CustomError> {
 }
 #[cfg(test)]
 mod tests {
-    use super::{UserEmail, UserName};
+    use super::{GameLabels, ImageUrl, UserEmail, UserName};
     use claim::{assert_err, assert_ok};
 
     #[test]
@@ -171,4 +227,19 @@ mod tests {
         let email = "@gmail.com".to_string();
         assert_err!(UserEmail::parse(email));
     }
+
+    #[test]
+    fn game_labels_are_normalized_and_deduplicated() {
+        let labels = vec![" Co-op ".to_string(), "co-op".to_string(), "PC".to_string()];
+        assert_eq!(GameLabels::parse(labels, "tag").unwrap().into_inner(), vec!["co-op", "pc"]);
+        assert_err!(GameLabels::parse(vec!["rpg, action".to_string()], "tag"));
+        assert_err!(GameLabels::parse(vec![" ".to_string()], "platform"));
+    }
+
+    #[test]
+    fn only_http_image_urls_are_accepted() {
+        assert_ok!(ImageUrl::parse("https://cdn.example.com/covers/witcher.png".to_string()));
+        assert_err!(ImageUrl::parse("javascript:alert(1)".to_string()));
+        assert_err!(ImageUrl::parse("https://".to_string()));
+    }
 }
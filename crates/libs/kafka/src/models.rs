@@ -25,6 +25,14 @@ pub enum UserEventType{
         time: NaiveDateTime
     },
 
+    /// Wrong password for an existing account, `locked` when it caused a lockout
+    LoginFailed{
+        ip: Option<String>,
+        user_agent: Option<String>,
+        locked: bool,
+        time: NaiveDateTime
+    },
+
     Rate{
         rating: i32,
         game_slug: String,
@@ -34,11 +42,195 @@ pub enum UserEventType{
     Update{
         username: String,
         email: String
+    },
+
+    /// The user proved they own their email, by the mailed link or through an identity provider
+    EmailVerified{
+        time: NaiveDateTime
+    },
+
+    UpdateRating{
+        rating: i32,
+        game_slug: String,
+        time: Option<NaiveDateTime>
+    },
+
+    DeleteRating{
+        game_slug: String,
+        time: Option<NaiveDateTime>
+    },
+
+    AddToList{
+        list_id: uuid::Uuid,
+        list_kind: GameListKind,
+        game_slug: String,
+        time: NaiveDateTime
+    },
+
+    RemoveFromList{
+        list_id: uuid::Uuid,
+        list_kind: GameListKind,
+        game_slug: String,
+        time: NaiveDateTime
+    },
+
+    /// The account was deactivated, suspended, scheduled for deletion, reactivated or erased
+    StatusChange{
+        status: AccountStatus,
+        time: NaiveDateTime
+    },
+
+    /// Every service removes or anonymises what it holds about the user, then answers with an
+    /// `ErasureEventsMessage`. Sent again until all have answered, handlers must be idempotent.
+    Erase{
+        erasure_id: uuid::Uuid,
+        time: NaiveDateTime
+    },
+
+    /// Every service sends what it holds about the user back as an `ExportEventsMessage`.
+    /// Sent again until all have answered, answering twice is harmless.
+    Export{
+        export_id: uuid::Uuid,
+        time: NaiveDateTime
     }
 }
 
+/// Lifecycle of an account, owned by user_service and mirrored by the other services
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum AccountStatus {
+    Active,
+    /// Deactivated by the user, logging in again reactivates it
+    Deactivated,
+    /// Deactivated by an admin, logins are refused until an admin reactivates it
+    Suspended,
+    /// The user asked for deletion, logging in during the grace period cancels it
+    PendingDeletion,
+    Erased
+}
+
+/// Built-in lists every user has, and lists they named themselves
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum GameListKind {
+    Wishlist,
+    Playing,
+    Completed,
+    Custom
+}
+
 impl KafkaTopic for UserEventsMessage {
     fn topic_name(&self) -> String {
         return "user_events".to_string()
     }
 }
+
+/// Account actions admins ask user_service for, it owns the accounts
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminEventsMessage {
+    pub user_id: uuid::Uuid,
+    pub action: AdminUserAction,
+    pub admin_id: uuid::Uuid,
+    pub time: NaiveDateTime
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum AdminUserAction {
+    Suspend,
+    Reactivate,
+    Erase
+}
+
+impl KafkaTopic for AdminEventsMessage {
+    fn topic_name(&self) -> String {
+        return "admin_events".to_string()
+    }
+}
+
+/// A service finished its part of an erasure
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ErasureEventsMessage {
+    pub erasure_id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub service: String,
+    pub time: NaiveDateTime
+}
+
+impl KafkaTopic for ErasureEventsMessage {
+    fn topic_name(&self) -> String {
+        return "erasure_events".to_string()
+    }
+}
+
+/// A service's part of a data export, `data` becomes `<service>.json` in the archive
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExportEventsMessage {
+    pub export_id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub service: String,
+    pub data: serde_json::Value,
+    pub time: NaiveDateTime
+}
+
+impl KafkaTopic for ExportEventsMessage {
+    fn topic_name(&self) -> String {
+        return "export_events".to_string()
+    }
+}
+
+/// Why a review was sent to the moderation queue
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ModerationReason {
+    WordFilter {
+        words: Vec<String>
+    },
+
+    UserFlag {
+        flagged_by: uuid::Uuid,
+        reason: Option<String>
+    },
+
+    /// A rejected or hidden review was edited and needs another decision
+    Edited
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReviewEventsMessage {
+    pub review_id: uuid::Uuid,
+    pub game_slug: String,
+    pub user_id: uuid::Uuid,
+    pub rating: i32,
+    pub review: String,
+    pub reason: ModerationReason,
+    /// Identifies the rating and text the moderator sees, sent back with the decision
+    pub content_hash: String,
+    pub time: NaiveDateTime
+}
+
+impl KafkaTopic for ReviewEventsMessage {
+    fn topic_name(&self) -> String {
+        return "review_events".to_string()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ModerationDecision {
+    Approve,
+    Reject,
+    Hide
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ModerationEventsMessage {
+    pub review_id: uuid::Uuid,
+    pub decision: ModerationDecision,
+    pub moderator_id: uuid::Uuid,
+    /// `content_hash` of the queued review the decision was made on
+    #[serde(default)]
+    pub content_hash: String,
+    pub time: NaiveDateTime
+}
+
+impl KafkaTopic for ModerationEventsMessage {
+    fn topic_name(&self) -> String {
+        return "moderation_events".to_string()
+    }
+}
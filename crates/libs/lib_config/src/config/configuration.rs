@@ -1,5 +1,6 @@
 use config::{Config, ConfigError};
 use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 #[derive(Debug, Deserialize)]
 pub struct DatabaseSettings {
@@ -47,14 +48,444 @@ pub struct KafkaSettings {
     pub game_subscribe_topics: Vec<String>,
     pub game_consumer_group: String,
     pub user_subscribe_topics: Vec<String>,
+    /// Only the gateway reads these, cached routes aren't invalidated while they are unset
+    #[serde(default)]
+    pub gateway_url: Option<String>,
+    #[serde(default)]
+    pub gateway_consumer_group: Option<String>,
 }
 #[derive(Debug, Deserialize)]
 pub struct MailSettings {
     pub mail_domain: String,
     pub user: String,
     pub api_key: String,
-    pub mail_url: String
+    pub mail_url: String,
+    /// Every mail goes to this address instead of its recipient, for sandboxed Mailgun domains
+    #[serde(default)]
+    pub redirect_to: Option<String>
 }
+
+/// Reviews containing any of `blocked_words` (case insensitive, whole words) wait for an admin decision
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ModerationSettings {
+    #[serde(default)]
+    pub blocked_words: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchBackend {
+    #[default]
+    Elasticsearch,
+    /// Games kept in game_service memory and rebuilt from Postgres at startup, for tests and small deployments
+    Memory,
+}
+
+/// Game search of game_service, `drift_check_interval_secs = 0` disables the periodic drift check
+#[derive(Debug, Deserialize, Clone)]
+pub struct SearchSettings {
+    #[serde(default)]
+    pub backend: SearchBackend,
+    #[serde(default = "default_search_url")]
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Alias the game endpoints read and write through
+    #[serde(default = "default_search_index")]
+    pub index: String,
+    #[serde(default = "default_drift_check_interval")]
+    pub drift_check_interval_secs: u64,
+}
+
+impl Default for SearchSettings {
+    fn default() -> Self {
+        Self {
+            backend: SearchBackend::default(),
+            url: default_search_url(),
+            username: None,
+            password: None,
+            index: default_search_index(),
+            drift_check_interval_secs: default_drift_check_interval(),
+        }
+    }
+}
+
+fn default_search_url() -> String {
+    "http://127.0.0.1:9200".to_string()
+}
+
+fn default_search_index() -> String {
+    "rate".to_string()
+}
+
+fn default_drift_check_interval() -> u64 {
+    900
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheBackend {
+    #[default]
+    Disabled,
+    Memory,
+    Redis,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct GatewayCacheSettings {
+    #[serde(default)]
+    pub backend: CacheBackend,
+    #[serde(default = "default_cache_capacity")]
+    pub capacity: usize,
+}
+
+impl Default for GatewayCacheSettings {
+    fn default() -> Self {
+        Self {
+            backend: CacheBackend::default(),
+            capacity: default_cache_capacity(),
+        }
+    }
+}
+
+fn default_cache_capacity() -> usize {
+    1000
+}
+
+/// Which parts of a request make up its cache key
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheKeyRule {
+    Path,
+    #[default]
+    PathQuery,
+    PathQueryUser,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RouteCacheSettings {
+    pub ttl_secs: u64,
+    #[serde(default)]
+    pub key: CacheKeyRule,
+    /// Kafka topics whose messages drop every cached entry of this route
+    #[serde(default)]
+    pub invalidate_on: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CorsSettings {
+    /// Allowed origins, `*` allows any origin
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_cors_methods")]
+    pub allowed_methods: Vec<String>,
+    #[serde(default = "default_cors_headers")]
+    pub allowed_headers: Vec<String>,
+    #[serde(default)]
+    pub allow_credentials: bool,
+    #[serde(default = "default_cors_max_age")]
+    pub max_age_secs: u64,
+}
+
+fn default_cors_methods() -> Vec<String> {
+    ["GET", "POST", "PUT", "PATCH", "DELETE"].iter().map(|m| m.to_string()).collect()
+}
+
+fn default_cors_headers() -> Vec<String> {
+    ["Authorization", "Content-Type"].iter().map(|h| h.to_string()).collect()
+}
+
+fn default_cors_max_age() -> u64 {
+    3600
+}
+
+/// Gateway wide defaults, routes can override the limits and CORS policy
+#[derive(Debug, Deserialize, Clone)]
+pub struct GatewayPolicySettings {
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    #[serde(default = "default_max_header_bytes")]
+    pub max_header_bytes: usize,
+    /// `Strict-Transport-Security` max-age, 0 disables the header
+    #[serde(default = "default_hsts_max_age")]
+    pub hsts_max_age_secs: u64,
+    #[serde(default)]
+    pub cors: Option<CorsSettings>,
+}
+
+impl Default for GatewayPolicySettings {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: default_max_body_bytes(),
+            max_header_bytes: default_max_header_bytes(),
+            hsts_max_age_secs: default_hsts_max_age(),
+            cors: None,
+        }
+    }
+}
+
+fn default_max_body_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_max_header_bytes() -> usize {
+    16 * 1024
+}
+
+fn default_hsts_max_age() -> u64 {
+    31_536_000
+}
+
+/// Policy for requests forwarded to `service` whose endpoint starts with `path_prefix`
+#[derive(Debug, Deserialize, Clone)]
+pub struct GatewayRouteSettings {
+    pub name: String,
+    pub service: String,
+    #[serde(default)]
+    pub path_prefix: String,
+    #[serde(default)]
+    pub require_auth: bool,
+    #[serde(default)]
+    pub cache: Option<RouteCacheSettings>,
+    #[serde(default)]
+    pub cors: Option<CorsSettings>,
+    #[serde(default)]
+    pub max_body_bytes: Option<usize>,
+    #[serde(default)]
+    pub max_header_bytes: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct GatewaySettings {
+    #[serde(default)]
+    pub cache: GatewayCacheSettings,
+    #[serde(default)]
+    pub policy: GatewayPolicySettings,
+    #[serde(default)]
+    pub routes: Vec<GatewayRouteSettings>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AuthSettings {
+    /// Admins without two-factor authentication can only log in to enrol it
+    #[serde(default)]
+    pub admin_mfa_required: bool,
+    /// Name authenticator apps show next to the account
+    #[serde(default = "default_mfa_issuer")]
+    pub mfa_issuer: String,
+    #[serde(default)]
+    pub lockout: LockoutSettings,
+    /// Identity providers users can sign in with, by `name`
+    #[serde(default)]
+    pub oidc_providers: Vec<OidcProviderSettings>,
+    /// Peers allowed to set `X-Forwarded-For`, the gateway's addresses
+    #[serde(default = "default_trusted_proxies")]
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        Self {
+            admin_mfa_required: false,
+            mfa_issuer: default_mfa_issuer(),
+            lockout: LockoutSettings::default(),
+            oidc_providers: Vec::new(),
+            trusted_proxies: default_trusted_proxies(),
+        }
+    }
+}
+
+fn default_trusted_proxies() -> Vec<IpAddr> {
+    vec![IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)]
+}
+
+fn default_mfa_issuer() -> String {
+    "Rate".to_string()
+}
+
+/// OpenID Connect provider, its endpoints come from `{issuer}/.well-known/openid-configuration`
+#[derive(Debug, Deserialize, Clone)]
+pub struct OidcProviderSettings {
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: String,
+    /// Where the provider sends the browser back, the page calls the callback route with `code` and `state`
+    pub redirect_uri: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    ["openid", "email", "profile"].iter().map(|scope| scope.to_string()).collect()
+}
+
+/// Failed logins are counted per account and per client IP; from the threshold on every
+/// further failure locks the login for twice as long as the previous one
+#[derive(Debug, Deserialize, Clone)]
+pub struct LockoutSettings {
+    #[serde(default = "default_account_threshold")]
+    pub account_threshold: u32,
+    #[serde(default = "default_ip_threshold")]
+    pub ip_threshold: u32,
+    #[serde(default = "default_base_lock_secs")]
+    pub base_lock_secs: u64,
+    #[serde(default = "default_max_lock_secs")]
+    pub max_lock_secs: u64,
+    /// How long failures are remembered after the first one, a successful login resets the account count
+    #[serde(default = "default_failure_window_secs")]
+    pub failure_window_secs: u64,
+}
+
+impl Default for LockoutSettings {
+    fn default() -> Self {
+        Self {
+            account_threshold: default_account_threshold(),
+            ip_threshold: default_ip_threshold(),
+            base_lock_secs: default_base_lock_secs(),
+            max_lock_secs: default_max_lock_secs(),
+            failure_window_secs: default_failure_window_secs(),
+        }
+    }
+}
+
+fn default_account_threshold() -> u32 {
+    5
+}
+
+fn default_ip_threshold() -> u32 {
+    20
+}
+
+fn default_base_lock_secs() -> u64 {
+    60
+}
+
+fn default_max_lock_secs() -> u64 {
+    3600
+}
+
+fn default_failure_window_secs() -> u64 {
+    86_400
+}
+
+/// user_service as an OAuth2 / OpenID Connect authorization server
+#[derive(Debug, Deserialize, Clone)]
+pub struct OAuthSettings {
+    /// Public URL of user_service, e.g. through the gateway, the discovery document lives under it
+    #[serde(default = "default_oauth_issuer")]
+    pub issuer: String,
+    /// Frontend page that logs the user in, shows the consent screen and calls
+    /// `/user/protected/oauth/authorize`, advertised as the authorization endpoint
+    #[serde(default = "default_authorization_page")]
+    pub authorization_page: String,
+    /// Base64 PKCS#8 Ed25519 key signing ID tokens, `user_service generate-signing-key` prints one.
+    /// Without it a key is generated at startup and ID tokens stop verifying after a restart.
+    #[serde(default)]
+    pub signing_key: Option<String>,
+    #[serde(default = "default_access_token_minutes")]
+    pub access_token_minutes: i64,
+    #[serde(default = "default_refresh_token_days")]
+    pub refresh_token_days: i64,
+    #[serde(default = "default_authorization_code_secs")]
+    pub authorization_code_secs: usize,
+}
+
+impl Default for OAuthSettings {
+    fn default() -> Self {
+        Self {
+            issuer: default_oauth_issuer(),
+            authorization_page: default_authorization_page(),
+            signing_key: None,
+            access_token_minutes: default_access_token_minutes(),
+            refresh_token_days: default_refresh_token_days(),
+            authorization_code_secs: default_authorization_code_secs(),
+        }
+    }
+}
+
+fn default_oauth_issuer() -> String {
+    "http://localhost:8000/user".to_string()
+}
+
+fn default_authorization_page() -> String {
+    "http://localhost:3000/oauth/authorize".to_string()
+}
+
+fn default_access_token_minutes() -> i64 {
+    60
+}
+
+fn default_refresh_token_days() -> i64 {
+    30
+}
+
+fn default_authorization_code_secs() -> usize {
+    60
+}
+
+/// Deactivation and erasure of user accounts
+#[derive(Debug, Deserialize, Clone)]
+pub struct AccountSettings {
+    /// Days between a deletion request and the erasure, logging in meanwhile cancels it
+    #[serde(default = "default_deletion_grace_days")]
+    pub deletion_grace_days: i64,
+    /// How often user_service starts due erasures and resends unconfirmed ones
+    #[serde(default = "default_erasure_sweep_secs")]
+    pub erasure_sweep_secs: u64,
+    /// Minutes without every confirmation before an erasure is sent again
+    #[serde(default = "default_erasure_retry_minutes")]
+    pub erasure_retry_minutes: i64,
+    /// Services that hold user data and must confirm an erasure
+    #[serde(default = "default_erasure_services")]
+    pub erasure_services: Vec<String>,
+    /// Services whose data goes into a data export, the archive is built once all have sent it
+    #[serde(default = "default_export_services")]
+    pub export_services: Vec<String>,
+    /// Hours the emailed download link of a data export works
+    #[serde(default = "default_export_link_hours")]
+    pub export_link_hours: i64,
+}
+
+impl Default for AccountSettings {
+    fn default() -> Self {
+        Self {
+            deletion_grace_days: default_deletion_grace_days(),
+            erasure_sweep_secs: default_erasure_sweep_secs(),
+            erasure_retry_minutes: default_erasure_retry_minutes(),
+            erasure_services: default_erasure_services(),
+            export_services: default_export_services(),
+            export_link_hours: default_export_link_hours(),
+        }
+    }
+}
+
+fn default_deletion_grace_days() -> i64 {
+    30
+}
+
+fn default_erasure_sweep_secs() -> u64 {
+    300
+}
+
+fn default_erasure_retry_minutes() -> i64 {
+    15
+}
+
+fn default_erasure_services() -> Vec<String> {
+    vec!["admin_service".to_string(), "game_service".to_string()]
+}
+
+fn default_export_services() -> Vec<String> {
+    default_erasure_services()
+}
+
+fn default_export_link_hours() -> i64 {
+    48
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Settings {
     pub service: ServiceSettings,
@@ -63,7 +494,19 @@ pub struct Settings {
     pub jwt: JwtSettings,
     pub kafka: KafkaSettings,
     pub domain: DomainSettings,
-    pub mail: MailSettings
+    pub mail: MailSettings,
+    #[serde(default)]
+    pub gateway: GatewaySettings,
+    #[serde(default)]
+    pub moderation: ModerationSettings,
+    #[serde(default)]
+    pub search: SearchSettings,
+    #[serde(default)]
+    pub auth: AuthSettings,
+    #[serde(default)]
+    pub oauth: OAuthSettings,
+    #[serde(default)]
+    pub account: AccountSettings,
 }
 
 // impl Settings {
@@ -14,9 +14,6 @@ struct MailgunPayload {
 pub async fn send_email(to: &str, mail_token: String) -> Result<(), CustomError> {
     let config = configuration::Settings::new().expect("Failed to load configurations");
 
-    let domain = config.mail.mail_domain; 
-    let api_key = config.mail.api_key;  
-
     let verification_link = format!(
         "{}/user/api/v1/users/verify-email?token={}",
         config.mail.mail_url,
@@ -26,19 +23,86 @@ pub async fn send_email(to: &str, mail_token: String) -> Result<(), CustomError>
 
     let body= &format!("Click the link to verify your email: {}", verification_link);
 
+    deliver(&config.mail, to, subject, body).await
+}
+
+/// Sends the invitation token an admin needs to register
+pub async fn send_admin_invitation(to: &str, invitation_token: &str, valid_days: i64) -> Result<(), CustomError> {
+    let config = configuration::Settings::new().expect("Failed to load configurations");
+
+    let subject = "You have been invited as an admin";
+    let body = format!(
+        "Register at {}/admin/api/v1/admins/register with this invitation token within {} days: {}",
+        config.mail.mail_url,
+        valid_days,
+        invitation_token
+    );
+
+    deliver(&config.mail, to, subject, &body).await
+}
+
+/// Tells the user about a sign-in from a device or network their account hasn't used before
+pub async fn send_login_alert(to: &str, device: &str, ip: &str, time: &str) -> Result<(), CustomError> {
+    let config = configuration::Settings::new().expect("Failed to load configurations");
+
+    let subject = "New sign-in to your account";
+    let body = format!(
+        "Your account was signed in to from a new device or location.\n\nDevice: {}\nIP address: {}\nTime: {} UTC\n\nIf this wasn't you, change your password and enable two-factor authentication.",
+        device,
+        ip,
+        time
+    );
+
+    deliver(&config.mail, to, subject, &body).await
+}
+
+/// Confirms a deletion request and how to cancel it during the grace period
+pub async fn send_deletion_scheduled(to: &str, scheduled_for: &str) -> Result<(), CustomError> {
+    let config = configuration::Settings::new().expect("Failed to load configurations");
+
+    let subject = "Your account will be deleted";
+    let body = format!(
+        "We received a request to delete your account. It will be erased on {} UTC, together with your ratings, reviews and lists.\n\nLog in before then to keep your account.",
+        scheduled_for
+    );
+
+    deliver(&config.mail, to, subject, &body).await
+}
+
+/// Sends the download link of a finished data export
+pub async fn send_export_ready(to: &str, export_id: &str, download_token: &str, valid_hours: i64) -> Result<(), CustomError> {
+    let config = configuration::Settings::new().expect("Failed to load configurations");
+
+    let download_link = format!(
+        "{}/user/api/v1/users/exports/{}?token={}",
+        config.mail.mail_url,
+        export_id,
+        download_token
+    );
+    let subject = "Your data export is ready";
+    let body = format!(
+        "The copy of your data you asked for is ready. Download it within {} hours: {}\n\nIf you didn't ask for it, change your password.",
+        valid_hours,
+        download_link
+    );
+
+    deliver(&config.mail, to, subject, &body).await
+}
+
+async fn deliver(mail: &configuration::MailSettings, to: &str, subject: &str, body: &str) -> Result<(), CustomError> {
     let client = Client::new();
-    let url = format!("https://api.mailgun.net/v3/{}/messages", domain);
-    let from= format!("{}@{}", config.mail.user, domain);
+    let url = format!("https://api.mailgun.net/v3/{}/messages", mail.mail_domain);
+    let from= format!("{}@{}", mail.user, mail.mail_domain);
     let payload = MailgunPayload {
         from: from.to_string(),
-        to: "kashish@rapidinnovation.dev".to_string(),
+        to: mail.redirect_to.as_deref().unwrap_or(to).to_string(),
         subject: subject.to_string(),
         text: body.to_string(),
     };
 
     let res = client
         .post(&url)
-        .basic_auth("api", Some(api_key)) 
+        .basic_auth("api", Some(&mail.api_key)) 
         .form(&payload)
         .send()
         .await;
@@ -46,8 +110,8 @@ pub async fn send_email(to: &str, mail_token: String) -> Result<(), CustomError>
     match res {
         Ok(response) if response.status().is_success() => Ok(()),
         Ok(response) => {
-            Err(CustomError::UnexpectedError(anyhow::anyhow!("Failed to send mail")))
+            Err(CustomError::UnexpectedError(anyhow::anyhow!("Failed to send mail: {}", response.status())))
         }
-        Err(err) => Err(CustomError::UnexpectedError(anyhow::anyhow!("Failed to send mail"))),
+        Err(err) => Err(CustomError::UnexpectedError(anyhow::anyhow!("Failed to send mail: {}", err))),
     }
 }
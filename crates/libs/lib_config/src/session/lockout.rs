@@ -0,0 +1,82 @@
+use actix_web::http::StatusCode;
+use errors::CustomError;
+
+use crate::config::configuration::LockoutSettings;
+use crate::session::redis::RedisService;
+
+/// Tracks failed logins of one kind of account, `scope` keeps users and admins apart
+pub struct LoginLockout<'a> {
+    redis: &'a RedisService,
+    settings: &'a LockoutSettings,
+    scope: &'static str,
+}
+
+impl<'a> LoginLockout<'a> {
+    pub fn new(redis: &'a RedisService, settings: &'a LockoutSettings, scope: &'static str) -> Self {
+        Self { redis, settings, scope }
+    }
+
+    fn account_key(&self, kind: &str, email: &str) -> String {
+        format!("login_{}:{}:account:{}", kind, self.scope, email.trim().to_lowercase())
+    }
+
+    fn ip_key(&self, kind: &str, ip: &str) -> String {
+        format!("login_{}:{}:ip:{}", kind, self.scope, ip)
+    }
+
+    /// Refuses the attempt with `429` while the account or the client IP is locked
+    pub async fn ensure_not_locked(&self, email: &str, ip: Option<&str>) -> Result<(), CustomError> {
+        let mut remaining = self.redis.flag_ttl(&self.account_key("lock", email)).await?;
+        if let Some(ip) = ip {
+            remaining = remaining.max(self.redis.flag_ttl(&self.ip_key("lock", ip)).await?);
+        }
+        match remaining {
+            Some(secs) => Err(CustomError::DatabaseError {
+                msg: format!("Login locked for {} more seconds", secs),
+                resp: format!("Too many failed logins, try again in {} seconds", secs),
+                status_code: StatusCode::TOO_MANY_REQUESTS,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Counts the failure and locks the account or IP once over its threshold. True when a lock was set.
+    pub async fn record_failure(&self, email: &str, ip: Option<&str>) -> Result<bool, CustomError> {
+        let window = self.settings.failure_window_secs as usize;
+        let account_failures = self.redis.increment_counter(&self.account_key("failures", email), window).await?;
+        let mut locked = self
+            .lock(&self.account_key("lock", email), account_failures, self.settings.account_threshold)
+            .await?;
+
+        if let Some(ip) = ip {
+            let ip_failures = self.redis.increment_counter(&self.ip_key("failures", ip), window).await?;
+            locked |= self
+                .lock(&self.ip_key("lock", ip), ip_failures, self.settings.ip_threshold)
+                .await?;
+        }
+        Ok(locked)
+    }
+
+    /// Forgets the account's failures after a successful login, the IP count keeps running
+    pub async fn clear_failures(&self, email: &str) -> Result<(), CustomError> {
+        self.redis.reset_counter(&self.account_key("failures", email)).await
+    }
+
+    async fn lock(&self, key: &str, failures: i64, threshold: u32) -> Result<bool, CustomError> {
+        match lock_secs(self.settings, failures, threshold) {
+            Some(secs) => {
+                self.redis.set_flag(key, secs as usize).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/// Lock duration after `failures` failures: the base at the threshold, doubled for every
+/// failure past it, up to the maximum
+pub fn lock_secs(settings: &LockoutSettings, failures: i64, threshold: u32) -> Option<u64> {
+    let past_threshold = u32::try_from(failures).ok()?.checked_sub(threshold)?;
+    let factor = 1u64.checked_shl(past_threshold).unwrap_or(u64::MAX);
+    Some(settings.base_lock_secs.saturating_mul(factor).min(settings.max_lock_secs))
+}
@@ -62,6 +62,113 @@ impl RedisService {
         Ok(())
     }
 
+    /// Remembers `session_id` as one of the owner's sessions so they can all be ended at once
+    pub async fn track_session(&self, owner_id: &str, session_id: &str) -> Result<(), CustomError> {
+        let mut con = self.get_connection()
+            .await
+            .context("Failed to get Redis connection")?;
+        let key = format!("sessions:{}", owner_id);
+        con.sadd::<_, _, ()>(&key, session_id)
+            .await
+            .context("Failed to track session")?;
+        con.expire::<_, ()>(&key, 3600)
+            .await
+            .context("Failed to set session index expiry")?;
+        Ok(())
+    }
+
+    /// Ends every tracked session of the owner, their tokens stop working at the gateway
+    pub async fn delete_all_sessions(&self, owner_id: &str) -> Result<(), CustomError> {
+        let mut con = self.get_connection()
+            .await
+            .context("Failed to get Redis connection")?;
+        let key = format!("sessions:{}", owner_id);
+        let session_ids: Vec<String> = con.smembers(&key)
+            .await
+            .context("Failed to get sessions")?;
+        for session_id in session_ids {
+            con.del::<_, ()>(&session_id)
+                .await
+                .context("Failed to delete session")?;
+        }
+        con.del::<_, ()>(&key)
+            .await
+            .context("Failed to delete session index")?;
+        Ok(())
+    }
+
+    /// Increments the counter at `key`, which expires `ttl_secs` after its first increment
+    pub async fn increment_counter(&self, key: &str, ttl_secs: usize) -> Result<i64, CustomError> {
+        let mut con = self.get_connection()
+            .await
+            .context("Failed to get Redis connection")?;
+        let count: i64 = con.incr(key, 1)
+            .await
+            .context("Failed to increment counter")?;
+        if count == 1 {
+            con.expire::<_, ()>(key, ttl_secs)
+                .await
+                .context("Failed to set counter expiry")?;
+        }
+        Ok(count)
+    }
+
+    pub async fn reset_counter(&self, key: &str) -> Result<(), CustomError> {
+        let mut con = self.get_connection()
+            .await
+            .context("Failed to get Redis connection")?;
+        con.del::<_, ()>(key)
+            .await
+            .context("Failed to reset counter")?;
+        Ok(())
+    }
+
+    /// Sets a marker at `key` that disappears after `ttl_secs`
+    pub async fn set_flag(&self, key: &str, ttl_secs: usize) -> Result<(), CustomError> {
+        let mut con = self.get_connection()
+            .await
+            .context("Failed to get Redis connection")?;
+        con.set_ex::<_, _, ()>(key, 1, ttl_secs)
+            .await
+            .context("Failed to set flag")?;
+        Ok(())
+    }
+
+    /// Seconds until the marker at `key` disappears, `None` when it isn't set
+    pub async fn flag_ttl(&self, key: &str) -> Result<Option<u64>, CustomError> {
+        let mut con = self.get_connection()
+            .await
+            .context("Failed to get Redis connection")?;
+        let ttl: i64 = con.ttl(key)
+            .await
+            .context("Failed to get flag expiry")?;
+        Ok(u64::try_from(ttl).ok())
+    }
+
+    /// Stores `value` at `key` for `ttl_secs`, to be read once with `take_value`
+    pub async fn store_value(&self, key: &str, value: &str, ttl_secs: usize) -> Result<(), CustomError> {
+        let mut con = self.get_connection()
+            .await
+            .context("Failed to get Redis connection")?;
+        con.set_ex::<_, _, ()>(key, value, ttl_secs)
+            .await
+            .context("Failed to store value")?;
+        Ok(())
+    }
+
+    /// Reads and deletes the value at `key`, so it can only be used once
+    pub async fn take_value(&self, key: &str) -> Result<Option<String>, CustomError> {
+        let mut con = self.get_connection()
+            .await
+            .context("Failed to get Redis connection")?;
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(key)
+            .query_async(&mut con)
+            .await
+            .context("Failed to take value")?;
+        Ok(value)
+    }
+
     pub async fn get_user_from_session(&self, sid: &String) -> Result<String, CustomError> {
         let mut con = self.get_connection()
         .await
@@ -1,9 +1,21 @@
-use helpers::auth_jwt::auth::{verify_jwt, Role};
+use std::future::Future;
+use std::pin::Pin;
+
+use helpers::auth_jwt::auth::{verify_jwt, Claims, Role};
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceRequest, ServiceResponse};
-use actix_web::error::ErrorUnauthorized;
-use actix_web::{Error, HttpMessage};
+use actix_web::error::{ErrorInternalServerError, ErrorUnauthorized};
+use actix_web::{web, Error, HttpMessage};
 use actix_web_lab::middleware::Next;
+use errors::CustomError;
+use lib_config::session::redis::RedisService;
+
+/// Tells whether the account behind a token may still act. Services that own the accounts
+/// register one as `web::Data<dyn AccountGuard>`, the others rely on the session alone.
+pub trait AccountGuard: Send + Sync {
+    /// False when the account is gone or deactivated, or the token predates a change of its access
+    fn accepts<'a>(&'a self, claims: &'a Claims) -> Pin<Box<dyn Future<Output = Result<bool, CustomError>> + 'a>>;
+}
 
 pub async fn jwt_auth_middleware<T: RoleRestrictor>(
     mut req: ServiceRequest,
@@ -22,6 +34,23 @@ pub async fn jwt_auth_middleware<T: RoleRestrictor>(
             if claims.role != T::role_allowed() {
                 return Err(ErrorUnauthorized("Invalid role"));
             }
+
+            // Logging out, deactivation and role changes end sessions, the token alone isn't enough
+            let redis_service = req
+                .app_data::<web::Data<RedisService>>()
+                .cloned()
+                .ok_or_else(|| ErrorInternalServerError("Session store not configured"))?;
+            let owner_id = redis_service.get_user_from_session(&claims.sid).await?;
+            if owner_id != claims.sub {
+                return Err(ErrorUnauthorized("Invalid session"));
+            }
+
+            if let Some(guard) = req.app_data::<web::Data<dyn AccountGuard>>().cloned() {
+                if !guard.accepts(&claims).await? {
+                    return Err(ErrorUnauthorized("Token revoked"));
+                }
+            }
+
             req.extensions_mut().insert(claims);
             next.call(req).await
         }
@@ -0,0 +1,56 @@
+use std::future::{ready, Ready};
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use actix_web::dev::Payload;
+use actix_web::error::{ErrorForbidden, ErrorUnauthorized};
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest};
+use helpers::auth_jwt::auth::Claims;
+use helpers::auth_jwt::permissions::Permission;
+
+/// Permissions a route needs, checked against the scopes of the token
+pub trait PermissionRestrictor {
+    fn permissions_required() -> &'static [Permission];
+}
+
+/******************************************/
+// Per-route permission extractor
+/******************************************/
+/// Claims of a token holding every permission `T` requires. Needs `jwt_auth_middleware`
+/// on the scope so the claims are verified, their session is alive and the account active.
+pub struct Authorized<T: PermissionRestrictor> {
+    claims: Claims,
+    restrictor: PhantomData<T>,
+}
+
+impl<T: PermissionRestrictor> Authorized<T> {
+    pub fn into_inner(self) -> Claims {
+        self.claims
+    }
+}
+
+impl<T: PermissionRestrictor> Deref for Authorized<T> {
+    type Target = Claims;
+    fn deref(&self) -> &Self::Target {
+        &self.claims
+    }
+}
+
+impl<T: PermissionRestrictor> FromRequest for Authorized<T> {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let claims = match req.extensions().get::<Claims>() {
+            Some(claims) => claims.clone(),
+            None => return ready(Err(ErrorUnauthorized("Missing token"))),
+        };
+        if !claims.has_permissions(T::permissions_required()) {
+            return ready(Err(ErrorForbidden("Missing permission")));
+        }
+        ready(Ok(Authorized {
+            claims,
+            restrictor: PhantomData,
+        }))
+    }
+}
@@ -7,6 +7,18 @@ use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
 use tokio::task::JoinHandle;
 use tracing_subscriber::fmt::MakeWriter;
 
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::HeaderMap;
+use actix_web::{Error, HttpMessage};
+use tracing::field::Empty;
+use tracing::Span;
+use tracing_actix_web::{DefaultRootSpanBuilder, RequestId, RootSpanBuilder};
+
+/// Header carrying the id that ties a client request to the logs of every service it reaches
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+const MAX_REQUEST_ID_LEN: usize = 128;
+
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
@@ -38,3 +50,86 @@ where
     let current_span = tracing::Span::current();
     tokio::task::spawn_blocking(move || current_span.in_scope(f))
 }
+
+/// Returns the request id sent by the caller, ignoring empty, oversized or non printable values
+pub fn request_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .filter(|id| id.chars().all(|c| c.is_ascii_graphic()))
+        .map(str::to_string)
+}
+
+pub fn generate_request_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Path and query for logs, with query values replaced: they carry download tokens, email
+/// verification tokens and OIDC codes
+pub fn redacted_target(path: &str, query: &str) -> String {
+    if query.is_empty() {
+        return path.to_string();
+    }
+    let names: Vec<&str> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| pair.split_once('=').map(|(name, _)| name).unwrap_or(pair))
+        .collect();
+    format!("{}?{}", path, names.iter().map(|name| format!("{}=REDACTED", name)).collect::<Vec<_>>().join("&"))
+}
+
+/// Root span of `TracingLogger` whose `request_id` is the `X-Request-Id` header when present,
+/// so every log line of a request carries the id assigned at the gateway
+pub struct RequestIdRootSpanBuilder;
+
+impl RootSpanBuilder for RequestIdRootSpanBuilder {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        let request_id = request_id(request.headers())
+            .or_else(|| request.extensions().get::<RequestId>().map(|id| id.to_string()))
+            .unwrap_or_else(generate_request_id);
+        let connection_info = request.connection_info();
+        let user_agent = request
+            .headers()
+            .get("User-Agent")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+
+        tracing::info_span!(
+            "HTTP request",
+            http.method = %request.method(),
+            http.route = %request.match_pattern().unwrap_or_else(|| request.path().to_string()),
+            http.target = %redacted_target(request.path(), request.query_string()),
+            http.client_ip = %connection_info.realip_remote_addr().unwrap_or(""),
+            http.user_agent = %user_agent,
+            http.status_code = Empty,
+            otel.status_code = Empty,
+            exception.message = Empty,
+            exception.details = Empty,
+            request_id = %request_id,
+        )
+    }
+
+    fn on_request_end<B: MessageBody>(span: Span, outcome: &Result<ServiceResponse<B>, Error>) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::redacted_target;
+
+    #[test]
+    fn query_values_are_redacted() {
+        assert_eq!(
+            redacted_target("/api/v1/users/export/download", "token=secret&part=1"),
+            "/api/v1/users/export/download?token=REDACTED&part=REDACTED"
+        );
+    }
+
+    #[test]
+    fn target_without_query_is_the_path() {
+        assert_eq!(redacted_target("/api/v1/games/search", ""), "/api/v1/games/search");
+    }
+}
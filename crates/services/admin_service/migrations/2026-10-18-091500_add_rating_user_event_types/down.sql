@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM user_events WHERE event_type IN ('UpdateRating', 'DeleteRating');
+
+ALTER TYPE user_event_type RENAME TO user_event_type_old;
+CREATE TYPE user_event_type AS ENUM ('Register', 'Login', 'Logout', 'Rate', 'Update');
+ALTER TABLE user_events
+    ALTER COLUMN event_type TYPE user_event_type USING event_type::text::user_event_type;
+DROP TYPE user_event_type_old;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TYPE user_event_type ADD VALUE 'UpdateRating';
+ALTER TYPE user_event_type ADD VALUE 'DeleteRating';
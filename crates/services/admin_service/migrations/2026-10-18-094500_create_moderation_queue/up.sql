@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TYPE moderation_status AS ENUM ('Open', 'Approved', 'Rejected', 'Hidden');
+
+CREATE TABLE moderation_queue (
+    review_id uuid PRIMARY KEY,
+    game_slug VARCHAR(512) NOT NULL,
+    user_id uuid NOT NULL,
+    rating INT NOT NULL,
+    review TEXT NOT NULL,
+    reasons JSONB NOT NULL DEFAULT '[]',
+    flag_count INT NOT NULL DEFAULT 0,
+    status moderation_status NOT NULL DEFAULT 'Open',
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    decided_by uuid,
+    decided_at TIMESTAMP
+);
+
+CREATE INDEX moderation_queue_status_idx ON moderation_queue (status, flag_count DESC, created_at);
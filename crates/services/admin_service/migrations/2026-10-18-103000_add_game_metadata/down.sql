@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE games
+    DROP COLUMN cover_image_url,
+    DROP COLUMN publisher,
+    DROP COLUMN release_date,
+    DROP COLUMN platforms,
+    DROP COLUMN tags;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+ALTER TABLE games
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN platforms TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN release_date DATE,
+    ADD COLUMN publisher VARCHAR(255),
+    ADD COLUMN cover_image_url TEXT;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE admin_roles;
+DROP TABLE role_permissions;
+DROP TABLE roles;
@@ -0,0 +1,55 @@
+-- Your SQL goes here
+CREATE TABLE roles (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(64) NOT NULL CONSTRAINT unique_role_name UNIQUE,
+    description TEXT,
+    built_in BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE role_permissions (
+    role_id uuid NOT NULL CONSTRAINT fk_permission_role REFERENCES roles (id) ON DELETE CASCADE,
+    permission VARCHAR(64) NOT NULL,
+    CONSTRAINT unique_role_permission PRIMARY KEY (role_id, permission)
+);
+
+CREATE TABLE admin_roles (
+    admin_id uuid NOT NULL CONSTRAINT fk_role_admin REFERENCES admins (id) ON DELETE CASCADE,
+    role_id uuid NOT NULL CONSTRAINT fk_admin_role REFERENCES roles (id) ON DELETE CASCADE,
+    CONSTRAINT unique_admin_role PRIMARY KEY (admin_id, role_id)
+);
+
+CREATE INDEX admin_roles_role_idx ON admin_roles (role_id);
+
+INSERT INTO roles (name, description, built_in) VALUES
+    ('super_admin', 'Every permission, including managing roles', TRUE),
+    ('moderator', 'Reviews the moderation queue', TRUE),
+    ('support', 'Looks up and removes user accounts', TRUE),
+    ('read_only', 'Reads games, users and the moderation queue', TRUE);
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT roles.id, grants.permission
+FROM roles
+JOIN (VALUES
+    ('super_admin', 'games:read'),
+    ('super_admin', 'games:write'),
+    ('super_admin', 'users:read'),
+    ('super_admin', 'users:delete'),
+    ('super_admin', 'moderation:read'),
+    ('super_admin', 'moderation:decide'),
+    ('super_admin', 'search:maintain'),
+    ('super_admin', 'roles:manage'),
+    ('moderator', 'games:read'),
+    ('moderator', 'moderation:read'),
+    ('moderator', 'moderation:decide'),
+    ('support', 'games:read'),
+    ('support', 'users:read'),
+    ('support', 'users:delete'),
+    ('read_only', 'games:read'),
+    ('read_only', 'users:read'),
+    ('read_only', 'moderation:read')
+) AS grants (role, permission) ON grants.role = roles.name;
+
+-- Admins created before roles existed keep the access they had
+INSERT INTO admin_roles (admin_id, role_id)
+SELECT admins.id, roles.id FROM admins, roles WHERE roles.name = 'super_admin';
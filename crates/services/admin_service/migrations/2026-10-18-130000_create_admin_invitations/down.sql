@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE admin_invitations;
+ALTER TABLE admins
+    DROP COLUMN deactivated_at,
+    DROP COLUMN is_active;
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+ALTER TABLE admins
+    ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN deactivated_at TIMESTAMP;
+
+CREATE TABLE admin_invitations (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    email VARCHAR NOT NULL,
+    token VARCHAR(64) NOT NULL CONSTRAINT unique_invitation_token UNIQUE,
+    role_id uuid NOT NULL CONSTRAINT fk_invitation_role REFERENCES roles (id) ON DELETE CASCADE,
+    invited_by uuid CONSTRAINT fk_invitation_admin REFERENCES admins (id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    accepted_at TIMESTAMP,
+    revoked_at TIMESTAMP
+);
+
+-- One open invitation per address, a new one replaces it
+CREATE UNIQUE INDEX unique_open_invitation ON admin_invitations (LOWER(email))
+    WHERE accepted_at IS NULL AND revoked_at IS NULL;
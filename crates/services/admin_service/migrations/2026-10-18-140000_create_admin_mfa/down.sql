@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE admin_recovery_codes;
+DROP TABLE admin_mfa;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE admin_mfa (
+    admin_id uuid PRIMARY KEY CONSTRAINT fk_mfa_admin REFERENCES admins (id) ON DELETE CASCADE,
+    secret VARCHAR(64) NOT NULL,
+    -- NULL until the first code is verified
+    enabled_at TIMESTAMP,
+    last_used_step BIGINT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE admin_recovery_codes (
+    admin_id uuid NOT NULL CONSTRAINT fk_recovery_code_admin REFERENCES admins (id) ON DELETE CASCADE,
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMP,
+    CONSTRAINT unique_admin_recovery_code PRIMARY KEY (admin_id, code_hash)
+);
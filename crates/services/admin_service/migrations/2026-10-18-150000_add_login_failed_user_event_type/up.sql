@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TYPE user_event_type ADD VALUE 'LoginFailed';
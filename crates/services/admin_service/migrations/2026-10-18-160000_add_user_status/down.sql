@@ -0,0 +1,16 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM role_permissions WHERE permission = 'users:suspend';
+
+DELETE FROM user_events WHERE event_type = 'StatusChange';
+
+ALTER TYPE user_event_type RENAME TO user_event_type_old;
+CREATE TYPE user_event_type AS ENUM ('Register', 'Login', 'Logout', 'Rate', 'Update', 'UpdateRating', 'DeleteRating', 'AddToList', 'RemoveFromList', 'LoginFailed');
+ALTER TABLE user_events
+    ALTER COLUMN event_type TYPE user_event_type USING event_type::text::user_event_type;
+DROP TYPE user_event_type_old;
+
+ALTER TABLE users
+    DROP COLUMN status,
+    DROP COLUMN status_changed_at;
+
+DROP TYPE account_status;
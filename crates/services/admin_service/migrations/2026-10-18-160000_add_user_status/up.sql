@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TYPE account_status AS ENUM ('active', 'deactivated', 'suspended', 'pending_deletion', 'erased');
+
+ALTER TABLE users
+    ADD COLUMN status account_status NOT NULL DEFAULT 'active',
+    ADD COLUMN status_changed_at TIMESTAMP;
+
+ALTER TYPE user_event_type ADD VALUE 'StatusChange';
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT roles.id, 'users:suspend'
+FROM roles
+WHERE roles.name IN ('super_admin', 'support');
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMP;
+
+-- Listing sorts and pages on created_at, rows mirrored before it was always sent need a value
+UPDATE users SET created_at = COALESCE(modified_at, NOW()) WHERE created_at IS NULL;
+ALTER TABLE users ALTER COLUMN created_at SET DEFAULT NOW();
+ALTER TABLE users ALTER COLUMN created_at SET NOT NULL;
+
+-- Prefix search and keyset pagination of the admin user listing
+CREATE INDEX users_username_prefix_idx ON users (lower(username) text_pattern_ops, id);
+CREATE INDEX users_email_prefix_idx ON users (lower(email) text_pattern_ops, id);
+CREATE INDEX users_created_at_idx ON users (created_at, id);
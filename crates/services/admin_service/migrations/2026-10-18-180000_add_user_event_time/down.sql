@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM role_permissions WHERE permission = 'analytics:read';
+
+DROP INDEX IF EXISTS user_events_type_time_idx;
+
+ALTER TABLE user_events DROP COLUMN occurred_at;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+ALTER TABLE user_events ADD COLUMN occurred_at TIMESTAMP;
+
+-- Every event kept its time in the payload, analytics buckets on a real column instead
+UPDATE user_events SET occurred_at = (data->>'time')::timestamp WHERE data->>'time' IS NOT NULL;
+UPDATE user_events SET occurred_at = NOW() WHERE occurred_at IS NULL;
+ALTER TABLE user_events ALTER COLUMN occurred_at SET DEFAULT NOW();
+ALTER TABLE user_events ALTER COLUMN occurred_at SET NOT NULL;
+
+CREATE INDEX user_events_type_time_idx ON user_events (event_type, occurred_at);
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT roles.id, 'analytics:read'
+FROM roles
+WHERE roles.name IN ('super_admin', 'read_only');
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE admins DROP COLUMN tokens_valid_after;
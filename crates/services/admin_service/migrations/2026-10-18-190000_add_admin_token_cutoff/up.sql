@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Tokens issued before this were minted with permissions the admin may no longer hold
+ALTER TABLE admins ADD COLUMN tokens_valid_after TIMESTAMP;
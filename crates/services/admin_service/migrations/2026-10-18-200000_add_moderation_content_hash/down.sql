@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE moderation_queue DROP COLUMN content_hash;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Entries queued before this have no hash, their decisions queue the review again
+ALTER TABLE moderation_queue ADD COLUMN content_hash TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,179 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::Context;
+use argon2::{Argon2, PasswordHasher};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use errors::CustomError;
+use helpers::auth_jwt::auth::Claims;
+use helpers::validations::validations::{check_password_strength, generate_random_salt, CreateUserBody};
+use lib_config::db::db::PgPool;
+use middleware::jwt::AccountGuard;
+use uuid::Uuid;
+
+use crate::db_error::DbError;
+use crate::permissions::SUPER_ADMIN_ROLE;
+use crate::schema::{admin_roles, admins, roles};
+
+/// Checks the password strength and returns its argon2 hash
+pub fn hash_password(password: &str) -> Result<String, CustomError> {
+    check_password_strength(password)?;
+    let salt = generate_random_salt();
+    let password_hashed = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .context("Failed to hash password")?;
+    Ok(password_hashed.to_string())
+}
+
+/// Inserts the admin with a single role, run it inside the caller's transaction
+pub async fn insert_admin(
+    conn: &mut AsyncPgConnection,
+    admin_id: Uuid,
+    admin_name: &str,
+    admin_email: &str,
+    admin_password_hash: &str,
+    role_id: Uuid,
+) -> Result<(), DieselError> {
+    diesel::insert_into(admins::table)
+        .values((
+            admins::id.eq(admin_id),
+            admins::username.eq(admin_name),
+            admins::password_hash.eq(admin_password_hash),
+            admins::email.eq(admin_email),
+        ))
+        .execute(conn)
+        .await?;
+    diesel::insert_into(admin_roles::table)
+        .values((admin_roles::admin_id.eq(admin_id), admin_roles::role_id.eq(role_id)))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// False for deactivated or deleted admins
+pub async fn admin_is_active(conn: &mut AsyncPgConnection, admin_id: Uuid) -> Result<bool, DbError> {
+    let active = admins::table
+        .find(admin_id)
+        .select(admins::is_active)
+        .first::<bool>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    Ok(active.unwrap_or(false))
+}
+
+/// Makes every token issued to the admins so far unusable, for when their access changed
+pub async fn revoke_admin_tokens(conn: &mut AsyncPgConnection, admin_ids: &[Uuid]) -> Result<(), DieselError> {
+    diesel::update(admins::table.filter(admins::id.eq_any(admin_ids)))
+        .set(admins::tokens_valid_after.eq(Some(Utc::now().naive_utc())))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Tokens only carry their issue time in seconds, so one issued in the same second as the
+/// cutoff is refused as well
+pub fn issued_after_cutoff(issued_at: usize, valid_after: Option<NaiveDateTime>) -> bool {
+    match valid_after {
+        Some(cutoff) => issued_at as i64 > cutoff.and_utc().timestamp(),
+        None => true,
+    }
+}
+
+/******************************************/
+// Rejecting tokens of deactivated admins
+/******************************************/
+/// Checked by `jwt_auth_middleware` on every admin request, so a deactivated or re-roled
+/// admin's tokens stop working even if ending their sessions failed
+pub struct ActiveAdmins {
+    pub pool: PgPool,
+}
+
+impl AccountGuard for ActiveAdmins {
+    fn accepts<'a>(&'a self, claims: &'a Claims) -> Pin<Box<dyn Future<Output = Result<bool, CustomError>> + 'a>> {
+        Box::pin(async move {
+            let Ok(admin_id) = Uuid::parse_str(&claims.sub) else {
+                return Ok(false);
+            };
+            let mut conn = self.pool
+                .get()
+                .await
+                .context("Failed to get connection from pool")?;
+            let account = admins::table
+                .find(admin_id)
+                .select((admins::is_active, admins::tokens_valid_after))
+                .first::<(bool, Option<NaiveDateTime>)>(&mut conn)
+                .await
+                .optional()
+                .map_err(DbError)?;
+            Ok(match account {
+                Some((is_active, valid_after)) => is_active && issued_after_cutoff(claims.iat, valid_after),
+                None => false,
+            })
+        })
+    }
+}
+
+/******************************************/
+// Bootstrapping the first admin
+/******************************************/
+/// Creates a `super_admin` without an invitation, used by the `create-admin` command
+pub async fn bootstrap_admin(pool: &PgPool, new_admin: CreateUserBody) -> Result<Uuid, CustomError> {
+    let password = new_admin.password.clone();
+    let (validated_name, validated_email) = new_admin.validate()?;
+    let password_hashed = hash_password(&password)?;
+    let admin_id = Uuid::new_v4();
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    conn.transaction::<_, DieselError, _>(|conn| {
+        async move {
+            let role_id = roles::table
+                .filter(roles::name.eq(SUPER_ADMIN_ROLE))
+                .select(roles::id)
+                .first::<Uuid>(conn)
+                .await?;
+            insert_admin(
+                conn,
+                admin_id,
+                validated_name.as_ref(),
+                validated_email.as_ref(),
+                &password_hashed,
+                role_id,
+            )
+            .await
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(DbError)?;
+
+    Ok(admin_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::issued_after_cutoff;
+    use chrono::DateTime;
+
+    #[test]
+    fn tokens_issued_before_a_revocation_are_refused() {
+        let cutoff = DateTime::from_timestamp(1_700_000_000, 500_000_000).unwrap().naive_utc();
+
+        assert!(!issued_after_cutoff(1_699_999_000, Some(cutoff)));
+        assert!(!issued_after_cutoff(1_700_000_000, Some(cutoff)));
+        assert!(issued_after_cutoff(1_700_000_001, Some(cutoff)));
+    }
+
+    #[test]
+    fn admins_never_revoked_keep_their_tokens() {
+        assert!(issued_after_cutoff(0, None));
+    }
+}
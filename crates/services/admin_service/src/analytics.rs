@@ -0,0 +1,274 @@
+use chrono::NaiveDateTime;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Nullable, Text, Timestamp};
+use diesel::QueryableByName;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use helpers::csv::CsvRecord;
+use serde::Serialize;
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::db_error::DbError;
+
+/// A login followed by a logout further apart than this is a session whose logout was lost
+pub const MAX_SESSION_HOURS: i64 = 24;
+
+/// Every bucket start between `$2` and `$3` for the `date_trunc` unit `$1`, empty buckets included
+const BUCKETS: &str = r#"
+buckets AS (
+    SELECT generate_series(
+        date_trunc($1, $2::timestamp),
+        $3::timestamp - INTERVAL '1 microsecond',
+        ('1 ' || $1)::interval
+    ) AS bucket_start
+)
+"#;
+
+/******************************************/
+// Active users
+/******************************************/
+#[derive(QueryableByName, Serialize, Debug, ToSchema)]
+pub struct ActiveUsers {
+    #[diesel(sql_type = Timestamp)]
+    pub bucket_start: NaiveDateTime,
+    /// Distinct users who logged in, rated or changed a list during the bucket
+    #[diesel(sql_type = BigInt)]
+    pub active_users: i64,
+}
+
+impl CsvRecord for ActiveUsers {
+    const HEADER: &'static [&'static str] = &["bucket_start", "active_users"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![self.bucket_start.to_string(), self.active_users.to_string()]
+    }
+}
+
+const ACTIVE_USERS_QUERY: &str = r#"
+SELECT buckets.bucket_start, COUNT(DISTINCT activity.user_id) AS active_users
+FROM buckets
+LEFT JOIN user_events activity
+    ON activity.event_type IN ('Login', 'Rate', 'UpdateRating', 'DeleteRating', 'AddToList', 'RemoveFromList')
+    AND activity.occurred_at >= $2 AND activity.occurred_at < $3
+    AND date_trunc($1, activity.occurred_at) = buckets.bucket_start
+GROUP BY buckets.bucket_start
+ORDER BY buckets.bucket_start
+"#;
+
+/******************************************/
+// Signup funnel
+/******************************************/
+/// Users who signed up during the bucket and how far they got since
+#[derive(QueryableByName, Serialize, Debug, ToSchema)]
+pub struct SignupFunnel {
+    #[diesel(sql_type = Timestamp)]
+    pub bucket_start: NaiveDateTime,
+    #[diesel(sql_type = BigInt)]
+    pub registered: i64,
+    /// Registered users who verified their email
+    #[diesel(sql_type = BigInt)]
+    pub verified: i64,
+    /// Verified users who rated at least one game
+    #[diesel(sql_type = BigInt)]
+    pub rated: i64,
+}
+
+impl CsvRecord for SignupFunnel {
+    const HEADER: &'static [&'static str] = &["bucket_start", "registered", "verified", "rated"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.bucket_start.to_string(),
+            self.registered.to_string(),
+            self.verified.to_string(),
+            self.rated.to_string(),
+        ]
+    }
+}
+
+const SIGNUP_FUNNEL_QUERY: &str = r#"
+SELECT buckets.bucket_start,
+    COUNT(users.id) AS registered,
+    COUNT(users.email_verified_at) AS verified,
+    COUNT(users.email_verified_at) FILTER (WHERE EXISTS (
+        SELECT 1 FROM user_events rating
+        WHERE rating.user_id = users.id AND rating.event_type = 'Rate'
+    )) AS rated
+FROM buckets
+LEFT JOIN users
+    ON users.created_at >= $2 AND users.created_at < $3
+    AND date_trunc($1, users.created_at) = buckets.bucket_start
+GROUP BY buckets.bucket_start
+ORDER BY buckets.bucket_start
+"#;
+
+/******************************************/
+// Ratings per game
+/******************************************/
+/// New ratings of one game during the bucket, buckets without ratings are left out
+#[derive(QueryableByName, Serialize, Debug, ToSchema)]
+pub struct GameRatings {
+    #[diesel(sql_type = Timestamp)]
+    pub bucket_start: NaiveDateTime,
+    #[diesel(sql_type = Text)]
+    pub game_slug: String,
+    #[diesel(sql_type = BigInt)]
+    pub ratings: i64,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub average_rating: f64,
+}
+
+impl CsvRecord for GameRatings {
+    const HEADER: &'static [&'static str] = &["bucket_start", "game_slug", "ratings", "average_rating"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.bucket_start.to_string(),
+            self.game_slug.clone(),
+            self.ratings.to_string(),
+            format!("{:.2}", self.average_rating),
+        ]
+    }
+}
+
+const GAME_RATINGS_QUERY: &str = r#"
+SELECT date_trunc($1, occurred_at) AS bucket_start,
+    data->>'game_slug' AS game_slug,
+    COUNT(*) AS ratings,
+    AVG((data->>'rating')::INT)::FLOAT8 AS average_rating
+FROM user_events
+WHERE event_type = 'Rate' AND occurred_at >= $2 AND occurred_at < $3
+    AND ($4::TEXT IS NULL OR data->>'game_slug' = $4)
+GROUP BY 1, 2
+ORDER BY 1, ratings DESC, game_slug
+"#;
+
+/******************************************/
+// Session durations
+/******************************************/
+/// Sessions started during the bucket that ended with a logout
+#[derive(QueryableByName, Serialize, Debug, ToSchema)]
+pub struct SessionDurations {
+    #[diesel(sql_type = Timestamp)]
+    pub bucket_start: NaiveDateTime,
+    #[diesel(sql_type = BigInt)]
+    pub sessions: i64,
+    #[diesel(sql_type = Nullable<diesel::sql_types::Double>)]
+    pub average_seconds: Option<f64>,
+    #[diesel(sql_type = Nullable<diesel::sql_types::Double>)]
+    pub median_seconds: Option<f64>,
+    #[diesel(sql_type = Nullable<diesel::sql_types::Double>)]
+    pub p90_seconds: Option<f64>,
+}
+
+impl CsvRecord for SessionDurations {
+    const HEADER: &'static [&'static str] = &["bucket_start", "sessions", "average_seconds", "median_seconds", "p90_seconds"];
+
+    fn fields(&self) -> Vec<String> {
+        let seconds = |value: Option<f64>| value.map(|s| format!("{:.0}", s)).unwrap_or_default();
+        vec![
+            self.bucket_start.to_string(),
+            self.sessions.to_string(),
+            seconds(self.average_seconds),
+            seconds(self.median_seconds),
+            seconds(self.p90_seconds),
+        ]
+    }
+}
+
+/// A login is paired with the user's next event when that is a logout, so a login on a second
+/// device before logging out of the first only counts the later session
+const SESSION_DURATIONS_QUERY: &str = r#"
+paired AS (
+    SELECT event_type, occurred_at,
+        LEAD(event_type) OVER by_user AS next_type,
+        LEAD(occurred_at) OVER by_user AS next_at
+    FROM user_events
+    WHERE event_type IN ('Login', 'Logout')
+        AND occurred_at >= $2 AND occurred_at < $3 + $4 * INTERVAL '1 hour'
+    WINDOW by_user AS (PARTITION BY user_id ORDER BY occurred_at)
+),
+sessions AS (
+    SELECT date_trunc($1, occurred_at) AS bucket_start,
+        EXTRACT(EPOCH FROM next_at - occurred_at)::FLOAT8 AS seconds
+    FROM paired
+    WHERE event_type = 'Login' AND next_type = 'Logout' AND occurred_at < $3
+        AND next_at - occurred_at <= $4 * INTERVAL '1 hour'
+)
+SELECT buckets.bucket_start,
+    COUNT(sessions.seconds) AS sessions,
+    AVG(sessions.seconds) AS average_seconds,
+    percentile_cont(0.5) WITHIN GROUP (ORDER BY sessions.seconds) AS median_seconds,
+    percentile_cont(0.9) WITHIN GROUP (ORDER BY sessions.seconds) AS p90_seconds
+FROM buckets
+LEFT JOIN sessions ON sessions.bucket_start = buckets.bucket_start
+GROUP BY buckets.bucket_start
+ORDER BY buckets.bucket_start
+"#;
+
+#[instrument(name = "Active users", skip(conn))]
+pub async fn active_users(
+    conn: &mut AsyncPgConnection,
+    unit: &str,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> Result<Vec<ActiveUsers>, DbError> {
+    sql_query(format!("WITH {}{}", BUCKETS, ACTIVE_USERS_QUERY))
+        .bind::<Text, _>(unit)
+        .bind::<Timestamp, _>(from)
+        .bind::<Timestamp, _>(to)
+        .load(conn)
+        .await
+        .map_err(DbError)
+}
+
+#[instrument(name = "Signup funnel", skip(conn))]
+pub async fn signup_funnel(
+    conn: &mut AsyncPgConnection,
+    unit: &str,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> Result<Vec<SignupFunnel>, DbError> {
+    sql_query(format!("WITH {}{}", BUCKETS, SIGNUP_FUNNEL_QUERY))
+        .bind::<Text, _>(unit)
+        .bind::<Timestamp, _>(from)
+        .bind::<Timestamp, _>(to)
+        .load(conn)
+        .await
+        .map_err(DbError)
+}
+
+#[instrument(name = "Game ratings", skip(conn))]
+pub async fn game_ratings(
+    conn: &mut AsyncPgConnection,
+    unit: &str,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    game_slug: Option<&str>,
+) -> Result<Vec<GameRatings>, DbError> {
+    sql_query(GAME_RATINGS_QUERY)
+        .bind::<Text, _>(unit)
+        .bind::<Timestamp, _>(from)
+        .bind::<Timestamp, _>(to)
+        .bind::<Nullable<Text>, _>(game_slug)
+        .load(conn)
+        .await
+        .map_err(DbError)
+}
+
+#[instrument(name = "Session durations", skip(conn))]
+pub async fn session_durations(
+    conn: &mut AsyncPgConnection,
+    unit: &str,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> Result<Vec<SessionDurations>, DbError> {
+    sql_query(format!("WITH {},{}", BUCKETS, SESSION_DURATIONS_QUERY))
+        .bind::<Text, _>(unit)
+        .bind::<Timestamp, _>(from)
+        .bind::<Timestamp, _>(to)
+        .bind::<BigInt, _>(MAX_SESSION_HOURS)
+        .load(conn)
+        .await
+        .map_err(DbError)
+}
@@ -30,6 +30,15 @@ fn diesel_db_response_error(err: &DieselError) -> CustomError{
                     match info.constraint_name() {
                         Some("unique_username") => ("Username has already been taken", StatusCode::BAD_REQUEST),
                         Some("unique_email") => ("Account already associated with this email", StatusCode::BAD_REQUEST),
+                        Some("unique_role_name") => ("Role name has already been taken", StatusCode::BAD_REQUEST),
+                        Some("unique_open_invitation") => ("An invitation for this email is already open", StatusCode::CONFLICT),
+                        _ => ("internal server error", StatusCode::INTERNAL_SERVER_ERROR)
+                    }
+                },
+                diesel::result::DatabaseErrorKind::ForeignKeyViolation => {
+                    match info.constraint_name() {
+                        Some("fk_role_admin") => ("Admin not found", StatusCode::NOT_FOUND),
+                        Some("fk_admin_role") | Some("fk_invitation_role") => ("Role not found", StatusCode::NOT_FOUND),
                         _ => ("internal server error", StatusCode::INTERNAL_SERVER_ERROR)
                     }
                 },
@@ -1,9 +1,15 @@
-use chrono::NaiveDateTime;
-use diesel::{prelude::Insertable, ExpressionMethods};
+use chrono::{NaiveDateTime, Utc};
+use diesel::{prelude::Insertable, ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
 use diesel_async::{AsyncPgConnection, RunQueryDsl};
-use flume::Receiver;
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::AsyncConnection;
+use flume::{Receiver, Sender};
 use futures::StreamExt;
-use kafka::models::{UserEventType, UserEventsMessage};
+use diesel::upsert::excluded;
+use diesel::PgJsonbExpressionMethods;
+use kafka::channel::{push_to_broker, KafkaMessage};
+use kafka::models::{ErasureEventsMessage, ExportEventsMessage, ModerationReason, ReviewEventsMessage, UserEventType, UserEventsMessage};
 use lib_config::db::db::PgPool;
 use rdkafka::message::OwnedMessage;
 use rdkafka::Message;
@@ -12,6 +18,9 @@ use serde_json::json;
 use tracing::instrument;
 use uuid::Uuid;
 
+use crate::routes::admin::model::{DbAccountStatus, User};
+use crate::routes::moderation::models::DbModerationStatus;
+
 #[derive(Deserialize, Insertable, Debug)]
 #[diesel(table_name = crate::schema::users)]
 pub struct ReceivedUser {
@@ -24,10 +33,12 @@ pub struct ReceivedUser {
 pub async fn process_kafka_message(
     kafka_receiver: Receiver<OwnedMessage>,
     pool: PgPool,
+    kafka_producer: Sender<KafkaMessage<String>>,
 ) {
     kafka_receiver.stream()
         .for_each_concurrent(Some(10), |msg| {
             let pool = pool.clone();
+            let kafka_producer = &kafka_producer;
             async move {
                 if msg.topic() == "user_events" {
                     let payload = match msg.payload() {
@@ -56,6 +67,7 @@ pub async fn process_kafka_message(
                                             user_events::id.eq(Uuid::new_v4()),
                                             user_events::user_id.eq(message.user_id),
                                             user_events::event_type.eq(DbUserEventType::Login),
+                                            user_events::occurred_at.eq(time),
                                             user_events::data.eq(json!({
                                                 "time": time
                                             }))
@@ -79,6 +91,7 @@ pub async fn process_kafka_message(
                                             user_events::id.eq(Uuid::new_v4()),
                                             user_events::user_id.eq(message.user_id),
                                             user_events::event_type.eq(DbUserEventType::Logout),
+                                            user_events::occurred_at.eq(time),
                                             user_events::data.eq(json!({
                                                 "time": time
                                             }))
@@ -92,6 +105,32 @@ pub async fn process_kafka_message(
                                         tracing::info!("Inserted user logout to user_events table")
                                     }
                                 },
+                                UserEventType::LoginFailed { ip, user_agent, locked, time } => {
+                                    use crate::schema::user_events;
+
+                                    let mut conn = pool.get().await.unwrap();
+                                    let res = diesel::insert_into(user_events::table)
+                                        .values((
+                                            user_events::id.eq(Uuid::new_v4()),
+                                            user_events::user_id.eq(message.user_id),
+                                            user_events::event_type.eq(DbUserEventType::LoginFailed),
+                                            user_events::occurred_at.eq(time),
+                                            user_events::data.eq(json!({
+                                                "ip": ip,
+                                                "user_agent": user_agent,
+                                                "locked": locked,
+                                                "time": time
+                                            }))
+                                        ))
+                                        .execute(&mut conn)
+                                        .await;
+
+                                    if let Err(e) = res {
+                                        tracing::error!("Failed to insert failed login to user_events table: {:?}", e);
+                                    } else {
+                                        tracing::info!("Inserted failed login to user_events table")
+                                    }
+                                },
                                 UserEventType::Rate { rating, game_slug, time } => {
                                     use crate::schema::user_events;
 
@@ -101,6 +140,7 @@ pub async fn process_kafka_message(
                                             user_events::id.eq(Uuid::new_v4()),
                                             user_events::user_id.eq(message.user_id),
                                             user_events::event_type.eq(DbUserEventType::Rate),
+                                            user_events::occurred_at.eq(time.unwrap_or_else(|| Utc::now().naive_utc())),
                                             user_events::data.eq(json!({
                                                 "rating": rating,
                                                 "game_slug": game_slug,
@@ -116,6 +156,107 @@ pub async fn process_kafka_message(
                                         tracing::info!("Inserted user rating to user_events table")
                                     }
                                 },
+                                UserEventType::UpdateRating { rating, game_slug, time } => {
+                                    use crate::schema::user_events;
+
+                                    let mut conn = pool.get().await.unwrap();
+                                    let res = diesel::insert_into(user_events::table)
+                                        .values((
+                                            user_events::id.eq(Uuid::new_v4()),
+                                            user_events::user_id.eq(message.user_id),
+                                            user_events::event_type.eq(DbUserEventType::UpdateRating),
+                                            user_events::occurred_at.eq(time.unwrap_or_else(|| Utc::now().naive_utc())),
+                                            user_events::data.eq(json!({
+                                                "rating": rating,
+                                                "game_slug": game_slug,
+                                                "time": time
+                                            }))
+                                        ))
+                                        .execute(&mut conn)
+                                        .await;
+
+                                    if let Err(e) = res {
+                                        tracing::error!("Failed to insert rating update to user_events table: {:?}", e);
+                                    } else {
+                                        tracing::info!("Inserted rating update to user_events table")
+                                    }
+                                },
+                                UserEventType::DeleteRating { game_slug, time } => {
+                                    use crate::schema::user_events;
+
+                                    let mut conn = pool.get().await.unwrap();
+                                    let res = diesel::insert_into(user_events::table)
+                                        .values((
+                                            user_events::id.eq(Uuid::new_v4()),
+                                            user_events::user_id.eq(message.user_id),
+                                            user_events::event_type.eq(DbUserEventType::DeleteRating),
+                                            user_events::occurred_at.eq(time.unwrap_or_else(|| Utc::now().naive_utc())),
+                                            user_events::data.eq(json!({
+                                                "game_slug": game_slug,
+                                                "time": time
+                                            }))
+                                        ))
+                                        .execute(&mut conn)
+                                        .await;
+
+                                    if let Err(e) = res {
+                                        tracing::error!("Failed to insert rating deletion to user_events table: {:?}", e);
+                                    } else {
+                                        tracing::info!("Inserted rating deletion to user_events table")
+                                    }
+                                },
+                                UserEventType::AddToList { list_id, list_kind, game_slug, time } => {
+                                    use crate::schema::user_events;
+
+                                    let mut conn = pool.get().await.unwrap();
+                                    let res = diesel::insert_into(user_events::table)
+                                        .values((
+                                            user_events::id.eq(Uuid::new_v4()),
+                                            user_events::user_id.eq(message.user_id),
+                                            user_events::event_type.eq(DbUserEventType::AddToList),
+                                            user_events::occurred_at.eq(time),
+                                            user_events::data.eq(json!({
+                                                "list_id": list_id,
+                                                "list_kind": list_kind,
+                                                "game_slug": game_slug,
+                                                "time": time
+                                            }))
+                                        ))
+                                        .execute(&mut conn)
+                                        .await;
+
+                                    if let Err(e) = res {
+                                        tracing::error!("Failed to insert list addition to user_events table: {:?}", e);
+                                    } else {
+                                        tracing::info!("Inserted list addition to user_events table")
+                                    }
+                                },
+                                UserEventType::RemoveFromList { list_id, list_kind, game_slug, time } => {
+                                    use crate::schema::user_events;
+
+                                    let mut conn = pool.get().await.unwrap();
+                                    let res = diesel::insert_into(user_events::table)
+                                        .values((
+                                            user_events::id.eq(Uuid::new_v4()),
+                                            user_events::user_id.eq(message.user_id),
+                                            user_events::event_type.eq(DbUserEventType::RemoveFromList),
+                                            user_events::occurred_at.eq(time),
+                                            user_events::data.eq(json!({
+                                                "list_id": list_id,
+                                                "list_kind": list_kind,
+                                                "game_slug": game_slug,
+                                                "time": time
+                                            }))
+                                        ))
+                                        .execute(&mut conn)
+                                        .await;
+
+                                    if let Err(e) = res {
+                                        tracing::error!("Failed to insert list removal to user_events table: {:?}", e);
+                                    } else {
+                                        tracing::info!("Inserted list removal to user_events table")
+                                    }
+                                },
                                 UserEventType::Register { username, email, created_at } => {
                                     let user = ReceivedUser {
                                         id: message.user_id,
@@ -130,6 +271,26 @@ pub async fn process_kafka_message(
                                 UserEventType::Update { username, email } => {
                                     let mut conn = pool.get().await.unwrap();
                                     update_user_info(message.user_id, username, email, &mut conn).await;
+                                },
+
+                                UserEventType::EmailVerified { time } => {
+                                    let mut conn = pool.get().await.unwrap();
+                                    mark_email_verified(message.user_id, time, &mut conn).await;
+                                },
+
+                                UserEventType::StatusChange { status, time } => {
+                                    let mut conn = pool.get().await.unwrap();
+                                    update_user_status(message.user_id, status.into(), time, &mut conn).await;
+                                },
+
+                                UserEventType::Erase { erasure_id, time: _ } => {
+                                    let mut conn = pool.get().await.unwrap();
+                                    erase_user(message.user_id, erasure_id, &mut conn, kafka_producer).await;
+                                },
+
+                                UserEventType::Export { export_id, time: _ } => {
+                                    let mut conn = pool.get().await.unwrap();
+                                    export_user(message.user_id, export_id, &mut conn, kafka_producer).await;
                                 }
                             }
                         },
@@ -145,6 +306,43 @@ pub async fn process_kafka_message(
                             );
                         }
                     }
+                } else if msg.topic() == "review_events" {
+                    let payload = match msg.payload() {
+                        Some(p) => p,
+                        None => {
+                            tracing::error!(
+                                "No payload found in message. Topic: {}, Partition: {}, Offset: {}",
+                                msg.topic(),
+                                msg.partition(),
+                                msg.offset()
+                            );
+                            return
+                        }
+                    };
+
+                    match serde_json::from_slice::<ReviewEventsMessage>(payload) {
+                        Ok(message) => {
+                            let mut conn = match pool.get().await {
+                                Ok(conn) => conn,
+                                Err(e) => {
+                                    tracing::error!("Failed to fetch connection from pool: {:?}", e);
+                                    return
+                                }
+                            };
+                            queue_review(message, &mut conn).await;
+                        },
+
+                        Err(e) => {
+                            tracing::error!(
+                                "Failed to deserialize message to review event
+                                Topic: {}, Partition: {}, Offset: {} | Error: {:?}",
+                                msg.topic(),
+                                msg.partition(),
+                                msg.offset(),
+                                e
+                            );
+                        }
+                    }
                 } else {
                     tracing::error!("Handler for topic {} not found", msg.topic());
                 }
@@ -187,7 +385,238 @@ pub async fn update_user_info(id: Uuid, username: String, email: String, conn: &
     };
 }
 
-#[derive(diesel_derive_enum::DbEnum, Debug)]
+#[instrument("Mark user email verified", skip(conn))]
+async fn mark_email_verified(id: Uuid, time: NaiveDateTime, conn: &mut AsyncPgConnection) {
+    use crate::schema::users;
+
+    let res = diesel::update(users::table)
+        .filter(users::id.eq(&id))
+        .set(users::email_verified_at.eq(time))
+        .execute(conn)
+        .await;
+
+    match res {
+        Ok(_) => tracing::info!("Marked email of user {} as verified", id),
+        Err(e) => tracing::error!("Failed to mark email of user {} as verified : {}", id, e),
+    };
+}
+
+#[instrument("Update user status", skip(conn))]
+async fn update_user_status(id: Uuid, status: DbAccountStatus, time: NaiveDateTime, conn: &mut AsyncPgConnection) {
+    use crate::schema::{user_events, users};
+
+    let res = conn
+        .transaction::<_, DieselError, _>(|conn| {
+            async move {
+                diesel::update(users::table)
+                    .filter(users::id.eq(&id))
+                    .set((
+                        users::status.eq(status),
+                        users::status_changed_at.eq(time)
+                    ))
+                    .execute(conn)
+                    .await?;
+                diesel::insert_into(user_events::table)
+                    .values((
+                        user_events::id.eq(Uuid::new_v4()),
+                        user_events::user_id.eq(id),
+                        user_events::event_type.eq(DbUserEventType::StatusChange),
+                        user_events::occurred_at.eq(time),
+                        user_events::data.eq(json!({
+                            "status": status,
+                            "time": time
+                        }))
+                    ))
+                    .execute(conn)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await;
+
+    match res {
+        Ok(_) => tracing::info!("Updated status of user {} to {:?}", id, status),
+        Err(e) => tracing::error!("Failed to update status of user {} : {}", id, e),
+    };
+}
+
+/// Anonymises the mirrored user and drops their event history and queued reviews, then
+/// confirms to user_service. Safe to run again when the erasure is resent.
+#[instrument("Erase user", skip(conn, kafka_producer))]
+async fn erase_user(
+    id: Uuid,
+    erasure_id: Uuid,
+    conn: &mut AsyncPgConnection,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+) {
+    use crate::schema::{moderation_queue, user_events, users};
+
+    let res = conn
+        .transaction::<_, DieselError, _>(|conn| {
+            async move {
+                let anonymous = id.simple().to_string();
+                diesel::update(users::table)
+                    .filter(users::id.eq(&id))
+                    .set((
+                        users::username.eq(format!("deleted_{}", anonymous)),
+                        users::email.eq(format!("{}@erased.invalid", anonymous)),
+                        users::status.eq(DbAccountStatus::Erased),
+                        users::status_changed_at.eq(Utc::now().naive_utc())
+                    ))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(user_events::table.filter(user_events::user_id.eq(id)))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(moderation_queue::table.filter(moderation_queue::user_id.eq(id)))
+                    .execute(conn)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await;
+
+    if let Err(e) = res {
+        // Not confirmed, user_service sends the erasure again later
+        tracing::error!("Failed to erase user {} : {}", id, e);
+        return
+    }
+
+    let confirmation = ErasureEventsMessage {
+        erasure_id,
+        user_id: id,
+        service: "admin_service".to_string(),
+        time: Utc::now().naive_utc(),
+    };
+    match push_to_broker(kafka_producer, &confirmation).await {
+        Ok(_) => tracing::info!("Erased user {} for erasure {}", id, erasure_id),
+        Err(e) => tracing::error!("Failed to confirm erasure {} : {:?}", erasure_id, e),
+    };
+}
+
+/// Sends the mirrored user, their event history and their queued reviews to user_service
+#[instrument("Export user", skip(conn, kafka_producer))]
+async fn export_user(
+    id: Uuid,
+    export_id: Uuid,
+    conn: &mut AsyncPgConnection,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+) {
+    use crate::schema::{moderation_queue, user_events, users};
+
+    let user = users::table
+        .find(id)
+        .select(User::as_select())
+        .first(conn)
+        .await
+        .optional();
+    let events = user_events::table
+        .filter(user_events::user_id.eq(id))
+        .select((user_events::event_type, user_events::data))
+        .load::<(DbUserEventType, serde_json::Value)>(conn)
+        .await;
+    let reviews = moderation_queue::table
+        .filter(moderation_queue::user_id.eq(id))
+        .select((
+            moderation_queue::game_slug,
+            moderation_queue::rating,
+            moderation_queue::review,
+            moderation_queue::status,
+            moderation_queue::created_at,
+            moderation_queue::decided_at,
+        ))
+        .load::<(String, i32, String, DbModerationStatus, NaiveDateTime, Option<NaiveDateTime>)>(conn)
+        .await;
+
+    let (user, events, reviews) = match (user, events, reviews) {
+        (Ok(user), Ok(events), Ok(reviews)) => (user, events, reviews),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            // Not answered, user_service asks again later
+            tracing::error!("Failed to collect data of user {} : {}", id, e);
+            return
+        }
+    };
+
+    let data = json!({
+        "user": user,
+        "events": events
+            .into_iter()
+            .map(|(event_type, data)| json!({ "event_type": event_type, "data": data }))
+            .collect::<Vec<_>>(),
+        "moderated_reviews": reviews
+            .into_iter()
+            .map(|(game_slug, rating, review, status, created_at, decided_at)| json!({
+                "game_slug": game_slug,
+                "rating": rating,
+                "review": review,
+                "status": status,
+                "queued_at": created_at,
+                "decided_at": decided_at
+            }))
+            .collect::<Vec<_>>(),
+    });
+    let part = ExportEventsMessage {
+        export_id,
+        user_id: id,
+        service: "admin_service".to_string(),
+        data,
+        time: Utc::now().naive_utc(),
+    };
+    match push_to_broker(kafka_producer, &part).await {
+        Ok(_) => tracing::info!("Sent data of user {} for export {}", id, export_id),
+        Err(e) => tracing::error!("Failed to send part of export {} : {:?}", export_id, e),
+    };
+}
+
+/// Adds the review to the moderation queue, or reopens it with the new reason when already queued
+#[instrument("Queue review for moderation", skip(message, conn), fields(review_id = %message.review_id))]
+async fn queue_review(message: ReviewEventsMessage, conn: &mut AsyncPgConnection) {
+    use crate::schema::moderation_queue;
+
+    let flags = match message.reason {
+        ModerationReason::UserFlag { .. } => 1,
+        _ => 0,
+    };
+
+    let res = diesel::insert_into(moderation_queue::table)
+        .values((
+            moderation_queue::review_id.eq(message.review_id),
+            moderation_queue::game_slug.eq(&message.game_slug),
+            moderation_queue::user_id.eq(message.user_id),
+            moderation_queue::rating.eq(message.rating),
+            moderation_queue::review.eq(&message.review),
+            moderation_queue::reasons.eq(json!([message.reason])),
+            moderation_queue::content_hash.eq(&message.content_hash),
+            moderation_queue::flag_count.eq(flags),
+            moderation_queue::status.eq(DbModerationStatus::Open),
+            moderation_queue::created_at.eq(message.time),
+            moderation_queue::updated_at.eq(message.time),
+        ))
+        .on_conflict(moderation_queue::review_id)
+        .do_update()
+        .set((
+            moderation_queue::rating.eq(excluded(moderation_queue::rating)),
+            moderation_queue::review.eq(excluded(moderation_queue::review)),
+            moderation_queue::content_hash.eq(excluded(moderation_queue::content_hash)),
+            moderation_queue::reasons.eq(moderation_queue::reasons.concat(excluded(moderation_queue::reasons))),
+            moderation_queue::flag_count.eq(moderation_queue::flag_count + excluded(moderation_queue::flag_count)),
+            moderation_queue::status.eq(DbModerationStatus::Open),
+            moderation_queue::updated_at.eq(excluded(moderation_queue::updated_at)),
+            moderation_queue::decided_by.eq(None::<Uuid>),
+            moderation_queue::decided_at.eq(None::<NaiveDateTime>),
+        ))
+        .execute(conn)
+        .await;
+
+    match res {
+        Ok(_) => tracing::info!("Queued review {} for moderation", message.review_id),
+        Err(e) => tracing::error!("Failed to queue review {} for moderation: {:?}", message.review_id, e),
+    };
+}
+
+#[derive(diesel_derive_enum::DbEnum, Debug, serde::Serialize)]
 #[ExistingTypePath = "crate::schema::sql_types::UserEventType"]
 #[DbValueStyle = "verbatim"]
 enum DbUserEventType{
@@ -195,5 +624,11 @@ enum DbUserEventType{
     Login,
     Logout,
     Rate,
-    Update
+    Update,
+    UpdateRating,
+    DeleteRating,
+    AddToList,
+    RemoveFromList,
+    LoginFailed,
+    StatusChange
 } 
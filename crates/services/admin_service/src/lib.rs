@@ -2,4 +2,6 @@ pub mod startup;
 pub mod routes;
 pub mod schema;
 pub mod kafka_handler;
-pub mod db_error;
\ No newline at end of file
+pub mod db_error;
+pub mod permissions;
+pub mod accounts;pub mod analytics;
@@ -1,6 +1,8 @@
 use lib_config::config::configuration;
 use lib_config::db::db::establish_connection;
+use admin_service::accounts::bootstrap_admin;
 use admin_service::startup::Application;
+use helpers::validations::validations::CreateUserBody;
 use utils::telemetry::{get_subscriber, init_subscriber};
 
 #[tokio::main]
@@ -11,6 +13,28 @@ async fn main() -> std::io::Result<()> {
     let config = configuration::Settings::new().expect("Failed to load configurations");
     let pool = establish_connection(&config.databases.admin_db_url).await;
 
+    // `admin_service create-admin <username> <email>` creates a super_admin with the password
+    // from `ADMIN_PASSWORD` and exits, registration otherwise needs an invitation
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("create-admin") {
+        let (username, email) = match (args.get(2), args.get(3)) {
+            (Some(username), Some(email)) => (username.clone(), email.clone()),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Usage: admin_service create-admin <username> <email>",
+                ))
+            }
+        };
+        let password = std::env::var("ADMIN_PASSWORD")
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "ADMIN_PASSWORD is not set"))?;
+        let admin_id = bootstrap_admin(&pool, CreateUserBody { username, password, email })
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", e)))?;
+        println!("Created super_admin {}", admin_id);
+        return Ok(());
+    }
+
     let application = Application::build(pool, &config).await?;
     application.run_until_stopped().await?;
     Ok(())
@@ -0,0 +1,102 @@
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use helpers::auth_jwt::permissions::Permission;
+use middleware::permissions::PermissionRestrictor;
+use uuid::Uuid;
+
+use crate::db_error::DbError;
+use crate::schema::{admin_roles, role_permissions};
+
+/// Granted to the admin created with the `create-admin` command so someone can hand out roles
+pub const SUPER_ADMIN_ROLE: &str = "super_admin";
+
+/******************************************/
+// Route permissions
+/******************************************/
+pub struct GamesRead();
+
+impl PermissionRestrictor for GamesRead {
+    fn permissions_required() -> &'static [Permission] {
+        &[Permission::GamesRead]
+    }
+}
+
+pub struct GamesWrite();
+
+impl PermissionRestrictor for GamesWrite {
+    fn permissions_required() -> &'static [Permission] {
+        &[Permission::GamesWrite]
+    }
+}
+
+pub struct UsersRead();
+
+impl PermissionRestrictor for UsersRead {
+    fn permissions_required() -> &'static [Permission] {
+        &[Permission::UsersRead]
+    }
+}
+
+pub struct UsersDelete();
+
+impl PermissionRestrictor for UsersDelete {
+    fn permissions_required() -> &'static [Permission] {
+        &[Permission::UsersDelete]
+    }
+}
+
+pub struct UsersSuspend();
+
+impl PermissionRestrictor for UsersSuspend {
+    fn permissions_required() -> &'static [Permission] {
+        &[Permission::UsersSuspend]
+    }
+}
+
+pub struct ModerationRead();
+
+impl PermissionRestrictor for ModerationRead {
+    fn permissions_required() -> &'static [Permission] {
+        &[Permission::ModerationRead]
+    }
+}
+
+pub struct ModerationDecide();
+
+impl PermissionRestrictor for ModerationDecide {
+    fn permissions_required() -> &'static [Permission] {
+        &[Permission::ModerationDecide]
+    }
+}
+
+pub struct RolesManage();
+
+impl PermissionRestrictor for RolesManage {
+    fn permissions_required() -> &'static [Permission] {
+        &[Permission::RolesManage]
+    }
+}
+
+pub struct AnalyticsRead();
+
+impl PermissionRestrictor for AnalyticsRead {
+    fn permissions_required() -> &'static [Permission] {
+        &[Permission::AnalyticsRead]
+    }
+}
+
+/******************************************/
+// Token scopes
+/******************************************/
+/// Every permission granted to the admin through any of their roles
+pub async fn admin_scopes(conn: &mut AsyncPgConnection, admin: Uuid) -> Result<Vec<String>, DbError> {
+    admin_roles::table
+        .inner_join(role_permissions::table.on(role_permissions::role_id.eq(admin_roles::role_id)))
+        .filter(admin_roles::admin_id.eq(admin))
+        .select(role_permissions::permission)
+        .distinct()
+        .order(role_permissions::permission.asc())
+        .load(conn)
+        .await
+        .map_err(DbError)
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use errors::CustomError;
+use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
+use middleware::permissions::Authorized;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::accounts::revoke_admin_tokens;
+use crate::db_error::DbError;
+use crate::permissions::RolesManage;
+use crate::routes::roles::models::RoleError;
+use crate::routes::roles::roles::ensure_role_manager_left;
+use crate::schema::{admin_roles, admins, roles};
+
+use super::model::{AdminAccount, AdminResponse};
+
+/******************************************/
+// Get admins Route
+/******************************************/
+/**
+ * @route   GET /api/v1/auth/admins
+ * @access  Private
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/admins",
+    tag = "admins",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Every admin with their roles", body = Vec<AdminResponse>),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing roles:manage permission")
+    )
+)]
+#[instrument(name = "Get admins", skip(pool, _admin))]
+pub async fn get_admins(
+    pool: web::Data<PgPool>,
+    _admin: Authorized<RolesManage>,
+) -> Result<HttpResponse, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let accounts = admins::table
+        .order(admins::username.asc())
+        .select(AdminAccount::as_select())
+        .load(&mut conn)
+        .await
+        .map_err(DbError)?;
+    let role_names: Vec<(Uuid, String)> = admin_roles::table
+        .inner_join(roles::table)
+        .order(roles::name.asc())
+        .select((admin_roles::admin_id, roles::name))
+        .load(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    let mut roles_by_admin: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for (admin_id, role_name) in role_names {
+        roles_by_admin.entry(admin_id).or_default().push(role_name);
+    }
+    let response: Vec<AdminResponse> = accounts
+        .into_iter()
+        .map(|account| AdminResponse {
+            roles: roles_by_admin.remove(&account.id).unwrap_or_default(),
+            id: account.id,
+            username: account.username,
+            email: account.email,
+            is_active: account.is_active,
+            deactivated_at: account.deactivated_at,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/******************************************/
+// Deactivate admin Route
+/******************************************/
+/**
+ * @route   POST /api/v1/auth/admins/{admin_id}/deactivate
+ * @access  Private
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/admins/{admin_id}/deactivate",
+    tag = "admins",
+    security(("bearer_auth" = [])),
+    params(("admin_id" = Uuid, Path, description = "Id of the admin")),
+    responses(
+        (status = 204, description = "Admin can't log in anymore and their sessions are ended"),
+        (status = 400, description = "Admins can't deactivate themselves"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing roles:manage permission"),
+        (status = 404, description = "Admin not found"),
+        (status = 409, description = "No active admin would be left to manage roles")
+    )
+)]
+#[instrument(name = "Deactivate admin", skip(pool, redis_service, admin))]
+pub async fn deactivate_admin(
+    pool: web::Data<PgPool>,
+    admin_id: web::Path<Uuid>,
+    redis_service: web::Data<RedisService>,
+    admin: Authorized<RolesManage>,
+) -> Result<HttpResponse, CustomError> {
+    let admin_id = admin_id.into_inner();
+    if admin.sub == admin_id.to_string() {
+        return Err(CustomError::ValidationError("You can't deactivate your own account.".to_string()));
+    }
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    conn.transaction::<_, RoleError, _>(|conn| {
+        async move {
+            let updated = diesel::update(admins::table.find(admin_id))
+                .set((
+                    admins::is_active.eq(false),
+                    admins::deactivated_at.eq(Some(Utc::now().naive_utc())),
+                ))
+                .execute(conn)
+                .await?;
+            if updated == 0 {
+                return Err(diesel::result::Error::NotFound.into());
+            }
+            // Reactivating later mustn't bring back tokens issued before
+            revoke_admin_tokens(conn, &[admin_id]).await?;
+            ensure_role_manager_left(conn).await
+        }
+        .scope_boxed()
+    })
+    .await?;
+
+    redis_service.delete_all_sessions(&admin_id.to_string()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/******************************************/
+// Reactivate admin Route
+/******************************************/
+/**
+ * @route   POST /api/v1/auth/admins/{admin_id}/reactivate
+ * @access  Private
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/admins/{admin_id}/reactivate",
+    tag = "admins",
+    security(("bearer_auth" = [])),
+    params(("admin_id" = Uuid, Path, description = "Id of the admin")),
+    responses(
+        (status = 204, description = "Admin can log in again"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing roles:manage permission"),
+        (status = 404, description = "Admin not found")
+    )
+)]
+#[instrument(name = "Reactivate admin", skip(pool, _admin))]
+pub async fn reactivate_admin(
+    pool: web::Data<PgPool>,
+    admin_id: web::Path<Uuid>,
+    _admin: Authorized<RolesManage>,
+) -> Result<HttpResponse, CustomError> {
+    let admin_id = admin_id.into_inner();
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let updated = diesel::update(admins::table.find(admin_id))
+        .set((
+            admins::is_active.eq(true),
+            admins::deactivated_at.eq(None::<chrono::NaiveDateTime>),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    if updated == 0 {
+        return Err(CustomError::DatabaseError {
+            msg: format!("Admin {} not found", admin_id),
+            resp: "Admin not found".into(),
+            status_code: StatusCode::NOT_FOUND,
+        });
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,21 +1,28 @@
-use actix_web::http::StatusCode;
-use helpers::auth_jwt::auth::{create_jwt, Claims};
+use helpers::auth_jwt::auth::{create_jwt, create_mfa_challenge, mfa_challenge_key, Claims, MFA_CHALLENGE_MINUTES};
 use anyhow::Context;
 use helpers::auth_jwt::auth::Role;
+use helpers::client_ip::client_ip;
+use lib_config::config::configuration::AuthSettings;
 use lib_config::db::db::PgPool;
 use errors::{AuthError, CustomError};
-use crate::{db_error::DbError, schema::admins::dsl::*};
+use crate::db_error::DbError;
+use crate::accounts::{hash_password, insert_admin};
+use crate::permissions::admin_scopes;
+use crate::schema::admin_invitations;
+use crate::routes::admin::mfa::mfa_enabled;
+use crate::routes::admin::model::RegisterAdminBody;
 use crate::routes::admin::validate_user::validate_credentials;
-use helpers::validations::validations::{check_password_strength, generate_random_salt, CreateUserBody, LoginUserBody, UserEmail, UserName};
-use actix_web::{web, HttpResponse};
-use argon2::{self, password_hash::SaltString, Argon2, PasswordHasher};
+use helpers::validations::validations::LoginUserBody;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
 use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
-use rand::Rng;
-use serde::Deserialize;
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
 use serde_json::json;
 use tracing::instrument;
 use uuid::Uuid;
+use lib_config::session::lockout::LoginLockout;
 use lib_config::session::redis::RedisService;
 
 /******************************************/
@@ -23,53 +30,83 @@ use lib_config::session::redis::RedisService;
 /******************************************/
 /**
  * @route   POST /ap1/v1/register
- * @access  Public
+ * @access  Invitation
  */
-#[instrument(name = "Register a new admin", skip(req_admin, pool, redis_service), fields(username = %req_admin.username, email = %req_admin.email))]
+#[utoipa::path(
+    post,
+    path = "/api/v1/admins/register",
+    tag = "admins",
+    request_body = RegisterAdminBody,
+    responses(
+        (status = 201, description = "Admin created with the invited role, returns a JWT token"),
+        (status = 400, description = "Invalid admin data or admin already exists"),
+        (status = 401, description = "Invitation token invalid, expired or issued for another email")
+    )
+)]
+#[instrument(name = "Register a new admin", skip(req_admin, pool, redis_service), fields(username = %req_admin.admin.username, email = %req_admin.admin.email))]
 pub async fn register_admin(
     pool: web::Data<PgPool>,
-    req_admin: web::Json<CreateUserBody>,
+    req_admin: web::Json<RegisterAdminBody>,
     redis_service: web::Data<RedisService>
 ) -> Result<HttpResponse, CustomError> {
-    let pool = pool.clone();
-    let admin_data = req_admin.into_inner();
+    let RegisterAdminBody { admin: admin_data, invitation_token } = req_admin.into_inner();
     let admin_password = admin_data.password.clone();
     let (validated_name, validated_email) = admin_data
         .validate()
         .map_err(|err| CustomError::ValidationError(err.to_string()))?;
-    let _= check_password_strength(&admin_password)?;
+    let password_hashed = hash_password(&admin_password)?;
     let admin_id = Uuid::new_v4();
     let mut conn = pool
         .get()
         .await
         .context("Failed to fetch connection from pool")?;
-    let argon2 = Argon2::default();
 
-    let salt = generate_random_salt();
-    let password_hashed = argon2
-        .hash_password(admin_password.as_bytes(), &salt)
-        .context("Failed to hash password")?;
+    let scopes = conn
+        .transaction::<_, DieselError, _>(|conn| {
+            async move {
+                let now = Utc::now().naive_utc();
+                let invitation = admin_invitations::table
+                    .filter(admin_invitations::token.eq(&invitation_token))
+                    .filter(admin_invitations::accepted_at.is_null())
+                    .filter(admin_invitations::revoked_at.is_null())
+                    .filter(admin_invitations::expires_at.gt(now))
+                    .select((admin_invitations::id, admin_invitations::email, admin_invitations::role_id))
+                    .for_update()
+                    .first::<(Uuid, String, Uuid)>(conn)
+                    .await
+                    .optional()?;
+                let (invitation_id, role_id) = match invitation {
+                    Some((invitation_id, invited_email, role_id))
+                        if invited_email.eq_ignore_ascii_case(validated_email.as_ref()) => (invitation_id, role_id),
+                    _ => return Ok(None),
+                };
+
+                insert_admin(
+                    conn,
+                    admin_id,
+                    validated_name.as_ref(),
+                    validated_email.as_ref(),
+                    &password_hashed,
+                    role_id,
+                )
+                .await?;
+                diesel::update(admin_invitations::table.find(invitation_id))
+                    .set(admin_invitations::accepted_at.eq(Some(now)))
+                    .execute(conn)
+                    .await?;
 
-    let result = diesel::insert_into(admins)
-        .values((
-            id.eq(admin_id),
-            username.eq(validated_name.as_ref()),
-            password_hash.eq(password_hashed.to_string()),
-            email.eq(validated_email.as_ref()),
-        ))
-        .execute(&mut conn)
+                let scopes = admin_scopes(conn, admin_id).await.map_err(|err| err.0)?;
+                Ok(Some(scopes))
+            }
+            .scope_boxed()
+        })
         .await
-        .map_err(DbError)?;
+        .map_err(DbError)?
+        .ok_or(AuthError::InvalidCredentials(anyhow::anyhow!("Invitation token invalid or expired")))?;
 
-    if result == 0 {
-        return Err(CustomError::DatabaseError{
-            msg: "Failed to insert user to table admins".into(),
-            resp: "Failed to create admin".into(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR
-        });
-    }
-    let (token, sid) = create_jwt(&admin_id.to_string(), Role::Admin)?;
+    let (token, sid) = create_jwt(&admin_id.to_string(), Role::Admin, scopes)?;
     let _= redis_service.set_session(&sid, &admin_id.to_string(), true).await?;
+    redis_service.track_session(&admin_id.to_string(), &sid).await?;
 
     Ok(HttpResponse::Created().json(serde_json::json!({
         "message":"Admin created successfully",
@@ -84,17 +121,77 @@ pub async fn register_admin(
  * @route   POST /ap1/v1/login
  * @access  Public
  */
-#[instrument(name = "Login a admin", skip(req_login, pool, redis_service), fields(username = %req_login.email))]
+#[utoipa::path(
+    post,
+    path = "/api/v1/admins/login",
+    tag = "admins",
+    request_body = LoginUserBody,
+    responses(
+        (status = 200, description = "Logged in, returns a JWT token, or a challenge token when 2FA is enabled"),
+        (status = 401, description = "Invalid credentials"),
+        (status = 429, description = "Too many failed logins for the account or IP, locked for a while")
+    )
+)]
+#[instrument(name = "Login a admin", skip(req, req_login, pool, redis_service, auth_settings), fields(username = %req_login.email))]
 
 pub async fn login_admin(
+    req: HttpRequest,
     pool: web::Data<PgPool>,
     req_login: web::Json<LoginUserBody>,
     redis_service: web::Data<RedisService>,
+    auth_settings: web::Data<AuthSettings>,
 ) -> Result<HttpResponse, CustomError> {
-    let id_admin = validate_credentials(&pool, &req_login.into_inner()).await?;
+    let req_login = req_login.into_inner();
+    let forwarded_for = req.headers().get("X-Forwarded-For").and_then(|value| value.to_str().ok());
+    let client_ip = client_ip(req.peer_addr().map(|addr| addr.ip()), forwarded_for, &auth_settings.trusted_proxies);
+    let lockout = LoginLockout::new(&redis_service, &auth_settings.lockout, "admin");
+    lockout.ensure_not_locked(&req_login.email, client_ip.as_deref()).await?;
 
-    let (token, sid) = create_jwt(&id_admin.to_string(), Role::Admin)?;
+    let id_admin = match validate_credentials(&pool, &req_login).await {
+        Ok(id_admin) => id_admin,
+        Err(err @ CustomError::AuthenticationError(_)) => {
+            if lockout.record_failure(&req_login.email, client_ip.as_deref()).await? {
+                tracing::warn!("Admin login for {} locked after repeated failures", req_login.email);
+            }
+            return Err(err);
+        }
+        Err(err) => return Err(err),
+    };
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    // Failures are only forgotten once the second factor passed too
+    if mfa_enabled(&mut conn, id_admin).await? {
+        // No session until the second factor is checked by /login/2fa
+        let (challenge_token, jti) = create_mfa_challenge(&id_admin.to_string(), Role::Admin)?;
+        redis_service
+            .store_value(&mfa_challenge_key(&jti), &id_admin.to_string(), (MFA_CHALLENGE_MINUTES * 60) as usize)
+            .await?;
+        return Ok(HttpResponse::Ok().json(json!({
+            "mfa_required": true,
+            "challenge_token": challenge_token
+        })));
+    }
+    lockout.clear_failures(&req_login.email).await?;
+    // Without 2FA where it's mandatory the token carries no permissions, it can only enrol
+    let enrollment_required = auth_settings.admin_mfa_required;
+    let scopes = if enrollment_required {
+        Vec::new()
+    } else {
+        admin_scopes(&mut conn, id_admin).await?
+    };
+
+    let (token, sid) = create_jwt(&id_admin.to_string(), Role::Admin, scopes)?;
     redis_service.set_session(&sid, &id_admin.to_string(), true).await?;
+    redis_service.track_session(&id_admin.to_string(), &sid).await?;
+    if enrollment_required {
+        return Ok(HttpResponse::Ok().json(json!({
+            "token": token,
+            "mfa_enrollment_required": true
+        })));
+    }
     Ok(HttpResponse::Ok().json(json!({"token": token})))
 }
 
@@ -105,6 +202,16 @@ pub async fn login_admin(
  * @route   Get /ap1/v1/protected/logout
  * @access  JWT Protected
  */
+#[utoipa::path(
+    get,
+    path = "/api/v1/protected/logout",
+    tag = "admins",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Logout successful"),
+        (status = 401, description = "Invalid token")
+    )
+)]
 #[instrument(name = "Logout a admin", skip(session))]
 pub async fn logout_admin(
     session: web::Data<RedisService>,
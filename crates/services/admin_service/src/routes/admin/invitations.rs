@@ -0,0 +1,209 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use errors::CustomError;
+use helpers::validations::mail_token::generate_token;
+use helpers::validations::validations::UserEmail;
+use lib_config::db::db::PgPool;
+use lib_config::send_mail::send::send_admin_invitation;
+use middleware::permissions::Authorized;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_error::DbError;
+use crate::permissions::RolesManage;
+use crate::schema::{admin_invitations, admins};
+
+use super::model::{CreateInvitationBody, Invitation};
+
+/// How long an invitation token can be used to register
+pub const INVITATION_VALID_DAYS: i64 = 7;
+
+define_sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
+
+/******************************************/
+// Invite admin Route
+/******************************************/
+/**
+ * @route   POST /api/v1/auth/invitations
+ * @access  Private
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/invitations",
+    tag = "admins",
+    security(("bearer_auth" = [])),
+    request_body = CreateInvitationBody,
+    responses(
+        (status = 201, description = "Invitation emailed, any earlier open invitation for the address is revoked", body = Invitation),
+        (status = 400, description = "Invalid email or already an admin"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing roles:manage permission"),
+        (status = 404, description = "Role not found")
+    )
+)]
+#[instrument(name = "Invite admin", skip(pool, req_invitation, admin), fields(email = %req_invitation.email))]
+pub async fn invite_admin(
+    pool: web::Data<PgPool>,
+    req_invitation: web::Json<CreateInvitationBody>,
+    admin: Authorized<RolesManage>,
+) -> Result<HttpResponse, CustomError> {
+    let req_invitation = req_invitation.into_inner();
+    let invited_email = UserEmail::parse(req_invitation.email.to_lowercase())?;
+    let inviter_id = Uuid::parse_str(&admin.sub)
+        .map_err(|err| CustomError::ValidationError(format!("Invalid admin ID format: {}", err)))?;
+    let invitation_token = generate_token();
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let already_admin = admins::table
+        .filter(lower(admins::email).eq(invited_email.as_ref()))
+        .select(admins::id)
+        .first::<Uuid>(&mut conn)
+        .await
+        .optional()
+        .map_err(DbError)?
+        .is_some();
+    if already_admin {
+        return Err(CustomError::ValidationError(format!("{} is already an admin.", invited_email.as_ref())));
+    }
+
+    let email_address = invited_email.as_ref().to_string();
+    let token = invitation_token.clone();
+    let invitation = conn
+        .transaction::<_, DieselError, _>(|conn| {
+            async move {
+                let now = Utc::now().naive_utc();
+                diesel::update(
+                    admin_invitations::table
+                        .filter(lower(admin_invitations::email).eq(&email_address))
+                        .filter(admin_invitations::accepted_at.is_null())
+                        .filter(admin_invitations::revoked_at.is_null()),
+                )
+                .set(admin_invitations::revoked_at.eq(Some(now)))
+                .execute(conn)
+                .await?;
+
+                diesel::insert_into(admin_invitations::table)
+                    .values((
+                        admin_invitations::email.eq(&email_address),
+                        admin_invitations::token.eq(&token),
+                        admin_invitations::role_id.eq(req_invitation.role_id),
+                        admin_invitations::invited_by.eq(Some(inviter_id)),
+                        admin_invitations::expires_at.eq(now + Duration::days(INVITATION_VALID_DAYS)),
+                    ))
+                    .returning(Invitation::as_returning())
+                    .get_result(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(DbError)?;
+
+    send_admin_invitation(invited_email.as_ref(), &invitation_token, INVITATION_VALID_DAYS).await?;
+
+    Ok(HttpResponse::Created().json(invitation))
+}
+
+/******************************************/
+// Get invitations Route
+/******************************************/
+/**
+ * @route   GET /api/v1/auth/invitations
+ * @access  Private
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/invitations",
+    tag = "admins",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Invitations that can still be used, newest first", body = Vec<Invitation>),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing roles:manage permission")
+    )
+)]
+#[instrument(name = "Get invitations", skip(pool, _admin))]
+pub async fn get_invitations(
+    pool: web::Data<PgPool>,
+    _admin: Authorized<RolesManage>,
+) -> Result<HttpResponse, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let invitations = admin_invitations::table
+        .filter(admin_invitations::accepted_at.is_null())
+        .filter(admin_invitations::revoked_at.is_null())
+        .filter(admin_invitations::expires_at.gt(Utc::now().naive_utc()))
+        .order(admin_invitations::created_at.desc())
+        .select(Invitation::as_select())
+        .load(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    Ok(HttpResponse::Ok().json(invitations))
+}
+
+/******************************************/
+// Revoke invitation Route
+/******************************************/
+/**
+ * @route   DELETE /api/v1/auth/invitations/{invitation_id}
+ * @access  Private
+ */
+#[utoipa::path(
+    delete,
+    path = "/api/v1/auth/invitations/{invitation_id}",
+    tag = "admins",
+    security(("bearer_auth" = [])),
+    params(("invitation_id" = Uuid, Path, description = "Id of the invitation")),
+    responses(
+        (status = 204, description = "Invitation revoked"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing roles:manage permission"),
+        (status = 404, description = "No open invitation with this id")
+    )
+)]
+#[instrument(name = "Revoke invitation", skip(pool, _admin))]
+pub async fn revoke_invitation(
+    pool: web::Data<PgPool>,
+    invitation_id: web::Path<Uuid>,
+    _admin: Authorized<RolesManage>,
+) -> Result<HttpResponse, CustomError> {
+    let invitation_id = invitation_id.into_inner();
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let revoked = diesel::update(
+        admin_invitations::table
+            .find(invitation_id)
+            .filter(admin_invitations::accepted_at.is_null())
+            .filter(admin_invitations::revoked_at.is_null()),
+    )
+    .set(admin_invitations::revoked_at.eq(Some(Utc::now().naive_utc())))
+    .execute(&mut conn)
+    .await
+    .map_err(DbError)?;
+
+    if revoked == 0 {
+        return Err(CustomError::DatabaseError {
+            msg: format!("Invitation {} not found or already used", invitation_id),
+            resp: "Invitation not found".into(),
+            status_code: StatusCode::NOT_FOUND,
+        });
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
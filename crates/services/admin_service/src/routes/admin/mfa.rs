@@ -0,0 +1,365 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use errors::{AuthError, CustomError};
+use helpers::auth_jwt::auth::{consume_mfa_challenge, create_jwt, verify_mfa_challenge, Claims, Role, MFA_CHALLENGE_MINUTES};
+use helpers::client_ip::client_ip;
+use helpers::mfa::{generate_recovery_codes, generate_secret, hash_recovery_code, provisioning_uri, verify_totp};
+use lib_config::config::configuration::AuthSettings;
+use lib_config::db::db::PgPool;
+use lib_config::session::lockout::LoginLockout;
+use lib_config::session::redis::RedisService;
+use serde_json::json;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::accounts::admin_is_active;
+use crate::db_error::DbError;
+use crate::permissions::admin_scopes;
+use crate::schema::{admin_mfa, admin_recovery_codes, admins};
+
+use super::model::{MfaCodeBody, MfaEnrollment, MfaLoginBody, RecoveryCodes};
+
+/// Wrong codes allowed per login challenge before the password has to be entered again
+const MAX_CHALLENGE_ATTEMPTS: i64 = 5;
+
+fn token_admin(claims: &Claims) -> Result<Uuid, CustomError> {
+    Uuid::parse_str(&claims.sub)
+        .map_err(|err| AuthError::InvalidSession(anyhow::anyhow!(err)).into())
+}
+
+fn mfa_error(status_code: StatusCode, message: &str) -> CustomError {
+    CustomError::DatabaseError {
+        msg: message.to_string(),
+        resp: message.to_string(),
+        status_code,
+    }
+}
+
+/// True once the admin has verified a code after enrolling
+pub async fn mfa_enabled(conn: &mut AsyncPgConnection, admin: Uuid) -> Result<bool, DbError> {
+    admin_mfa::table
+        .find(admin)
+        .select(admin_mfa::enabled_at.is_not_null())
+        .first::<bool>(conn)
+        .await
+        .optional()
+        .map(|enabled| enabled.unwrap_or(false))
+        .map_err(DbError)
+}
+
+/// Checks an authenticator code or consumes a recovery code of an admin with 2FA enabled
+async fn check_second_factor(conn: &mut AsyncPgConnection, admin: Uuid, code: &str) -> Result<bool, DbError> {
+    let stored = admin_mfa::table
+        .find(admin)
+        .filter(admin_mfa::enabled_at.is_not_null())
+        .select((admin_mfa::secret, admin_mfa::last_used_step))
+        .first::<(String, Option<i64>)>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    let Some((secret, last_used_step)) = stored else {
+        return Ok(false);
+    };
+
+    let now = Utc::now();
+    if let Some(step) = verify_totp(&secret, code, now.timestamp() as u64, last_used_step) {
+        // Only one login can claim a time step, a replayed code updates nothing
+        let claimed = diesel::update(
+            admin_mfa::table
+                .find(admin)
+                .filter(admin_mfa::last_used_step.is_null().or(admin_mfa::last_used_step.lt(step))),
+        )
+        .set(admin_mfa::last_used_step.eq(Some(step)))
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+        return Ok(claimed == 1);
+    }
+
+    let used = diesel::update(
+        admin_recovery_codes::table
+            .find((admin, hash_recovery_code(code)))
+            .filter(admin_recovery_codes::used_at.is_null()),
+    )
+    .set(admin_recovery_codes::used_at.eq(Some(now.naive_utc())))
+    .execute(conn)
+    .await
+    .map_err(DbError)?;
+    Ok(used == 1)
+}
+
+/******************************************/
+// Enrol 2FA Route
+/******************************************/
+/**
+ * @route   POST /api/v1/protected/2fa/enroll
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/protected/2fa/enroll",
+    tag = "admins",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "New secret, 2FA is enabled once a code is verified", body = MfaEnrollment),
+        (status = 401, description = "Invalid token"),
+        (status = 409, description = "Two-factor authentication is already enabled")
+    )
+)]
+#[instrument(name = "Enrol admin 2FA", skip(pool, req, auth_settings))]
+pub async fn enroll_mfa(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    auth_settings: web::Data<AuthSettings>,
+) -> Result<HttpResponse, CustomError> {
+    let admin_id = token_admin(&req)?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    if mfa_enabled(&mut conn, admin_id).await? {
+        return Err(mfa_error(StatusCode::CONFLICT, "Two-factor authentication is already enabled"));
+    }
+    let admin_email = admins::table
+        .find(admin_id)
+        .select(admins::email)
+        .first::<String>(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    let secret = generate_secret();
+    diesel::insert_into(admin_mfa::table)
+        .values((admin_mfa::admin_id.eq(admin_id), admin_mfa::secret.eq(&secret)))
+        .on_conflict(admin_mfa::admin_id)
+        .do_update()
+        .set((
+            admin_mfa::secret.eq(&secret),
+            admin_mfa::last_used_step.eq(None::<i64>),
+            admin_mfa::created_at.eq(Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    Ok(HttpResponse::Ok().json(MfaEnrollment {
+        provisioning_uri: provisioning_uri(&auth_settings.mfa_issuer, &admin_email, &secret),
+        secret,
+    }))
+}
+
+/******************************************/
+// Verify 2FA enrolment Route
+/******************************************/
+/**
+ * @route   POST /api/v1/protected/2fa/verify
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/protected/2fa/verify",
+    tag = "admins",
+    security(("bearer_auth" = [])),
+    request_body = MfaCodeBody,
+    responses(
+        (status = 200, description = "2FA enabled, returns the recovery codes. Log in again to get the role permissions", body = RecoveryCodes),
+        (status = 400, description = "No enrolment started or wrong code"),
+        (status = 401, description = "Invalid token"),
+        (status = 409, description = "Two-factor authentication is already enabled")
+    )
+)]
+#[instrument(name = "Verify admin 2FA enrolment", skip(pool, req, req_code))]
+pub async fn verify_mfa(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    req_code: web::Json<MfaCodeBody>,
+) -> Result<HttpResponse, CustomError> {
+    let admin_id = token_admin(&req)?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let pending = admin_mfa::table
+        .find(admin_id)
+        .select((admin_mfa::secret, admin_mfa::enabled_at.is_not_null()))
+        .first::<(String, bool)>(&mut conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    let secret = match pending {
+        None => return Err(CustomError::ValidationError("Start the enrolment first.".to_string())),
+        Some((_, true)) => return Err(mfa_error(StatusCode::CONFLICT, "Two-factor authentication is already enabled")),
+        Some((secret, false)) => secret,
+    };
+    let step = verify_totp(&secret, &req_code.code, Utc::now().timestamp() as u64, None)
+        .ok_or(CustomError::ValidationError("Invalid code.".to_string()))?;
+
+    let recovery_codes = generate_recovery_codes();
+    let hashes: Vec<_> = recovery_codes
+        .iter()
+        .map(|code| {
+            (
+                admin_recovery_codes::admin_id.eq(admin_id),
+                admin_recovery_codes::code_hash.eq(hash_recovery_code(code)),
+            )
+        })
+        .collect();
+    conn.transaction::<_, DieselError, _>(|conn| {
+        async move {
+            diesel::update(admin_mfa::table.find(admin_id))
+                .set((
+                    admin_mfa::enabled_at.eq(Some(Utc::now().naive_utc())),
+                    admin_mfa::last_used_step.eq(Some(step)),
+                ))
+                .execute(conn)
+                .await?;
+            diesel::delete(admin_recovery_codes::table.filter(admin_recovery_codes::admin_id.eq(admin_id)))
+                .execute(conn)
+                .await?;
+            diesel::insert_into(admin_recovery_codes::table)
+                .values(&hashes)
+                .execute(conn)
+                .await?;
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(DbError)?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodes { recovery_codes }))
+}
+
+/******************************************/
+// Disable 2FA Route
+/******************************************/
+/**
+ * @route   POST /api/v1/protected/2fa/disable
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/protected/2fa/disable",
+    tag = "admins",
+    security(("bearer_auth" = [])),
+    request_body = MfaCodeBody,
+    responses(
+        (status = 200, description = "2FA disabled and recovery codes deleted"),
+        (status = 400, description = "2FA not enabled or wrong code"),
+        (status = 401, description = "Invalid token"),
+        (status = 409, description = "Two-factor authentication is required for admins")
+    )
+)]
+#[instrument(name = "Disable admin 2FA", skip(pool, req, req_code, auth_settings))]
+pub async fn disable_mfa(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    req_code: web::Json<MfaCodeBody>,
+    auth_settings: web::Data<AuthSettings>,
+) -> Result<HttpResponse, CustomError> {
+    if auth_settings.admin_mfa_required {
+        return Err(mfa_error(StatusCode::CONFLICT, "Two-factor authentication is required for admins"));
+    }
+    let admin_id = token_admin(&req)?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    if !check_second_factor(&mut conn, admin_id, &req_code.code).await? {
+        return Err(CustomError::ValidationError("Two-factor authentication is not enabled or the code is invalid.".to_string()));
+    }
+    conn.transaction::<_, DieselError, _>(|conn| {
+        async move {
+            diesel::delete(admin_recovery_codes::table.filter(admin_recovery_codes::admin_id.eq(admin_id)))
+                .execute(conn)
+                .await?;
+            diesel::delete(admin_mfa::table.find(admin_id)).execute(conn).await?;
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(DbError)?;
+
+    Ok(HttpResponse::Ok().json(json!({"message": "Two-factor authentication disabled"})))
+}
+
+/******************************************/
+// Second factor login Route
+/******************************************/
+/**
+ * @route   POST /api/v1/admins/login/2fa
+ * @access  Public
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/admins/login/2fa",
+    tag = "admins",
+    request_body = MfaLoginBody,
+    responses(
+        (status = 200, description = "Logged in, returns a JWT token"),
+        (status = 401, description = "Invalid or expired challenge, wrong code or too many attempts"),
+        (status = 429, description = "Too many failed logins for the account or IP, locked for a while")
+    )
+)]
+#[instrument(name = "Admin login second factor", skip(req, pool, req_login, redis_service, auth_settings))]
+pub async fn login_mfa(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    req_login: web::Json<MfaLoginBody>,
+    redis_service: web::Data<RedisService>,
+    auth_settings: web::Data<AuthSettings>,
+) -> Result<HttpResponse, CustomError> {
+    let req_login = req_login.into_inner();
+    let challenge = verify_mfa_challenge(&req_login.challenge_token, Role::Admin)
+        .map_err(|err| AuthError::InvalidCredentials(anyhow::anyhow!(err)))?;
+    let admin_id = Uuid::parse_str(&challenge.sub)
+        .map_err(|err| AuthError::InvalidCredentials(anyhow::anyhow!(err)))?;
+
+    let attempts = redis_service
+        .increment_counter(&format!("mfa_attempts:{}", challenge.jti), (MFA_CHALLENGE_MINUTES * 60) as usize)
+        .await?;
+    if attempts > MAX_CHALLENGE_ATTEMPTS {
+        return Err(AuthError::InvalidCredentials(anyhow::anyhow!("Too many attempts, log in again")).into());
+    }
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+    // Wrong codes count against the account like wrong passwords, whatever challenge they came with
+    let admin_email = admins::table
+        .find(admin_id)
+        .select(admins::email)
+        .first::<String>(&mut conn)
+        .await
+        .map_err(DbError)?;
+    let forwarded_for = req.headers().get("X-Forwarded-For").and_then(|value| value.to_str().ok());
+    let client_ip = client_ip(req.peer_addr().map(|addr| addr.ip()), forwarded_for, &auth_settings.trusted_proxies);
+    let lockout = LoginLockout::new(&redis_service, &auth_settings.lockout, "admin");
+    lockout.ensure_not_locked(&admin_email, client_ip.as_deref()).await?;
+    if !check_second_factor(&mut conn, admin_id, &req_login.code).await? {
+        lockout.record_failure(&admin_email, client_ip.as_deref()).await?;
+        return Err(AuthError::InvalidCredentials(anyhow::anyhow!("Invalid second factor")).into());
+    }
+    // The admin may have been deactivated since the password was accepted
+    if !admin_is_active(&mut conn, admin_id).await? {
+        return Err(AuthError::InvalidCredentials(anyhow::anyhow!("Admin {} is deactivated", admin_id)).into());
+    }
+    consume_mfa_challenge(&redis_service, &challenge).await?;
+    lockout.clear_failures(&admin_email).await?;
+    let scopes = admin_scopes(&mut conn, admin_id).await?;
+
+    let (token, sid) = create_jwt(&admin_id.to_string(), Role::Admin, scopes)?;
+    redis_service.set_session(&sid, &admin_id.to_string(), true).await?;
+    redis_service.track_session(&admin_id.to_string(), &sid).await?;
+    Ok(HttpResponse::Ok().json(json!({"token": token})))
+}
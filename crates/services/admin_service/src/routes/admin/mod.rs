@@ -1,4 +1,7 @@
 pub mod crud;
 pub mod model;
 pub mod validate_user;
-pub mod user;
\ No newline at end of file
+pub mod user;
+pub mod invitations;
+pub mod admins;
+pub mod mfa;
\ No newline at end of file
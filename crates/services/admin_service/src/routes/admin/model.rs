@@ -1,8 +1,10 @@
 use chrono::NaiveDateTime;
 use diesel::{Queryable, Selectable};
-use kafka::setup::KafkaTopic;
+use helpers::validations::validations::CreateUserBody;
+use kafka::models::AccountStatus;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Queryable, Deserialize, Serialize, Debug)]
 pub struct Admin {
@@ -13,29 +15,180 @@ pub struct Admin {
     pub created_at: Option<NaiveDateTime>,
 }
 
-#[derive(Queryable, Deserialize, Serialize, Debug, Selectable)]
+#[derive(Queryable, Deserialize, Serialize, Debug, Selectable, ToSchema)]
 #[diesel(table_name = crate::schema::users)]
 pub struct User {
     pub id: Uuid,
     pub username: String,
     pub email: String,
-    pub created_at: Option<NaiveDateTime>
+    pub created_at: NaiveDateTime,
+    pub status: DbAccountStatus,
+    pub status_changed_at: Option<NaiveDateTime>,
+    /// When user_service last confirmed the email, `None` while unverified
+    pub email_verified_at: Option<NaiveDateTime>
 }
 
-#[derive(Deserialize)]
-pub struct Paginate {
-    pub page: i64,
-    pub limit: i64
+/// Mirror of the account status kept by user_service
+#[derive(diesel_derive_enum::DbEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[ExistingTypePath = "crate::schema::sql_types::AccountStatus"]
+#[serde(rename_all = "snake_case")]
+pub enum DbAccountStatus {
+    Active,
+    Deactivated,
+    Suspended,
+    PendingDeletion,
+    Erased,
 }
 
-#[derive(Serialize, Debug)]
-pub struct DeleteUserMessage {
-    pub id: uuid::Uuid
+impl From<AccountStatus> for DbAccountStatus {
+    fn from(status: AccountStatus) -> Self {
+        match status {
+            AccountStatus::Active => DbAccountStatus::Active,
+            AccountStatus::Deactivated => DbAccountStatus::Deactivated,
+            AccountStatus::Suspended => DbAccountStatus::Suspended,
+            AccountStatus::PendingDeletion => DbAccountStatus::PendingDeletion,
+            AccountStatus::Erased => DbAccountStatus::Erased,
+        }
+    }
 }
 
-impl KafkaTopic for DeleteUserMessage {
-    fn topic_name(&self) -> String {
-        "admin_events".into()
-    }
+fn default_list_limit() -> i64 {
+    20
+}
+
+/// Most users returned by one page of the listing
+pub const MAX_LIST_LIMIT: i64 = 100;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    #[default]
+    CreatedAt,
+    Username,
+    Email,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserListQuery {
+    /// Case insensitive prefix of the username or email
+    pub q: Option<String>,
+    /// Only users whose email is (or is not) verified
+    pub verified: Option<bool>,
+    /// Only users who deactivated their account, including those waiting out the deletion grace period
+    pub deactivated: Option<bool>,
+    /// Only users created at or after this time
+    pub created_from: Option<NaiveDateTime>,
+    /// Only users created before this time
+    pub created_to: Option<NaiveDateTime>,
+    #[serde(default)]
+    #[param(inline)]
+    pub sort: UserSort,
+    #[serde(default)]
+    #[param(inline)]
+    pub order: SortOrder,
+    /// Page size, 20 by default and at most 100
+    #[serde(default = "default_list_limit")]
+    pub limit: i64,
+    /// `next_cursor` of the previous page, the same filters and sort have to be sent with it
+    pub cursor: Option<String>,
+}
+
+/// Sort key and id of the last user of a page, the listing continues after it
+#[derive(Serialize, Deserialize, Debug)]
+pub enum UserCursor {
+    CreatedAt(NaiveDateTime, Uuid),
+    Username(String, Uuid),
+    Email(String, Uuid),
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct UserListResponse {
+    /// Users matching the filters across all pages
+    pub total: i64,
+    pub users: Vec<User>,
+    /// Pass as `cursor` to get the next page, `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+
+#[derive(Deserialize, ToSchema)]
+pub struct RegisterAdminBody {
+    #[serde(flatten)]
+    pub admin: CreateUserBody,
+    /// Token from the invitation email, it only works for the invited address
+    pub invitation_token: String,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CreateInvitationBody {
+    pub email: String,
+    /// Role the admin gets when registering
+    pub role_id: Uuid,
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug, ToSchema)]
+#[diesel(table_name = crate::schema::admin_invitations)]
+pub struct Invitation {
+    pub id: Uuid,
+    pub email: String,
+    pub role_id: Uuid,
+    pub invited_by: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::schema::admins)]
+pub struct AdminAccount {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub is_active: bool,
+    pub deactivated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AdminResponse {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub is_active: bool,
+    pub deactivated_at: Option<NaiveDateTime>,
+    /// Names of the admin's roles
+    pub roles: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct MfaCodeBody {
+    /// Current code of the authenticator app, or an unused recovery code
+    pub code: String,
 }
 
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct MfaLoginBody {
+    /// Token returned by the password login
+    pub challenge_token: String,
+    pub code: String,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct MfaEnrollment {
+    /// Base32 secret for apps that can't scan the QR code
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct RecoveryCodes {
+    /// Shown only once, each code can be used a single time instead of an app code
+    pub recovery_codes: Vec<String>,
+}
@@ -1,22 +1,31 @@
 use actix_web::http::StatusCode;
 use actix_web::{web, HttpResponse};
 use anyhow::Context;
-use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
-use diesel::prelude::OptionalExtension;
+use chrono::Utc;
+use diesel::pg::Pg;
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, SelectableHelper, TextExpressionMethods};
+use diesel::prelude::{define_sql_function, OptionalExtension};
 use diesel_async::RunQueryDsl;
 use errors::{AuthError, CustomError};
 use flume::Sender;
+use helpers::auth_jwt::auth::Claims;
+use helpers::pagination::{decode_cursor, encode_cursor};
 use kafka::channel::{push_to_broker, KafkaMessage};
+use kafka::models::{AdminEventsMessage, AdminUserAction};
+use middleware::permissions::Authorized;
 use lib_config::db::db::PgPool;
 use serde_json::json;
 use uuid::Uuid;
 
 use crate::db_error::DbError;
-use crate::routes::admin::model::{DeleteUserMessage, User};
+use crate::permissions::{UsersDelete, UsersRead, UsersSuspend};
+use crate::routes::admin::model::{DbAccountStatus, User};
 
-use super::model::Paginate;
+use super::model::{SortOrder, UserCursor, UserListQuery, UserListResponse, UserSort, MAX_LIST_LIMIT};
 use tracing::instrument;
 
+define_sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
+
 /******************************************/
 // Get user Route
 /******************************************/
@@ -24,10 +33,23 @@ use tracing::instrument;
  * @route   GET /ap1/v1/auth/users/{user_id}
  * @access  Privare
  */
-#[instrument(name = "Get user by id", skip(user_id, pool))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/users/{user_id}",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    params(("user_id" = Uuid, Path, description = "Id of the user")),
+    responses(
+        (status = 200, description = "User found", body = User),
+        (status = 403, description = "Missing users:read permission"),
+        (status = 404, description = "User not found")
+    )
+)]
+#[instrument(name = "Get user by id", skip(user_id, pool, _admin))]
 pub async fn get_user_by_id(
     pool: web::Data<PgPool>,
-    user_id: web::Path<Uuid>
+    user_id: web::Path<Uuid>,
+    _admin: Authorized<UsersRead>,
 ) -> Result<HttpResponse, CustomError> {
     use crate::schema::users;
 
@@ -53,82 +75,283 @@ pub async fn get_user_by_id(
     Ok(HttpResponse::Ok().json(res))
 }
 /******************************************/
-// Get user by ID Route
+// Get users Route
 /******************************************/
 /**
  * @route   GET /ap1/v1/auth/users/
  * @access  Private
  */
-#[instrument(name = "Get users", skip(query, pool))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/users/",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    params(UserListQuery),
+    responses(
+        (status = 200, description = "Page of users matching the filters", body = UserListResponse),
+        (status = 400, description = "Invalid cursor or limit"),
+        (status = 403, description = "Missing users:read permission")
+    )
+)]
+#[instrument(name = "Get users", skip(query, pool, _admin))]
 pub async fn get_users(
     pool: web::Data<PgPool>,
-    query: web::Query<Paginate>
+    query: web::Query<UserListQuery>,
+    _admin: Authorized<UsersRead>,
 ) -> Result<HttpResponse, CustomError> {
     use crate::schema::users;
 
     let query = query.into_inner();
-    let offset =  (query.page - 1) * query.limit;
+    if query.limit < 1 || query.limit > MAX_LIST_LIMIT {
+        return Err(CustomError::ValidationError(format!("limit must be between 1 and {}", MAX_LIST_LIMIT)));
+    }
 
     let mut conn = pool.get()
         .await
         .context("Failed to get connection from pool")?;
 
-    let user_ids = users::table
-        .select(users::id)
-        .offset(offset)
-        .limit(query.limit)
-        .get_results::<Uuid>(&mut conn)
+    let total = filtered_users(&query)
+        .count()
+        .get_result::<i64>(&mut conn)
         .await
         .map_err(DbError)?;
 
-    if user_ids.len() == 0 {
-        return Ok(HttpResponse::NotFound().json(json!({ "page": query.page, "limit": query.limit, "message": "No more results" })))
+    // The lowercased sort keys come back from Postgres, its lower() and Rust's to_lowercase
+    // don't agree on every character and the cursor has to match what the filter compares
+    let mut page = filtered_users(&query).select((User::as_select(), lower(users::username), lower(users::email)));
+
+    if let Some(cursor) = &query.cursor {
+        let cursor = decode_cursor::<UserCursor>(cursor)
+            .ok_or_else(|| CustomError::ValidationError("Invalid cursor".to_string()))?;
+        let desc = query.order == SortOrder::Desc;
+
+        page = match (query.sort, cursor) {
+            (UserSort::CreatedAt, UserCursor::CreatedAt(at, id)) if desc => page
+                .filter(users::created_at.lt(at).or(users::created_at.eq(at).and(users::id.lt(id)))),
+            (UserSort::CreatedAt, UserCursor::CreatedAt(at, id)) => page
+                .filter(users::created_at.gt(at).or(users::created_at.eq(at).and(users::id.gt(id)))),
+            (UserSort::Username, UserCursor::Username(name, id)) if desc => page
+                .filter(lower(users::username).lt(name.clone()).or(lower(users::username).eq(name).and(users::id.lt(id)))),
+            (UserSort::Username, UserCursor::Username(name, id)) => page
+                .filter(lower(users::username).gt(name.clone()).or(lower(users::username).eq(name).and(users::id.gt(id)))),
+            (UserSort::Email, UserCursor::Email(email, id)) if desc => page
+                .filter(lower(users::email).lt(email.clone()).or(lower(users::email).eq(email).and(users::id.lt(id)))),
+            (UserSort::Email, UserCursor::Email(email, id)) => page
+                .filter(lower(users::email).gt(email.clone()).or(lower(users::email).eq(email).and(users::id.gt(id)))),
+            _ => return Err(CustomError::ValidationError("Cursor does not match the requested sort".to_string())),
+        };
     }
 
-    Ok(HttpResponse::Ok().json(json!({ "page": query.page, "limit": query.limit, "result": user_ids })))
+    page = match (query.sort, query.order) {
+        (UserSort::CreatedAt, SortOrder::Asc) => page.order((users::created_at.asc(), users::id.asc())),
+        (UserSort::CreatedAt, SortOrder::Desc) => page.order((users::created_at.desc(), users::id.desc())),
+        (UserSort::Username, SortOrder::Asc) => page.order((lower(users::username).asc(), users::id.asc())),
+        (UserSort::Username, SortOrder::Desc) => page.order((lower(users::username).desc(), users::id.desc())),
+        (UserSort::Email, SortOrder::Asc) => page.order((lower(users::email).asc(), users::id.asc())),
+        (UserSort::Email, SortOrder::Desc) => page.order((lower(users::email).desc(), users::id.desc())),
+    };
+
+    // One extra row tells whether another page follows without a second query
+    let mut rows = page
+        .limit(query.limit + 1)
+        .load::<(User, String, String)>(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    let has_more = rows.len() as i64 > query.limit;
+    rows.truncate(query.limit as usize);
+
+    let next_cursor = rows.last()
+        .filter(|_| has_more)
+        .map(|(last, username, email)| {
+            let position = match query.sort {
+                UserSort::CreatedAt => UserCursor::CreatedAt(last.created_at, last.id),
+                UserSort::Username => UserCursor::Username(username.clone(), last.id),
+                UserSort::Email => UserCursor::Email(email.clone(), last.id),
+            };
+            encode_cursor(&position)
+        });
+    let users = rows.into_iter().map(|(user, _, _)| user).collect();
+
+    Ok(HttpResponse::Ok().json(UserListResponse { total, users, next_cursor }))
 }
-/******************************************/
-// Dekete user by ID Route
-/******************************************/
-/**
- * @route   DELETE /ap1/v1/auth/users/{user_id}
- * @access  Private
- */
-#[instrument(name = "Delete user", skip(user_id, pool, kafka_producer))]
-pub async fn delete_user(
-    pool: web::Data<PgPool>,
-    user_id: web::Path<Uuid>,
-    kafka_producer: web::Data<Sender<KafkaMessage<String>>>
+
+/// Users matching the search and filters of the listing, shared by the count and the page query
+fn filtered_users(query: &UserListQuery) -> crate::schema::users::BoxedQuery<'static, Pg> {
+    use crate::schema::users;
+
+    let mut users_query = users::table.into_boxed();
+
+    if let Some(prefix) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let escaped = prefix
+            .to_lowercase()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let pattern = format!("{}%", escaped);
+        users_query = users_query.filter(
+            lower(users::username).like(pattern.clone()).or(lower(users::email).like(pattern))
+        );
+    }
+
+    if let Some(verified) = query.verified {
+        users_query = if verified {
+            users_query.filter(users::email_verified_at.is_not_null())
+        } else {
+            users_query.filter(users::email_verified_at.is_null())
+        };
+    }
+
+    if let Some(deactivated) = query.deactivated {
+        let statuses = [DbAccountStatus::Deactivated, DbAccountStatus::PendingDeletion];
+        users_query = if deactivated {
+            users_query.filter(users::status.eq_any(statuses))
+        } else {
+            users_query.filter(users::status.ne_all(statuses))
+        };
+    }
+
+    if let Some(from) = query.created_from {
+        users_query = users_query.filter(users::created_at.ge(from));
+    }
+    if let Some(to) = query.created_to {
+        users_query = users_query.filter(users::created_at.lt(to));
+    }
+
+    users_query
+}
+
+/// Checks the user exists and asks user_service, which owns accounts, to apply the action
+async fn request_user_action(
+    pool: &PgPool,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    admin: Claims,
+    user_id: Uuid,
+    action: AdminUserAction,
 ) -> Result<HttpResponse, CustomError> {
     use crate::schema::users;
 
-    let user_id = user_id.into_inner();
+    let admin_id = Uuid::parse_str(&admin.sub)
+        .map_err(|err| CustomError::ValidationError(format!("Invalid admin ID format: {}", err)))?;
 
     let mut conn = pool.get()
         .await
         .context("Failed to get connection from pool")?;
 
-    let res = diesel::delete(users::table)
-        .filter(users::id.eq(user_id))
-        .execute(&mut conn)
+    let status = users::table
+        .select(users::status)
+        .filter(users::id.eq(&user_id))
+        .get_result::<DbAccountStatus>(&mut conn)
         .await
+        .optional()
         .map_err(DbError)?;
+    match status {
+        None | Some(DbAccountStatus::Erased) => {
+            return Err(CustomError::DatabaseError {
+                msg: format!("No user {} to {:?}", user_id, action),
+                resp: "User not found".into(),
+                status_code: StatusCode::NOT_FOUND
+            })
+        },
+        Some(_) => {}
+    }
+
+    let message = AdminEventsMessage {
+        user_id,
+        action,
+        admin_id,
+        time: Utc::now().naive_utc(),
+    };
+    push_to_broker(kafka_producer, &message)
+        .await
+        .context("Failed to send message to broker")?;
 
+    Ok(HttpResponse::Accepted().json(json!({ "user_id": user_id, "action": action })))
+}
 
-    if res == 0 {
-        Err(CustomError::DatabaseError {
-            msg: "Returned usize == 0".into(),
-            resp: "Did not find user".into(),
-            status_code: StatusCode::NOT_FOUND
-        })
-    } else {
-        let message = DeleteUserMessage {
-            id: user_id
-        };
+/******************************************/
+// Delete user by ID Route
+/******************************************/
+/**
+ * @route   DELETE /ap1/v1/auth/users/{user_id}
+ * @access  Private
+ */
+#[utoipa::path(
+    delete,
+    path = "/api/v1/auth/users/{user_id}",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    params(("user_id" = Uuid, Path, description = "Id of the user")),
+    responses(
+        (status = 202, description = "Erasure requested, every service removes or anonymises the user's data"),
+        (status = 403, description = "Missing users:delete permission"),
+        (status = 404, description = "User not found or already erased")
+    )
+)]
+#[instrument(name = "Delete user", skip(user_id, pool, kafka_producer, admin))]
+pub async fn delete_user(
+    pool: web::Data<PgPool>,
+    user_id: web::Path<Uuid>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    admin: Authorized<UsersDelete>,
+) -> Result<HttpResponse, CustomError> {
+    request_user_action(&pool, &kafka_producer, admin.into_inner(), user_id.into_inner(), AdminUserAction::Erase).await
+}
 
-        let _ = push_to_broker(&kafka_producer, &message).await
-            .map_err(|_| tracing::error!("Failed to send message to broker: {:?}", message));
+/******************************************/
+// Suspend user Route
+/******************************************/
+/**
+ * @route   POST /ap1/v1/auth/users/{user_id}/suspend
+ * @access  Private
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/users/{user_id}/suspend",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    params(("user_id" = Uuid, Path, description = "Id of the user")),
+    responses(
+        (status = 202, description = "Suspension requested, the user is logged out and can't log in until reactivated"),
+        (status = 403, description = "Missing users:suspend permission"),
+        (status = 404, description = "User not found or erased")
+    )
+)]
+#[instrument(name = "Suspend user", skip(user_id, pool, kafka_producer, admin))]
+pub async fn suspend_user(
+    pool: web::Data<PgPool>,
+    user_id: web::Path<Uuid>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    admin: Authorized<UsersSuspend>,
+) -> Result<HttpResponse, CustomError> {
+    request_user_action(&pool, &kafka_producer, admin.into_inner(), user_id.into_inner(), AdminUserAction::Suspend).await
+}
 
-        Ok(HttpResponse::Ok().json(format!("Deleted user: {}", user_id)))
-    }
+/******************************************/
+// Reactivate user Route
+/******************************************/
+/**
+ * @route   POST /ap1/v1/auth/users/{user_id}/reactivate
+ * @access  Private
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/users/{user_id}/reactivate",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    params(("user_id" = Uuid, Path, description = "Id of the user")),
+    responses(
+        (status = 202, description = "Reactivation requested, also cancels a pending deletion"),
+        (status = 403, description = "Missing users:suspend permission"),
+        (status = 404, description = "User not found or erased")
+    )
+)]
+#[instrument(name = "Reactivate user", skip(user_id, pool, kafka_producer, admin))]
+pub async fn reactivate_user(
+    pool: web::Data<PgPool>,
+    user_id: web::Path<Uuid>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    admin: Authorized<UsersSuspend>,
+) -> Result<HttpResponse, CustomError> {
+    request_user_action(&pool, &kafka_producer, admin.into_inner(), user_id.into_inner(), AdminUserAction::Reactivate).await
 }
@@ -22,6 +22,7 @@ async fn get_stored_credentials(
 
     let row: Option<Vec<(String, Uuid)>> = admins
         .filter(email.eq(user_email))
+        .filter(is_active.eq(true))
         .select((password_hash, id))
         .load::<(String, Uuid)>(&mut conn)
         .await
@@ -0,0 +1,169 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use errors::CustomError;
+use helpers::csv::{to_csv, CsvRecord};
+use lib_config::db::db::PgPool;
+use middleware::permissions::Authorized;
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::analytics::{self, ActiveUsers, GameRatings, SessionDurations, SignupFunnel};
+use crate::permissions::AnalyticsRead;
+
+use super::models::{AnalyticsQuery, ExportFormat, GameRatingsFilter};
+
+/// Rows as JSON, or as a CSV attachment named after the report
+fn export<T: Serialize + CsvRecord>(rows: Vec<T>, format: ExportFormat, report: &str) -> HttpResponse {
+    match format {
+        ExportFormat::Json => HttpResponse::Ok().json(rows),
+        ExportFormat::Csv => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(format!("{}.csv", report))],
+            })
+            .body(to_csv(&rows)),
+    }
+}
+
+/******************************************/
+// Active users Route
+/******************************************/
+/**
+ * @route   GET /api/v1/auth/analytics/active-users
+ * @access  Private
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/analytics/active-users",
+    tag = "analytics",
+    security(("bearer_auth" = [])),
+    params(AnalyticsQuery),
+    responses(
+        (status = 200, description = "Distinct active users per bucket, DAU with day buckets and MAU with month buckets", body = [ActiveUsers]),
+        (status = 400, description = "Invalid range"),
+        (status = 403, description = "Missing analytics:read permission")
+    )
+)]
+#[instrument(name = "Get active users", skip(pool, _admin))]
+pub async fn get_active_users(
+    pool: web::Data<PgPool>,
+    query: web::Query<AnalyticsQuery>,
+    _admin: Authorized<AnalyticsRead>,
+) -> Result<HttpResponse, CustomError> {
+    let (from, to) = query.range()?;
+
+    let mut conn = pool.get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let rows = analytics::active_users(&mut conn, query.bucket.as_str(), from, to).await?;
+    Ok(export(rows, query.format, "active_users"))
+}
+
+/******************************************/
+// Signup funnel Route
+/******************************************/
+/**
+ * @route   GET /api/v1/auth/analytics/signup-funnel
+ * @access  Private
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/analytics/signup-funnel",
+    tag = "analytics",
+    security(("bearer_auth" = [])),
+    params(AnalyticsQuery),
+    responses(
+        (status = 200, description = "Users grouped by signup bucket with how many verified their email and then rated a game", body = [SignupFunnel]),
+        (status = 400, description = "Invalid range"),
+        (status = 403, description = "Missing analytics:read permission")
+    )
+)]
+#[instrument(name = "Get signup funnel", skip(pool, _admin))]
+pub async fn get_signup_funnel(
+    pool: web::Data<PgPool>,
+    query: web::Query<AnalyticsQuery>,
+    _admin: Authorized<AnalyticsRead>,
+) -> Result<HttpResponse, CustomError> {
+    let (from, to) = query.range()?;
+
+    let mut conn = pool.get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let rows = analytics::signup_funnel(&mut conn, query.bucket.as_str(), from, to).await?;
+    Ok(export(rows, query.format, "signup_funnel"))
+}
+
+/******************************************/
+// Game ratings Route
+/******************************************/
+/**
+ * @route   GET /api/v1/auth/analytics/ratings
+ * @access  Private
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/analytics/ratings",
+    tag = "analytics",
+    security(("bearer_auth" = [])),
+    params(AnalyticsQuery, GameRatingsFilter),
+    responses(
+        (status = 200, description = "New ratings per game and bucket, most rated games first", body = [GameRatings]),
+        (status = 400, description = "Invalid range"),
+        (status = 403, description = "Missing analytics:read permission")
+    )
+)]
+#[instrument(name = "Get game ratings", skip(pool, _admin))]
+pub async fn get_game_ratings(
+    pool: web::Data<PgPool>,
+    query: web::Query<AnalyticsQuery>,
+    filter: web::Query<GameRatingsFilter>,
+    _admin: Authorized<AnalyticsRead>,
+) -> Result<HttpResponse, CustomError> {
+    let (from, to) = query.range()?;
+
+    let mut conn = pool.get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let rows = analytics::game_ratings(&mut conn, query.bucket.as_str(), from, to, filter.game_slug.as_deref()).await?;
+    Ok(export(rows, query.format, "game_ratings"))
+}
+
+/******************************************/
+// Session durations Route
+/******************************************/
+/**
+ * @route   GET /api/v1/auth/analytics/sessions
+ * @access  Private
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/analytics/sessions",
+    tag = "analytics",
+    security(("bearer_auth" = [])),
+    params(AnalyticsQuery),
+    responses(
+        (status = 200, description = "Durations of the sessions started in each bucket, from login and logout pairs", body = [SessionDurations]),
+        (status = 400, description = "Invalid range"),
+        (status = 403, description = "Missing analytics:read permission")
+    )
+)]
+#[instrument(name = "Get session durations", skip(pool, _admin))]
+pub async fn get_session_durations(
+    pool: web::Data<PgPool>,
+    query: web::Query<AnalyticsQuery>,
+    _admin: Authorized<AnalyticsRead>,
+) -> Result<HttpResponse, CustomError> {
+    let (from, to) = query.range()?;
+
+    let mut conn = pool.get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let rows = analytics::session_durations(&mut conn, query.bucket.as_str(), from, to).await?;
+    Ok(export(rows, query.format, "session_durations"))
+}
@@ -0,0 +1,2 @@
+pub mod analytics;
+pub mod models;
\ No newline at end of file
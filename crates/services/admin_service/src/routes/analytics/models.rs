@@ -0,0 +1,103 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use errors::CustomError;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+/// Widest time range one analytics query may cover
+pub const MAX_RANGE_DAYS: i64 = 731;
+
+/// Range used when `from` is left out
+pub const DEFAULT_RANGE_DAYS: i64 = 30;
+
+/// Width of the time buckets results are grouped in, day buckets give DAU and month buckets MAU
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Bucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl Bucket {
+    /// Unit understood by Postgres `date_trunc`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Bucket::Day => "day",
+            Bucket::Week => "week",
+            Bucket::Month => "month",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AnalyticsQuery {
+    /// Start of the range, 30 days before `to` by default
+    pub from: Option<NaiveDateTime>,
+    /// End of the range (exclusive), now by default
+    pub to: Option<NaiveDateTime>,
+    #[serde(default)]
+    #[param(inline)]
+    pub bucket: Bucket,
+    /// `csv` downloads the rows as a CSV file
+    #[serde(default)]
+    #[param(inline)]
+    pub format: ExportFormat,
+}
+
+impl AnalyticsQuery {
+    /// The requested range with defaults applied, refused when empty or wider than `MAX_RANGE_DAYS`
+    pub fn range(&self) -> Result<(NaiveDateTime, NaiveDateTime), CustomError> {
+        let to = self.to.unwrap_or_else(|| Utc::now().naive_utc());
+        let from = match self.from {
+            Some(from) => from,
+            None => to
+                .checked_sub_signed(Duration::days(DEFAULT_RANGE_DAYS))
+                .ok_or_else(|| CustomError::ValidationError("to is out of range".to_string()))?,
+        };
+
+        if from >= to {
+            return Err(CustomError::ValidationError("from must be before to".to_string()));
+        }
+        if to - from > Duration::days(MAX_RANGE_DAYS) {
+            return Err(CustomError::ValidationError(format!("The range can't be wider than {} days", MAX_RANGE_DAYS)));
+        }
+        Ok((from, to))
+    }
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GameRatingsFilter {
+    /// Only ratings of this game
+    pub game_slug: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnalyticsQuery, Bucket, ExportFormat, DEFAULT_RANGE_DAYS};
+    use chrono::{Duration, NaiveDateTime};
+
+    fn query(from: Option<NaiveDateTime>, to: Option<NaiveDateTime>) -> AnalyticsQuery {
+        AnalyticsQuery { from, to, bucket: Bucket::Day, format: ExportFormat::Json }
+    }
+
+    #[test]
+    fn range_defaults_and_bounds() {
+        let to = NaiveDateTime::parse_from_str("2024-03-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        assert_eq!(query(None, Some(to)).range().unwrap(), (to - Duration::days(DEFAULT_RANGE_DAYS), to));
+        assert!(query(Some(to), Some(to)).range().is_err());
+        assert!(query(Some(to - Duration::days(800)), Some(to)).range().is_err());
+        assert!(query(None, Some(NaiveDateTime::MIN)).range().is_err());
+    }
+}
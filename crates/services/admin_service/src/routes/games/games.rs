@@ -1,7 +1,7 @@
 use anyhow::Context;
 use flume::Sender;
 use kafka::channel::{push_to_broker, KafkaMessage};
-use helpers::auth_jwt::auth::Claims;
+use middleware::permissions::Authorized;
 use lib_config::db::db::PgPool;
 use errors::{AuthError, CustomError};
 use actix_web::{http::StatusCode, web, HttpResponse};
@@ -14,6 +14,7 @@ use crate::schema::games::dsl::*;
 use lib_config::session::redis::RedisService;
 
 use super::models::KafkaGameMessage;
+use crate::permissions::{GamesRead, GamesWrite};
 
 /******************************************/
 // Create New Game Route
@@ -22,15 +23,28 @@ use super::models::KafkaGameMessage;
  * @route   POST /api/v1/auth/games/new
  * @access  Private
  */
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/games/new",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    request_body = CreateGameBody,
+    responses(
+        (status = 201, description = "Game created", body = Game),
+        (status = 400, description = "Invalid tags, platforms or cover image url"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing games:write permission")
+    )
+)]
 #[instrument(name = "Create a new game", skip(pool, kafka_producer, admin))]
 pub async fn create_game(
     pool: web::Data<PgPool>,
     req_game: web::Json<CreateGameBody>, 
     kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
-    admin: web::ReqData<Claims>,
+    admin: Authorized<GamesWrite>,
 ) -> Result<HttpResponse, CustomError> {
     let pool = pool.clone();
-    let game_data = req_game.into_inner();
+    let game_data = req_game.into_inner().validate()?;
     
     let game_slug = game_data.name.to_lowercase().replace(" ", "-");
     let game_id = Uuid::new_v4();
@@ -53,6 +67,11 @@ pub async fn create_game(
         created_by_uid: Some(admin_id),
         is_admin: Some(true),
         genre: game_data.genre,
+        tags: game_data.tags,
+        platforms: game_data.platforms,
+        release_date: game_data.release_date,
+        publisher: game_data.publisher,
+        cover_image_url: game_data.cover_image_url,
     };
 
     let result = diesel::insert_into(games)
@@ -83,10 +102,23 @@ pub async fn create_game(
  * @route   GET /api/v1/auth/games/get/{slug}
  * @access  Private
  */
-#[instrument(name = "Get game", skip(pool))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/games/get/{slug}",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    params(("slug" = String, Path, description = "Slug of the game")),
+    responses(
+        (status = 200, description = "Game found", body = Game),
+        (status = 403, description = "Missing games:read permission"),
+        (status = 404, description = "Game not found")
+    )
+)]
+#[instrument(name = "Get game", skip(pool, _admin))]
 pub async fn get_game(
     pool: web::Data<PgPool>,
-    game_slug: web::Path<String>
+    game_slug: web::Path<String>,
+    _admin: Authorized<GamesRead>,
 ) -> Result<HttpResponse, CustomError> {
     let mut conn = pool
         .get()
@@ -110,15 +142,30 @@ pub async fn get_game(
  * @route   PATCH /api/v1/auth/games/{slug}
  * @access  Private
  */
-#[instrument(name = "Update game", skip(pool, kafka_producer))]
+#[utoipa::path(
+    patch,
+    path = "/api/v1/auth/games/update/{slug}",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    params(("slug" = String, Path, description = "Slug of the game")),
+    request_body = UpdateGameBody,
+    responses(
+        (status = 200, description = "Game updated", body = Game),
+        (status = 400, description = "Invalid tags, platforms or cover image url"),
+        (status = 403, description = "Missing games:write permission"),
+        (status = 404, description = "Game not found")
+    )
+)]
+#[instrument(name = "Update game", skip(pool, kafka_producer, _admin))]
 pub async fn update_game(
     pool: web::Data<PgPool>,
     game_slug: web::Path<String>,
     game_data: web::Json<UpdateGameBody>,
-    kafka_producer: web::Data<Sender<KafkaMessage<String>>>
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    _admin: Authorized<GamesWrite>,
 ) -> Result<HttpResponse, CustomError> {
     let game_slug = game_slug.into_inner();
-    let updated_data = game_data.into_inner();
+    let updated_data = game_data.into_inner().validate()?;
 
     let mut conn = pool
         .get()
@@ -141,6 +188,21 @@ pub async fn update_game(
     if let Some(new_genre) = updated_data.genre.clone() {
         game.genre = Some(new_genre);
     }
+    if let Some(new_tags) = updated_data.tags.clone() {
+        game.tags = new_tags;
+    }
+    if let Some(new_platforms) = updated_data.platforms.clone() {
+        game.platforms = new_platforms;
+    }
+    if let Some(new_release_date) = updated_data.release_date {
+        game.release_date = Some(new_release_date);
+    }
+    if let Some(new_publisher) = updated_data.publisher.clone() {
+        game.publisher = Some(new_publisher);
+    }
+    if let Some(new_cover_image_url) = updated_data.cover_image_url.clone() {
+        game.cover_image_url = Some(new_cover_image_url);
+    }
 
     diesel::update(games
         .filter(slug.eq(&game_slug)))
@@ -163,11 +225,24 @@ pub async fn update_game(
  * @route   POST /api/v1/auth/games/remove/{slug}
  * @access  Private
  */
-#[instrument(name = "Create a new game", skip(pool, kafka_producer))]
+#[utoipa::path(
+    delete,
+    path = "/api/v1/auth/games/remove/{slug}",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    params(("slug" = String, Path, description = "Slug of the game")),
+    responses(
+        (status = 200, description = "Game deleted"),
+        (status = 403, description = "Missing games:write permission"),
+        (status = 404, description = "Game not found")
+    )
+)]
+#[instrument(name = "Create a new game", skip(pool, kafka_producer, _admin))]
 pub async fn delete_game(
     game_slug: web::Path<String>,
     pool: web::Data<PgPool>,
-    kafka_producer: web::Data<Sender<KafkaMessage<String>>>
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    _admin: Authorized<GamesWrite>,
 ) -> Result<HttpResponse, CustomError> {
     let game_slug = game_slug.into_inner();
 
@@ -4,10 +4,13 @@ use diesel::{Queryable, Insertable};
 use crate::schema::games;
 use diesel::prelude::*;
 use chrono;
+use errors::CustomError;
+use helpers::validations::validations::{GameLabels, ImageUrl};
+use utoipa::ToSchema;
 
 use kafka::setup::KafkaTopic;
 
-#[derive(Serialize, Deserialize, Queryable, Insertable, AsChangeset)]
+#[derive(Serialize, Deserialize, Queryable, Insertable, AsChangeset, ToSchema)]
 #[diesel(table_name = games)]
 pub struct Game {
     pub slug: String,
@@ -18,22 +21,70 @@ pub struct Game {
     pub created_by_uid: Option<Uuid>,
     pub is_admin: Option<bool>,
     pub genre: Option<String>,
+    pub tags: Vec<String>,
+    pub platforms: Vec<String>,
+    pub release_date: Option<chrono::NaiveDate>,
+    pub publisher: Option<String>,
+    pub cover_image_url: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Insertable, Debug, Queryable)]
+#[derive(Serialize, Deserialize, Insertable, Debug, Queryable, ToSchema)]
 #[diesel(table_name = games)]
 pub struct CreateGameBody {
     pub name: String,
     pub title: Option<String>,
     pub description: Option<String>,
     pub genre: Option<String>,
+    /// Extra genres and labels, lowercased
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    pub release_date: Option<chrono::NaiveDate>,
+    pub publisher: Option<String>,
+    pub cover_image_url: Option<String>,
 }
-#[derive(Serialize, Deserialize, Insertable, Debug, Queryable)]
+
+impl CreateGameBody {
+    /// Normalizes tags and platforms and checks the cover image url
+    pub fn validate(mut self) -> Result<Self, CustomError> {
+        self.tags = GameLabels::parse(self.tags, "tag")?.into_inner();
+        self.platforms = GameLabels::parse(self.platforms, "platform")?.into_inner();
+        if let Some(url) = self.cover_image_url.take() {
+            self.cover_image_url = Some(ImageUrl::parse(url)?.as_ref().to_string());
+        }
+        Ok(self)
+    }
+}
+#[derive(Serialize, Deserialize, Insertable, Debug, Queryable, ToSchema)]
 #[diesel(table_name = games)]
 pub struct UpdateGameBody {
     pub title: Option<String>,
     pub description: Option<String>,
     pub genre: Option<String>,
+    /// Replaces every tag of the game
+    pub tags: Option<Vec<String>>,
+    /// Replaces every platform of the game
+    pub platforms: Option<Vec<String>>,
+    pub release_date: Option<chrono::NaiveDate>,
+    pub publisher: Option<String>,
+    pub cover_image_url: Option<String>,
+}
+
+impl UpdateGameBody {
+    /// Normalizes tags and platforms and checks the cover image url
+    pub fn validate(mut self) -> Result<Self, CustomError> {
+        if let Some(tags) = self.tags.take() {
+            self.tags = Some(GameLabels::parse(tags, "tag")?.into_inner());
+        }
+        if let Some(platforms) = self.platforms.take() {
+            self.platforms = Some(GameLabels::parse(platforms, "platform")?.into_inner());
+        }
+        if let Some(url) = self.cover_image_url.take() {
+            self.cover_image_url = Some(ImageUrl::parse(url)?.as_ref().to_string());
+        }
+        Ok(self)
+    }
 }
 
 #[derive(Serialize)]
@@ -1,3 +1,7 @@
 pub mod health_check;
 pub mod admin;
-pub mod games;
\ No newline at end of file
+pub mod games;
+pub mod moderation;
+pub mod roles;
+pub mod openapi;
+pub mod analytics;
\ No newline at end of file
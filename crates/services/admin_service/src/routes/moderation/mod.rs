@@ -0,0 +1,2 @@
+pub mod moderation;
+pub mod models;
\ No newline at end of file
@@ -0,0 +1,90 @@
+use chrono::NaiveDateTime;
+use diesel::{Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use errors::CustomError;
+use serde_json::Value;
+use uuid::Uuid;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(diesel_derive_enum::DbEnum, Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, ToSchema)]
+#[ExistingTypePath = "crate::schema::sql_types::ModerationStatus"]
+#[DbValueStyle = "verbatim"]
+pub enum DbModerationStatus {
+    #[default]
+    Open,
+    Approved,
+    Rejected,
+    Hidden,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ModerationQueueQuery {
+    /// Defaults to `Open`
+    pub status: Option<DbModerationStatus>,
+    pub page: i64,
+    /// At most 100
+    pub limit: i64,
+}
+
+const MAX_QUEUE_LIMIT: i64 = 100;
+const MAX_QUEUE_PAGE: i64 = 10_000;
+
+impl ModerationQueueQuery {
+    /// Rows to skip, refusing pages whose offset would overflow
+    pub fn offset(&self) -> Result<i64, CustomError> {
+        if !(1..=MAX_QUEUE_PAGE).contains(&self.page) {
+            return Err(CustomError::ValidationError(format!("Page must be between 1 and {}.", MAX_QUEUE_PAGE)));
+        }
+        if !(1..=MAX_QUEUE_LIMIT).contains(&self.limit) {
+            return Err(CustomError::ValidationError(format!("Limit must be between 1 and {}.", MAX_QUEUE_LIMIT)));
+        }
+        (self.page - 1)
+            .checked_mul(self.limit)
+            .ok_or_else(|| CustomError::ValidationError("Page is too large.".to_string()))
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug, ToSchema)]
+#[diesel(table_name = crate::schema::moderation_queue)]
+pub struct QueuedReview {
+    pub review_id: Uuid,
+    pub game_slug: String,
+    pub user_id: Uuid,
+    pub rating: i32,
+    pub review: String,
+    /// Every reason the review was queued for, word filter hits and user flags
+    #[schema(value_type = Vec<Object>)]
+    pub reasons: Value,
+    pub flag_count: i32,
+    pub status: DbModerationStatus,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub decided_by: Option<Uuid>,
+    pub decided_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ModerationQueueResponse {
+    pub total: i64,
+    pub page: i64,
+    pub limit: i64,
+    pub reviews: Vec<QueuedReview>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModerationQueueQuery;
+
+    fn query(page: i64, limit: i64) -> ModerationQueueQuery {
+        ModerationQueueQuery { status: None, page, limit }
+    }
+
+    #[test]
+    fn offset_is_bounded() {
+        assert_eq!(query(3, 20).offset().ok(), Some(40));
+        assert!(query(0, 20).offset().is_err());
+        assert!(query(2, 101).offset().is_err());
+        assert!(query(i64::MAX, 100).offset().is_err());
+    }
+}
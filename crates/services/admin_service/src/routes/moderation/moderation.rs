@@ -0,0 +1,225 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use errors::CustomError;
+use flume::Sender;
+use helpers::auth_jwt::auth::Claims;
+use kafka::channel::{push_to_broker, KafkaMessage};
+use kafka::models::{ModerationDecision, ModerationEventsMessage};
+use lib_config::db::db::PgPool;
+use middleware::permissions::Authorized;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_error::DbError;
+use crate::permissions::{ModerationDecide, ModerationRead};
+use crate::schema::moderation_queue;
+
+use super::models::{DbModerationStatus, ModerationQueueQuery, ModerationQueueResponse, QueuedReview};
+
+/******************************************/
+// Moderation queue Route
+/******************************************/
+/**
+ * @route   GET /api/v1/auth/moderation/reviews
+ * @access  Private
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/moderation/reviews",
+    tag = "moderation",
+    security(("bearer_auth" = [])),
+    params(ModerationQueueQuery),
+    responses(
+        (status = 200, description = "Queued reviews, most flagged first", body = ModerationQueueResponse),
+        (status = 400, description = "Invalid pagination"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing moderation:read permission")
+    )
+)]
+#[instrument(name = "Get moderation queue", skip(pool, _admin))]
+pub async fn get_moderation_queue(
+    pool: web::Data<PgPool>,
+    query: web::Query<ModerationQueueQuery>,
+    _admin: Authorized<ModerationRead>,
+) -> Result<HttpResponse, CustomError> {
+    let query = query.into_inner();
+    let offset = query.offset()?;
+    let status = query.status.unwrap_or_default();
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let total = moderation_queue::table
+        .filter(moderation_queue::status.eq(status))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    let reviews = moderation_queue::table
+        .filter(moderation_queue::status.eq(status))
+        .order((moderation_queue::flag_count.desc(), moderation_queue::created_at.asc()))
+        .offset(offset)
+        .limit(query.limit)
+        .select(QueuedReview::as_select())
+        .load(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    Ok(HttpResponse::Ok().json(ModerationQueueResponse {
+        total,
+        page: query.page,
+        limit: query.limit,
+        reviews,
+    }))
+}
+
+/// Records the decision on the queued review and sends it to game_service
+async fn decide_review(
+    pool: &PgPool,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    admin: Claims,
+    review_id: Uuid,
+    decision: ModerationDecision,
+) -> Result<HttpResponse, CustomError> {
+    let moderator_id = Uuid::parse_str(&admin.sub)
+        .map_err(|err| CustomError::ValidationError(format!("Invalid admin ID format: {}", err)))?;
+    let status = match decision {
+        ModerationDecision::Approve => DbModerationStatus::Approved,
+        ModerationDecision::Reject => DbModerationStatus::Rejected,
+        ModerationDecision::Hide => DbModerationStatus::Hidden,
+    };
+    let now = Utc::now().naive_utc();
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let (review, content_hash) = diesel::update(moderation_queue::table.filter(moderation_queue::review_id.eq(review_id)))
+        .set((
+            moderation_queue::status.eq(status),
+            moderation_queue::decided_by.eq(Some(moderator_id)),
+            moderation_queue::decided_at.eq(Some(now)),
+            moderation_queue::updated_at.eq(now),
+        ))
+        .returning((QueuedReview::as_returning(), moderation_queue::content_hash))
+        .get_result(&mut conn)
+        .await
+        .optional()
+        .map_err(DbError)?
+        .ok_or(CustomError::DatabaseError {
+            msg: format!("Review {} not found in moderation queue", review_id),
+            resp: "Review not found".into(),
+            status_code: StatusCode::NOT_FOUND,
+        })?;
+
+    let message = ModerationEventsMessage {
+        review_id,
+        decision,
+        moderator_id,
+        content_hash,
+        time: now,
+    };
+    push_to_broker(kafka_producer, &message)
+        .await
+        .context("Failed to send moderation event message to broker")?;
+
+    Ok(HttpResponse::Ok().json(review))
+}
+
+/******************************************/
+// Approve review Route
+/******************************************/
+/**
+ * @route   POST /api/v1/auth/moderation/reviews/{review_id}/approve
+ * @access  Private
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/moderation/reviews/{review_id}/approve",
+    tag = "moderation",
+    security(("bearer_auth" = [])),
+    params(("review_id" = Uuid, Path, description = "Id of the queued review")),
+    responses(
+        (status = 200, description = "Review published again", body = QueuedReview),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing moderation:decide permission"),
+        (status = 404, description = "Review not in the queue")
+    )
+)]
+#[instrument(name = "Approve review", skip(pool, kafka_producer, admin))]
+pub async fn approve_review(
+    pool: web::Data<PgPool>,
+    review_id: web::Path<Uuid>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    admin: Authorized<ModerationDecide>,
+) -> Result<HttpResponse, CustomError> {
+    decide_review(&pool, &kafka_producer, admin.into_inner(), review_id.into_inner(), ModerationDecision::Approve).await
+}
+
+/******************************************/
+// Reject review Route
+/******************************************/
+/**
+ * @route   POST /api/v1/auth/moderation/reviews/{review_id}/reject
+ * @access  Private
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/moderation/reviews/{review_id}/reject",
+    tag = "moderation",
+    security(("bearer_auth" = [])),
+    params(("review_id" = Uuid, Path, description = "Id of the queued review")),
+    responses(
+        (status = 200, description = "Review rejected, it stays out of listings until edited and approved", body = QueuedReview),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing moderation:decide permission"),
+        (status = 404, description = "Review not in the queue")
+    )
+)]
+#[instrument(name = "Reject review", skip(pool, kafka_producer, admin))]
+pub async fn reject_review(
+    pool: web::Data<PgPool>,
+    review_id: web::Path<Uuid>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    admin: Authorized<ModerationDecide>,
+) -> Result<HttpResponse, CustomError> {
+    decide_review(&pool, &kafka_producer, admin.into_inner(), review_id.into_inner(), ModerationDecision::Reject).await
+}
+
+/******************************************/
+// Hide review Route
+/******************************************/
+/**
+ * @route   POST /api/v1/auth/moderation/reviews/{review_id}/hide
+ * @access  Private
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/moderation/reviews/{review_id}/hide",
+    tag = "moderation",
+    security(("bearer_auth" = [])),
+    params(("review_id" = Uuid, Path, description = "Id of the queued review")),
+    responses(
+        (status = 200, description = "Review hidden from listings", body = QueuedReview),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing moderation:decide permission"),
+        (status = 404, description = "Review not in the queue")
+    )
+)]
+#[instrument(name = "Hide review", skip(pool, kafka_producer, admin))]
+pub async fn hide_review(
+    pool: web::Data<PgPool>,
+    review_id: web::Path<Uuid>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    admin: Authorized<ModerationDecide>,
+) -> Result<HttpResponse, CustomError> {
+    decide_review(&pool, &kafka_producer, admin.into_inner(), review_id.into_inner(), ModerationDecision::Hide).await
+}
@@ -0,0 +1,72 @@
+use actix_web::HttpResponse;
+use helpers::auth_jwt::permissions::Permission;
+use helpers::openapi::BearerAuth;
+use helpers::validations::validations::{CreateUserBody, LoginUserBody};
+use utoipa::OpenApi;
+
+use crate::analytics::{ActiveUsers, GameRatings, SessionDurations, SignupFunnel};
+use crate::routes::admin::{admins, crud, invitations, mfa, model::{DbAccountStatus, User, UserListResponse}, user};
+use crate::routes::admin::model::{AdminResponse, CreateInvitationBody, Invitation, MfaCodeBody, MfaEnrollment, MfaLoginBody, RecoveryCodes, RegisterAdminBody};
+use crate::routes::analytics::analytics;
+use crate::routes::games::games;
+use crate::routes::games::models::{CreateGameBody, Game, UpdateGameBody};
+use crate::routes::moderation::moderation;
+use crate::routes::moderation::models::{DbModerationStatus, ModerationQueueResponse, QueuedReview};
+use crate::routes::roles::roles;
+use crate::routes::roles::models::{AdminRolesBody, CreateRoleBody, RolePermissionsBody, RoleResponse};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Admin Service"),
+    paths(
+        crud::register_admin,
+        crud::login_admin,
+        crud::logout_admin,
+        mfa::enroll_mfa,
+        mfa::verify_mfa,
+        mfa::disable_mfa,
+        mfa::login_mfa,
+        invitations::invite_admin,
+        invitations::get_invitations,
+        invitations::revoke_invitation,
+        admins::get_admins,
+        admins::deactivate_admin,
+        admins::reactivate_admin,
+        games::create_game,
+        games::get_game,
+        games::update_game,
+        games::delete_game,
+        user::get_users,
+        user::get_user_by_id,
+        user::delete_user,
+        user::suspend_user,
+        user::reactivate_user,
+        moderation::get_moderation_queue,
+        moderation::approve_review,
+        moderation::reject_review,
+        moderation::hide_review,
+        roles::get_roles,
+        roles::create_role,
+        roles::update_role_permissions,
+        roles::delete_role,
+        roles::assign_admin_roles,
+        analytics::get_active_users,
+        analytics::get_signup_funnel,
+        analytics::get_game_ratings,
+        analytics::get_session_durations
+    ),
+    components(schemas(CreateUserBody, RegisterAdminBody, LoginUserBody, MfaCodeBody, MfaLoginBody, MfaEnrollment, RecoveryCodes, CreateInvitationBody, Invitation, AdminResponse, CreateGameBody, UpdateGameBody, Game, User, UserListResponse, DbAccountStatus, DbModerationStatus, QueuedReview, ModerationQueueResponse, Permission, RoleResponse, CreateRoleBody, RolePermissionsBody, AdminRolesBody, ActiveUsers, SignupFunnel, GameRatings, SessionDurations)),
+    modifiers(&BearerAuth)
+)]
+pub struct ApiDoc;
+
+/******************************************/
+// OpenAPI spec route
+/******************************************/
+/**
+ * @route   GET /api-docs/openapi.json
+ * @access  Public
+ */
+pub async fn openapi_spec() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
@@ -0,0 +1,122 @@
+use std::collections::BTreeSet;
+
+use actix_web::http::StatusCode;
+use chrono::NaiveDateTime;
+use diesel::result::Error as DieselError;
+use diesel::{Queryable, Selectable};
+use errors::CustomError;
+use helpers::auth_jwt::permissions::Permission;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db_error::DbError;
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::roles)]
+pub struct Role {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub built_in: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct RoleResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Built-in roles can't be edited or deleted
+    pub built_in: bool,
+    pub created_at: NaiveDateTime,
+    pub permissions: Vec<Permission>,
+}
+
+impl RoleResponse {
+    pub fn new(role: Role, permissions: Vec<Permission>) -> Self {
+        RoleResponse {
+            id: role.id,
+            name: role.name,
+            description: role.description,
+            built_in: role.built_in,
+            created_at: role.created_at,
+            permissions,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CreateRoleBody {
+    /// Lowercase letters, digits and underscores
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<Permission>,
+}
+
+impl CreateRoleBody {
+    pub fn validate(self) -> Result<Self, CustomError> {
+        let valid_name = !self.name.is_empty()
+            && self.name.len() <= 64
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid_name {
+            return Err(CustomError::ValidationError(
+                "Role name must be 1 to 64 lowercase letters, digits or underscores.".to_string(),
+            ));
+        }
+        if self.permissions.is_empty() {
+            return Err(CustomError::ValidationError("A role needs at least one permission.".to_string()));
+        }
+        Ok(self)
+    }
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct RolePermissionsBody {
+    /// Replaces every permission of the role
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct AdminRolesBody {
+    /// Replaces every role of the admin
+    pub roles: Vec<Uuid>,
+}
+
+/// Permissions without duplicates, in a stable order for storage and responses
+pub fn unique_permissions(permissions: &[Permission]) -> Vec<Permission> {
+    let unique: BTreeSet<Permission> = permissions.iter().copied().collect();
+    unique.into_iter().collect()
+}
+
+#[derive(Error, Debug)]
+pub enum RoleError {
+    #[error(transparent)]
+    Db(#[from] DieselError),
+    #[error("Role not found")]
+    NotFound,
+    #[error("Built-in roles can't be changed")]
+    BuiltIn,
+    #[error("At least one admin must keep the roles:manage permission")]
+    LastRoleManager,
+}
+
+impl From<RoleError> for CustomError {
+    fn from(value: RoleError) -> Self {
+        let status_code = match value {
+            RoleError::Db(err) => return DbError(err).into(),
+            RoleError::NotFound => StatusCode::NOT_FOUND,
+            RoleError::BuiltIn | RoleError::LastRoleManager => StatusCode::CONFLICT,
+        };
+        CustomError::DatabaseError {
+            msg: value.to_string(),
+            resp: value.to_string(),
+            status_code,
+        }
+    }
+}
+
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use diesel::dsl::count_distinct;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use errors::CustomError;
+use helpers::auth_jwt::permissions::Permission;
+use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
+use middleware::permissions::Authorized;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::accounts::revoke_admin_tokens;
+use crate::db_error::DbError;
+use crate::permissions::RolesManage;
+use crate::schema::{admin_roles, admins, role_permissions, roles};
+
+use super::models::{unique_permissions, AdminRolesBody, CreateRoleBody, Role, RoleError, RoleResponse, RolePermissionsBody};
+
+/// Stored permissions of the given roles, unknown permission strings are skipped
+async fn permissions_by_role(
+    conn: &mut AsyncPgConnection,
+    role_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<Permission>>, DieselError> {
+    let rows: Vec<(Uuid, String)> = role_permissions::table
+        .filter(role_permissions::role_id.eq_any(role_ids))
+        .order(role_permissions::permission.asc())
+        .select((role_permissions::role_id, role_permissions::permission))
+        .load(conn)
+        .await?;
+
+    let mut grouped: HashMap<Uuid, Vec<Permission>> = HashMap::new();
+    for (role_id, permission) in rows {
+        if let Ok(permission) = permission.parse() {
+            grouped.entry(role_id).or_default().push(permission);
+        }
+    }
+    Ok(grouped)
+}
+
+async fn find_role(conn: &mut AsyncPgConnection, role_id: Uuid) -> Result<Role, RoleError> {
+    roles::table
+        .find(role_id)
+        .select(Role::as_select())
+        .get_result(conn)
+        .await
+        .optional()?
+        .ok_or(RoleError::NotFound)
+}
+
+async fn replace_permissions(
+    conn: &mut AsyncPgConnection,
+    role_id: Uuid,
+    permissions: &[Permission],
+) -> Result<(), DieselError> {
+    diesel::delete(role_permissions::table.filter(role_permissions::role_id.eq(role_id)))
+        .execute(conn)
+        .await?;
+    let rows: Vec<_> = permissions
+        .iter()
+        .map(|permission| {
+            (
+                role_permissions::role_id.eq(role_id),
+                role_permissions::permission.eq(permission.as_str()),
+            )
+        })
+        .collect();
+    diesel::insert_into(role_permissions::table)
+        .values(&rows)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Admins holding the role, whose tokens carry its old permissions
+async fn role_holders(conn: &mut AsyncPgConnection, role_id: Uuid) -> Result<Vec<Uuid>, DieselError> {
+    admin_roles::table
+        .filter(admin_roles::role_id.eq(role_id))
+        .select(admin_roles::admin_id)
+        .load(conn)
+        .await
+}
+
+/// Logs the admins out, their tokens are already refused through the revocation cutoff
+async fn end_sessions(redis_service: &RedisService, admin_ids: &[Uuid]) -> Result<(), CustomError> {
+    for admin_id in admin_ids {
+        redis_service.delete_all_sessions(&admin_id.to_string()).await?;
+    }
+    Ok(())
+}
+
+/// Fails the surrounding transaction when no active admin could manage roles anymore
+pub(crate) async fn ensure_role_manager_left(conn: &mut AsyncPgConnection) -> Result<(), RoleError> {
+    let managers: i64 = admin_roles::table
+        .inner_join(role_permissions::table.on(role_permissions::role_id.eq(admin_roles::role_id)))
+        .inner_join(admins::table)
+        .filter(role_permissions::permission.eq(Permission::RolesManage.as_str()))
+        .filter(admins::is_active.eq(true))
+        .select(count_distinct(admin_roles::admin_id))
+        .get_result(conn)
+        .await?;
+    if managers == 0 {
+        return Err(RoleError::LastRoleManager);
+    }
+    Ok(())
+}
+
+/******************************************/
+// Get roles Route
+/******************************************/
+/**
+ * @route   GET /api/v1/auth/roles
+ * @access  Private
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/roles",
+    tag = "roles",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Every role with its permissions", body = Vec<RoleResponse>),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing roles:manage permission")
+    )
+)]
+#[instrument(name = "Get roles", skip(pool, _admin))]
+pub async fn get_roles(
+    pool: web::Data<PgPool>,
+    _admin: Authorized<RolesManage>,
+) -> Result<HttpResponse, CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let all_roles = roles::table
+        .order(roles::name.asc())
+        .select(Role::as_select())
+        .load(&mut conn)
+        .await
+        .map_err(DbError)?;
+    let role_ids: Vec<Uuid> = all_roles.iter().map(|role| role.id).collect();
+    let mut permissions = permissions_by_role(&mut conn, &role_ids).await.map_err(DbError)?;
+
+    let response: Vec<RoleResponse> = all_roles
+        .into_iter()
+        .map(|role| {
+            let granted = permissions.remove(&role.id).unwrap_or_default();
+            RoleResponse::new(role, granted)
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/******************************************/
+// Create role Route
+/******************************************/
+/**
+ * @route   POST /api/v1/auth/roles
+ * @access  Private
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/roles",
+    tag = "roles",
+    security(("bearer_auth" = [])),
+    request_body = CreateRoleBody,
+    responses(
+        (status = 201, description = "Role created", body = RoleResponse),
+        (status = 400, description = "Invalid role or name already taken"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing roles:manage permission")
+    )
+)]
+#[instrument(name = "Create role", skip(pool, req_role, _admin), fields(name = %req_role.name))]
+pub async fn create_role(
+    pool: web::Data<PgPool>,
+    req_role: web::Json<CreateRoleBody>,
+    _admin: Authorized<RolesManage>,
+) -> Result<HttpResponse, CustomError> {
+    let req_role = req_role.into_inner().validate()?;
+    let permissions = unique_permissions(&req_role.permissions);
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let role = conn
+        .transaction::<_, DieselError, _>(|conn| {
+            async move {
+                let role = diesel::insert_into(roles::table)
+                    .values((
+                        roles::name.eq(&req_role.name),
+                        roles::description.eq(&req_role.description),
+                    ))
+                    .returning(Role::as_returning())
+                    .get_result(conn)
+                    .await?;
+                replace_permissions(conn, role.id, &permissions).await?;
+                Ok(RoleResponse::new(role, permissions))
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(DbError)?;
+
+    Ok(HttpResponse::Created().json(role))
+}
+
+/******************************************/
+// Update role permissions Route
+/******************************************/
+/**
+ * @route   PUT /api/v1/auth/roles/{role_id}/permissions
+ * @access  Private
+ */
+#[utoipa::path(
+    put,
+    path = "/api/v1/auth/roles/{role_id}/permissions",
+    tag = "roles",
+    security(("bearer_auth" = [])),
+    params(("role_id" = Uuid, Path, description = "Id of the role")),
+    request_body = RolePermissionsBody,
+    responses(
+        (status = 200, description = "Permissions replaced and the admins holding the role logged out", body = RoleResponse),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing roles:manage permission"),
+        (status = 404, description = "Role not found"),
+        (status = 409, description = "Built-in role, or no admin would be left to manage roles")
+    )
+)]
+#[instrument(name = "Update role permissions", skip(pool, req_permissions, redis_service, _admin))]
+pub async fn update_role_permissions(
+    pool: web::Data<PgPool>,
+    role_id: web::Path<Uuid>,
+    req_permissions: web::Json<RolePermissionsBody>,
+    redis_service: web::Data<RedisService>,
+    _admin: Authorized<RolesManage>,
+) -> Result<HttpResponse, CustomError> {
+    let role_id = role_id.into_inner();
+    let permissions = unique_permissions(&req_permissions.into_inner().permissions);
+    if permissions.is_empty() {
+        return Err(CustomError::ValidationError("A role needs at least one permission.".to_string()));
+    }
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let (role, holders) = conn
+        .transaction::<_, RoleError, _>(|conn| {
+            async move {
+                let role = find_role(conn, role_id).await?;
+                if role.built_in {
+                    return Err(RoleError::BuiltIn);
+                }
+                replace_permissions(conn, role_id, &permissions).await?;
+                ensure_role_manager_left(conn).await?;
+                let holders = role_holders(conn, role_id).await?;
+                revoke_admin_tokens(conn, &holders).await?;
+                Ok((RoleResponse::new(role, permissions), holders))
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    end_sessions(&redis_service, &holders).await?;
+    Ok(HttpResponse::Ok().json(role))
+}
+
+/******************************************/
+// Delete role Route
+/******************************************/
+/**
+ * @route   DELETE /api/v1/auth/roles/{role_id}
+ * @access  Private
+ */
+#[utoipa::path(
+    delete,
+    path = "/api/v1/auth/roles/{role_id}",
+    tag = "roles",
+    security(("bearer_auth" = [])),
+    params(("role_id" = Uuid, Path, description = "Id of the role")),
+    responses(
+        (status = 204, description = "Role deleted, removed from every admin and its holders logged out"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing roles:manage permission"),
+        (status = 404, description = "Role not found"),
+        (status = 409, description = "Built-in role, or no admin would be left to manage roles")
+    )
+)]
+#[instrument(name = "Delete role", skip(pool, redis_service, _admin))]
+pub async fn delete_role(
+    pool: web::Data<PgPool>,
+    role_id: web::Path<Uuid>,
+    redis_service: web::Data<RedisService>,
+    _admin: Authorized<RolesManage>,
+) -> Result<HttpResponse, CustomError> {
+    let role_id = role_id.into_inner();
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let holders = conn
+        .transaction::<_, RoleError, _>(|conn| {
+            async move {
+                let role = find_role(conn, role_id).await?;
+                if role.built_in {
+                    return Err(RoleError::BuiltIn);
+                }
+                let holders = role_holders(conn, role_id).await?;
+                diesel::delete(roles::table.find(role_id)).execute(conn).await?;
+                ensure_role_manager_left(conn).await?;
+                revoke_admin_tokens(conn, &holders).await?;
+                Ok(holders)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    end_sessions(&redis_service, &holders).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/******************************************/
+// Assign admin roles Route
+/******************************************/
+/**
+ * @route   PUT /api/v1/auth/admins/{admin_id}/roles
+ * @access  Private
+ */
+#[utoipa::path(
+    put,
+    path = "/api/v1/auth/admins/{admin_id}/roles",
+    tag = "roles",
+    security(("bearer_auth" = [])),
+    params(("admin_id" = Uuid, Path, description = "Id of the admin")),
+    request_body = AdminRolesBody,
+    responses(
+        (status = 200, description = "Roles replaced and the admin logged out, they apply from the next login", body = Vec<RoleResponse>),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Missing roles:manage permission"),
+        (status = 404, description = "Admin or role not found"),
+        (status = 409, description = "No admin would be left to manage roles")
+    )
+)]
+#[instrument(name = "Assign admin roles", skip(pool, req_roles, redis_service, _admin))]
+pub async fn assign_admin_roles(
+    pool: web::Data<PgPool>,
+    admin_id: web::Path<Uuid>,
+    req_roles: web::Json<AdminRolesBody>,
+    redis_service: web::Data<RedisService>,
+    _admin: Authorized<RolesManage>,
+) -> Result<HttpResponse, CustomError> {
+    let admin_id = admin_id.into_inner();
+    let mut role_ids = req_roles.into_inner().roles;
+    role_ids.sort();
+    role_ids.dedup();
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get connection from pool")?;
+
+    let assigned = conn
+        .transaction::<_, RoleError, _>(|conn| {
+            async move {
+                let assigned = roles::table
+                    .filter(roles::id.eq_any(&role_ids))
+                    .order(roles::name.asc())
+                    .select(Role::as_select())
+                    .load(conn)
+                    .await?;
+                if assigned.len() != role_ids.len() {
+                    return Err(RoleError::NotFound);
+                }
+
+                diesel::delete(admin_roles::table.filter(admin_roles::admin_id.eq(admin_id)))
+                    .execute(conn)
+                    .await?;
+                let rows: Vec<_> = role_ids
+                    .iter()
+                    .map(|role_id| (admin_roles::admin_id.eq(admin_id), admin_roles::role_id.eq(*role_id)))
+                    .collect();
+                diesel::insert_into(admin_roles::table)
+                    .values(&rows)
+                    .execute(conn)
+                    .await?;
+                ensure_role_manager_left(conn).await?;
+                revoke_admin_tokens(conn, &[admin_id]).await?;
+
+                let mut permissions = permissions_by_role(conn, &role_ids).await?;
+                Ok(assigned
+                    .into_iter()
+                    .map(|role| {
+                        let granted = permissions.remove(&role.id).unwrap_or_default();
+                        RoleResponse::new(role, granted)
+                    })
+                    .collect::<Vec<_>>())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    // Tokens carry the old scopes, the admin has to log in again
+    end_sessions(&redis_service, &[admin_id]).await?;
+
+    Ok(HttpResponse::Ok().json(assigned))
+}
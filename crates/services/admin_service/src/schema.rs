@@ -1,17 +1,70 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "account_status"))]
+    pub struct AccountStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "moderation_status"))]
+    pub struct ModerationStatus;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "user_event_type"))]
     pub struct UserEventType;
 }
 
+diesel::table! {
+    admin_invitations (id) {
+        id -> Uuid,
+        email -> Varchar,
+        #[max_length = 64]
+        token -> Varchar,
+        role_id -> Uuid,
+        invited_by -> Nullable<Uuid>,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        accepted_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    admin_mfa (admin_id) {
+        admin_id -> Uuid,
+        #[max_length = 64]
+        secret -> Varchar,
+        enabled_at -> Nullable<Timestamp>,
+        last_used_step -> Nullable<Int8>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    admin_recovery_codes (admin_id, code_hash) {
+        admin_id -> Uuid,
+        #[max_length = 64]
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    admin_roles (admin_id, role_id) {
+        admin_id -> Uuid,
+        role_id -> Uuid,
+    }
+}
+
 diesel::table! {
     admins (id) {
         id -> Uuid,
         username -> Varchar,
         password_hash -> Varchar,
         email -> Varchar,
+        is_active -> Bool,
+        deactivated_at -> Nullable<Timestamp>,
+        tokens_valid_after -> Nullable<Timestamp>,
     }
 }
 
@@ -29,6 +82,53 @@ diesel::table! {
         is_admin -> Nullable<Bool>,
         #[max_length = 255]
         genre -> Nullable<Varchar>,
+        tags -> Array<Text>,
+        platforms -> Array<Text>,
+        release_date -> Nullable<Date>,
+        #[max_length = 255]
+        publisher -> Nullable<Varchar>,
+        cover_image_url -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ModerationStatus;
+
+    moderation_queue (review_id) {
+        review_id -> Uuid,
+        #[max_length = 512]
+        game_slug -> Varchar,
+        user_id -> Uuid,
+        rating -> Int4,
+        review -> Text,
+        reasons -> Jsonb,
+        flag_count -> Int4,
+        status -> ModerationStatus,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        decided_by -> Nullable<Uuid>,
+        decided_at -> Nullable<Timestamp>,
+        content_hash -> Text,
+    }
+}
+
+diesel::table! {
+    role_permissions (role_id, permission) {
+        role_id -> Uuid,
+        #[max_length = 64]
+        permission -> Varchar,
+    }
+}
+
+diesel::table! {
+    roles (id) {
+        id -> Uuid,
+        #[max_length = 64]
+        name -> Varchar,
+        description -> Nullable<Text>,
+        built_in -> Bool,
+        created_at -> Timestamp,
     }
 }
 
@@ -41,22 +141,44 @@ diesel::table! {
         event_type -> UserEventType,
         data -> Jsonb,
         user_id -> Uuid,
+        occurred_at -> Timestamp,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AccountStatus;
+
     users (id) {
         id -> Uuid,
         username -> Varchar,
         email -> Varchar,
-        created_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
         modified_at -> Nullable<Timestamp>,
+        status -> AccountStatus,
+        status_changed_at -> Nullable<Timestamp>,
+        email_verified_at -> Nullable<Timestamp>,
     }
 }
 
+diesel::joinable!(admin_invitations -> admins (invited_by));
+diesel::joinable!(admin_invitations -> roles (role_id));
+diesel::joinable!(admin_mfa -> admins (admin_id));
+diesel::joinable!(admin_recovery_codes -> admins (admin_id));
+diesel::joinable!(admin_roles -> admins (admin_id));
+diesel::joinable!(admin_roles -> roles (role_id));
+diesel::joinable!(role_permissions -> roles (role_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
+    admin_invitations,
+    admin_mfa,
+    admin_recovery_codes,
+    admin_roles,
     admins,
     games,
+    moderation_queue,
+    role_permissions,
+    roles,
     user_events,
     users,
 );
@@ -1,18 +1,28 @@
 use flume::Sender;
 use helpers::auth_jwt::auth::Role;
 use kafka::{channel::KafkaMessage, setup::{setup_kafka_receiver, setup_kafka_sender}};
-use lib_config::{config::configuration::Settings, db::db::PgPool};
+use lib_config::{config::configuration::{AuthSettings, Settings}, db::db::PgPool};
 // use crate::middleware::jwt_auth_middleware;
 use crate::routes::{
-    admin::{crud::{login_admin, logout_admin, register_admin}, user::{delete_user, get_users, get_user_by_id}}, games::games::{create_game, delete_game, get_game, update_game}, health_check::health_check,
+    admin::{crud::{login_admin, logout_admin, register_admin}, user::{delete_user, get_users, get_user_by_id, reactivate_user, suspend_user}},
+    admin::admins::{deactivate_admin, get_admins, reactivate_admin},
+    admin::mfa::{disable_mfa, enroll_mfa, login_mfa, verify_mfa},
+    admin::invitations::{get_invitations, invite_admin, revoke_invitation}, games::games::{create_game, delete_game, get_game, update_game}, health_check::health_check,
+    moderation::moderation::{approve_review, get_moderation_queue, hide_review, reject_review},
+    analytics::analytics::{get_active_users, get_game_ratings, get_session_durations, get_signup_funnel},
+    roles::roles::{assign_admin_roles, create_role, delete_role, get_roles, update_role_permissions},
+    openapi::openapi_spec,
 };
 
+use crate::accounts::ActiveAdmins;
 use crate::kafka_handler::process_kafka_message;
 use actix_web::{dev::Server, web, App, HttpServer};
 use actix_web_lab::middleware::from_fn;
-use middleware::jwt::{jwt_auth_middleware, RoleRestrictor};
+use middleware::jwt::{jwt_auth_middleware, AccountGuard, RoleRestrictor};
 use std::net::TcpListener;
+use std::sync::Arc;
 use tracing_actix_web::TracingLogger;
+use utils::telemetry::RequestIdRootSpanBuilder;
 use lib_config::session::redis::RedisService;
 
 /**************************************************************/
@@ -39,11 +49,12 @@ impl Application {
         let rx = setup_kafka_receiver(&config.kafka.admin_url, &config.kafka.admin_subscribe_topics, &consumer_group).await;
 
         let pool_clone = pool.clone();
+        let tx_clone = tx.clone();
         tokio::spawn(async move {
-            process_kafka_message(rx, pool_clone).await;
+            process_kafka_message(rx, pool_clone, tx_clone).await;
         });
 
-        let server = run_server(listener, pool.clone(), config.redis.uri.clone(), tx).await?;
+        let server = run_server(listener, pool.clone(), config.redis.uri.clone(), tx, config.auth.clone()).await?;
         Ok(Self {
             port: actual_port,
             server,
@@ -64,27 +75,36 @@ pub async fn run_server(
     listener: TcpListener,
     pool: PgPool,
     redis_uri: String,
-    kafka_sender: Sender<KafkaMessage<String>>
+    kafka_sender: Sender<KafkaMessage<String>>,
+    auth_settings: AuthSettings
 ) -> Result<Server, std::io::Error> {
     let redis_service = RedisService::new(redis_uri).await;
+    let account_guard: Arc<dyn AccountGuard> = Arc::new(ActiveAdmins { pool: pool.clone() });
     let server = HttpServer::new(move || {
         App::new()
-            .wrap(TracingLogger::default())
+            .wrap(TracingLogger::<RequestIdRootSpanBuilder>::new())
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(kafka_sender.clone()))
             .app_data(web::Data::new(redis_service.clone()))
+            .app_data(web::Data::from(account_guard.clone()))
+            .app_data(web::Data::new(auth_settings.clone()))
             .route("/health_check", web::get().to(health_check))
+            .route("/api-docs/openapi.json", web::get().to(openapi_spec))
             .service(
                 web::scope("/api/v1")
                 .service(
                     web::scope("/admins")
                                 .route("/register", web::post().to(register_admin))
                                 .route("/login", web::post().to(login_admin))
+                                .route("/login/2fa", web::post().to(login_mfa))
                 )
                 .service(
                     web::scope("/protected")
                     .wrap(from_fn(jwt_auth_middleware::<AdminRoleRestrictor>))
                     .route("/logout", web::get().to(logout_admin))
+                    .route("/2fa/enroll", web::post().to(enroll_mfa))
+                    .route("/2fa/verify", web::post().to(verify_mfa))
+                    .route("/2fa/disable", web::post().to(disable_mfa))
                 )
                 .service(
                     web::scope("/auth/games")
@@ -100,6 +120,47 @@ pub async fn run_server(
                     .route("/", web::get().to(get_users))
                     .route("/{user_id}", web::get().to(get_user_by_id))
                     .route("/{user_id}", web::delete().to(delete_user))
+                    .route("/{user_id}/suspend", web::post().to(suspend_user))
+                    .route("/{user_id}/reactivate", web::post().to(reactivate_user))
+                )
+                .service(
+                    web::scope("/auth/moderation")
+                    .wrap(from_fn(jwt_auth_middleware::<AdminRoleRestrictor>))
+                    .route("/reviews", web::get().to(get_moderation_queue))
+                    .route("/reviews/{review_id}/approve", web::post().to(approve_review))
+                    .route("/reviews/{review_id}/reject", web::post().to(reject_review))
+                    .route("/reviews/{review_id}/hide", web::post().to(hide_review))
+                )
+                .service(
+                    web::scope("/auth/roles")
+                    .wrap(from_fn(jwt_auth_middleware::<AdminRoleRestrictor>))
+                    .route("", web::get().to(get_roles))
+                    .route("", web::post().to(create_role))
+                    .route("/{role_id}/permissions", web::put().to(update_role_permissions))
+                    .route("/{role_id}", web::delete().to(delete_role))
+                )
+                .service(
+                    web::scope("/auth/admins")
+                    .wrap(from_fn(jwt_auth_middleware::<AdminRoleRestrictor>))
+                    .route("", web::get().to(get_admins))
+                    .route("/{admin_id}/roles", web::put().to(assign_admin_roles))
+                    .route("/{admin_id}/deactivate", web::post().to(deactivate_admin))
+                    .route("/{admin_id}/reactivate", web::post().to(reactivate_admin))
+                )
+                .service(
+                    web::scope("/auth/invitations")
+                    .wrap(from_fn(jwt_auth_middleware::<AdminRoleRestrictor>))
+                    .route("", web::get().to(get_invitations))
+                    .route("", web::post().to(invite_admin))
+                    .route("/{invitation_id}", web::delete().to(revoke_invitation))
+                )
+                .service(
+                    web::scope("/auth/analytics")
+                    .wrap(from_fn(jwt_auth_middleware::<AdminRoleRestrictor>))
+                    .route("/active-users", web::get().to(get_active_users))
+                    .route("/signup-funnel", web::get().to(get_signup_funnel))
+                    .route("/ratings", web::get().to(get_game_ratings))
+                    .route("/sessions", web::get().to(get_session_durations))
                 )
             )       
     })
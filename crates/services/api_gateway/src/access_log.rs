@@ -0,0 +1,55 @@
+use std::time::{Duration, Instant};
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage};
+use utils::telemetry::{generate_request_id, redacted_target, request_id};
+
+/// Outcome of the upstream call, left in the request extensions by `forward_requests`
+#[derive(Debug, Clone)]
+pub struct UpstreamCall {
+    pub service: String,
+    pub status: u16,
+    pub latency: Duration,
+}
+
+/******************************************/
+// Request id and access log middleware
+/******************************************/
+/// Outermost gateway middleware: assigns the `X-Request-Id` (kept from the client when valid) before the
+/// tracing root span is built, echoes it on the response and writes one access log line per request
+pub async fn access_log<B: MessageBody + 'static>(
+    mut req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<B>, Error> {
+    let request_id = request_id(req.headers()).unwrap_or_else(generate_request_id);
+    let header_name = HeaderName::from_static("x-request-id");
+    let header_value = HeaderValue::from_str(&request_id).expect("Request ids are printable ascii");
+    req.headers_mut().insert(header_name.clone(), header_value.clone());
+
+    let method = req.method().to_string();
+    let target = redacted_target(req.path(), req.query_string());
+    let client_ip = req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_default();
+    let started = Instant::now();
+
+    let mut res = next.call(req).await?;
+    res.headers_mut().insert(header_name, header_value);
+
+    let upstream = res.request().extensions().get::<UpstreamCall>().cloned();
+    tracing::info!(
+        request_id = %request_id,
+        http.method = %method,
+        http.target = %target,
+        http.client_ip = %client_ip,
+        http.status_code = res.status().as_u16(),
+        latency_ms = started.elapsed().as_millis() as u64,
+        upstream.service = upstream.as_ref().map(|call| call.service.as_str()),
+        upstream.status_code = upstream.as_ref().map(|call| call.status),
+        upstream.latency_ms = upstream.as_ref().map(|call| call.latency.as_millis() as u64),
+        "Request completed"
+    );
+
+    Ok(res)
+}
@@ -0,0 +1,24 @@
+use actix_web::HttpRequest;
+use errors::{AuthError, CustomError};
+use helpers::auth_jwt::auth::{verify_jwt, Claims};
+use lib_config::session::redis::RedisService;
+
+/******************************************/
+// Authenticating requests at the gateway
+/******************************************/
+/// Verifies the bearer token and checks that its session is still alive
+pub async fn authenticate(req: &HttpRequest, redis_service: &RedisService) -> Result<Claims, CustomError> {
+    let token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.replace("Bearer ", ""))
+        .filter(|token| !token.is_empty())
+        .ok_or(AuthError::InvalidCredentials(anyhow::anyhow!("Missing token")))?;
+
+    let claims = verify_jwt(&token)
+        .map_err(|err| AuthError::InvalidCredentials(anyhow::anyhow!(err)))?;
+    redis_service.get_user_from_session(&claims.sid).await?;
+
+    Ok(claims)
+}
@@ -0,0 +1,380 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use actix_web::http::header::{HeaderMap, CACHE_CONTROL, ETAG, IF_NONE_MATCH, PRAGMA};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse};
+use anyhow::Context;
+use bytes::Bytes;
+use helpers::auth_jwt::auth::Claims;
+use lib_config::config::configuration::{
+    CacheBackend, CacheKeyRule, GatewayCacheSettings, GatewayRouteSettings, RouteCacheSettings,
+};
+use lib_config::session::redis::RedisService;
+use lru::LruCache;
+use redis::AsyncCommands;
+
+const REDIS_KEY_PREFIX: &str = "gateway:cache";
+
+/// Headers that describe the original transfer and must not be replayed from cache
+const SKIPPED_HEADERS: [&str; 4] = ["content-length", "transfer-encoding", "connection", "date"];
+
+#[derive(Clone, Debug)]
+pub struct CachedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Bytes,
+    pub etag: String,
+}
+
+impl CachedResponse {
+    pub fn new(status: StatusCode, headers: &reqwest::header::HeaderMap, body: Bytes) -> Self {
+        let etag = headers
+            .get(ETAG.as_str())
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| etag_for(&body));
+
+        let headers = headers
+            .iter()
+            .filter(|(name, _)| !SKIPPED_HEADERS.contains(&name.as_str()) && *name != ETAG.as_str())
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+
+        Self {
+            status: status.as_u16(),
+            headers,
+            body,
+            etag,
+        }
+    }
+
+    /// Builds the client response, answering `304 Not Modified` when the client already holds this version
+    pub fn respond_to(&self, req: &HttpRequest, cache_status: &str) -> HttpResponse {
+        if etag_matches(req.headers(), &self.etag) {
+            return HttpResponse::NotModified()
+                .insert_header((ETAG, self.etag.clone()))
+                .insert_header(("X-Cache", cache_status))
+                .finish();
+        }
+
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let mut response = HttpResponse::build(status);
+        for (name, value) in &self.headers {
+            response.insert_header((name.as_str(), value.as_str()));
+        }
+        response
+            .insert_header((ETAG, self.etag.clone()))
+            .insert_header(("X-Cache", cache_status))
+            .body(self.body.clone())
+    }
+}
+
+/******************************************/
+// Response cache backends
+/******************************************/
+pub enum ResponseCache {
+    Disabled,
+    Memory(Mutex<LruCache<String, MemoryEntry>>),
+    Redis(RedisService),
+}
+
+pub struct MemoryEntry {
+    route: String,
+    expires_at: Instant,
+    response: CachedResponse,
+}
+
+impl ResponseCache {
+    pub fn new(settings: &GatewayCacheSettings, redis_service: &RedisService) -> Self {
+        match settings.backend {
+            CacheBackend::Disabled => ResponseCache::Disabled,
+            CacheBackend::Memory => {
+                let capacity = NonZeroUsize::new(settings.capacity).unwrap_or(NonZeroUsize::MIN);
+                ResponseCache::Memory(Mutex::new(LruCache::new(capacity)))
+            }
+            CacheBackend::Redis => ResponseCache::Redis(redis_service.clone()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, ResponseCache::Disabled)
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<CachedResponse>, anyhow::Error> {
+        match self {
+            ResponseCache::Disabled => Ok(None),
+            ResponseCache::Memory(entries) => {
+                let mut entries = entries.lock().map_err(|_| anyhow::anyhow!("Cache lock poisoned"))?;
+                let expired = match entries.get(key) {
+                    Some(entry) if entry.expires_at > Instant::now() => return Ok(Some(entry.response.clone())),
+                    Some(_) => true,
+                    None => false,
+                };
+                if expired {
+                    entries.pop(key);
+                }
+                Ok(None)
+            }
+            ResponseCache::Redis(redis_service) => {
+                let mut con = redis_service.get_connection()
+                    .await
+                    .context("Failed to get Redis connection")?;
+                let (status, headers, body, etag): (Option<u16>, Option<String>, Option<Vec<u8>>, Option<String>) = redis::cmd("HMGET")
+                    .arg(redis_entry_key(key))
+                    .arg(&["status", "headers", "body", "etag"])
+                    .query_async(&mut con)
+                    .await
+                    .context("Failed to read cached response")?;
+
+                match (status, headers, body, etag) {
+                    (Some(status), Some(headers), Some(body), Some(etag)) => Ok(Some(CachedResponse {
+                        status,
+                        headers: serde_json::from_str(&headers).context("Failed to parse cached headers")?,
+                        body: Bytes::from(body),
+                        etag,
+                    })),
+                    _ => Ok(None),
+                }
+            }
+        }
+    }
+
+    /// Stores `response` for `ttl`; `route_ttl` bounds how long the route keeps track of its entries
+    pub async fn put(
+        &self,
+        route: &str,
+        key: &str,
+        response: CachedResponse,
+        ttl: Duration,
+        route_ttl: Duration,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            ResponseCache::Disabled => Ok(()),
+            ResponseCache::Memory(entries) => {
+                let mut entries = entries.lock().map_err(|_| anyhow::anyhow!("Cache lock poisoned"))?;
+                entries.put(key.to_string(), MemoryEntry {
+                    route: route.to_string(),
+                    expires_at: Instant::now() + ttl,
+                    response,
+                });
+                Ok(())
+            }
+            ResponseCache::Redis(redis_service) => {
+                let mut con = redis_service.get_connection()
+                    .await
+                    .context("Failed to get Redis connection")?;
+                let entry_key = redis_entry_key(key);
+                let route_key = redis_route_key(route);
+                let headers = serde_json::to_string(&response.headers).context("Failed to serialize headers")?;
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .context("System clock is before the Unix epoch")?
+                    .as_secs();
+
+                redis::pipe()
+                    .atomic()
+                    .hset_multiple(&entry_key, &[
+                        ("status", response.status.to_string().into_bytes()),
+                        ("headers", headers.into_bytes()),
+                        ("body", response.body.to_vec()),
+                        ("etag", response.etag.into_bytes()),
+                    ])
+                    .ignore()
+                    .expire(&entry_key, ttl.as_secs().max(1) as usize)
+                    .ignore()
+                    // The route index is scored by expiry, so entries gone from Redis are pruned
+                    // on the next store and the index itself expires with the route's last entry
+                    .zadd(&route_key, &entry_key, now + ttl.as_secs().max(1))
+                    .ignore()
+                    .zrembyscore(&route_key, "-inf", now)
+                    .ignore()
+                    .expire(&route_key, route_ttl.max(ttl).as_secs().max(1) as usize)
+                    .ignore()
+                    .query_async::<_, ()>(&mut con)
+                    .await
+                    .context("Failed to store cached response")?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Drops every cached response stored for `route`
+    pub async fn invalidate_route(&self, route: &str) -> Result<(), anyhow::Error> {
+        match self {
+            ResponseCache::Disabled => Ok(()),
+            ResponseCache::Memory(entries) => {
+                let mut entries = entries.lock().map_err(|_| anyhow::anyhow!("Cache lock poisoned"))?;
+                let stale_keys: Vec<String> = entries
+                    .iter()
+                    .filter(|(_, entry)| entry.route == route)
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                for key in stale_keys {
+                    entries.pop(&key);
+                }
+                Ok(())
+            }
+            ResponseCache::Redis(redis_service) => {
+                let mut con = redis_service.get_connection()
+                    .await
+                    .context("Failed to get Redis connection")?;
+                let route_key = redis_route_key(route);
+                let mut entry_keys: Vec<String> = con.zrange(&route_key, 0, -1)
+                    .await
+                    .context("Failed to read cached keys of route")?;
+                entry_keys.push(route_key);
+                con.del::<_, ()>(entry_keys)
+                    .await
+                    .context("Failed to invalidate cached responses")?;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn redis_entry_key(key: &str) -> String {
+    format!("{}:entry:{}", REDIS_KEY_PREFIX, key)
+}
+
+fn redis_route_key(route: &str) -> String {
+    format!("{}:route:{}", REDIS_KEY_PREFIX, route)
+}
+
+/******************************************/
+// Cache key and HTTP caching rules
+/******************************************/
+/// Builds the cache key of a request, or `None` when the rule needs a user and there is none
+pub fn cache_key(
+    route: &GatewayRouteSettings,
+    rule: &RouteCacheSettings,
+    req: &HttpRequest,
+    claims: Option<&Claims>,
+) -> Option<String> {
+    let mut key = format!("{}:{}", route.name, req.path());
+    if rule.key != CacheKeyRule::Path && !req.query_string().is_empty() {
+        key.push('?');
+        key.push_str(req.query_string());
+    }
+    if rule.key == CacheKeyRule::PathQueryUser {
+        key.push_str("#user=");
+        key.push_str(&claims?.sub);
+    }
+    Some(key)
+}
+
+/// Whether the client asked for a fresh response with `Cache-Control: no-cache` or `no-store`
+pub fn request_bypasses_cache(headers: &HeaderMap) -> bool {
+    let cache_control = request_directives(headers);
+    let pragma_no_cache = headers
+        .get(PRAGMA)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.eq_ignore_ascii_case("no-cache"))
+        .unwrap_or(false);
+
+    pragma_no_cache
+        || cache_control
+            .iter()
+            .any(|(directive, _)| directive == "no-cache" || directive == "no-store")
+}
+
+/// Whether the client forbade storing the response with `Cache-Control: no-store`
+pub fn request_forbids_store(headers: &HeaderMap) -> bool {
+    request_directives(headers)
+        .iter()
+        .any(|(directive, _)| directive == "no-store")
+}
+
+fn request_directives(headers: &HeaderMap) -> Vec<(String, Option<String>)> {
+    headers
+        .get(CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .map(cache_directives)
+        .unwrap_or_default()
+}
+
+/// How long an upstream response may be cached, honouring its `Cache-Control` header
+pub fn response_ttl(cache_control: Option<&str>, route_ttl: Duration, per_user: bool) -> Option<Duration> {
+    let directives = cache_control.map(cache_directives).unwrap_or_default();
+    let mut ttl = route_ttl;
+
+    for (directive, value) in &directives {
+        match directive.as_str() {
+            "no-store" | "no-cache" => return None,
+            "private" if !per_user => return None,
+            "max-age" | "s-maxage" => {
+                let max_age = value.as_deref()?.parse::<u64>().ok()?;
+                ttl = ttl.min(Duration::from_secs(max_age));
+            }
+            _ => {}
+        }
+    }
+
+    if ttl.is_zero() {
+        None
+    } else {
+        Some(ttl)
+    }
+}
+
+fn cache_directives(header: &str) -> Vec<(String, Option<String>)> {
+    header
+        .split(',')
+        .map(|directive| directive.trim())
+        .filter(|directive| !directive.is_empty())
+        .map(|directive| match directive.split_once('=') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), Some(value.trim().trim_matches('"').to_string())),
+            None => (directive.to_ascii_lowercase(), None),
+        })
+        .collect()
+}
+
+fn etag_for(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value.split(',').any(|candidate| {
+                let candidate = candidate.trim();
+                candidate == "*" || candidate.trim_start_matches("W/") == etag.trim_start_matches("W/")
+            })
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::response_ttl;
+    use std::time::Duration;
+
+    #[test]
+    fn route_ttl_is_used_without_cache_control() {
+        assert_eq!(response_ttl(None, Duration::from_secs(30), false), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn max_age_shortens_route_ttl() {
+        let ttl = response_ttl(Some("public, max-age=10"), Duration::from_secs(30), false);
+        assert_eq!(ttl, Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn no_store_responses_are_not_cached() {
+        assert_eq!(response_ttl(Some("no-store"), Duration::from_secs(30), false), None);
+    }
+
+    #[test]
+    fn private_responses_are_only_cached_per_user() {
+        assert_eq!(response_ttl(Some("private"), Duration::from_secs(30), false), None);
+        assert_eq!(response_ttl(Some("private"), Duration::from_secs(30), true), Some(Duration::from_secs(30)));
+    }
+}
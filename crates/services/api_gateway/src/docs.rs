@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use actix_web::{web, HttpResponse};
+use errors::CustomError;
+use lib_config::config::configuration;
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+use tracing::instrument;
+
+const SPEC_PATH: &str = "api-docs/openapi.json";
+const SCHEMA_REF_PREFIX: &str = "#/components/schemas/";
+
+/******************************************/
+// Aggregated OpenAPI spec route
+/******************************************/
+/**
+ * @route   GET /docs/openapi.json
+ * @access  Public
+ */
+#[instrument("Aggregate OpenAPI specs", skip(client))]
+pub async fn openapi_spec(client: web::Data<Arc<Client>>) -> Result<HttpResponse, CustomError> {
+    let config = configuration::Settings::new()
+        .map_err(|_| CustomError::UnexpectedError(anyhow::anyhow!("Failed to load configurations")))?;
+
+    let services = [
+        ("user", &config.domain.user_service_domain, config.service.user_service_port),
+        ("admin", &config.domain.admin_service_domain, config.service.admin_service_port),
+        ("game", &config.domain.game_service_domain, config.service.game_service_port),
+    ];
+
+    let mut specs = Vec::new();
+    for (service, domain, port) in services {
+        let url = format!("http://{}:{}/{}", domain, port, SPEC_PATH);
+        let spec = match client.get(&url).send().await {
+            Ok(resp) if resp.status().is_success() => resp.json::<Value>().await.ok(),
+            Ok(resp) => {
+                tracing::warn!("{} service returned {} for its OpenAPI spec", service, resp.status());
+                None
+            }
+            Err(e) => {
+                tracing::warn!("Failed to fetch OpenAPI spec of {} service: {:?}", service, e);
+                None
+            }
+        };
+        if let Some(spec) = spec {
+            specs.push((service, spec));
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(merge_specs(specs)))
+}
+
+/// Merges service specs into one, prefixing paths with the gateway routing prefix of their service
+pub fn merge_specs(specs: Vec<(&str, Value)>) -> Value {
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+    let mut security_schemes = Map::new();
+    let mut tags = Vec::new();
+
+    for (service, mut spec) in specs {
+        let renames = conflicting_schema_names(service, &spec, &schemas);
+        if !renames.is_empty() {
+            rename_schema_refs(&mut spec, &renames);
+        }
+
+        let components = spec.get("components").cloned().unwrap_or_default();
+        if let Some(service_schemas) = components.get("schemas").and_then(Value::as_object) {
+            for (name, schema) in service_schemas {
+                let name = renames.get(name).unwrap_or(name);
+                schemas.insert(name.clone(), schema.clone());
+            }
+        }
+        if let Some(service_schemes) = components.get("securitySchemes").and_then(Value::as_object) {
+            security_schemes.extend(service_schemes.clone());
+        }
+
+        let title = spec
+            .pointer("/info/title")
+            .and_then(Value::as_str)
+            .unwrap_or(service)
+            .to_string();
+        tags.push(json!({ "name": service, "description": title }));
+
+        if let Some(service_paths) = spec.get_mut("paths").and_then(Value::as_object_mut) {
+            for (path, item) in service_paths.iter_mut() {
+                if let Some(operations) = item.as_object_mut() {
+                    for operation in operations.values_mut().filter_map(Value::as_object_mut) {
+                        operation.insert("tags".into(), json!([service]));
+                    }
+                }
+                paths.insert(format!("/{}{}", service, path), item.take());
+            }
+        }
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "API Gateway",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "tags": tags,
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": security_schemes
+        }
+    })
+}
+
+/// Schemas already merged under the same name with a different definition get prefixed with their service
+fn conflicting_schema_names(service: &str, spec: &Value, merged: &Map<String, Value>) -> HashMap<String, String> {
+    spec.pointer("/components/schemas")
+        .and_then(Value::as_object)
+        .map(|schemas| {
+            schemas
+                .iter()
+                .filter(|(name, schema)| merged.get(*name).map(|existing| existing != *schema).unwrap_or(false))
+                .map(|(name, _)| (name.clone(), format!("{}_{}", service, name)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn rename_schema_refs(value: &mut Value, renames: &HashMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                if key == "$ref" {
+                    if let Some(new_name) = child
+                        .as_str()
+                        .and_then(|reference| reference.strip_prefix(SCHEMA_REF_PREFIX))
+                        .and_then(|name| renames.get(name))
+                    {
+                        *child = Value::String(format!("{}{}", SCHEMA_REF_PREFIX, new_name));
+                    }
+                } else {
+                    rename_schema_refs(child, renames);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| rename_schema_refs(item, renames)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_specs;
+    use serde_json::json;
+
+    #[test]
+    fn paths_are_prefixed_with_service() {
+        let spec = json!({ "paths": { "/api/v1/rate": { "post": { "tags": ["games"] } } } });
+        let merged = merge_specs(vec![("game", spec)]);
+
+        assert!(merged.pointer("/paths/~1game~1api~1v1~1rate/post").is_some());
+        assert_eq!(merged.pointer("/paths/~1game~1api~1v1~1rate/post/tags/0"), Some(&json!("game")));
+    }
+
+    #[test]
+    fn conflicting_schemas_are_renamed_with_their_refs() {
+        let admin = json!({
+            "components": { "schemas": { "User": { "type": "object", "required": ["id"] } } }
+        });
+        let game = json!({
+            "paths": { "/users": { "get": { "responses": { "200": { "$ref": "#/components/schemas/User" } } } } },
+            "components": { "schemas": { "User": { "type": "object" } } }
+        });
+        let merged = merge_specs(vec![("admin", admin), ("game", game)]);
+
+        assert!(merged.pointer("/components/schemas/User").is_some());
+        assert!(merged.pointer("/components/schemas/game_User").is_some());
+        assert_eq!(
+            merged.pointer("/paths/~1game~1users/get/responses/200/$ref"),
+            Some(&json!("#/components/schemas/game_User"))
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use flume::Receiver;
+use futures::StreamExt;
+use lib_config::config::configuration::GatewayRouteSettings;
+use rdkafka::message::OwnedMessage;
+use rdkafka::Message;
+
+use crate::cache::ResponseCache;
+
+/// Topics that invalidate at least one cached route
+pub fn invalidation_topics(routes: &[GatewayRouteSettings]) -> Vec<String> {
+    let mut topics: Vec<String> = routes
+        .iter()
+        .filter_map(|route| route.cache.as_ref())
+        .flat_map(|cache| cache.invalidate_on.iter().cloned())
+        .collect();
+    topics.sort();
+    topics.dedup();
+    topics
+}
+
+pub async fn process_kafka_message(
+    kafka_receiver: Receiver<OwnedMessage>,
+    routes: Vec<GatewayRouteSettings>,
+    cache: Arc<ResponseCache>,
+) {
+    kafka_receiver
+        .stream()
+        .for_each(|msg| {
+            let routes = &routes;
+            let cache = cache.clone();
+            async move {
+                let stale_routes = routes.iter().filter(|route| {
+                    route.cache
+                        .as_ref()
+                        .map(|cache| cache.invalidate_on.iter().any(|topic| topic == msg.topic()))
+                        .unwrap_or(false)
+                });
+
+                for route in stale_routes {
+                    match cache.invalidate_route(&route.name).await {
+                        Ok(_) => tracing::info!("Invalidated cached responses of route {} after {} event", route.name, msg.topic()),
+                        Err(e) => tracing::error!("Failed to invalidate cached responses of route {}: {:?}", route.name, e),
+                    }
+                }
+            }
+        })
+        .await;
+}
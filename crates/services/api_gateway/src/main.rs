@@ -1,19 +1,43 @@
-use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
-use actix_web::middleware::Logger;
+use actix_web::{web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer};
+use actix_web::middleware::from_fn;
+use tracing_actix_web::TracingLogger;
 use errors::CustomError;
 use reqwest::Client;
 use std::sync::Arc;
 use lib_config::config::configuration;
-use utils::telemetry::{get_subscriber, init_subscriber};
-use serde_json::{Value, to_string_pretty};
+use utils::telemetry::{get_subscriber, init_subscriber, RequestIdRootSpanBuilder, REQUEST_ID_HEADER};
+use serde_json::to_string_pretty;
 use reqwest::{header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE}};
 use serde::{Serialize, Deserialize};
 use actix_web::http::StatusCode;
 
 use lib_config::session::redis::RedisService;
+use lib_config::config::configuration::{CacheKeyRule, GatewaySettings};
 use bytes::Bytes;
 use anyhow;
 use tracing::instrument;
+use kafka::setup::setup_kafka_receiver;
+use std::time::{Duration, Instant};
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+use crate::access_log::{access_log, UpstreamCall};
+use crate::auth::authenticate;
+use crate::docs::openapi_spec;
+use crate::cache::{cache_key, request_bypasses_cache, request_forbids_store, response_ttl, CachedResponse, ResponseCache};
+use crate::kafka_handler::{invalidation_topics, process_kafka_message};
+use crate::policy::{check_cors, gateway_policy, payload_too_large, read_body, route_policy, BodyError};
+use crate::realtime::{is_event_stream, is_websocket_upgrade, proxy_websocket, stream_response, websocket_url};
+use crate::route::find_route;
+
+mod access_log;
+mod auth;
+mod cache;
+mod docs;
+mod kafka_handler;
+mod policy;
+mod realtime;
+mod route;
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let subscriber = get_subscriber("api_gateway".into(), "info".into(), std::io::stdout);
@@ -23,25 +47,80 @@ async fn main() -> std::io::Result<()> {
 
     let client = Arc::new(Client::new());
     let redis_service = RedisService::new(config.redis.uri).await;
+    let gateway = config.gateway;
+    check_cors(&gateway).expect("Invalid gateway CORS policy");
+    let cache = Arc::new(ResponseCache::new(&gateway.cache, &redis_service));
+
+    let topics = invalidation_topics(&gateway.routes);
+    if cache.is_enabled() && !topics.is_empty() {
+        match (&config.kafka.gateway_url, &config.kafka.gateway_consumer_group) {
+            (Some(url), Some(group)) => {
+                let rx = setup_kafka_receiver(url, &topics, group).await;
+                let routes = gateway.routes.clone();
+                let cache = cache.clone();
+                tokio::spawn(async move {
+                    process_kafka_message(rx, routes, cache).await;
+                });
+            }
+            _ => tracing::warn!("kafka.gateway_url or kafka.gateway_consumer_group unset, cached routes are only refreshed by their TTL"),
+        }
+    }
+
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(client.clone())) 
-            .wrap(Logger::default())
+            .wrap(from_fn(gateway_policy))
+            .wrap(TracingLogger::<RequestIdRootSpanBuilder>::new())
+            .wrap(from_fn(access_log))
             .app_data(web::Data::new(redis_service.clone()))
+            .app_data(web::Data::from(cache.clone()))
+            .app_data(web::Data::new(gateway.clone()))
+            .route("/docs/openapi.json", web::get().to(openapi_spec))
+            .service(SwaggerUi::new("/docs/{_:.*}").config(Config::from("/docs/openapi.json")))
             .route("/{service}/{endpoint:.*}", web::to(forward_requests))
     })
     .bind(format!("{}:{}", config.domain.gateway_service_domain, config.service.gateway_service_port))?
     .run()
     .await
 }
-#[instrument("forward requests", skip(client, req, body))]
+#[instrument("forward requests", skip(client, req, body, redis_service, cache, gateway))]
 pub(crate) async fn forward_requests(
     path: web::Path<(String, String)>, 
     client: web::Data<Arc<Client>>,
     req: HttpRequest,
-    body: Option<web::Json<Value>>,
+    body: web::Payload,
+    redis_service: web::Data<RedisService>,
+    cache: web::Data<ResponseCache>,
+    gateway: web::Data<GatewaySettings>,
 ) -> Result<HttpResponse, CustomError> {
     let (service, endpoint) = path.into_inner();
+
+    let route = find_route(&gateway.routes, &service, &endpoint);
+    let policy = route_policy(&gateway, route);
+    let cache_rule = route
+        .and_then(|route| route.cache.as_ref().map(|rule| (route, rule)))
+        .filter(|_| cache.is_enabled() && req.method() == actix_web::http::Method::GET && !is_websocket_upgrade(&req));
+
+    let claims = match route {
+        Some(route) if route.require_auth => Some(authenticate(&req, &redis_service).await?),
+        _ if cache_rule.is_some() && req.headers().contains_key("Authorization") => {
+            authenticate(&req, &redis_service).await.ok()
+        }
+        _ => None,
+    };
+
+    let cache_entry = cache_rule.and_then(|(route, rule)| {
+        cache_key(route, rule, &req, claims.as_ref()).map(|key| (route, rule, key))
+    });
+    let bypass_cache = request_bypasses_cache(req.headers());
+
+    if let Some((_, _, key)) = cache_entry.as_ref().filter(|_| !bypass_cache) {
+        match cache.get(key).await {
+            Ok(Some(cached)) => return Ok(cached.respond_to(&req, "HIT")),
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to read response cache: {:?}", e),
+        }
+    }
     
     let config = match configuration::Settings::new() {
         Ok(conf) => conf,
@@ -73,24 +152,70 @@ pub(crate) async fn forward_requests(
     if let Some(auth_header_value) = auth_header {
         headers.insert("Authorization", auth_header_value);
     }
-    headers.insert("Content-Type", HeaderValue::from_static("application/json"));
+    if let Some(request_id) = req.headers().get(REQUEST_ID_HEADER) {
+        headers.insert(REQUEST_ID_HEADER, request_id.clone());
+    }
+    // Services see the gateway as the peer, they need these for login lockouts and alerts. The
+    // header is replaced with the peer the gateway saw, never trusted from the client.
+    if let Some(client_ip) = req.peer_addr().and_then(|addr| HeaderValue::from_str(&addr.ip().to_string()).ok()) {
+        headers.insert("X-Forwarded-For", client_ip);
+    }
+    if let Some(user_agent) = req.headers().get("User-Agent") {
+        headers.insert("User-Agent", user_agent.clone());
+    }
+
+    if is_websocket_upgrade(&req) {
+        return proxy_websocket(&req, body, &websocket_url(&full_url), &headers).await;
+    }
+
+    // The OAuth token, introspection and revocation endpoints take form bodies
+    let content_type = req.headers().get("Content-Type").cloned();
+    headers.insert("Content-Type", content_type.unwrap_or(HeaderValue::from_static("application/json")));
+    let body = match read_body(body, policy.max_body_bytes).await {
+        Ok(body) => body,
+        Err(BodyError::TooLarge) => return Ok(payload_too_large(policy.max_body_bytes)),
+        Err(BodyError::Read(e)) => {
+            return Err(CustomError::ValidationError(format!("Failed to read request body: {}", e)))
+        }
+    };
 
     let mut request_builder = client.request(req.method().clone(), full_url)
         .headers(headers);
 
-    let request_builder = request_builder.body(match body {
-        Some(b) => serde_json::to_string(&b).unwrap_or_default(),
-        None => "".to_string(),
-    });
+    let request_builder = request_builder.body(body);
 
+    let started = Instant::now();
     let response = request_builder.send().await;
+    if let Ok(resp) = &response {
+        req.extensions_mut().insert(UpstreamCall {
+            service: service.clone(),
+            status: resp.status().as_u16(),
+            latency: started.elapsed(),
+        });
+    }
 
     match response {
+        Ok(resp) if is_event_stream(resp.headers()) => Ok(stream_response(resp)),
         Ok(mut resp) => {
             let status = resp.status().clone();
             let headers = resp.headers().clone();
             let body = resp.bytes().await.unwrap_or_else(|_| Bytes::from("Failed to read response body"));
 
+            if let Some((route, rule, key)) = cache_entry.filter(|_| status == StatusCode::OK) {
+                let cache_control = headers.get("Cache-Control").and_then(|value| value.to_str().ok());
+                let per_user = rule.key == CacheKeyRule::PathQueryUser;
+                let ttl = response_ttl(cache_control, Duration::from_secs(rule.ttl_secs), per_user)
+                    .filter(|_| !request_forbids_store(req.headers()));
+
+                if let Some(ttl) = ttl {
+                    let cached = CachedResponse::new(status, &headers, body);
+                    if let Err(e) = cache.put(&route.name, &key, cached.clone(), ttl, Duration::from_secs(rule.ttl_secs)).await {
+                        tracing::error!("Failed to store response in cache: {:?}", e);
+                    }
+                    return Ok(cached.respond_to(&req, "MISS"));
+                }
+            }
+
             let mut http_response = HttpResponse::build(status);
             for (header_name, header_value) in headers.iter() {
                 http_response.insert_header((
@@ -101,7 +226,8 @@ pub(crate) async fn forward_requests(
             Ok(http_response.body(body))
         },
         Err(err) => {
-            let err_msg = format!("Error forwarding request: {:#?}", err);
+            // The URL holds the query string, which may carry tokens
+            let err_msg = format!("Error forwarding request: {:#?}", err.without_url());
             Err(CustomError::UnexpectedError(anyhow::Error::msg(err_msg)))
         }
     }
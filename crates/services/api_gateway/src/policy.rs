@@ -0,0 +1,294 @@
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{error::PayloadError, web, Error, HttpResponse};
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use lib_config::config::configuration::{CorsSettings, GatewayRouteSettings, GatewaySettings};
+use serde_json::json;
+
+use crate::route::find_route;
+
+const DOCS_PREFIX: &str = "docs";
+const EXPOSED_HEADERS: &str = "ETag, X-Cache, X-Request-Id";
+const API_CSP: &str = "default-src 'none'; frame-ancestors 'none'";
+const DOCS_CSP: &str = "default-src 'self'; script-src 'self'; style-src 'self' 'unsafe-inline'; \
+                        img-src 'self' data:; connect-src 'self'; frame-ancestors 'none'";
+
+/******************************************/
+// Effective policy of a route
+/******************************************/
+/// Gateway defaults overridden by whatever the matched route configures
+#[derive(Debug, Clone, Copy)]
+pub struct RoutePolicy<'a> {
+    pub max_body_bytes: usize,
+    pub max_header_bytes: usize,
+    pub cors: Option<&'a CorsSettings>,
+}
+
+pub fn route_policy<'a>(gateway: &'a GatewaySettings, route: Option<&'a GatewayRouteSettings>) -> RoutePolicy<'a> {
+    let defaults = &gateway.policy;
+    RoutePolicy {
+        max_body_bytes: route.and_then(|route| route.max_body_bytes).unwrap_or(defaults.max_body_bytes),
+        max_header_bytes: route.and_then(|route| route.max_header_bytes).unwrap_or(defaults.max_header_bytes),
+        cors: route.and_then(|route| route.cors.as_ref()).or(defaults.cors.as_ref()),
+    }
+}
+
+/// Splits a gateway path into the `(service, endpoint)` pair used for routing
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_start_matches('/');
+    path.split_once('/').unwrap_or((path, ""))
+}
+
+/******************************************/
+// Policy middleware
+/******************************************/
+/// Enforces size limits and CORS of the matched route and adds the security headers to every response
+pub async fn gateway_policy<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let gateway = req
+        .app_data::<web::Data<GatewaySettings>>()
+        .cloned()
+        .unwrap_or_default();
+    let (service, endpoint) = split_path(req.path());
+    let is_docs = service == DOCS_PREFIX;
+    let route = find_route(&gateway.routes, service, endpoint);
+    let policy = route_policy(&gateway, route);
+    let origin = req.headers().get(header::ORIGIN).cloned();
+
+    let rejection = if headers_size(req.headers()) > policy.max_header_bytes {
+        Some(policy_rejection(
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            format!("Request headers exceed {} bytes", policy.max_header_bytes),
+        ))
+    } else if declared_body_size(req.headers()).is_some_and(|size| size > policy.max_body_bytes) {
+        Some(payload_too_large(policy.max_body_bytes))
+    } else if is_preflight(&req) {
+        Some(preflight_response(policy.cors, origin.as_ref(), req.headers()))
+    } else {
+        None
+    };
+
+    let mut res = match rejection {
+        Some(response) => req.into_response(response).map_into_right_body(),
+        None => next.call(req).await?.map_into_left_body(),
+    };
+
+    let headers = res.headers_mut();
+    if let (Some(cors), Some(origin)) = (policy.cors, origin.as_ref()) {
+        if origin_allowed(cors, origin) {
+            apply_cors_headers(headers, cors, origin);
+            headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static(EXPOSED_HEADERS));
+        }
+    }
+    apply_security_headers(headers, gateway.policy.hsts_max_age_secs, is_docs);
+
+    Ok(res)
+}
+
+/******************************************/
+// Size limits
+/******************************************/
+fn headers_size(headers: &HeaderMap) -> usize {
+    headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum()
+}
+
+fn declared_body_size(headers: &HeaderMap) -> Option<usize> {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+pub enum BodyError {
+    TooLarge,
+    Read(PayloadError),
+}
+
+/// Reads the request body, failing as soon as it grows past `limit` (covers chunked bodies without a Content-Length)
+pub async fn read_body(mut payload: web::Payload, limit: usize) -> Result<Bytes, BodyError> {
+    let mut body = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(BodyError::Read)?;
+        if body.len() + chunk.len() > limit {
+            return Err(BodyError::TooLarge);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+pub fn payload_too_large(limit: usize) -> HttpResponse {
+    policy_rejection(StatusCode::PAYLOAD_TOO_LARGE, format!("Request body exceeds {} bytes", limit))
+}
+
+fn policy_rejection(status: StatusCode, message: String) -> HttpResponse {
+    HttpResponse::build(status).json(json!({
+        "status": "Failure",
+        "message": message
+    }))
+}
+
+/******************************************/
+// CORS
+/******************************************/
+fn is_preflight(req: &ServiceRequest) -> bool {
+    req.method() == Method::OPTIONS
+        && req.headers().contains_key(header::ORIGIN)
+        && req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+pub fn origin_allowed(cors: &CorsSettings, origin: &HeaderValue) -> bool {
+    let Ok(origin) = origin.to_str() else {
+        return false;
+    };
+    cors.allowed_origins
+        .iter()
+        .any(|allowed| allowed == "*" || allowed.trim_end_matches('/').eq_ignore_ascii_case(origin))
+}
+
+fn method_allowed(cors: &CorsSettings, headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|method| cors.allowed_methods.iter().any(|allowed| allowed.eq_ignore_ascii_case(method)))
+}
+
+fn preflight_response(cors: Option<&CorsSettings>, origin: Option<&HeaderValue>, headers: &HeaderMap) -> HttpResponse {
+    let allowed = match (cors, origin) {
+        (Some(cors), Some(origin)) => origin_allowed(cors, origin) && method_allowed(cors, headers),
+        _ => false,
+    };
+    let (Some(cors), Some(origin)) = (cors.filter(|_| allowed), origin) else {
+        return policy_rejection(StatusCode::FORBIDDEN, "CORS request not allowed".to_string());
+    };
+
+    let mut response = HttpResponse::NoContent().finish();
+    let response_headers = response.headers_mut();
+    apply_cors_headers(response_headers, cors, origin);
+    insert_joined(response_headers, header::ACCESS_CONTROL_ALLOW_METHODS, &cors.allowed_methods);
+    insert_joined(response_headers, header::ACCESS_CONTROL_ALLOW_HEADERS, &cors.allowed_headers);
+    response_headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(cors.max_age_secs));
+    response
+}
+
+/// Refuses a CORS policy allowing any origin with credentials, which would let every site make
+/// requests with the user's cookies
+pub fn check_cors(gateway: &GatewaySettings) -> Result<(), String> {
+    let defaults = gateway.policy.cors.iter().map(|cors| ("[gateway.policy.cors]".to_string(), cors));
+    let routes = gateway
+        .routes
+        .iter()
+        .filter_map(|route| route.cors.as_ref().map(|cors| (format!("route {}", route.name), cors)));
+
+    for (name, cors) in defaults.chain(routes) {
+        if cors.allow_credentials && cors.allowed_origins.iter().any(|allowed| allowed == "*") {
+            return Err(format!("CORS of {} allows any origin with credentials, list the origins instead", name));
+        }
+    }
+    Ok(())
+}
+
+/// Wildcard origins get a literal `*` and never credentials, listed origins are echoed back
+fn apply_cors_headers(headers: &mut HeaderMap, cors: &CorsSettings, origin: &HeaderValue) {
+    if cors.allowed_origins.iter().any(|allowed| allowed == "*") {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        return;
+    }
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+    if cors.allow_credentials {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+    }
+    headers.append(header::VARY, HeaderValue::from_static("Origin"));
+}
+
+fn insert_joined(headers: &mut HeaderMap, name: HeaderName, values: &[String]) {
+    if let Ok(value) = HeaderValue::from_str(&values.join(", ")) {
+        headers.insert(name, value);
+    }
+}
+
+/******************************************/
+// Security headers
+/******************************************/
+/// Upstream values win, the gateway only fills in what is missing
+fn apply_security_headers(headers: &mut HeaderMap, hsts_max_age_secs: u64, is_docs: bool) {
+    let csp = if is_docs { DOCS_CSP } else { API_CSP };
+    let defaults = [
+        (header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+        (header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY")),
+        (header::REFERRER_POLICY, HeaderValue::from_static("no-referrer")),
+        (header::CONTENT_SECURITY_POLICY, HeaderValue::from_static(csp)),
+    ];
+    for (name, value) in defaults {
+        if !headers.contains_key(&name) {
+            headers.insert(name, value);
+        }
+    }
+
+    if hsts_max_age_secs > 0 && !headers.contains_key(header::STRICT_TRANSPORT_SECURITY) {
+        if let Ok(value) = HeaderValue::from_str(&format!("max-age={}; includeSubDomains", hsts_max_age_secs)) {
+            headers.insert(header::STRICT_TRANSPORT_SECURITY, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_cors_headers, check_cors, origin_allowed, split_path};
+    use actix_web::http::header::{self, HeaderMap, HeaderValue};
+    use lib_config::config::configuration::{CorsSettings, GatewaySettings};
+
+    fn cors(origins: &[&str]) -> CorsSettings {
+        CorsSettings {
+            allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            allowed_methods: vec!["GET".into()],
+            allowed_headers: vec![],
+            allow_credentials: false,
+            max_age_secs: 0,
+        }
+    }
+
+    #[test]
+    fn origins_match_exactly_or_by_wildcard() {
+        let origin = HeaderValue::from_static("http://localhost:3000");
+
+        assert!(origin_allowed(&cors(&["http://localhost:3000/"]), &origin));
+        assert!(origin_allowed(&cors(&["*"]), &origin));
+        assert!(!origin_allowed(&cors(&["http://localhost:3001"]), &origin));
+    }
+
+    #[test]
+    fn any_origin_with_credentials_is_refused() {
+        let mut gateway = GatewaySettings::default();
+        gateway.policy.cors = Some(CorsSettings { allow_credentials: true, ..cors(&["*"]) });
+        assert!(check_cors(&gateway).is_err());
+
+        gateway.policy.cors = Some(CorsSettings { allow_credentials: true, ..cors(&["http://localhost:3000"]) });
+        assert!(check_cors(&gateway).is_ok());
+    }
+
+    #[test]
+    fn wildcard_origin_is_never_echoed_with_credentials() {
+        let origin = HeaderValue::from_static("http://evil.example");
+        let mut headers = HeaderMap::new();
+        apply_cors_headers(&mut headers, &CorsSettings { allow_credentials: true, ..cors(&["*"]) }, &origin);
+
+        assert_eq!(headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "*");
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS));
+    }
+
+    #[test]
+    fn paths_split_into_service_and_endpoint() {
+        assert_eq!(split_path("/game/api/v1/"), ("game", "api/v1/"));
+        assert_eq!(split_path("/docs"), ("docs", ""));
+    }
+}
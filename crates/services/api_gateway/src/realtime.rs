@@ -0,0 +1,162 @@
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_ws::{AggregatedMessage, CloseCode as ClientCloseCode, CloseReason, MessageStream, Session};
+use anyhow::Context;
+use errors::CustomError;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName as UpstreamHeaderName, HeaderValue as UpstreamHeaderValue};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode as UpstreamCloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message as UpstreamMessage;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+type UpstreamSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Headers of a streamed response that only make sense for the upstream connection
+const HOP_BY_HOP_HEADERS: [&str; 3] = ["content-length", "transfer-encoding", "connection"];
+
+/******************************************/
+// Detecting realtime requests
+/******************************************/
+pub fn is_websocket_upgrade(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
+
+pub fn is_event_stream(headers: &reqwest::header::HeaderMap) -> bool {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"))
+}
+
+/// Upstream services are plain http, their WebSocket endpoints are the same url over ws
+pub fn websocket_url(http_url: &str) -> String {
+    match http_url.strip_prefix("http://") {
+        Some(rest) => format!("ws://{}", rest),
+        None => http_url.replacen("https://", "wss://", 1),
+    }
+}
+
+/******************************************/
+// Server-Sent Events
+/******************************************/
+/// Relays an upstream `text/event-stream` response chunk by chunk instead of buffering it
+pub fn stream_response(response: reqwest::Response) -> HttpResponse {
+    let status = StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let mut http_response = HttpResponse::build(status);
+    for (name, value) in response.headers().iter() {
+        if !HOP_BY_HOP_HEADERS.contains(&name.as_str()) {
+            http_response.insert_header((name.as_str(), value.as_bytes()));
+        }
+    }
+    http_response.insert_header((header::CACHE_CONTROL, "no-cache"));
+    http_response.insert_header(("X-Accel-Buffering", "no"));
+    http_response.streaming(response.bytes_stream())
+}
+
+/******************************************/
+// WebSocket proxy
+/******************************************/
+/// Opens the upstream socket first so a failing upstream is reported before the client is upgraded
+pub async fn proxy_websocket(
+    req: &HttpRequest,
+    body: web::Payload,
+    upstream_url: &str,
+    forwarded_headers: &reqwest::header::HeaderMap,
+) -> Result<HttpResponse, CustomError> {
+    let mut request = upstream_url
+        .into_client_request()
+        .context("Invalid upstream WebSocket url")?;
+    for (name, value) in forwarded_headers.iter() {
+        let name = UpstreamHeaderName::from_bytes(name.as_str().as_bytes());
+        let value = UpstreamHeaderValue::from_bytes(value.as_bytes());
+        if let (Ok(name), Ok(value)) = (name, value) {
+            request.headers_mut().insert(name, value);
+        }
+    }
+
+    let (upstream, _) = connect_async(request)
+        .await
+        .context("Failed to connect to upstream WebSocket")?;
+    let (response, session, client_stream) = actix_ws::handle(req, body)
+        .map_err(|e| CustomError::ValidationError(format!("WebSocket handshake failed: {}", e)))?;
+
+    let (upstream_sink, upstream_stream) = upstream.split();
+    actix_web::rt::spawn(client_to_upstream(client_stream, upstream_sink));
+    actix_web::rt::spawn(upstream_to_client(upstream_stream, session));
+
+    Ok(response)
+}
+
+async fn client_to_upstream(client_stream: MessageStream, mut upstream: SplitSink<UpstreamSocket, UpstreamMessage>) {
+    let mut client_stream = client_stream.aggregate_continuations();
+    while let Some(Ok(msg)) = client_stream.next().await {
+        let (msg, closing) = match msg {
+            AggregatedMessage::Text(text) => (UpstreamMessage::Text(text.to_string()), false),
+            AggregatedMessage::Binary(bytes) => (UpstreamMessage::Binary(bytes.to_vec()), false),
+            AggregatedMessage::Ping(bytes) => (UpstreamMessage::Ping(bytes.to_vec()), false),
+            AggregatedMessage::Pong(bytes) => (UpstreamMessage::Pong(bytes.to_vec()), false),
+            AggregatedMessage::Close(reason) => (UpstreamMessage::Close(reason.map(upstream_close_frame)), true),
+        };
+        if let Err(e) = upstream.send(msg).await {
+            tracing::warn!("Failed to relay WebSocket message upstream: {:?}", e);
+            break;
+        }
+        if closing {
+            return;
+        }
+    }
+    let _ = upstream.close().await;
+}
+
+async fn upstream_to_client(mut upstream: SplitStream<UpstreamSocket>, mut session: Session) {
+    while let Some(Ok(msg)) = upstream.next().await {
+        let relayed = match msg {
+            UpstreamMessage::Text(text) => session.text(text).await,
+            UpstreamMessage::Binary(bytes) => session.binary(bytes).await,
+            UpstreamMessage::Ping(bytes) => session.ping(&bytes).await,
+            UpstreamMessage::Pong(bytes) => session.pong(&bytes).await,
+            UpstreamMessage::Close(frame) => {
+                let _ = session.close(frame.map(client_close_reason)).await;
+                return;
+            }
+            UpstreamMessage::Frame(_) => Ok(()),
+        };
+        if relayed.is_err() {
+            return;
+        }
+    }
+    let _ = session.close(Some(ClientCloseCode::Away.into())).await;
+}
+
+fn upstream_close_frame(reason: CloseReason) -> CloseFrame<'static> {
+    CloseFrame {
+        code: UpstreamCloseCode::from(u16::from(reason.code)),
+        reason: reason.description.unwrap_or_default().into(),
+    }
+}
+
+fn client_close_reason(frame: CloseFrame<'_>) -> CloseReason {
+    CloseReason {
+        code: ClientCloseCode::from(u16::from(frame.code)),
+        description: Some(frame.reason.into_owned()).filter(|reason| !reason.is_empty()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::websocket_url;
+
+    #[test]
+    fn upstream_urls_switch_to_websocket_scheme() {
+        assert_eq!(websocket_url("http://localhost:8082/api/v1/live"), "ws://localhost:8082/api/v1/live");
+        assert_eq!(websocket_url("https://game.internal/live"), "wss://game.internal/live");
+    }
+}
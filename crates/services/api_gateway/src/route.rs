@@ -0,0 +1,16 @@
+use lib_config::config::configuration::GatewayRouteSettings;
+
+/******************************************/
+// Matching a request to its route policy
+/******************************************/
+/// Returns the configured route for `service` with the longest `path_prefix` matching `endpoint`
+pub fn find_route<'a>(
+    routes: &'a [GatewayRouteSettings],
+    service: &str,
+    endpoint: &str,
+) -> Option<&'a GatewayRouteSettings> {
+    routes
+        .iter()
+        .filter(|route| route.service == service && endpoint.starts_with(&route.path_prefix))
+        .max_by_key(|route| route.path_prefix.len())
+}
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE games
+    DROP COLUMN rating_version,
+    DROP COLUMN rating_count,
+    DROP COLUMN average_rating;
+
+ALTER TABLE rate_game
+    DROP CONSTRAINT unique_user_game_rating,
+    DROP COLUMN updated_at;
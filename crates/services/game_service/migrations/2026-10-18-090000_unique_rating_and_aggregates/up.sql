@@ -0,0 +1,26 @@
+-- Your SQL goes here
+DELETE FROM rate_game older
+    USING rate_game newer
+    WHERE older.user_id = newer.user_id
+      AND older.game_slug = newer.game_slug
+      AND (older.created_at, older.id) < (newer.created_at, newer.id);
+
+ALTER TABLE rate_game
+    ADD COLUMN updated_at TIMESTAMP,
+    ADD CONSTRAINT unique_user_game_rating UNIQUE (user_id, game_slug);
+
+ALTER TABLE games
+    ADD COLUMN average_rating REAL NOT NULL DEFAULT 0,
+    ADD COLUMN rating_count INT NOT NULL DEFAULT 0,
+    ADD COLUMN rating_version BIGINT NOT NULL DEFAULT 0;
+
+UPDATE games SET
+    average_rating = stats.average_rating,
+    rating_count = stats.rating_count,
+    rating_version = 1
+FROM (
+    SELECT game_slug, AVG(rating)::REAL AS average_rating, COUNT(*)::INT AS rating_count
+    FROM rate_game
+    GROUP BY game_slug
+) stats
+WHERE games.slug = stats.game_slug;
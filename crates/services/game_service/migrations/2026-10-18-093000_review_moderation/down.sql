@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE review_flags;
+ALTER TABLE rate_game DROP COLUMN moderation_status;
+DROP TYPE review_status;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TYPE review_status AS ENUM ('Published', 'Pending', 'Rejected', 'Hidden');
+
+ALTER TABLE rate_game ADD COLUMN moderation_status review_status NOT NULL DEFAULT 'Published';
+
+CREATE TABLE review_flags (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    review_id uuid NOT NULL,
+    flagged_by uuid NOT NULL,
+    reason TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+
+    CONSTRAINT fk_flagged_review FOREIGN KEY (review_id) REFERENCES rate_game(id) ON DELETE CASCADE,
+    CONSTRAINT fk_flagged_by FOREIGN KEY (flagged_by) REFERENCES users(id) ON DELETE CASCADE,
+    CONSTRAINT unique_review_flag UNIQUE (review_id, flagged_by)
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX rate_game_liked_by_user;
+DROP INDEX rate_game_liked_by_game;
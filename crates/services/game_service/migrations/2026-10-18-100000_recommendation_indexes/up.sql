@@ -0,0 +1,3 @@
+-- Your SQL goes here
+CREATE INDEX rate_game_liked_by_game ON rate_game (game_slug, user_id) WHERE rating >= 4;
+CREATE INDEX rate_game_liked_by_user ON rate_game (user_id, game_slug) WHERE rating >= 4;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE game_list_items;
+DROP TABLE game_lists;
+DROP TYPE list_visibility;
+DROP TYPE list_kind;
@@ -0,0 +1,32 @@
+-- Your SQL goes here
+CREATE TYPE list_kind AS ENUM ('Wishlist', 'Playing', 'Completed', 'Custom');
+CREATE TYPE list_visibility AS ENUM ('Private', 'Public');
+
+CREATE TABLE game_lists (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id uuid NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    kind list_kind NOT NULL,
+    visibility list_visibility NOT NULL DEFAULT 'Private',
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+
+    CONSTRAINT fk_list_owner FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    CONSTRAINT unique_list_name UNIQUE (user_id, name)
+);
+
+-- Every user has at most one list of each built-in kind
+CREATE UNIQUE INDEX unique_default_list ON game_lists (user_id, kind) WHERE kind <> 'Custom';
+
+CREATE TABLE game_list_items (
+    list_id uuid NOT NULL,
+    game_slug VARCHAR(512) NOT NULL,
+    position INT4 NOT NULL,
+    added_at TIMESTAMP NOT NULL DEFAULT NOW(),
+
+    CONSTRAINT unique_list_game PRIMARY KEY (list_id, game_slug),
+    CONSTRAINT fk_list FOREIGN KEY (list_id) REFERENCES game_lists(id) ON DELETE CASCADE,
+    CONSTRAINT fk_list_game FOREIGN KEY (game_slug) REFERENCES games(slug) ON DELETE CASCADE
+);
+
+CREATE INDEX game_list_items_by_position ON game_list_items (list_id, position);
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Set while the account is deactivated, suspended or pending deletion, their reviews are hidden meanwhile
+ALTER TABLE users ADD COLUMN deactivated_at TIMESTAMP;
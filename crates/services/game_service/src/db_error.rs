@@ -29,6 +29,17 @@ fn diesel_db_response_error(err: &DieselError) -> CustomError{
                     match info.constraint_name() {
                         Some("fk_game") => ("Game doesn't exist", StatusCode::BAD_REQUEST),
                         Some("fk_user") => ("User doesn't exist", StatusCode::BAD_REQUEST),
+                        Some("fk_list_game") => ("Game doesn't exist", StatusCode::BAD_REQUEST),
+                        Some("fk_list_owner") => ("User doesn't exist", StatusCode::BAD_REQUEST),
+                        _ => ("internal server error", StatusCode::INTERNAL_SERVER_ERROR)
+                    }
+                },
+
+                diesel::result::DatabaseErrorKind::UniqueViolation => {
+                    match info.constraint_name() {
+                        Some("unique_review_flag") => ("You already flagged this review", StatusCode::BAD_REQUEST),
+                        Some("unique_list_game") => ("The game is already in this list", StatusCode::BAD_REQUEST),
+                        Some("unique_list_name") => ("You already have a list with this name", StatusCode::BAD_REQUEST),
                         _ => ("internal server error", StatusCode::INTERNAL_SERVER_ERROR)
                     }
                 },
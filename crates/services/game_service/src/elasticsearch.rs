@@ -1,11 +1,10 @@
-use elasticsearch::{Elasticsearch, IndexParts, SearchParts, UpdateParts, DeleteParts};
-use elasticsearch::http::transport::Transport;
-use serde_json::json;
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
-use tracing::instrument;
+use serde_json::{json, Value};
+use utoipa::ToSchema;
 use crate::kafka_handler::ReceivedGame;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct ElasticsearchGame {
     pub slug: String,
     pub name: String,
@@ -14,7 +13,19 @@ pub struct ElasticsearchGame {
     pub genre: Option<String>,
     // pub rating: Option<Vec<i32>>,
     pub average_rating: Option<f32>,
-    pub rating_count: Option<i32>
+    pub rating_count: Option<i32>,
+    #[serde(default)]
+    pub created_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    #[serde(default)]
+    pub release_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub publisher: Option<String>,
+    #[serde(default)]
+    pub cover_image_url: Option<String>,
 }
 
 impl ElasticsearchGame {
@@ -27,69 +38,88 @@ impl ElasticsearchGame {
             genre: game.genre.clone(),
             // rating:None,
             average_rating: None,
-            rating_count: None
+            rating_count: None,
+            created_at: game.created_at,
+            tags: game.tags.clone(),
+            platforms: game.platforms.clone(),
+            release_date: game.release_date,
+            publisher: game.publisher.clone(),
+            cover_image_url: game.cover_image_url.clone(),
         }
     }
 
-    pub async fn index_game(elastic_client: &Elasticsearch, game: &ElasticsearchGame) -> Result<(), Box<dyn std::error::Error>> {
-        let response = elastic_client
-            .index(IndexParts::IndexId("rate", &game.slug))
-            .body(json!({
-                "slug": game.slug,
-                "name": game.name,
-                "title": game.title,
-                "description": game.description,
-                "genre": game.genre,
-                "average_rating": 0,
-                "rating_count": 0
-            }))
-            .send()
-            .await?;
-
-        if response.status_code().is_success() {
-            tracing::info!("Indexed game: {} into Elasticsearch", game.slug);
-        } else {
-            tracing::error!("Failed to index game into Elasticsearch: {}", game.slug);
-        }
-
-        Ok(())
-    }
-    pub async fn update_game(elastic_client: &Elasticsearch, game: &ElasticsearchGame, avg_rating: Option<f32>, count: Option<i32>) -> Result<(), Box<dyn std::error::Error>> {
-        let response = elastic_client
-            .update(UpdateParts::IndexId("rate", &game.slug))
-            .body(json!({
-                "doc": {
-                    "name": game.name,
-                    "title": game.title,
-                    "description": game.description,
-                    "genre": game.genre,
-                    "average_rating": avg_rating,
-                    "rating_count":count
+    /// `game_text` folds case and accents and stems english words, `autocomplete` indexes name prefixes
+    pub fn index_definition() -> Value {
+        json!({
+            "settings": {
+                "analysis": {
+                    "filter": {
+                        "english_stemmer": { "type": "stemmer", "language": "english" },
+                        "autocomplete_filter": { "type": "edge_ngram", "min_gram": 2, "max_gram": 20 }
+                    },
+                    "analyzer": {
+                        "game_text": {
+                            "type": "custom",
+                            "tokenizer": "standard",
+                            "filter": ["lowercase", "asciifolding", "english_stemmer"]
+                        },
+                        "autocomplete": {
+                            "type": "custom",
+                            "tokenizer": "standard",
+                            "filter": ["lowercase", "asciifolding", "autocomplete_filter"]
+                        }
+                    },
+                    "normalizer": {
+                        "lowercase_keyword": {
+                            "type": "custom",
+                            "filter": ["lowercase", "asciifolding"]
+                        }
+                    }
                 }
-            }))
-            .send()
-            .await?;
-    
-        if response.status_code().is_success() {
-            tracing::info!("Updated game: {} in Elasticsearch", game.slug);
-        } else {
-            tracing::error!("Failed to update game in Elasticsearch: {}", game.slug);
-        }
-    
-        Ok(())
-    }
-    pub async fn delete_game(elastic_client: &Elasticsearch, slug: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let response = elastic_client
-            .delete(DeleteParts::IndexId("rate", slug))
-            .send()
-            .await?;
-
-        if response.status_code().is_success() {
-            tracing::info!("Deleted game: {} from Elasticsearch", slug);
-        } else {
-            tracing::error!("Failed to delete game from Elasticsearch: {}", slug);
-        }
-
-        Ok(())
+            },
+            "mappings": {
+                "properties": {
+                    "slug": { "type": "keyword" },
+                    "name": {
+                        "type": "text",
+                        "analyzer": "game_text",
+                        "fields": {
+                            "keyword": { "type": "keyword" },
+                            "autocomplete": { "type": "text", "analyzer": "autocomplete", "search_analyzer": "game_text" }
+                        }
+                    },
+                    "title": { "type": "text", "analyzer": "game_text" },
+                    "description": { "type": "text", "analyzer": "game_text" },
+                    "genre": {
+                        "type": "keyword",
+                        "normalizer": "lowercase_keyword",
+                        "fields": {
+                            "text": { "type": "text", "analyzer": "game_text" }
+                        }
+                    },
+                    "tags": {
+                        "type": "keyword",
+                        "normalizer": "lowercase_keyword",
+                        "fields": {
+                            "text": { "type": "text", "analyzer": "game_text" }
+                        }
+                    },
+                    "platforms": { "type": "keyword", "normalizer": "lowercase_keyword" },
+                    "release_date": { "type": "date" },
+                    "publisher": {
+                        "type": "keyword",
+                        "normalizer": "lowercase_keyword",
+                        "fields": {
+                            "text": { "type": "text", "analyzer": "game_text" }
+                        }
+                    },
+                    "cover_image_url": { "type": "keyword", "index": false },
+                    "average_rating": { "type": "float" },
+                    "rating_count": { "type": "integer" },
+                    "rating_version": { "type": "long" },
+                    "created_at": { "type": "date" }
+                }
+            }
+        })
     }
 }
@@ -0,0 +1,510 @@
+use std::collections::HashSet;
+
+use actix_web::http::StatusCode;
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel::sql_types::{Array, Text, Uuid as SqlUuid};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use errors::CustomError;
+use kafka::models::GameListKind;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db_error::DbError;
+use crate::schema::{game_list_items, game_lists, games};
+
+pub const MAX_CUSTOM_LISTS: i64 = 50;
+pub const MAX_LIST_GAMES: i64 = 1000;
+pub const MAX_LIST_NAME_LENGTH: usize = 100;
+
+#[derive(diesel_derive_enum::DbEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[ExistingTypePath = "crate::schema::sql_types::ListKind"]
+#[DbValueStyle = "verbatim"]
+pub enum ListKind {
+    Wishlist,
+    Playing,
+    Completed,
+    Custom,
+}
+
+#[derive(diesel_derive_enum::DbEnum, Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, ToSchema)]
+#[ExistingTypePath = "crate::schema::sql_types::ListVisibility"]
+#[DbValueStyle = "verbatim"]
+pub enum ListVisibility {
+    /// Only the owner sees the list
+    #[default]
+    Private,
+    /// Anyone signed in sees the list on the owner's profile
+    Public,
+}
+
+impl From<ListKind> for GameListKind {
+    fn from(kind: ListKind) -> Self {
+        match kind {
+            ListKind::Wishlist => GameListKind::Wishlist,
+            ListKind::Playing => GameListKind::Playing,
+            ListKind::Completed => GameListKind::Completed,
+            ListKind::Custom => GameListKind::Custom,
+        }
+    }
+}
+
+/// Lists every user has, a game sits in at most one of them
+const DEFAULT_LISTS: [(ListKind, &str); 3] = [
+    (ListKind::Wishlist, "Wishlist"),
+    (ListKind::Playing, "Playing"),
+    (ListKind::Completed, "Completed"),
+];
+
+#[derive(Queryable, Selectable, Serialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = game_lists)]
+pub struct GameList {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub kind: ListKind,
+    pub visibility: ListVisibility,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Serialize, Debug, ToSchema)]
+pub struct ListedGame {
+    pub slug: String,
+    pub name: String,
+    pub title: Option<String>,
+    pub genre: Option<String>,
+    pub cover_image_url: Option<String>,
+    pub average_rating: f32,
+    /// Zero based place of the game in the list
+    pub position: i32,
+    pub added_at: NaiveDateTime,
+}
+
+#[derive(Error, Debug)]
+pub enum ListError {
+    #[error("List not found")]
+    NotFound,
+    #[error("Built-in lists can't be renamed or deleted")]
+    BuiltIn,
+    #[error("A user can't have more than {MAX_CUSTOM_LISTS} custom lists")]
+    TooManyLists,
+    #[error("A list can't hold more than {MAX_LIST_GAMES} games")]
+    Full,
+    #[error("{0}")]
+    InvalidOrder(String),
+    #[error(transparent)]
+    Db(#[from] DieselError),
+}
+
+impl From<ListError> for CustomError {
+    fn from(err: ListError) -> Self {
+        match err {
+            ListError::NotFound => CustomError::DatabaseError {
+                msg: "List doesn't exist or isn't visible to the user".to_string(),
+                resp: "List not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            },
+            ListError::Db(err) => DbError(err).into(),
+            err => CustomError::ValidationError(err.to_string()),
+        }
+    }
+}
+
+/// A list the user lost a game from when it moved to another built-in list
+#[derive(Debug, Clone, Copy)]
+pub struct MovedFrom {
+    pub list_id: Uuid,
+    pub kind: ListKind,
+}
+
+/// Where a game added at `requested` ends up in a list of `count` games, the end by default
+pub fn insert_position(requested: Option<i32>, count: i64) -> i32 {
+    let count = count as i32;
+    requested.map_or(count, |position| position.clamp(0, count))
+}
+
+/// A new order has to name every game of the list exactly once
+pub fn check_order(current: &[String], requested: &[String]) -> Result<(), ListError> {
+    let unique: HashSet<&String> = requested.iter().collect();
+    if unique.len() != requested.len() {
+        return Err(ListError::InvalidOrder("Games can only appear once in the new order".to_string()));
+    }
+    let current: HashSet<&String> = current.iter().collect();
+    if unique != current {
+        return Err(ListError::InvalidOrder("The new order must contain exactly the games of the list".to_string()));
+    }
+    Ok(())
+}
+
+/******************************************/
+// Lists
+/******************************************/
+/// Creates the built-in lists the user doesn't have yet
+pub async fn ensure_default_lists(conn: &mut AsyncPgConnection, user: Uuid) -> Result<(), DieselError> {
+    let now = Utc::now().naive_utc();
+    let rows: Vec<_> = DEFAULT_LISTS
+        .iter()
+        .map(|(kind, name)| {
+            (
+                game_lists::user_id.eq(user),
+                game_lists::name.eq(*name),
+                game_lists::kind.eq(*kind),
+                game_lists::created_at.eq(now),
+                game_lists::updated_at.eq(now),
+            )
+        })
+        .collect();
+
+    diesel::insert_into(game_lists::table)
+        .values(rows)
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Lists of `owner` with their game counts, built-in lists first
+#[instrument(name = "Load user lists", skip(conn))]
+pub async fn user_lists(
+    conn: &mut AsyncPgConnection,
+    owner: Uuid,
+    public_only: bool,
+) -> Result<Vec<(GameList, i64)>, DieselError> {
+    let mut query = game_lists::table
+        .filter(game_lists::user_id.eq(owner))
+        .select(GameList::as_select())
+        .order((game_lists::kind, game_lists::created_at))
+        .into_boxed();
+    if public_only {
+        query = query.filter(game_lists::visibility.eq(ListVisibility::Public));
+    }
+    let lists: Vec<GameList> = query.load(conn).await?;
+
+    let ids: Vec<Uuid> = lists.iter().map(|list| list.id).collect();
+    let counts: Vec<(Uuid, i64)> = game_list_items::table
+        .filter(game_list_items::list_id.eq_any(&ids))
+        .group_by(game_list_items::list_id)
+        .select((game_list_items::list_id, count_star()))
+        .load(conn)
+        .await?;
+
+    Ok(lists
+        .into_iter()
+        .map(|list| {
+            let count = counts.iter().find(|(id, _)| *id == list.id).map_or(0, |(_, count)| *count);
+            (list, count)
+        })
+        .collect())
+}
+
+/// The list when `viewer` owns it or it is public
+pub async fn visible_list(conn: &mut AsyncPgConnection, list_id: Uuid, viewer: Uuid) -> Result<GameList, ListError> {
+    let list = game_lists::table
+        .find(list_id)
+        .select(GameList::as_select())
+        .first(conn)
+        .await
+        .optional()?
+        .ok_or(ListError::NotFound)?;
+
+    match list.user_id == viewer || list.visibility == ListVisibility::Public {
+        true => Ok(list),
+        false => Err(ListError::NotFound),
+    }
+}
+
+/// The list when `owner` owns it, others get `NotFound` whatever the visibility
+pub async fn owned_list(conn: &mut AsyncPgConnection, list_id: Uuid, owner: Uuid) -> Result<GameList, ListError> {
+    game_lists::table
+        .find(list_id)
+        .filter(game_lists::user_id.eq(owner))
+        .select(GameList::as_select())
+        .first(conn)
+        .await
+        .optional()?
+        .ok_or(ListError::NotFound)
+}
+
+pub async fn list_games(conn: &mut AsyncPgConnection, list_id: Uuid) -> Result<Vec<ListedGame>, DieselError> {
+    game_list_items::table
+        .inner_join(games::table)
+        .filter(game_list_items::list_id.eq(list_id))
+        .select((
+            games::slug,
+            games::name,
+            games::title,
+            games::genre,
+            games::cover_image_url,
+            games::average_rating,
+            game_list_items::position,
+            game_list_items::added_at,
+        ))
+        .order(game_list_items::position)
+        .load(conn)
+        .await
+}
+
+#[instrument(name = "Create list", skip(conn))]
+pub async fn create_list(
+    conn: &mut AsyncPgConnection,
+    owner: Uuid,
+    name: &str,
+    visibility: ListVisibility,
+) -> Result<GameList, ListError> {
+    let custom_lists: i64 = game_lists::table
+        .filter(game_lists::user_id.eq(owner))
+        .filter(game_lists::kind.eq(ListKind::Custom))
+        .count()
+        .get_result(conn)
+        .await?;
+    if custom_lists >= MAX_CUSTOM_LISTS {
+        return Err(ListError::TooManyLists);
+    }
+
+    let now = Utc::now().naive_utc();
+    let list = diesel::insert_into(game_lists::table)
+        .values((
+            game_lists::user_id.eq(owner),
+            game_lists::name.eq(name),
+            game_lists::kind.eq(ListKind::Custom),
+            game_lists::visibility.eq(visibility),
+            game_lists::created_at.eq(now),
+            game_lists::updated_at.eq(now),
+        ))
+        .returning(GameList::as_returning())
+        .get_result(conn)
+        .await?;
+    Ok(list)
+}
+
+/// Renames custom lists and changes the visibility of any list
+pub async fn update_list(
+    conn: &mut AsyncPgConnection,
+    list: &GameList,
+    name: Option<&str>,
+    visibility: Option<ListVisibility>,
+) -> Result<GameList, ListError> {
+    if name.is_some_and(|name| name != list.name) && list.kind != ListKind::Custom {
+        return Err(ListError::BuiltIn);
+    }
+
+    let list = diesel::update(game_lists::table.find(list.id))
+        .set((
+            game_lists::name.eq(name.unwrap_or(&list.name)),
+            game_lists::visibility.eq(visibility.unwrap_or(list.visibility)),
+            game_lists::updated_at.eq(Utc::now().naive_utc()),
+        ))
+        .returning(GameList::as_returning())
+        .get_result(conn)
+        .await?;
+    Ok(list)
+}
+
+pub async fn delete_list(conn: &mut AsyncPgConnection, list: &GameList) -> Result<(), ListError> {
+    if list.kind != ListKind::Custom {
+        return Err(ListError::BuiltIn);
+    }
+    diesel::delete(game_lists::table.find(list.id)).execute(conn).await?;
+    Ok(())
+}
+
+/******************************************/
+// List items
+/******************************************/
+/// Locks the list so concurrent edits see contiguous positions
+async fn lock_list(conn: &mut AsyncPgConnection, list_id: Uuid) -> Result<(), DieselError> {
+    game_lists::table
+        .find(list_id)
+        .select(game_lists::id)
+        .for_update()
+        .get_result::<Uuid>(conn)
+        .await?;
+    Ok(())
+}
+
+async fn touch_list(conn: &mut AsyncPgConnection, list_id: Uuid) -> Result<(), DieselError> {
+    diesel::update(game_lists::table.find(list_id))
+        .set(game_lists::updated_at.eq(Utc::now().naive_utc()))
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Deletes the game and closes the gap it leaves, `false` when it wasn't in the list
+async fn take_out(conn: &mut AsyncPgConnection, list_id: Uuid, slug: &str) -> Result<bool, DieselError> {
+    let position = diesel::delete(
+        game_list_items::table
+            .filter(game_list_items::list_id.eq(list_id))
+            .filter(game_list_items::game_slug.eq(slug)),
+    )
+    .returning(game_list_items::position)
+    .get_result::<i32>(conn)
+    .await
+    .optional()?;
+
+    let Some(position) = position else { return Ok(false) };
+    diesel::update(
+        game_list_items::table
+            .filter(game_list_items::list_id.eq(list_id))
+            .filter(game_list_items::position.gt(position)),
+    )
+    .set(game_list_items::position.eq(game_list_items::position - 1))
+    .execute(conn)
+    .await?;
+    Ok(true)
+}
+
+/// Inserts the game at `position`, moving it out of the owner's other built-in lists when `list` is one
+#[instrument(name = "Add game to list", skip(conn, list), fields(list_id = %list.id))]
+pub async fn add_game(
+    conn: &mut AsyncPgConnection,
+    list: &GameList,
+    slug: &str,
+    position: Option<i32>,
+) -> Result<Vec<MovedFrom>, ListError> {
+    let list = list.clone();
+    let slug = slug.to_string();
+
+    conn.transaction::<_, ListError, _>(|conn| {
+        async move {
+            let mut moved_from = Vec::new();
+            if list.kind != ListKind::Custom {
+                let others: Vec<(Uuid, ListKind)> = game_lists::table
+                    .inner_join(game_list_items::table)
+                    .filter(game_lists::user_id.eq(list.user_id))
+                    .filter(game_lists::kind.ne(ListKind::Custom))
+                    .filter(game_lists::id.ne(list.id))
+                    .filter(game_list_items::game_slug.eq(&slug))
+                    .select((game_lists::id, game_lists::kind))
+                    .load(conn)
+                    .await?;
+
+                for (list_id, kind) in others {
+                    lock_list(conn, list_id).await?;
+                    if take_out(conn, list_id, &slug).await? {
+                        touch_list(conn, list_id).await?;
+                        moved_from.push(MovedFrom { list_id, kind });
+                    }
+                }
+            }
+
+            lock_list(conn, list.id).await?;
+            let count: i64 = game_list_items::table
+                .filter(game_list_items::list_id.eq(list.id))
+                .count()
+                .get_result(conn)
+                .await?;
+            if count >= MAX_LIST_GAMES {
+                return Err(ListError::Full);
+            }
+
+            let position = insert_position(position, count);
+            diesel::update(
+                game_list_items::table
+                    .filter(game_list_items::list_id.eq(list.id))
+                    .filter(game_list_items::position.ge(position)),
+            )
+            .set(game_list_items::position.eq(game_list_items::position + 1))
+            .execute(conn)
+            .await?;
+
+            diesel::insert_into(game_list_items::table)
+                .values((
+                    game_list_items::list_id.eq(list.id),
+                    game_list_items::game_slug.eq(&slug),
+                    game_list_items::position.eq(position),
+                    game_list_items::added_at.eq(Utc::now().naive_utc()),
+                ))
+                .execute(conn)
+                .await?;
+            touch_list(conn, list.id).await?;
+
+            Ok(moved_from)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// `false` when the game wasn't in the list
+#[instrument(name = "Remove game from list", skip(conn))]
+pub async fn remove_game(conn: &mut AsyncPgConnection, list_id: Uuid, slug: &str) -> Result<bool, DieselError> {
+    let slug = slug.to_string();
+
+    conn.transaction::<_, DieselError, _>(|conn| {
+        async move {
+            lock_list(conn, list_id).await?;
+            let removed = take_out(conn, list_id, &slug).await?;
+            if removed {
+                touch_list(conn, list_id).await?;
+            }
+            Ok(removed)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Gives every game the position of its slug in `slugs`
+#[instrument(name = "Reorder list", skip(conn, slugs))]
+pub async fn reorder(conn: &mut AsyncPgConnection, list_id: Uuid, slugs: Vec<String>) -> Result<(), ListError> {
+    conn.transaction::<_, ListError, _>(|conn| {
+        async move {
+            lock_list(conn, list_id).await?;
+            let current: Vec<String> = game_list_items::table
+                .filter(game_list_items::list_id.eq(list_id))
+                .select(game_list_items::game_slug)
+                .load(conn)
+                .await?;
+            check_order(&current, &slugs)?;
+
+            diesel::sql_query(
+                "UPDATE game_list_items SET position = ordered.position - 1 \
+                 FROM unnest($2) WITH ORDINALITY AS ordered(game_slug, position) \
+                 WHERE game_list_items.list_id = $1 AND game_list_items.game_slug = ordered.game_slug",
+            )
+            .bind::<SqlUuid, _>(list_id)
+            .bind::<Array<Text>, _>(&slugs)
+            .execute(conn)
+            .await?;
+            touch_list(conn, list_id).await?;
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_order, insert_position};
+
+    fn slugs(slugs: &[&str]) -> Vec<String> {
+        slugs.iter().map(|slug| slug.to_string()).collect()
+    }
+
+    #[test]
+    fn games_are_appended_unless_a_position_is_given() {
+        assert_eq!(insert_position(None, 3), 3);
+        assert_eq!(insert_position(Some(0), 3), 0);
+        assert_eq!(insert_position(Some(7), 3), 3);
+        assert_eq!(insert_position(Some(-2), 3), 0);
+    }
+
+    #[test]
+    fn a_new_order_names_every_game_once() {
+        let current = slugs(&["a", "b", "c"]);
+
+        assert!(check_order(&current, &slugs(&["c", "a", "b"])).is_ok());
+        assert!(check_order(&current, &slugs(&["a", "b"])).is_err());
+        assert!(check_order(&current, &slugs(&["a", "b", "b"])).is_err());
+        assert!(check_order(&current, &slugs(&["a", "b", "d"])).is_err());
+    }
+}
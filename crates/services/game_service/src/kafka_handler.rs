@@ -1,9 +1,13 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::Insertable;
 use diesel_async::{AsyncPgConnection, RunQueryDsl};
 use errors::CustomError;
-use flume::Receiver;
-use kafka::models::{UserEventType, UserEventsMessage};
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::AsyncConnection;
+use flume::{Receiver, Sender};
+use kafka::channel::{push_to_broker, KafkaMessage};
+use kafka::models::{AccountStatus, ErasureEventsMessage, ExportEventsMessage, ModerationEventsMessage, UserEventType, UserEventsMessage};
 use lib_config::db::db::PgPool;
 use rdkafka::message::OwnedMessage;
 use futures::StreamExt;
@@ -12,8 +16,11 @@ use serde::{Deserialize, Serialize};
 use tracing::instrument;
 use diesel::prelude::*;
 use uuid::Uuid;
-use elasticsearch::Elasticsearch;
+use std::sync::Arc;
 use crate::elasticsearch::ElasticsearchGame;
+use crate::game_lists::{ListKind, ListVisibility};
+use crate::moderation::{apply_decision, DbReviewStatus};
+use crate::search::GameSearch;
 use crate::routes::game::games::get_game_by_slug;
 
 #[derive(Deserialize, Insertable, Debug, Serialize, Clone, Queryable, AsChangeset, Selectable)]
@@ -27,6 +34,13 @@ pub struct ReceivedGame {
     pub created_by_uid: Option<Uuid>,
     pub is_admin: Option<bool>,
     pub genre: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    pub release_date: Option<chrono::NaiveDate>,
+    pub publisher: Option<String>,
+    pub cover_image_url: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Serialize)]
@@ -44,6 +58,11 @@ pub struct UpdateGameBody {
     pub title: Option<String>,
     pub description: Option<String>,
     pub genre: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub platforms: Option<Vec<String>>,
+    pub release_date: Option<chrono::NaiveDate>,
+    pub publisher: Option<String>,
+    pub cover_image_url: Option<String>,
 }
 
 #[derive(Deserialize, Insertable, Debug, Serialize, Clone)]
@@ -63,13 +82,15 @@ pub struct UpdateUserBody {
 pub async fn process_kafka_game_message(
     kafka_receiver: Receiver<OwnedMessage>,
     pool: PgPool,
-    elastic_client: Elasticsearch,
+    search_backend: Arc<dyn GameSearch>,
+    kafka_producer: Sender<KafkaMessage<String>>,
 ) {
     kafka_receiver
         .stream()
         .for_each_concurrent(Some(10), |msg| {
             let pool = pool.clone();
-            let value = elastic_client.clone();
+            let search_backend = search_backend.clone();
+            let kafka_producer = &kafka_producer;
             async move {
                 if msg.topic() == "game_events" {
                     let payload = match msg.payload() {
@@ -91,35 +112,55 @@ pub async fn process_kafka_game_message(
                         Ok(msg) => {
                             match msg {
                                 KafkaGameMessage::Create(game) => {
-                                    let mut conn = pool.get().await.unwrap();
+                                    let mut conn = match pool.get().await {
+                                        Ok(conn) => conn,
+                                        Err(e) => {
+                                            tracing::error!("Failed to fetch connection from pool: {:?}", e);
+                                            return
+                                        }
+                                    };
                                     add_game_to_db(game.clone(), &mut conn).await;
 
                                     let es_game = ElasticsearchGame::new(&game);
-                                    ElasticsearchGame::index_game(&value, &es_game).await.unwrap();
+                                    if let Err(e) = search_backend.index_game(&es_game).await {
+                                        tracing::error!("Failed to index game {}, the drift check will repair it: {:?}", game.slug, e);
+                                    }
                                 }
                                 KafkaGameMessage::Update { slug, changes } => {
-                                    let mut conn = pool.get().await.unwrap();
+                                    let mut conn = match pool.get().await {
+                                        Ok(conn) => conn,
+                                        Err(e) => {
+                                            tracing::error!("Failed to fetch connection from pool: {:?}", e);
+                                            return
+                                        }
+                                    };
                                     update_game_in_db(slug.clone(), changes.clone(), &mut conn).await;
-                                    let full_game = get_game_by_slug(&slug.clone(), &pool).await.unwrap(); 
-
-                                    let es_game = ReceivedGame {
-                                        slug: full_game.slug, 
-                                        name: full_game.name, 
-                                        title: changes.title.clone(), 
-                                        description: changes.description.clone(), 
-                                        genre: changes.genre.clone(),
-                                        created_at: full_game.created_at,
-                                        created_by_uid: full_game.created_by_uid,
-                                        is_admin: full_game.is_admin,
+                                    let full_game = match get_game_by_slug(&slug, &pool).await {
+                                        Ok(game) => game,
+                                        Err(e) => {
+                                            tracing::error!("Failed to fetch updated game {}: {:?}", slug, e);
+                                            return
+                                        }
                                     };
-                                    let es_game = ElasticsearchGame::new(&es_game);
-                                    ElasticsearchGame::update_game(&value, &es_game, None::<f32>, None::<i32>).await.unwrap();
+
+                                    let es_game = ElasticsearchGame::new(&full_game);
+                                    if let Err(e) = search_backend.update_game(&es_game).await {
+                                        tracing::error!("Failed to update game {} in the search index, the drift check will repair it: {:?}", slug, e);
+                                    }
                                 }
                                 KafkaGameMessage::Delete(slug) => {
-                                    let mut conn = pool.get().await.unwrap();
+                                    let mut conn = match pool.get().await {
+                                        Ok(conn) => conn,
+                                        Err(e) => {
+                                            tracing::error!("Failed to fetch connection from pool: {:?}", e);
+                                            return
+                                        }
+                                    };
                                     delete_game_from_db(slug.clone(), &mut conn).await;
 
-                                    ElasticsearchGame::delete_game(&value, &slug).await.unwrap();
+                                    if let Err(e) = search_backend.delete_game(&slug).await {
+                                        tracing::error!("Failed to delete game {} from the search index, the drift check will repair it: {:?}", slug, e);
+                                    }
                                 }
                             }
                         }
@@ -167,6 +208,21 @@ pub async fn process_kafka_game_message(
                                     let mut conn = pool.get().await.unwrap();
                                     update_user_info(message.user_id, username, email, &mut conn).await;
                                 },
+
+                                UserEventType::StatusChange { status, time } => {
+                                    let mut conn = pool.get().await.unwrap();
+                                    update_user_status(message.user_id, status, time, &mut conn).await;
+                                },
+
+                                UserEventType::Erase { erasure_id, time: _ } => {
+                                    let mut conn = pool.get().await.unwrap();
+                                    erase_user(message.user_id, erasure_id, &mut conn, kafka_producer).await;
+                                },
+
+                                UserEventType::Export { export_id, time: _ } => {
+                                    let mut conn = pool.get().await.unwrap();
+                                    export_user(message.user_id, export_id, &mut conn, kafka_producer).await;
+                                },
                                 _ => {}
                             }
                         },
@@ -182,6 +238,51 @@ pub async fn process_kafka_game_message(
                             );
                         }
                     }
+                } else if msg.topic() == "moderation_events" {
+                    let payload = match msg.payload() {
+                        Some(p) => p,
+                        None => {
+                            tracing::error!(
+                                "No payload found in message. Topic: {}, Partition: {}, Offset: {}",
+                                msg.topic(),
+                                msg.partition(),
+                                msg.offset()
+                            );
+                            return
+                        }
+                    };
+
+                    match serde_json::from_slice::<ModerationEventsMessage>(payload) {
+                        Ok(message) => {
+                            let mut conn = match pool.get().await {
+                                Ok(conn) => conn,
+                                Err(e) => {
+                                    tracing::error!("Failed to fetch connection from pool: {:?}", e);
+                                    return
+                                }
+                            };
+                            match apply_decision(&mut conn, message.review_id, message.decision, &message.content_hash).await {
+                                Ok(Some(requeue)) => {
+                                    if let Err(e) = push_to_broker(kafka_producer, &requeue).await {
+                                        tracing::error!("Failed to queue edited review {} again: {:?}", message.review_id, e);
+                                    }
+                                }
+                                Ok(None) => {}
+                                Err(e) => tracing::error!("Failed to apply moderation decision to review {}: {:?}", message.review_id, e),
+                            }
+                        },
+
+                        Err(e) => {
+                            tracing::error!(
+                                "Failed to deserialize message to moderation decision
+                                Topic: {}, Partition: {}, Offset: {} | Error: {:?}",
+                                msg.topic(),
+                                msg.partition(),
+                                msg.offset(),
+                                e
+                            );
+                        }
+                    }
                 } else {
                     tracing::error!("Handler for topic {} not found", msg.topic());
                 }
@@ -209,6 +310,201 @@ pub async fn update_user_info(id: Uuid, username: String, email: String, conn: &
     };
 }
 
+/// Keeps `deactivated_at` set while the account isn't active so the user's reviews stay hidden
+#[instrument("Update user status", skip(conn))]
+async fn update_user_status(id: Uuid, status: AccountStatus, time: NaiveDateTime, conn: &mut AsyncPgConnection) {
+    use crate::schema::users;
+
+    let deactivated_at = match status {
+        AccountStatus::Active => None,
+        _ => Some(time),
+    };
+    let res = diesel::update(users::table)
+        .filter(users::id.eq(&id))
+        .set(users::deactivated_at.eq(deactivated_at))
+        .execute(conn)
+        .await;
+
+    match res {
+        Ok(_) => tracing::info!("Updated status of user {} to {:?}", id, status),
+        Err(e) => tracing::error!("Failed to update status of user {} : {}", id, e),
+    };
+}
+
+/// Anonymises the user: ratings stay in the game scores but lose their review text, flags
+/// and lists go. Confirms to user_service, running it again is harmless.
+#[instrument("Erase user", skip(conn, kafka_producer))]
+async fn erase_user(
+    id: Uuid,
+    erasure_id: Uuid,
+    conn: &mut AsyncPgConnection,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+) {
+    use crate::schema::{game_lists, rate_game, review_flags, users};
+
+    let res = conn
+        .transaction::<_, DieselError, _>(|conn| {
+            async move {
+                let now = Utc::now().naive_utc();
+                let anonymous = id.simple().to_string();
+                diesel::update(users::table)
+                    .filter(users::id.eq(&id))
+                    .set((
+                        users::username.eq(format!("deleted_{}", anonymous)),
+                        users::email.eq(format!("{}@erased.invalid", anonymous)),
+                        users::deactivated_at.eq(Some(now)),
+                        users::modified_at.eq(Some(now))
+                    ))
+                    .execute(conn)
+                    .await?;
+                let reviews = rate_game::table
+                    .filter(rate_game::user_id.eq(id))
+                    .select(rate_game::id);
+                diesel::delete(
+                    review_flags::table.filter(
+                        review_flags::flagged_by.eq(id).or(review_flags::review_id.eq_any(reviews)),
+                    ),
+                )
+                .execute(conn)
+                .await?;
+                diesel::update(rate_game::table.filter(rate_game::user_id.eq(id)))
+                    .set(rate_game::review.eq(None::<String>))
+                    .execute(conn)
+                    .await?;
+                // Items go with their lists
+                diesel::delete(game_lists::table.filter(game_lists::user_id.eq(id)))
+                    .execute(conn)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await;
+
+    if let Err(e) = res {
+        // Not confirmed, user_service sends the erasure again later
+        tracing::error!("Failed to erase user {} : {}", id, e);
+        return
+    }
+
+    let confirmation = ErasureEventsMessage {
+        erasure_id,
+        user_id: id,
+        service: "game_service".to_string(),
+        time: Utc::now().naive_utc(),
+    };
+    match push_to_broker(kafka_producer, &confirmation).await {
+        Ok(_) => tracing::info!("Erased user {} for erasure {}", id, erasure_id),
+        Err(e) => tracing::error!("Failed to confirm erasure {} : {:?}", erasure_id, e),
+    };
+}
+
+/// Sends the user's ratings, reviews, flags and lists to user_service
+#[instrument("Export user", skip(conn, kafka_producer))]
+async fn export_user(
+    id: Uuid,
+    export_id: Uuid,
+    conn: &mut AsyncPgConnection,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+) {
+    let data = match collect_user_data(id, conn).await {
+        Ok(data) => data,
+        Err(e) => {
+            // Not answered, user_service asks again later
+            tracing::error!("Failed to collect data of user {} : {}", id, e);
+            return
+        }
+    };
+
+    let part = ExportEventsMessage {
+        export_id,
+        user_id: id,
+        service: "game_service".to_string(),
+        data,
+        time: Utc::now().naive_utc(),
+    };
+    match push_to_broker(kafka_producer, &part).await {
+        Ok(_) => tracing::info!("Sent data of user {} for export {}", id, export_id),
+        Err(e) => tracing::error!("Failed to send part of export {} : {:?}", export_id, e),
+    };
+}
+
+async fn collect_user_data(id: Uuid, conn: &mut AsyncPgConnection) -> Result<serde_json::Value, DieselError> {
+    use crate::schema::{game_list_items, game_lists, rate_game, review_flags};
+
+    let ratings = rate_game::table
+        .filter(rate_game::user_id.eq(id))
+        .select((
+            rate_game::game_slug,
+            rate_game::rating,
+            rate_game::review,
+            rate_game::moderation_status,
+            rate_game::created_at,
+            rate_game::updated_at,
+        ))
+        .order(rate_game::created_at.asc())
+        .load::<(String, i32, Option<String>, DbReviewStatus, NaiveDateTime, Option<NaiveDateTime>)>(conn)
+        .await?
+        .into_iter()
+        .map(|(game_slug, rating, review, moderation_status, created_at, updated_at)| serde_json::json!({
+            "game_slug": game_slug,
+            "rating": rating,
+            "review": review,
+            "moderation_status": moderation_status,
+            "created_at": created_at,
+            "updated_at": updated_at
+        }))
+        .collect::<Vec<_>>();
+
+    let flags = review_flags::table
+        .inner_join(rate_game::table)
+        .filter(review_flags::flagged_by.eq(id))
+        .select((rate_game::game_slug, review_flags::reason, review_flags::created_at))
+        .load::<(String, Option<String>, NaiveDateTime)>(conn)
+        .await?
+        .into_iter()
+        .map(|(game_slug, reason, created_at)| serde_json::json!({
+            "game_slug": game_slug,
+            "reason": reason,
+            "created_at": created_at
+        }))
+        .collect::<Vec<_>>();
+
+    let lists = game_lists::table
+        .filter(game_lists::user_id.eq(id))
+        .select((game_lists::id, game_lists::name, game_lists::kind, game_lists::visibility, game_lists::created_at))
+        .order(game_lists::created_at.asc())
+        .load::<(Uuid, String, ListKind, ListVisibility, NaiveDateTime)>(conn)
+        .await?;
+    let list_ids: Vec<Uuid> = lists.iter().map(|(list_id, ..)| *list_id).collect();
+    let items = game_list_items::table
+        .filter(game_list_items::list_id.eq_any(&list_ids))
+        .select((game_list_items::list_id, game_list_items::game_slug, game_list_items::added_at))
+        .order((game_list_items::list_id, game_list_items::position.asc()))
+        .load::<(Uuid, String, NaiveDateTime)>(conn)
+        .await?;
+    let lists = lists
+        .into_iter()
+        .map(|(list_id, name, kind, visibility, created_at)| serde_json::json!({
+            "name": name,
+            "kind": kind,
+            "visibility": visibility,
+            "created_at": created_at,
+            "games": items
+                .iter()
+                .filter(|(item_list, ..)| *item_list == list_id)
+                .map(|(_, game_slug, added_at)| serde_json::json!({ "game_slug": game_slug, "added_at": added_at }))
+                .collect::<Vec<_>>()
+        }))
+        .collect::<Vec<_>>();
+
+    Ok(serde_json::json!({
+        "ratings": ratings,
+        "review_flags": flags,
+        "lists": lists,
+    }))
+}
+
 #[instrument("Adding game to db", skip(conn))]
 async fn add_game_to_db(game: ReceivedGame, conn: &mut AsyncPgConnection) {
     use crate::schema::games;
@@ -234,6 +530,7 @@ async fn update_game_in_db(
 
     let game: Option<ReceivedGame> = games::table
         .filter(games::slug.eq(slug.clone()))
+        .select(ReceivedGame::as_select())
         .first(conn)
         .await
         .ok();
@@ -249,6 +546,21 @@ async fn update_game_in_db(
             if let Some(new_genre) = &changes.genre {
                 game.genre = Some(new_genre.clone());
             }
+            if let Some(new_tags) = &changes.tags {
+                game.tags = new_tags.clone();
+            }
+            if let Some(new_platforms) = &changes.platforms {
+                game.platforms = new_platforms.clone();
+            }
+            if let Some(new_release_date) = changes.release_date {
+                game.release_date = Some(new_release_date);
+            }
+            if let Some(new_publisher) = &changes.publisher {
+                game.publisher = Some(new_publisher.clone());
+            }
+            if let Some(new_cover_image_url) = &changes.cover_image_url {
+                game.cover_image_url = Some(new_cover_image_url.clone());
+            }
 
             let res = diesel::update(games::table.filter(games::slug.eq(slug)))
                 .set(&game)
@@ -4,3 +4,9 @@ pub mod schema;
 pub mod kafka_handler;
 pub mod elasticsearch;
 pub mod db_error;
+pub mod ratings;
+pub mod moderation;
+pub mod reindex;
+pub mod search;
+pub mod recommendations;
+pub mod game_lists;
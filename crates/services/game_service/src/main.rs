@@ -1,5 +1,7 @@
 use lib_config::config::configuration;
 use lib_config::db::db::establish_connection;
+use game_service::reindex::{reindex, try_lock_maintenance};
+use game_service::search::init_search;
 use game_service::startup::Application;
 use utils::telemetry::{get_subscriber, init_subscriber};
 
@@ -12,6 +14,21 @@ async fn main() -> std::io::Result<()> {
     let pool = establish_connection(&config.databases.game_db_url).await;
     let port = config.service.game_service_port;
 
+    // `game_service reindex` rebuilds the Elasticsearch index from Postgres and exits
+    if std::env::args().nth(1).as_deref() == Some("reindex") {
+        let search_backend = init_search(&config.search)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", e)))?;
+        let _guard = try_lock_maintenance(&pool)
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", e)))?
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "A reindex or drift check is already running"))?;
+        let report = reindex(search_backend.as_ref(), &pool)
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", e)))?;
+        println!("Reindexed {} games into {}", report.documents, report.index);
+        return Ok(());
+    }
+
     let application = Application::build(pool, &config).await?;
     application.run_until_stopped().await?;
     Ok(())
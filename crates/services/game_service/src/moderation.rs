@@ -0,0 +1,201 @@
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use chrono::Utc;
+use kafka::models::{ModerationDecision, ModerationReason, ReviewEventsMessage};
+use lib_config::config::configuration::ModerationSettings;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::schema::rate_game;
+
+#[derive(diesel_derive_enum::DbEnum, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[ExistingTypePath = "crate::schema::sql_types::ReviewStatus"]
+#[DbValueStyle = "verbatim"]
+pub enum DbReviewStatus {
+    Published,
+    Pending,
+    Rejected,
+    Hidden,
+}
+
+impl From<ModerationDecision> for DbReviewStatus {
+    fn from(decision: ModerationDecision) -> Self {
+        match decision {
+            ModerationDecision::Approve => DbReviewStatus::Published,
+            ModerationDecision::Reject => DbReviewStatus::Rejected,
+            ModerationDecision::Hide => DbReviewStatus::Hidden,
+        }
+    }
+}
+
+/******************************************/
+// Word filter
+/******************************************/
+#[derive(Debug, Clone, Default)]
+pub struct WordFilter {
+    blocked_words: Vec<String>,
+}
+
+impl WordFilter {
+    pub fn new(settings: &ModerationSettings) -> Self {
+        WordFilter {
+            blocked_words: settings
+                .blocked_words
+                .iter()
+                .map(|word| word.trim().to_lowercase())
+                .filter(|word| !word.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Blocked words found in `text` as whole words, ignoring case
+    pub fn matches(&self, text: &str) -> Vec<String> {
+        let text = text.to_lowercase();
+        let words: Vec<&str> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        self.blocked_words
+            .iter()
+            .filter(|blocked| words.contains(&blocked.as_str()))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Status of a review after it is written: filtered reviews and edits of rejected or hidden ones wait for an admin
+pub fn next_status(previous: Option<DbReviewStatus>, has_review: bool, filter_hit: bool) -> DbReviewStatus {
+    if !has_review {
+        return DbReviewStatus::Published;
+    }
+    match (filter_hit, previous) {
+        (true, _) => DbReviewStatus::Pending,
+        (false, Some(DbReviewStatus::Rejected | DbReviewStatus::Hidden | DbReviewStatus::Pending)) => DbReviewStatus::Pending,
+        _ => DbReviewStatus::Published,
+    }
+}
+
+/// Fingerprint of what a moderator decides on, a decision only applies while it still matches
+pub fn content_hash(rating: i32, review: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(rating.to_string())
+        .chain_update(b"\n")
+        .chain_update(review)
+        .finalize();
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Event for the admin moderation queue when a written review waits for a decision
+pub fn pending_review_event(
+    review_id: Uuid,
+    status: DbReviewStatus,
+    game_slug: &str,
+    user_id: Uuid,
+    rating: i32,
+    review: Option<&str>,
+    filtered_words: Vec<String>,
+) -> Option<ReviewEventsMessage> {
+    if status != DbReviewStatus::Pending {
+        return None;
+    }
+    let reason = match filtered_words.is_empty() {
+        true => ModerationReason::Edited,
+        false => ModerationReason::WordFilter { words: filtered_words },
+    };
+
+    let review = review?;
+    Some(ReviewEventsMessage {
+        review_id,
+        game_slug: game_slug.to_string(),
+        user_id,
+        rating,
+        review: review.to_string(),
+        reason,
+        content_hash: content_hash(rating, review),
+        time: Utc::now().naive_utc(),
+    })
+}
+
+/// Applies an admin decision received on `moderation_events` when the review is still the one
+/// the moderator saw. An edited review isn't touched, the event to queue it again is returned.
+#[instrument("Apply moderation decision", skip(conn))]
+pub async fn apply_decision(
+    conn: &mut AsyncPgConnection,
+    review_id: Uuid,
+    decision: ModerationDecision,
+    decided_hash: &str,
+) -> Result<Option<ReviewEventsMessage>, diesel::result::Error> {
+    let current = rate_game::table
+        .filter(rate_game::id.eq(review_id))
+        .select((rate_game::game_slug, rate_game::user_id, rate_game::rating, rate_game::review))
+        .first::<(String, Uuid, i32, Option<String>)>(conn)
+        .await
+        .optional()?;
+    let Some((game_slug, user_id, rating, Some(review))) = current else {
+        tracing::warn!("Review {} to moderate no longer exists", review_id);
+        return Ok(None);
+    };
+
+    if content_hash(rating, &review) == decided_hash {
+        // Matching on the content too, an edit between the read and the write is not overruled
+        let updated = diesel::update(
+            rate_game::table
+                .filter(rate_game::id.eq(review_id))
+                .filter(rate_game::rating.eq(rating))
+                .filter(rate_game::review.eq(&review)),
+        )
+        .set(rate_game::moderation_status.eq(DbReviewStatus::from(decision)))
+        .execute(conn)
+        .await?;
+        if updated == 1 {
+            return Ok(None);
+        }
+    }
+
+    tracing::info!("Review {} changed since it was moderated, queueing it again", review_id);
+    Ok(Some(ReviewEventsMessage {
+        review_id,
+        game_slug,
+        user_id,
+        rating,
+        content_hash: content_hash(rating, &review),
+        review,
+        reason: ModerationReason::Edited,
+        time: Utc::now().naive_utc(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_hash, next_status, DbReviewStatus, WordFilter};
+    use lib_config::config::configuration::ModerationSettings;
+
+    #[test]
+    fn word_filter_matches_whole_words_ignoring_case() {
+        let filter = WordFilter::new(&ModerationSettings {
+            blocked_words: vec!["Scam".into(), " ".into()],
+        });
+
+        assert_eq!(filter.matches("Total SCAM, avoid!"), vec!["scam".to_string()]);
+        assert!(filter.matches("Scampering through levels").is_empty());
+    }
+
+    #[test]
+    fn content_hash_changes_with_rating_or_text() {
+        let hash = content_hash(3, "Fine game");
+        assert_eq!(hash, content_hash(3, "Fine game"));
+        assert_ne!(hash, content_hash(4, "Fine game"));
+        assert_ne!(hash, content_hash(3, "Fine game!"));
+    }
+
+    #[test]
+    fn edits_of_moderated_reviews_go_back_to_the_queue() {
+        assert_eq!(next_status(None, true, false), DbReviewStatus::Published);
+        assert_eq!(next_status(None, true, true), DbReviewStatus::Pending);
+        assert_eq!(next_status(Some(DbReviewStatus::Rejected), true, false), DbReviewStatus::Pending);
+        assert_eq!(next_status(Some(DbReviewStatus::Hidden), false, false), DbReviewStatus::Published);
+    }
+}
@@ -0,0 +1,198 @@
+use chrono::Utc;
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel::upsert::excluded;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+use tracing::instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db_error::DbError;
+use crate::moderation::{next_status, DbReviewStatus};
+use crate::routes::game::model::RateGame;
+use crate::schema::{games, rate_game};
+
+/// Aggregates of a game as stored on its `games` row, `rating_version` grows with every recalculation
+#[derive(Queryable, Serialize, Debug, Clone, Copy, ToSchema)]
+pub struct GameRating {
+    pub average_rating: f32,
+    pub rating_count: i32,
+    pub rating_version: i64,
+}
+
+/******************************************/
+// Rating aggregation
+/******************************************/
+/// Rating as written by the user, `status` tells whether its review waits for moderation
+#[derive(Debug, Clone, Copy)]
+pub struct SavedRating {
+    pub aggregate: GameRating,
+    pub review_id: Uuid,
+    pub status: DbReviewStatus,
+}
+
+/// Inserts the rating or edits the user's existing one, then recalculates the game aggregates
+#[instrument(name = "Save rating", skip(conn, new_rating), fields(game_slug = %new_rating.game_slug))]
+pub async fn save_rating(conn: &mut AsyncPgConnection, new_rating: RateGame, filter_hit: bool) -> Result<SavedRating, DbError> {
+    conn.transaction::<_, DieselError, _>(|conn| {
+        async move {
+            lock_game(conn, &new_rating.game_slug).await?;
+
+            let previous = previous_status(conn, new_rating.user_id, &new_rating.game_slug).await?;
+            let status = next_status(previous, new_rating.review.is_some(), filter_hit);
+
+            let review_id = diesel::insert_into(rate_game::table)
+                .values((&new_rating, rate_game::moderation_status.eq(status)))
+                .on_conflict((rate_game::user_id, rate_game::game_slug))
+                .do_update()
+                .set((
+                    rate_game::rating.eq(excluded(rate_game::rating)),
+                    rate_game::review.eq(excluded(rate_game::review)),
+                    rate_game::updated_at.eq(Some(Utc::now().naive_utc())),
+                    rate_game::moderation_status.eq(status),
+                ))
+                .returning(rate_game::id)
+                .get_result::<Uuid>(conn)
+                .await?;
+
+            let aggregate = recalculate(conn, &new_rating.game_slug).await?;
+            Ok(SavedRating { aggregate, review_id, status })
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(DbError)
+}
+
+/// Edits the user's rating of a game, `None` when the user hasn't rated it
+#[instrument(name = "Edit rating", skip(conn, review))]
+pub async fn edit_rating(
+    conn: &mut AsyncPgConnection,
+    user: Uuid,
+    slug: &str,
+    new_rating: i32,
+    review: Option<String>,
+    filter_hit: bool,
+) -> Result<Option<SavedRating>, DbError> {
+    let slug = slug.to_string();
+    conn.transaction::<_, DieselError, _>(|conn| {
+        async move {
+            lock_game(conn, &slug).await?;
+
+            let Some(previous) = previous_status(conn, user, &slug).await? else {
+                return Ok(None);
+            };
+            let status = next_status(Some(previous), review.is_some(), filter_hit);
+
+            let review_id = diesel::update(
+                rate_game::table
+                    .filter(rate_game::user_id.eq(user))
+                    .filter(rate_game::game_slug.eq(&slug)),
+            )
+            .set((
+                rate_game::rating.eq(new_rating),
+                rate_game::review.eq(review),
+                rate_game::updated_at.eq(Some(Utc::now().naive_utc())),
+                rate_game::moderation_status.eq(status),
+            ))
+            .returning(rate_game::id)
+            .get_result::<Uuid>(conn)
+            .await?;
+
+            let aggregate = recalculate(conn, &slug).await?;
+            Ok(Some(SavedRating { aggregate, review_id, status }))
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(DbError)
+}
+
+/// Deletes the user's rating of a game, `None` when the user hasn't rated it
+#[instrument(name = "Delete rating", skip(conn))]
+pub async fn delete_rating(conn: &mut AsyncPgConnection, user: Uuid, slug: &str) -> Result<Option<GameRating>, DbError> {
+    let slug = slug.to_string();
+    conn.transaction::<_, DieselError, _>(|conn| {
+        async move {
+            lock_game(conn, &slug).await?;
+
+            let deleted = diesel::delete(
+                rate_game::table
+                    .filter(rate_game::user_id.eq(user))
+                    .filter(rate_game::game_slug.eq(&slug)),
+            )
+            .execute(conn)
+            .await?;
+
+            match deleted {
+                0 => Ok(None),
+                _ => recalculate(conn, &slug).await.map(Some),
+            }
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(DbError)
+}
+
+/// Recalculates the aggregates of a game from `rate_game`, used whenever ratings change outside `save_rating`
+#[instrument(name = "Refresh game rating", skip(conn))]
+pub async fn refresh_game_rating(conn: &mut AsyncPgConnection, slug: &str) -> Result<GameRating, DbError> {
+    let slug = slug.to_string();
+    conn.transaction::<_, DieselError, _>(|conn| {
+        async move {
+            lock_game(conn, &slug).await?;
+            recalculate(conn, &slug).await
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(DbError)
+}
+
+async fn previous_status(conn: &mut AsyncPgConnection, user: Uuid, slug: &str) -> Result<Option<DbReviewStatus>, DieselError> {
+    rate_game::table
+        .filter(rate_game::user_id.eq(user))
+        .filter(rate_game::game_slug.eq(slug))
+        .select(rate_game::moderation_status)
+        .first::<DbReviewStatus>(conn)
+        .await
+        .optional()
+}
+
+/// Row lock on the game so concurrent ratings of the same game are aggregated one after another
+async fn lock_game(conn: &mut AsyncPgConnection, slug: &str) -> Result<(), DieselError> {
+    games::table
+        .filter(games::slug.eq(slug))
+        .select(games::slug)
+        .for_update()
+        .first::<String>(conn)
+        .await
+        .map(|_| ())
+}
+
+async fn recalculate(conn: &mut AsyncPgConnection, slug: &str) -> Result<GameRating, DieselError> {
+    let (total, count): (Option<i64>, i64) = rate_game::table
+        .filter(rate_game::game_slug.eq(slug))
+        .select((diesel::dsl::sum(rate_game::rating), count_star()))
+        .first(conn)
+        .await?;
+
+    let average = match count {
+        0 => 0.0,
+        count => total.unwrap_or(0) as f32 / count as f32,
+    };
+
+    diesel::update(games::table.filter(games::slug.eq(slug)))
+        .set((
+            games::average_rating.eq(average),
+            games::rating_count.eq(count as i32),
+            games::rating_version.eq(games::rating_version + 1),
+        ))
+        .returning((games::average_rating, games::rating_count, games::rating_version))
+        .get_result::<GameRating>(conn)
+        .await
+}
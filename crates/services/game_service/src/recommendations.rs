@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Uuid as SqlUuid};
+use diesel::QueryableByName;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+use tracing::instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db_error::DbError;
+
+/// Candidates liked by fewer users alongside the seeds are too noisy to recommend
+pub const MIN_SHARED_FANS: i64 = 2;
+
+/// A game picked for the user, `score` and `based_on` are empty for popularity picks
+#[derive(QueryableByName, Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct RecommendedGame {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub slug: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub name: String,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Text>)]
+    pub title: Option<String>,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Text>)]
+    pub genre: Option<String>,
+    #[diesel(sql_type = diesel::sql_types::Float)]
+    pub average_rating: f32,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub rating_count: i32,
+    /// Summed cosine similarity to the games the user liked
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Double>)]
+    pub score: Option<f64>,
+    /// Liked games this one is recommended for, most shared fans first
+    #[diesel(sql_type = diesel::sql_types::Array<diesel::sql_types::Text>)]
+    pub based_on: Vec<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct RecommendationsResponse {
+    /// False when the user has no usable rating history and only popular games are returned
+    pub personalised: bool,
+    pub games: Vec<RecommendedGame>,
+}
+
+/******************************************/
+// Item-item collaborative filtering
+/******************************************/
+/// Games liked (rated 4 or 5) by the users who liked the same games as `user`. Similarity of
+/// two games is the cosine of their fan sets, `shared / sqrt(fans_a * fans_b)`, weighted by how
+/// much the user liked the seed game. Games the user already rated are left out.
+const SIMILAR_GAMES_QUERY: &str = r#"
+WITH seeds AS (
+    SELECT game_slug, rating - 3 AS weight
+    FROM rate_game
+    WHERE user_id = $1 AND rating >= 4
+),
+pairs AS (
+    SELECT seeds.game_slug AS seed, seeds.weight, other.game_slug AS candidate, COUNT(*) AS shared
+    FROM seeds
+    JOIN rate_game fan
+        ON fan.game_slug = seeds.game_slug AND fan.rating >= 4 AND fan.user_id <> $1
+    JOIN rate_game other
+        ON other.user_id = fan.user_id AND other.rating >= 4 AND other.game_slug <> seeds.game_slug
+    WHERE NOT EXISTS (
+        SELECT 1 FROM rate_game rated WHERE rated.user_id = $1 AND rated.game_slug = other.game_slug
+    )
+    GROUP BY seeds.game_slug, seeds.weight, other.game_slug
+),
+likes AS (
+    SELECT game_slug, COUNT(*) AS fans
+    FROM rate_game
+    WHERE rating >= 4
+        AND game_slug IN (SELECT seed FROM pairs UNION SELECT candidate FROM pairs)
+    GROUP BY game_slug
+),
+scored AS (
+    SELECT pairs.candidate,
+        SUM(pairs.weight * pairs.shared / SQRT(seed_likes.fans * candidate_likes.fans))::FLOAT8 AS score,
+        ARRAY_AGG(pairs.seed::TEXT ORDER BY pairs.shared DESC, pairs.seed) AS based_on
+    FROM pairs
+    JOIN likes seed_likes ON seed_likes.game_slug = pairs.seed
+    JOIN likes candidate_likes ON candidate_likes.game_slug = pairs.candidate
+    GROUP BY pairs.candidate
+    HAVING SUM(pairs.shared) >= $2
+)
+SELECT games.slug, games.name, games.title, games.genre, games.average_rating, games.rating_count,
+    scored.score, scored.based_on
+FROM scored
+JOIN games ON games.slug = scored.candidate
+ORDER BY scored.score DESC, games.slug
+LIMIT $3
+"#;
+
+/// Best rated games the user hasn't rated, the average is damped towards 3 for games with few ratings
+const POPULAR_GAMES_QUERY: &str = r#"
+SELECT games.slug, games.name, games.title, games.genre, games.average_rating, games.rating_count,
+    NULL::FLOAT8 AS score, ARRAY[]::TEXT[] AS based_on
+FROM games
+WHERE NOT EXISTS (
+    SELECT 1 FROM rate_game rated WHERE rated.user_id = $1 AND rated.game_slug = games.slug
+)
+ORDER BY (games.average_rating * games.rating_count + 15) / (games.rating_count + 5) DESC,
+    games.rating_count DESC, games.slug
+LIMIT $2
+"#;
+
+#[instrument(name = "Similar games", skip(conn))]
+pub async fn similar_games(conn: &mut AsyncPgConnection, user: Uuid, limit: i64) -> Result<Vec<RecommendedGame>, DbError> {
+    sql_query(SIMILAR_GAMES_QUERY)
+        .bind::<SqlUuid, _>(user)
+        .bind::<BigInt, _>(MIN_SHARED_FANS)
+        .bind::<BigInt, _>(limit)
+        .load(conn)
+        .await
+        .map_err(DbError)
+}
+
+#[instrument(name = "Popular games", skip(conn))]
+pub async fn popular_games(conn: &mut AsyncPgConnection, user: Uuid, limit: i64) -> Result<Vec<RecommendedGame>, DbError> {
+    sql_query(POPULAR_GAMES_QUERY)
+        .bind::<SqlUuid, _>(user)
+        .bind::<BigInt, _>(limit)
+        .load(conn)
+        .await
+        .map_err(DbError)
+}
+
+/// Tops the personalised games up with popular ones the list doesn't hold yet
+pub fn fill_with_popular(
+    mut games: Vec<RecommendedGame>,
+    popular: Vec<RecommendedGame>,
+    limit: usize,
+) -> Vec<RecommendedGame> {
+    let mut seen: HashSet<String> = games.iter().map(|game| game.slug.clone()).collect();
+    for game in popular {
+        if games.len() >= limit {
+            break;
+        }
+        if seen.insert(game.slug.clone()) {
+            games.push(game);
+        }
+    }
+    games.truncate(limit);
+    games
+}
+
+/// Personalised recommendations, falling back to popular games for users without enough history
+pub async fn recommend(conn: &mut AsyncPgConnection, user: Uuid, limit: i64) -> Result<RecommendationsResponse, DbError> {
+    let similar = similar_games(conn, user, limit).await?;
+    let personalised = !similar.is_empty();
+
+    let games = if similar.len() as i64 >= limit {
+        similar
+    } else {
+        // Ask for enough popular games to still fill the list after dropping duplicates
+        let popular = popular_games(conn, user, limit + similar.len() as i64).await?;
+        fill_with_popular(similar, popular, limit as usize)
+    };
+
+    Ok(RecommendationsResponse { personalised, games })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill_with_popular, RecommendedGame};
+
+    fn game(slug: &str, score: Option<f64>) -> RecommendedGame {
+        RecommendedGame {
+            slug: slug.into(),
+            name: slug.into(),
+            title: None,
+            genre: None,
+            average_rating: 4.0,
+            rating_count: 3,
+            score,
+            based_on: score.map(|_| vec!["seed".to_string()]).unwrap_or_default(),
+        }
+    }
+
+    #[test]
+    fn popular_games_fill_the_list_without_duplicates() {
+        let similar = vec![game("a", Some(0.9)), game("b", Some(0.4))];
+        let popular = vec![game("b", None), game("c", None), game("d", None)];
+
+        let games = fill_with_popular(similar, popular, 3);
+
+        let slugs: Vec<&str> = games.iter().map(|game| game.slug.as_str()).collect();
+        assert_eq!(slugs, ["a", "b", "c"]);
+        assert_eq!(games[1].score, Some(0.4));
+    }
+
+    #[test]
+    fn new_users_only_get_popular_games() {
+        let games = fill_with_popular(vec![], vec![game("x", None), game("y", None)], 5);
+        assert_eq!(games.len(), 2);
+        assert!(games.iter().all(|game| game.based_on.is_empty()));
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use diesel_async::pooled_connection::deadpool::Object;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use lib_config::db::db::PgPool;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::elasticsearch::ElasticsearchGame;
+use crate::schema::games;
+use crate::search::GameSearch;
+
+define_sql_function!(fn pg_try_advisory_lock(key: BigInt) -> Bool);
+
+/// Advisory lock key shared by every game_service instance and the `reindex` CLI
+const MAINTENANCE_LOCK_KEY: i64 = 0x6761_6d65_7365_6172;
+
+/// Reindex and drift repair both write every document, only one of them runs at a time.
+/// The lock is a Postgres session advisory lock, so it holds across processes; it is
+/// released when the guard drops and its connection is closed.
+pub struct MaintenanceLock {
+    conn: Option<Object<AsyncPgConnection>>,
+}
+
+impl Drop for MaintenanceLock {
+    fn drop(&mut self) {
+        // Closing the session releases the lock, the connection never goes back to the pool holding it
+        if let Some(conn) = self.conn.take() {
+            drop(Object::take(conn));
+        }
+    }
+}
+
+/// Takes the maintenance lock, `None` while another runner holds it
+pub async fn try_lock_maintenance(pool: &PgPool) -> Result<Option<MaintenanceLock>, anyhow::Error> {
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+    let locked: bool = diesel::select(pg_try_advisory_lock(MAINTENANCE_LOCK_KEY))
+        .get_result(&mut conn)
+        .await
+        .context("Failed to take the search maintenance lock")?;
+    Ok(locked.then(|| MaintenanceLock { conn: Some(conn) }))
+}
+
+/// A game as Postgres says it should be indexed
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = games)]
+pub struct GameDocument {
+    pub slug: String,
+    pub name: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub genre: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub average_rating: f32,
+    pub rating_count: i32,
+    pub rating_version: i64,
+    pub tags: Vec<String>,
+    pub platforms: Vec<String>,
+    pub release_date: Option<NaiveDate>,
+    pub publisher: Option<String>,
+    pub cover_image_url: Option<String>,
+}
+
+impl GameDocument {
+    pub fn source(&self) -> Value {
+        json!({
+            "slug": self.slug,
+            "name": self.name,
+            "title": self.title,
+            "description": self.description,
+            "genre": self.genre,
+            "average_rating": self.average_rating,
+            "rating_count": self.rating_count,
+            "rating_version": self.rating_version,
+            "created_at": self.created_at,
+            "tags": self.tags,
+            "platforms": self.platforms,
+            "release_date": self.release_date,
+            "publisher": self.publisher,
+            "cover_image_url": self.cover_image_url
+        })
+    }
+
+    pub fn game(&self) -> ElasticsearchGame {
+        ElasticsearchGame {
+            slug: self.slug.clone(),
+            name: self.name.clone(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            genre: self.genre.clone(),
+            average_rating: Some(self.average_rating),
+            rating_count: Some(self.rating_count),
+            created_at: self.created_at,
+            tags: self.tags.clone(),
+            platforms: self.platforms.clone(),
+            release_date: self.release_date,
+            publisher: self.publisher.clone(),
+            cover_image_url: self.cover_image_url.clone(),
+        }
+    }
+}
+
+/// The fields of an indexed game compared against Postgres
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexedGame {
+    pub slug: String,
+    pub name: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub genre: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    pub release_date: Option<NaiveDate>,
+    pub publisher: Option<String>,
+    pub cover_image_url: Option<String>,
+    pub rating_version: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq, ToSchema)]
+pub struct Drift {
+    /// Games in Postgres without a document
+    pub missing: Vec<String>,
+    /// Documents whose content or rating version differs from Postgres
+    pub stale: Vec<String>,
+    /// Documents of games no longer in Postgres
+    pub orphaned: Vec<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ReindexReport {
+    pub index: String,
+    pub documents: usize,
+    /// Indices the alias pointed to before the swap, they are deleted afterwards
+    pub replaced: Vec<String>,
+}
+
+pub async fn load_documents(conn: &mut AsyncPgConnection) -> Result<Vec<GameDocument>, diesel::result::Error> {
+    games::table
+        .select(GameDocument::as_select())
+        .order(games::slug)
+        .load(conn)
+        .await
+}
+
+/// Compares Postgres, the source of truth, with what the index holds
+pub fn find_drift(expected: &[GameDocument], indexed: &[IndexedGame]) -> Drift {
+    let indexed: HashMap<&str, &IndexedGame> = indexed.iter().map(|game| (game.slug.as_str(), game)).collect();
+    let mut drift = Drift::default();
+
+    for game in expected {
+        match indexed.get(game.slug.as_str()) {
+            None => drift.missing.push(game.slug.clone()),
+            Some(doc) => {
+                let same = doc.name == game.name
+                    && doc.title == game.title
+                    && doc.description == game.description
+                    && doc.genre == game.genre
+                    && doc.tags == game.tags
+                    && doc.platforms == game.platforms
+                    && doc.release_date == game.release_date
+                    && doc.publisher == game.publisher
+                    && doc.cover_image_url == game.cover_image_url
+                    && doc.rating_version == Some(game.rating_version);
+                if !same {
+                    drift.stale.push(game.slug.clone());
+                }
+            }
+        }
+    }
+
+    let expected: HashSet<&str> = expected.iter().map(|game| game.slug.as_str()).collect();
+    drift.orphaned = indexed
+        .keys()
+        .filter(|slug| !expected.contains(*slug))
+        .map(|slug| slug.to_string())
+        .collect();
+    drift.orphaned.sort();
+
+    drift
+}
+
+/******************************************/
+// Reindex
+/******************************************/
+/// Rebuilds the search index from Postgres
+#[instrument(name = "Reindex games", skip(search, pool))]
+pub async fn reindex(search: &dyn GameSearch, pool: &PgPool) -> Result<ReindexReport, anyhow::Error> {
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+    let documents = load_documents(&mut conn).await.context("Failed to load games")?;
+    drop(conn);
+
+    let report = search.rebuild(&documents).await?;
+    tracing::info!("Reindexed {} games into {}", report.documents, report.index);
+    Ok(report)
+}
+
+/******************************************/
+// Drift check
+/******************************************/
+/// Finds drift between Postgres and the search index and rewrites the affected documents
+#[instrument(name = "Reconcile search index", skip(search, pool))]
+pub async fn reconcile(search: &dyn GameSearch, pool: &PgPool) -> Result<Drift, anyhow::Error> {
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+    let expected = load_documents(&mut conn).await.context("Failed to load games")?;
+    drop(conn);
+
+    let indexed = search.indexed_games().await?;
+    let drift = find_drift(&expected, &indexed);
+
+    let repair: Vec<GameDocument> = expected
+        .iter()
+        .filter(|game| drift.missing.contains(&game.slug) || drift.stale.contains(&game.slug))
+        .cloned()
+        .collect();
+    search.write_documents(&repair, &drift.orphaned).await?;
+
+    if drift == Drift::default() {
+        tracing::info!("Search index is in sync with {} games", expected.len());
+    } else {
+        tracing::warn!(
+            missing = drift.missing.len(),
+            stale = drift.stale.len(),
+            orphaned = drift.orphaned.len(),
+            "Repaired drift between Postgres and the search index"
+        );
+    }
+    Ok(drift)
+}
+
+/// Runs `reconcile` every `interval`, skipping a round while a reindex holds the lock
+pub async fn run_drift_check(search: Arc<dyn GameSearch>, pool: PgPool, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        let _guard = match try_lock_maintenance(&pool).await {
+            Ok(Some(guard)) => guard,
+            Ok(None) => {
+                tracing::info!("Search maintenance already running, skipping drift check");
+                continue;
+            }
+            Err(e) => {
+                tracing::error!("Search index drift check failed: {:?}", e);
+                continue;
+            }
+        };
+        if let Err(e) = reconcile(search.as_ref(), &pool).await {
+            tracing::error!("Search index drift check failed: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_drift, GameDocument, IndexedGame};
+
+    fn game(slug: &str, rating_version: i64) -> GameDocument {
+        GameDocument {
+            slug: slug.into(),
+            name: slug.into(),
+            title: None,
+            description: None,
+            genre: Some("rpg".into()),
+            created_at: None,
+            average_rating: 0.0,
+            rating_count: 0,
+            rating_version,
+            tags: vec!["open-world".into()],
+            platforms: Vec::new(),
+            release_date: None,
+            publisher: None,
+            cover_image_url: None,
+        }
+    }
+
+    fn indexed(slug: &str, rating_version: Option<i64>) -> IndexedGame {
+        IndexedGame {
+            slug: slug.into(),
+            name: slug.into(),
+            title: None,
+            description: None,
+            genre: Some("rpg".into()),
+            tags: vec!["open-world".into()],
+            platforms: Vec::new(),
+            release_date: None,
+            publisher: None,
+            cover_image_url: None,
+            rating_version,
+        }
+    }
+
+    #[test]
+    fn drift_finds_missing_stale_and_orphaned_documents() {
+        let expected = [game("a", 2), game("b", 1), game("c", 0), game("d", 0)];
+        let mut renamed = indexed("c", Some(0));
+        renamed.name = "old".into();
+        let mut retagged = indexed("d", Some(0));
+        retagged.tags.clear();
+        let docs = [indexed("a", Some(1)), renamed, retagged, indexed("z", Some(4))];
+
+        let drift = find_drift(&expected, &docs);
+
+        assert_eq!(drift.missing, vec!["b".to_string()]);
+        assert_eq!(drift.stale, vec!["a".to_string(), "c".to_string(), "d".to_string()]);
+        assert_eq!(drift.orphaned, vec!["z".to_string()]);
+        assert_eq!(find_drift(&[game("a", 3)], &[indexed("a", Some(3))]), Default::default());
+    }
+}
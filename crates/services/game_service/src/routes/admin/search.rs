@@ -0,0 +1,104 @@
+use actix_web::{web, HttpResponse};
+use errors::CustomError;
+use helpers::auth_jwt::permissions::Permission;
+use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
+use middleware::permissions::{Authorized, PermissionRestrictor};
+use tracing::instrument;
+
+use crate::reindex::{reconcile, reindex, try_lock_maintenance, Drift, ReindexReport};
+use crate::search::GameSearch;
+
+pub struct SearchMaintain();
+
+impl PermissionRestrictor for SearchMaintain {
+    fn permissions_required() -> &'static [Permission] {
+        &[Permission::SearchMaintain]
+    }
+}
+
+fn maintenance_running() -> CustomError {
+    CustomError::DatabaseError {
+        msg: "Search maintenance lock is held".to_string(),
+        resp: "A reindex or drift check is already running".to_string(),
+        status_code: actix_web::http::StatusCode::CONFLICT,
+    }
+}
+
+/******************************************/
+// Reindex games Route
+/******************************************/
+/**
+ * @route   POST /admin/search/reindex
+ * @access  Private
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/search/reindex",
+    tag = "search",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Search index rebuilt from Postgres", body = ReindexReport),
+        (status = 401, description = "Invalid token or session"),
+        (status = 403, description = "Missing search:maintain permission"),
+        (status = 409, description = "A reindex or drift check is already running")
+    )
+)]
+#[instrument(name = "Reindex games route", skip(pool, search_backend, req, redis_service))]
+pub async fn reindex_games(
+    pool: web::Data<PgPool>,
+    search_backend: web::Data<dyn GameSearch>,
+    req: Authorized<SearchMaintain>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let _ = redis_service.get_user_from_session(&req.into_inner().sid).await?;
+    let _guard = try_lock_maintenance(&pool)
+        .await
+        .map_err(CustomError::UnexpectedError)?
+        .ok_or_else(maintenance_running)?;
+
+    let report = reindex(search_backend.as_ref(), &pool)
+        .await
+        .map_err(CustomError::UnexpectedError)?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/******************************************/
+// Reconcile search index Route
+/******************************************/
+/**
+ * @route   POST /admin/search/reconcile
+ * @access  Private
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/search/reconcile",
+    tag = "search",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Drift found between Postgres and the index, already repaired", body = Drift),
+        (status = 401, description = "Invalid token or session"),
+        (status = 403, description = "Missing search:maintain permission"),
+        (status = 409, description = "A reindex or drift check is already running")
+    )
+)]
+#[instrument(name = "Reconcile search index route", skip(pool, search_backend, req, redis_service))]
+pub async fn reconcile_index(
+    pool: web::Data<PgPool>,
+    search_backend: web::Data<dyn GameSearch>,
+    req: Authorized<SearchMaintain>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let _ = redis_service.get_user_from_session(&req.into_inner().sid).await?;
+    let _guard = try_lock_maintenance(&pool)
+        .await
+        .map_err(CustomError::UnexpectedError)?
+        .ok_or_else(maintenance_running)?;
+
+    let drift = reconcile(search_backend.as_ref(), &pool)
+        .await
+        .map_err(CustomError::UnexpectedError)?;
+
+    Ok(HttpResponse::Ok().json(drift))
+}
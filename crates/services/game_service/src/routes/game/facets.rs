@@ -0,0 +1,77 @@
+use actix_web::{web, HttpResponse};
+use errors::CustomError;
+use helpers::auth_jwt::auth::Claims;
+use lib_config::session::redis::RedisService;
+use serde::Serialize;
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::elasticsearch::ElasticsearchGame;
+use crate::routes::game::search::SearchQuery;
+use crate::search::GameSearch;
+
+pub const MAX_GENRE_FACETS: usize = 50;
+pub const NEWEST_GAMES: usize = 5;
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct GenreFacet {
+    pub genre: String,
+    pub count: u64,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct RatingBucket {
+    /// Lower bound of the bucket, it covers `[rating, rating + 1)`
+    pub rating: f32,
+    pub count: u64,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FacetsResponse {
+    /// Games matching every filter
+    pub total: u64,
+    /// Genre counts ignore the genre filter so other genres stay selectable
+    pub genres: Vec<GenreFacet>,
+    pub rating_histogram: Vec<RatingBucket>,
+    pub newest: Vec<ElasticsearchGame>,
+}
+
+/******************************************/
+// Game facets Route
+/******************************************/
+/**
+ * @route   GET /games/facets
+ * @access  Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/facets",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Genre facets, rating histogram and newest games for the filters", body = FacetsResponse),
+        (status = 400, description = "Invalid filters"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "Game facets", skip(search_backend, req, redis_service))]
+pub async fn game_facets(
+    search: web::Query<SearchQuery>,
+    search_backend: web::Data<dyn GameSearch>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let session_id = req.into_inner().sid;
+    let _ = redis_service.get_user_from_session(&session_id).await?;
+
+    let search = search.into_inner();
+    search.validate()?;
+
+    let facets = search_backend.facets(&search).await.map_err(|err| {
+        tracing::error!("Failed to fetch game facets: {:?}", err);
+        CustomError::UnexpectedError(anyhow::anyhow!("Failed to fetch game facets"))
+    })?;
+
+    Ok(HttpResponse::Ok().json(facets))
+}
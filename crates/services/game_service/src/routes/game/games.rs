@@ -5,19 +5,18 @@ use kafka::models::{UserEventType, UserEventsMessage};
 use lib_config::db::db::PgPool;
 use errors::{AuthError, CustomError};
 use crate::kafka_handler::ReceivedGame;
-use crate::schema::users::dsl::*;
-use crate::schema::rate_game::dsl::*;
 use crate::routes::game::model::{Game, RateGame, RateGameRequest};
 use actix_web::{web, HttpResponse, HttpRequest};
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
 use uuid::Uuid;
 use lib_config::session::redis::RedisService;
-use serde_json::json;
 use tracing::instrument;
 use chrono::Utc;
 use crate::elasticsearch::ElasticsearchGame;
-use elasticsearch::{Elasticsearch, SearchParts};
+use crate::ratings::save_rating;
+use crate::moderation::{pending_review_event, DbReviewStatus, WordFilter};
+use crate::search::GameSearch;
 use anyhow::Context;
 use super::model::Paginate;
 use  crate::db_error;
@@ -28,13 +27,27 @@ use  crate::db_error;
  * @route   POST /rate
  * @access  Protected
  */
-#[instrument(name = "Rate game", skip(pool, rate_game_req, req, elastic_client, redis_service))]
+#[utoipa::path(
+    post,
+    path = "/api/v1/rate",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    request_body = RateGameRequest,
+    responses(
+        (status = 200, description = "Rating added, or the user's previous rating of the game replaced"),
+        (status = 400, description = "Invalid rating"),
+        (status = 404, description = "Game doesn't exist"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "Rate game", skip(pool, rate_game_req, req, search_backend, redis_service, word_filter))]
 pub async fn rate(
     pool: web::Data<PgPool>,
     rate_game_req: web::Json<RateGameRequest>, 
     req: web::ReqData<Claims>,
-    elastic_client: web::Data<Elasticsearch>,
+    search_backend: web::Data<dyn GameSearch>,
     redis_service: web::Data<RedisService>,
+    word_filter: web::Data<WordFilter>,
     kafka_producer: web::Data<Sender<KafkaMessage<String>>>
 
 ) -> Result<HttpResponse, CustomError>{
@@ -43,8 +56,6 @@ pub async fn rate(
 
     let _ = redis_service.get_user_from_session(&session_id).await?;
 
-    let pool_clone= pool.clone();
-    let pool_ref= pool_clone.as_ref();
     let mut conn = pool
         .get()
         .await
@@ -66,59 +77,36 @@ pub async fn rate(
     })?;
     tracing::info!("User id: {}", id_user);
 
+    let filtered_words = game_review.as_deref().map(|review| word_filter.matches(review)).unwrap_or_default();
     let new_rate_game = RateGame {
         id: uuid::Uuid::new_v4(),
         game_slug: slug.clone(),
         user_id: id_user.clone(),
         rating: game_rating.clone(),
-        review: game_review,
+        review: game_review.clone(),
         created_at: Utc::now().naive_utc(),
     };
 
-    let result= diesel::insert_into(rate_game)
-        .values(&new_rate_game)
-        .execute(&mut conn)
-        .await
-        .map_err(|err| db_error::DbError(err))?;
+    let saved = save_rating(&mut conn, new_rate_game, !filtered_words.is_empty()).await?;
 
-    let new_game= get_game_by_slug(&slug, pool_ref)
-    .await?;
+    if let Err(err) = search_backend.update_rating(&slug, &saved.aggregate).await {
+        tracing::error!("Failed to sync rating of game {} to the search index: {:?}", slug, err);
+    }
+
+    if let Some(review_event) = pending_review_event(
+        saved.review_id,
+        saved.status,
+        &slug,
+        id_user,
+        game_rating,
+        game_review.as_deref(),
+        filtered_words,
+    ) {
+        let _ = push_to_broker(&kafka_producer, &review_event)
+            .await
+            .context("Failed to send review event message to broker");
+    }
 
-    let es_game = ElasticsearchGame::new(&new_game);
-    let current_game = elastic_client
-        .get(elasticsearch::GetParts::IndexId("rate", &es_game.slug))
-        .send()
-        .await
-        .map_err(|err| 
-            CustomError::UnexpectedError(anyhow::anyhow!("Failed to retrieve game data").into())
-        )?;
-
-        let json_response = current_game.json::<serde_json::Value>().await.ok();
-        
-        let (current_avg_rating, current_rating_count) = if let Some(hit) = json_response {
-            let source = hit.get("_source");
-            if let Some(res) = source {
-                tracing::info!("Elasticsearch response source: {}", res);
-                let avg_rating = res.get("average_rating")
-                                    .and_then(|r| r.as_f64())
-                                    .unwrap_or(0.0) as f32;
-                let rating_count = res.get("rating_count")
-                                      .and_then(|r| r.as_i64())
-                                      .unwrap_or(0) as i32;
-                tracing::info!("Found rating: avg_rating = {}, rating_count = {}", avg_rating, rating_count);
-                (avg_rating, rating_count)
-            } else {
-                tracing::error!("NO source in elasticsearch response");
-                (0.0, 0)
-            }
-        } else {
-            tracing::error!("No data found in the Elasticsearch response.");
-            (0.0, 0)
-        };
-    let new_rating_count = current_rating_count + 1;
-    let new_average_rating = ((current_avg_rating * current_rating_count as f32) + game_rating as f32) / new_rating_count as f32;
-    
-    ElasticsearchGame::update_game(&elastic_client, &es_game, Some(new_average_rating), Some(new_rating_count)).await.unwrap();
     let message = UserEventsMessage{
         user_id: id_user,
         event_type: UserEventType::Rate {
@@ -132,13 +120,28 @@ pub async fn rate(
         .await
         .context("Failed to send user event message to broker");
 
-    Ok(HttpResponse::Ok().json("Rating successfully added."))
+    let response = match saved.status {
+        DbReviewStatus::Pending => "Rating successfully added, the review will be visible once approved.",
+        _ => "Rating successfully added.",
+    };
+    Ok(HttpResponse::Ok().json(response))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    params(Paginate),
+    responses(
+        (status = 200, description = "Games sorted by average rating", body = Vec<ElasticsearchGame>),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
 #[instrument(name = "Get game list", skip_all)]
 pub async fn get_game(
     paginate: web::Query<Paginate>,
-    elastic_client: web::Data<Elasticsearch>,
+    search_backend: web::Data<dyn GameSearch>,
     req: web::ReqData<Claims>,
     redis_service: web::Data<RedisService>
 ) -> Result<HttpResponse, CustomError> {
@@ -148,73 +151,14 @@ pub async fn get_game(
     let paginate = paginate.into_inner();
     let from = (paginate.page - 1) * paginate.limit;
 
-    let response = elastic_client
-        .search(SearchParts::Index(&vec![&String::from("rate")[..]]))
-        .body(json!({
-            "sort": [
-                {
-                    "average_rating": {
-                        "order": "desc"
-                    }
-                }
-            ],
-            "from": from,
-            "size": paginate.limit
-        }))
-        .send()
-        .await
-        .map_err(|err| {
-            tracing::error!("Failed to fetch game data Elasticsearch response: {:?}", err);
-            CustomError::UnexpectedError(anyhow::anyhow!("Failed to get response from elasticsearch").into())
-        })?;
-
-    let response_json = response.json::<serde_json::Value>()
+    let ret = search_backend
+        .top_rated(from, paginate.limit)
         .await
         .map_err(|err| {
-            tracing::error!("Failed to parse Elasticsearch response: {:?}", err);
-            CustomError::UnexpectedError(anyhow::anyhow!("Error parsing response").into())
+            tracing::error!("Failed to fetch game data from the search index: {:?}", err);
+            CustomError::UnexpectedError(anyhow::anyhow!("Failed to fetch game data"))
         })?;
 
-    tracing::info!("{}", response_json.to_string());
-
-    let hits = response_json.get("hits")
-        .ok_or(CustomError::UnexpectedError(anyhow::anyhow!("Failed to fetch outer hits field").into()))?
-        .get("hits")
-        .ok_or(CustomError::UnexpectedError(anyhow::anyhow!("Failed to fetch inner hits field").into()))?;
-
-    let ret = match hits {
-        serde_json::Value::Array(res_vec) => {
-            let temp: Vec<ElasticsearchGame> = res_vec.into_iter()
-                .map(|res| {
-                    let temp = res.get("_source")
-                        .ok_or(
-                            CustomError::UnexpectedError(anyhow::anyhow!(
-                                "Failed to fetch \"_source\" field"
-                            ).into()
-                        ));
-
-                    temp
-                })
-                .map(|source| {
-                    let temp = source.map(|doc| {
-                        let game: Result<ElasticsearchGame, serde_json::Error> = serde_json::from_value(doc.clone());
-                        game.map_err(|_| {
-                            CustomError::UnexpectedError(anyhow::anyhow!("Failed to deserialize \"_source\"").into())
-                        })
-                    })?;
-
-                    temp
-                })
-                .collect::<Result<Vec<_>, _>>()?;
-
-            Ok(temp)
-        }
-
-        _ => {
-            Err(CustomError::UnexpectedError(anyhow::anyhow!("Failed to fetch game data").into()))
-        }
-    }?;
-
     if ret.len() == 0 {
         tracing::info!("No more games");
         return Ok(HttpResponse::Ok().json("No more games"))
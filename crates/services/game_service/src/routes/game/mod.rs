@@ -1,2 +1,6 @@
 pub mod games;
-pub mod model;
\ No newline at end of file
+pub mod model;
+pub mod search;
+pub mod facets;
+pub mod reviews;
+pub mod recommendations;
@@ -3,6 +3,7 @@ use diesel::Queryable;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::NaiveDateTime;
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Queryable, Deserialize, Serialize, Debug)]
 pub struct Game {
@@ -38,7 +39,7 @@ pub struct User {
     pub created_at: Option<NaiveDateTime>,
 }
 
-#[derive(Deserialize, Queryable)]
+#[derive(Deserialize, Queryable, ToSchema)]
 #[diesel(table_name = crate::schema::rate_game)]
 pub struct RateGameRequest {
     pub game_slug: String,
@@ -46,8 +47,57 @@ pub struct RateGameRequest {
     pub review: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Paginate {
     pub page: i64,
     pub limit: i64
 }
+
+#[derive(Deserialize, ToSchema)]
+pub struct EditRatingRequest {
+    pub rating: i32,
+    pub review: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewSort {
+    #[default]
+    Newest,
+    Oldest,
+    Highest,
+    Lowest,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReviewsQuery {
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+    pub sort: Option<ReviewSort>,
+}
+
+#[derive(Queryable, Serialize, Debug, ToSchema)]
+pub struct Review {
+    /// Id to flag the review with
+    pub id: Uuid,
+    pub username: String,
+    pub rating: i32,
+    pub review: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct FlagReviewRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ReviewsResponse {
+    pub total: i64,
+    pub page: i64,
+    pub limit: i64,
+    pub reviews: Vec<Review>,
+}
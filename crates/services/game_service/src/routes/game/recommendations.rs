@@ -0,0 +1,66 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use errors::CustomError;
+use helpers::auth_jwt::auth::Claims;
+use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
+use serde::Deserialize;
+use tracing::instrument;
+use utoipa::IntoParams;
+
+use crate::recommendations::{recommend, RecommendationsResponse};
+use crate::routes::game::reviews::session_user;
+use crate::routes::lists::lists::private_no_store;
+
+const MAX_RECOMMENDATIONS: i64 = 50;
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RecommendationsQuery {
+    /// Number of games, 10 by default
+    pub limit: Option<i64>,
+}
+
+/******************************************/
+// Game recommendations Route
+/******************************************/
+/**
+ * @route   GET /games/recommendations
+ * @access  Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/recommendations",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    params(RecommendationsQuery),
+    responses(
+        (status = 200, description = "Games similar to the ones the user liked, topped up with popular games", body = RecommendationsResponse),
+        (status = 400, description = "Invalid limit"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "Game recommendations", skip(pool, req, redis_service))]
+pub async fn game_recommendations(
+    query: web::Query<RecommendationsQuery>,
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+
+    let limit = query.into_inner().limit.unwrap_or(10);
+    if !(1..=MAX_RECOMMENDATIONS).contains(&limit) {
+        return Err(CustomError::ValidationError(format!("Limit must be between 1 and {}.", MAX_RECOMMENDATIONS)));
+    }
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let recommendations = recommend(&mut conn, user, limit).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(private_no_store())
+        .json(recommendations))
+}
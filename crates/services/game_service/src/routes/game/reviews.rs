@@ -0,0 +1,356 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use errors::{AuthError, CustomError};
+use flume::Sender;
+use helpers::auth_jwt::auth::Claims;
+use kafka::channel::{push_to_broker, KafkaMessage};
+use kafka::models::{ModerationReason, ReviewEventsMessage, UserEventType, UserEventsMessage};
+use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_error::DbError;
+use crate::moderation::{content_hash, pending_review_event, DbReviewStatus, WordFilter};
+use crate::ratings::{delete_rating, edit_rating, GameRating};
+use crate::routes::game::games::get_game_by_slug;
+use crate::routes::game::model::{EditRatingRequest, FlagReviewRequest, Review, ReviewSort, ReviewsQuery, ReviewsResponse};
+use crate::routes::game::search::pagination;
+use crate::schema::{rate_game, review_flags, users};
+use crate::search::GameSearch;
+
+/// Authenticated user id, after checking the session is still alive
+pub(crate) async fn session_user(claims: Claims, redis_service: &RedisService) -> Result<Uuid, CustomError> {
+    let _ = redis_service.get_user_from_session(&claims.sid).await?;
+    Uuid::parse_str(&claims.sub).map_err(|_| {
+        CustomError::AuthenticationError(AuthError::InvalidSession(anyhow::anyhow!("Invalid session ID".to_string())))
+    })
+}
+
+async fn sync_rating(search_backend: &dyn GameSearch, slug: &str, aggregate: &GameRating) {
+    if let Err(err) = search_backend.update_rating(slug, aggregate).await {
+        tracing::error!("Failed to sync rating of game {} to the search index: {:?}", slug, err);
+    }
+}
+
+fn not_rated() -> CustomError {
+    CustomError::DatabaseError {
+        msg: "No rating of the user for this game".to_string(),
+        resp: "You haven't rated this game".to_string(),
+        status_code: actix_web::http::StatusCode::NOT_FOUND,
+    }
+}
+
+/******************************************/
+// Edit rating Route
+/******************************************/
+/**
+ * @route   PUT /ratings/{slug}
+ * @access  Protected
+ */
+#[utoipa::path(
+    put,
+    path = "/api/v1/ratings/{slug}",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    params(("slug" = String, Path, description = "Slug of the rated game")),
+    request_body = EditRatingRequest,
+    responses(
+        (status = 200, description = "Rating updated", body = GameRating),
+        (status = 400, description = "Invalid rating"),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "Game doesn't exist or the user hasn't rated it")
+    )
+)]
+#[instrument(name = "Edit rating", skip(pool, body, req, search_backend, redis_service, word_filter, kafka_producer))]
+pub async fn update_rating(
+    pool: web::Data<PgPool>,
+    slug: web::Path<String>,
+    body: web::Json<EditRatingRequest>,
+    req: web::ReqData<Claims>,
+    search_backend: web::Data<dyn GameSearch>,
+    redis_service: web::Data<RedisService>,
+    word_filter: web::Data<WordFilter>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+    let slug = slug.into_inner();
+    let body = body.into_inner();
+
+    if body.rating < 1 || body.rating > 5 {
+        return Err(CustomError::ValidationError("Rating must be between 1 and 5.".to_string()));
+    }
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let filtered_words = body.review.as_deref().map(|review| word_filter.matches(review)).unwrap_or_default();
+    let saved = edit_rating(&mut conn, user, &slug, body.rating, body.review.clone(), !filtered_words.is_empty())
+        .await?
+        .ok_or_else(not_rated)?;
+    sync_rating(search_backend.as_ref(), &slug, &saved.aggregate).await;
+
+    if let Some(review_event) = pending_review_event(
+        saved.review_id,
+        saved.status,
+        &slug,
+        user,
+        body.rating,
+        body.review.as_deref(),
+        filtered_words,
+    ) {
+        let _ = push_to_broker(&kafka_producer, &review_event)
+            .await
+            .context("Failed to send review event message to broker");
+    }
+
+    let message = UserEventsMessage {
+        user_id: user,
+        event_type: UserEventType::UpdateRating {
+            rating: body.rating,
+            game_slug: slug,
+            time: Some(Utc::now().naive_utc()),
+        },
+    };
+    let _ = push_to_broker(&kafka_producer, &message)
+        .await
+        .context("Failed to send user event message to broker");
+
+    Ok(HttpResponse::Ok().json(saved.aggregate))
+}
+
+/******************************************/
+// Delete rating Route
+/******************************************/
+/**
+ * @route   DELETE /ratings/{slug}
+ * @access  Protected
+ */
+#[utoipa::path(
+    delete,
+    path = "/api/v1/ratings/{slug}",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    params(("slug" = String, Path, description = "Slug of the rated game")),
+    responses(
+        (status = 200, description = "Rating and review deleted", body = GameRating),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "Game doesn't exist or the user hasn't rated it")
+    )
+)]
+#[instrument(name = "Delete rating", skip(pool, req, search_backend, redis_service, kafka_producer))]
+pub async fn remove_rating(
+    pool: web::Data<PgPool>,
+    slug: web::Path<String>,
+    req: web::ReqData<Claims>,
+    search_backend: web::Data<dyn GameSearch>,
+    redis_service: web::Data<RedisService>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+    let slug = slug.into_inner();
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let aggregate = delete_rating(&mut conn, user, &slug)
+        .await?
+        .ok_or_else(not_rated)?;
+    sync_rating(search_backend.as_ref(), &slug, &aggregate).await;
+
+    let message = UserEventsMessage {
+        user_id: user,
+        event_type: UserEventType::DeleteRating {
+            game_slug: slug,
+            time: Some(Utc::now().naive_utc()),
+        },
+    };
+    let _ = push_to_broker(&kafka_producer, &message)
+        .await
+        .context("Failed to send user event message to broker");
+
+    Ok(HttpResponse::Ok().json(aggregate))
+}
+
+/******************************************/
+// Game reviews Route
+/******************************************/
+/**
+ * @route   GET /games/{slug}/reviews
+ * @access  Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{slug}/reviews",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    params(("slug" = String, Path, description = "Slug of the game"), ReviewsQuery),
+    responses(
+        (status = 200, description = "Reviews of the game", body = ReviewsResponse),
+        (status = 400, description = "Invalid pagination, or a page past the first 10000 reviews"),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "Game doesn't exist")
+    )
+)]
+#[instrument(name = "Get game reviews", skip(pool, req, redis_service))]
+pub async fn get_reviews(
+    pool: web::Data<PgPool>,
+    slug: web::Path<String>,
+    query: web::Query<ReviewsQuery>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let _ = session_user(req.into_inner(), &redis_service).await?;
+    let slug = slug.into_inner();
+    let query = query.into_inner();
+    // Bounds page * limit as well, the offset below can't overflow
+    let (page, limit) = pagination(query.page, query.limit)?;
+
+    let _ = get_game_by_slug(&slug, &pool).await?;
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    // Reviews of deactivated accounts are hidden until they come back
+    let total = rate_game::table
+        .inner_join(users::table)
+        .filter(rate_game::game_slug.eq(&slug))
+        .filter(rate_game::review.is_not_null())
+        .filter(rate_game::moderation_status.eq(DbReviewStatus::Published))
+        .filter(users::deactivated_at.is_null())
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    let reviews = rate_game::table
+        .inner_join(users::table)
+        .filter(rate_game::game_slug.eq(&slug))
+        .filter(rate_game::review.is_not_null())
+        .filter(rate_game::moderation_status.eq(DbReviewStatus::Published))
+        .filter(users::deactivated_at.is_null())
+        .select((
+            rate_game::id,
+            users::username,
+            rate_game::rating,
+            rate_game::review,
+            rate_game::created_at,
+            rate_game::updated_at,
+        ))
+        .into_boxed();
+
+    let reviews = match query.sort.unwrap_or_default() {
+        ReviewSort::Newest => reviews.order((rate_game::created_at.desc(), rate_game::id)),
+        ReviewSort::Oldest => reviews.order((rate_game::created_at.asc(), rate_game::id)),
+        ReviewSort::Highest => reviews.order((rate_game::rating.desc(), rate_game::created_at.desc())),
+        ReviewSort::Lowest => reviews.order((rate_game::rating.asc(), rate_game::created_at.desc())),
+    };
+
+    let reviews = reviews
+        .offset((page - 1) * limit)
+        .limit(limit)
+        .load::<Review>(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    Ok(HttpResponse::Ok().json(ReviewsResponse { total, page, limit, reviews }))
+}
+
+/******************************************/
+// Flag review Route
+/******************************************/
+/**
+ * @route   POST /reviews/{review_id}/flag
+ * @access  Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/reviews/{review_id}/flag",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    params(("review_id" = Uuid, Path, description = "Id of the flagged review")),
+    request_body = FlagReviewRequest,
+    responses(
+        (status = 200, description = "Review flagged for moderation"),
+        (status = 400, description = "Own review or already flagged by the user"),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "Review doesn't exist")
+    )
+)]
+#[instrument(name = "Flag review", skip(pool, body, req, redis_service, kafka_producer))]
+pub async fn flag_review(
+    pool: web::Data<PgPool>,
+    review_id: web::Path<Uuid>,
+    body: web::Json<FlagReviewRequest>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+    let review_id = review_id.into_inner();
+    let reason = body
+        .into_inner()
+        .reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let (game_slug, author, rating, review) = rate_game::table
+        .filter(rate_game::id.eq(review_id))
+        .filter(rate_game::moderation_status.eq(DbReviewStatus::Published))
+        .select((rate_game::game_slug, rate_game::user_id, rate_game::rating, rate_game::review))
+        .first::<(String, Uuid, i32, Option<String>)>(&mut conn)
+        .await
+        .optional()
+        .map_err(DbError)?
+        .and_then(|(game_slug, author, rating, review)| Some((game_slug, author, rating, review?)))
+        .ok_or_else(|| CustomError::DatabaseError {
+            msg: format!("No published review with id {}", review_id),
+            resp: "Review doesn't exist".to_string(),
+            status_code: actix_web::http::StatusCode::NOT_FOUND,
+        })?;
+
+    if author == user {
+        return Err(CustomError::ValidationError("You can't flag your own review.".to_string()));
+    }
+
+    diesel::insert_into(review_flags::table)
+        .values((
+            review_flags::id.eq(Uuid::new_v4()),
+            review_flags::review_id.eq(review_id),
+            review_flags::flagged_by.eq(user),
+            review_flags::reason.eq(&reason),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    let message = ReviewEventsMessage {
+        review_id,
+        game_slug,
+        user_id: author,
+        rating,
+        content_hash: content_hash(rating, &review),
+        review,
+        reason: ModerationReason::UserFlag { flagged_by: user, reason },
+        time: Utc::now().naive_utc(),
+    };
+    let _ = push_to_broker(&kafka_producer, &message)
+        .await
+        .context("Failed to send review event message to broker");
+
+    Ok(HttpResponse::Ok().json("Review flagged for moderation."))
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpResponse};
+use chrono::NaiveDate;
+use errors::CustomError;
+use helpers::auth_jwt::auth::Claims;
+use lib_config::session::redis::RedisService;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::elasticsearch::ElasticsearchGame;
+use crate::search::{GameSearch, SearchPage};
+
+const MAX_SEARCH_LIMIT: i64 = 100;
+/// Elasticsearch's default `index.max_result_window`, pages can't reach past it
+const MAX_RESULT_WINDOW: i64 = 10_000;
+const SEARCH_FIELDS: [&str; 7] = [
+    "name^3",
+    "name.autocomplete^2",
+    "title^2",
+    "description",
+    "genre.text",
+    "tags.text",
+    "publisher.text",
+];
+
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    /// Free text matched against name, title, description, genre, tags and publisher
+    pub q: Option<String>,
+    /// Comma separated genres, any of them matches
+    pub genre: Option<String>,
+    /// Comma separated tags, games need every one of them
+    pub tags: Option<String>,
+    /// Comma separated platforms, any of them matches
+    pub platforms: Option<String>,
+    pub publisher: Option<String>,
+    /// Games released on or after this date, `YYYY-MM-DD`
+    pub released_after: Option<NaiveDate>,
+    /// Games released on or before this date, `YYYY-MM-DD`
+    pub released_before: Option<NaiveDate>,
+    pub min_rating: Option<f32>,
+    pub max_rating: Option<f32>,
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub game: ElasticsearchGame,
+    pub score: Option<f64>,
+    /// Matched fragments per field, matches are wrapped in `<em>` tags
+    pub highlight: HashMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct SearchResponse {
+    pub total: u64,
+    pub page: i64,
+    pub limit: i64,
+    pub games: Vec<SearchHit>,
+}
+
+impl SearchQuery {
+    pub fn text(&self) -> Option<&str> {
+        self.q.as_deref().map(str::trim).filter(|q| !q.is_empty())
+    }
+
+    pub fn validate(&self) -> Result<(), CustomError> {
+        let in_range = |rating: Option<f32>| rating.map_or(true, |rating| (0.0..=5.0).contains(&rating));
+        if !in_range(self.min_rating) || !in_range(self.max_rating) {
+            return Err(CustomError::ValidationError("Ratings must be between 0 and 5.".to_string()));
+        }
+        if let (Some(min), Some(max)) = (self.min_rating, self.max_rating) {
+            if min > max {
+                return Err(CustomError::ValidationError("min_rating can't be greater than max_rating.".to_string()));
+            }
+        }
+        if let (Some(after), Some(before)) = (self.released_after, self.released_before) {
+            if after > before {
+                return Err(CustomError::ValidationError("released_after can't be later than released_before.".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Requested genres, lowercased like the `genre` keyword normalizer
+    pub fn genres(&self) -> Vec<String> {
+        keywords(self.genre.as_deref())
+    }
+
+    pub fn tag_list(&self) -> Vec<String> {
+        keywords(self.tags.as_deref())
+    }
+
+    pub fn platform_list(&self) -> Vec<String> {
+        keywords(self.platforms.as_deref())
+    }
+
+    pub fn publisher_name(&self) -> Option<String> {
+        self.publisher
+            .as_deref()
+            .map(|publisher| publisher.trim().to_lowercase())
+            .filter(|publisher| !publisher.is_empty())
+    }
+
+    pub fn genre_filter(&self) -> Option<Value> {
+        let genres = self.genres();
+        (!genres.is_empty()).then(|| json!({ "terms": { "genre": genres } }))
+    }
+
+    pub fn rating_filter(&self) -> Option<Value> {
+        if self.min_rating.is_none() && self.max_rating.is_none() {
+            return None;
+        }
+        let mut range = serde_json::Map::new();
+        if let Some(min) = self.min_rating {
+            range.insert("gte".into(), json!(min));
+        }
+        if let Some(max) = self.max_rating {
+            range.insert("lte".into(), json!(max));
+        }
+        Some(json!({ "range": { "average_rating": range } }))
+    }
+
+    pub fn release_filter(&self) -> Option<Value> {
+        if self.released_after.is_none() && self.released_before.is_none() {
+            return None;
+        }
+        let mut range = serde_json::Map::new();
+        if let Some(after) = self.released_after {
+            range.insert("gte".into(), json!(after));
+        }
+        if let Some(before) = self.released_before {
+            range.insert("lte".into(), json!(before));
+        }
+        Some(json!({ "range": { "release_date": range } }))
+    }
+
+    /// Every filter but the genre one, the genre facet counts games with these
+    pub fn catalog_filters(&self) -> Vec<Value> {
+        let platforms = self.platform_list();
+
+        self.tag_list()
+            .into_iter()
+            .map(|tag| json!({ "term": { "tags": tag } }))
+            .chain((!platforms.is_empty()).then(|| json!({ "terms": { "platforms": platforms } })))
+            .chain(self.publisher_name().map(|publisher| json!({ "term": { "publisher": publisher } })))
+            .chain(self.release_filter())
+            .chain(self.rating_filter())
+            .collect()
+    }
+
+    /// Genre, catalog and rating filters, shared by search and facets so both describe the same games
+    pub fn filters(&self) -> Vec<Value> {
+        self.genre_filter().into_iter().chain(self.catalog_filters()).collect()
+    }
+
+    /// Fuzzy full text match when `q` is given, every game otherwise
+    pub fn text_query(&self) -> Value {
+        match self.text() {
+            Some(text) => json!({
+                "multi_match": {
+                    "query": text,
+                    "fields": SEARCH_FIELDS,
+                    "fuzziness": "AUTO",
+                    "prefix_length": 1
+                }
+            }),
+            None => json!({ "match_all": {} }),
+        }
+    }
+
+    pub fn query(&self) -> Value {
+        json!({ "bool": { "must": self.text_query(), "filter": self.filters() } })
+    }
+}
+
+/// Comma separated values, trimmed and lowercased like the keyword normalizer
+fn keywords(list: Option<&str>) -> Vec<String> {
+    list.unwrap_or_default()
+        .split(',')
+        .map(|value| value.trim().to_lowercase())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// Returns `(page, limit)` after validating the requested values
+pub fn pagination(page: Option<i64>, limit: Option<i64>) -> Result<(i64, i64), CustomError> {
+    let page = page.unwrap_or(1);
+    let limit = limit.unwrap_or(10);
+    if page < 1 {
+        return Err(CustomError::ValidationError("Page must be at least 1.".to_string()));
+    }
+    if !(1..=MAX_SEARCH_LIMIT).contains(&limit) {
+        return Err(CustomError::ValidationError(format!("Limit must be between 1 and {}.", MAX_SEARCH_LIMIT)));
+    }
+    if page.checked_mul(limit).is_none_or(|end| end > MAX_RESULT_WINDOW) {
+        return Err(CustomError::ValidationError(format!("Only the first {} results can be paged through.", MAX_RESULT_WINDOW)));
+    }
+    Ok((page, limit))
+}
+
+/******************************************/
+// Search games Route
+/******************************************/
+/**
+ * @route   GET /games/search
+ * @access  Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/search",
+    tag = "games",
+    security(("bearer_auth" = [])),
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Games matching the query, best match first", body = SearchResponse),
+        (status = 400, description = "Invalid filters or pagination"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "Search games", skip(search_backend, req, redis_service))]
+pub async fn search_games(
+    search: web::Query<SearchQuery>,
+    search_backend: web::Data<dyn GameSearch>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let session_id = req.into_inner().sid;
+    let _ = redis_service.get_user_from_session(&session_id).await?;
+
+    let search = search.into_inner();
+    search.validate()?;
+    let (page, limit) = pagination(search.page, search.limit)?;
+
+    let SearchPage { total, hits } = search_backend
+        .search(&search, (page - 1) * limit, limit)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to search games: {:?}", err);
+            CustomError::UnexpectedError(anyhow::anyhow!("Failed to search games"))
+        })?;
+
+    Ok(HttpResponse::Ok().json(SearchResponse { total, page, limit, games: hits }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pagination, SearchQuery};
+    use serde_json::json;
+
+    fn query(genre: Option<&str>, min_rating: Option<f32>, max_rating: Option<f32>) -> SearchQuery {
+        SearchQuery {
+            genre: genre.map(str::to_string),
+            min_rating,
+            max_rating,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn filters_combine_genres_and_rating_range() {
+        let filters = query(Some("RPG, strategy,"), Some(3.5), None).filters();
+
+        assert_eq!(filters[0], json!({ "terms": { "genre": ["rpg", "strategy"] } }));
+        assert_eq!(filters[1], json!({ "range": { "average_rating": { "gte": 3.5 } } }));
+        assert!(query(None, Some(4.0), Some(3.0)).validate().is_err());
+    }
+
+    #[test]
+    fn every_tag_is_required_and_any_platform_matches() {
+        let search = SearchQuery {
+            tags: Some("Co-op, Open-World".into()),
+            platforms: Some("pc,Switch".into()),
+            released_after: chrono::NaiveDate::from_ymd_opt(2020, 1, 1),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            search.filters(),
+            vec![
+                json!({ "term": { "tags": "co-op" } }),
+                json!({ "term": { "tags": "open-world" } }),
+                json!({ "terms": { "platforms": ["pc", "switch"] } }),
+                json!({ "range": { "release_date": { "gte": "2020-01-01" } } }),
+            ]
+        );
+    }
+
+    #[test]
+    fn pagination_is_bounded() {
+        assert_eq!(pagination(None, None).ok(), Some((1, 10)));
+        assert!(pagination(Some(0), None).is_err());
+        assert!(pagination(None, Some(101)).is_err());
+        assert_eq!(pagination(Some(100), Some(100)).ok(), Some((100, 100)));
+        assert!(pagination(Some(101), Some(100)).is_err());
+        assert!(pagination(Some(i64::MAX), Some(2)).is_err());
+    }
+}
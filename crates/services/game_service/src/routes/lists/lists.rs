@@ -0,0 +1,447 @@
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use errors::CustomError;
+use flume::Sender;
+use helpers::auth_jwt::auth::Claims;
+use kafka::channel::{push_to_broker, KafkaMessage};
+use kafka::models::{UserEventType, UserEventsMessage};
+use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_error::DbError;
+use crate::game_lists::{
+    add_game, create_list as insert_list, delete_list as remove_list, ensure_default_lists, list_games, owned_list,
+    remove_game, reorder, update_list as change_list, user_lists, visible_list, GameList, ListKind,
+};
+use crate::routes::game::reviews::session_user;
+use crate::routes::lists::models::{
+    list_name, AddListGameRequest, CreateListRequest, GameListResponse, GameListSummary, ReorderListRequest,
+    UpdateListRequest,
+};
+
+/// For responses that depend on who asks, so no shared cache may keep them
+pub(crate) fn private_no_store() -> CacheControl {
+    CacheControl(vec![CacheDirective::Private, CacheDirective::NoStore])
+}
+
+async fn push_list_event(
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    user: Uuid,
+    list_id: Uuid,
+    kind: ListKind,
+    game_slug: String,
+    added: bool,
+) {
+    let time = Utc::now().naive_utc();
+    let event_type = match added {
+        true => UserEventType::AddToList { list_id, list_kind: kind.into(), game_slug, time },
+        false => UserEventType::RemoveFromList { list_id, list_kind: kind.into(), game_slug, time },
+    };
+    let message = UserEventsMessage { user_id: user, event_type };
+    let _ = push_to_broker(kafka_producer, &message)
+        .await
+        .context("Failed to send user event message to broker");
+}
+
+/******************************************/
+// My lists Route
+/******************************************/
+/**
+ * @route   GET /lists
+ * @access  Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/lists",
+    tag = "lists",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Lists of the user, built-in lists first", body = Vec<GameListSummary>),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "Get my lists", skip(pool, req, redis_service))]
+pub async fn get_my_lists(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    ensure_default_lists(&mut conn, user).await.map_err(DbError)?;
+    let lists: Vec<GameListSummary> = user_lists(&mut conn, user, false)
+        .await
+        .map_err(DbError)?
+        .into_iter()
+        .map(GameListSummary::from)
+        .collect();
+
+    Ok(HttpResponse::Ok()
+        .insert_header(private_no_store())
+        .json(lists))
+}
+
+/******************************************/
+// User lists Route
+/******************************************/
+/**
+ * @route   GET /users/{user_id}/lists
+ * @access  Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{user_id}/lists",
+    tag = "lists",
+    security(("bearer_auth" = [])),
+    params(("user_id" = Uuid, Path, description = "Owner of the lists")),
+    responses(
+        (status = 200, description = "Public lists of the user, every list for the user themselves", body = Vec<GameListSummary>),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "Get user lists", skip(pool, req, redis_service))]
+pub async fn get_user_lists(
+    pool: web::Data<PgPool>,
+    owner: web::Path<Uuid>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+    let owner = owner.into_inner();
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let lists: Vec<GameListSummary> = user_lists(&mut conn, owner, owner != user)
+        .await
+        .map_err(DbError)?
+        .into_iter()
+        .map(GameListSummary::from)
+        .collect();
+
+    Ok(HttpResponse::Ok()
+        .insert_header(private_no_store())
+        .json(lists))
+}
+
+/******************************************/
+// Create list Route
+/******************************************/
+/**
+ * @route   POST /lists
+ * @access  Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/lists",
+    tag = "lists",
+    security(("bearer_auth" = [])),
+    request_body = CreateListRequest,
+    responses(
+        (status = 201, description = "Custom list created", body = GameList),
+        (status = 400, description = "Invalid name, name already used or too many lists"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "Create list", skip(pool, body, req, redis_service))]
+pub async fn create_list(
+    pool: web::Data<PgPool>,
+    body: web::Json<CreateListRequest>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+    let body = body.into_inner();
+    let name = list_name(&body.name)?;
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    ensure_default_lists(&mut conn, user).await.map_err(DbError)?;
+    let list = insert_list(&mut conn, user, &name, body.visibility.unwrap_or_default()).await?;
+
+    Ok(HttpResponse::Created().json(list))
+}
+
+/******************************************/
+// Get list Route
+/******************************************/
+/**
+ * @route   GET /lists/{list_id}
+ * @access  Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/lists/{list_id}",
+    tag = "lists",
+    security(("bearer_auth" = [])),
+    params(("list_id" = Uuid, Path, description = "Id of the list")),
+    responses(
+        (status = 200, description = "The list with its games in order", body = GameListResponse),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "List doesn't exist or is private")
+    )
+)]
+#[instrument(name = "Get list", skip(pool, req, redis_service))]
+pub async fn get_list(
+    pool: web::Data<PgPool>,
+    list_id: web::Path<Uuid>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let list = visible_list(&mut conn, list_id.into_inner(), user).await?;
+    let games = list_games(&mut conn, list.id).await.map_err(DbError)?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(private_no_store())
+        .json(GameListResponse { list, games }))
+}
+
+/******************************************/
+// Update list Route
+/******************************************/
+/**
+ * @route   PATCH /lists/{list_id}
+ * @access  Protected
+ */
+#[utoipa::path(
+    patch,
+    path = "/api/v1/lists/{list_id}",
+    tag = "lists",
+    security(("bearer_auth" = [])),
+    params(("list_id" = Uuid, Path, description = "Id of the list")),
+    request_body = UpdateListRequest,
+    responses(
+        (status = 200, description = "List renamed or its visibility changed", body = GameList),
+        (status = 400, description = "Invalid name, name already used or built-in list renamed"),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "List doesn't exist or belongs to someone else")
+    )
+)]
+#[instrument(name = "Update list", skip(pool, body, req, redis_service))]
+pub async fn update_list(
+    pool: web::Data<PgPool>,
+    list_id: web::Path<Uuid>,
+    body: web::Json<UpdateListRequest>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+    let body = body.into_inner();
+    let name = body.name.as_deref().map(list_name).transpose()?;
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let list = owned_list(&mut conn, list_id.into_inner(), user).await?;
+    let list = change_list(&mut conn, &list, name.as_deref(), body.visibility).await?;
+
+    Ok(HttpResponse::Ok().json(list))
+}
+
+/******************************************/
+// Delete list Route
+/******************************************/
+/**
+ * @route   DELETE /lists/{list_id}
+ * @access  Protected
+ */
+#[utoipa::path(
+    delete,
+    path = "/api/v1/lists/{list_id}",
+    tag = "lists",
+    security(("bearer_auth" = [])),
+    params(("list_id" = Uuid, Path, description = "Id of the list")),
+    responses(
+        (status = 200, description = "Custom list deleted with its games"),
+        (status = 400, description = "Built-in lists can't be deleted"),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "List doesn't exist or belongs to someone else")
+    )
+)]
+#[instrument(name = "Delete list", skip(pool, req, redis_service))]
+pub async fn delete_list(
+    pool: web::Data<PgPool>,
+    list_id: web::Path<Uuid>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let list = owned_list(&mut conn, list_id.into_inner(), user).await?;
+    remove_list(&mut conn, &list).await?;
+
+    Ok(HttpResponse::Ok().json("List deleted successfully"))
+}
+
+/******************************************/
+// Add game to list Route
+/******************************************/
+/**
+ * @route   POST /lists/{list_id}/games
+ * @access  Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/lists/{list_id}/games",
+    tag = "lists",
+    security(("bearer_auth" = [])),
+    params(("list_id" = Uuid, Path, description = "Id of the list")),
+    request_body = AddListGameRequest,
+    responses(
+        (status = 200, description = "Game added, and taken out of the user's other built-in lists", body = GameListResponse),
+        (status = 400, description = "Game doesn't exist, is already in the list or the list is full"),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "List doesn't exist or belongs to someone else")
+    )
+)]
+#[instrument(name = "Add game to list", skip(pool, body, req, redis_service, kafka_producer))]
+pub async fn add_list_game(
+    pool: web::Data<PgPool>,
+    list_id: web::Path<Uuid>,
+    body: web::Json<AddListGameRequest>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+    let body = body.into_inner();
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let list = owned_list(&mut conn, list_id.into_inner(), user).await?;
+    let moved_from = add_game(&mut conn, &list, &body.game_slug, body.position).await?;
+
+    for from in moved_from {
+        push_list_event(&kafka_producer, user, from.list_id, from.kind, body.game_slug.clone(), false).await;
+    }
+    push_list_event(&kafka_producer, user, list.id, list.kind, body.game_slug, true).await;
+
+    let games = list_games(&mut conn, list.id).await.map_err(DbError)?;
+    Ok(HttpResponse::Ok().json(GameListResponse { list, games }))
+}
+
+/******************************************/
+// Remove game from list Route
+/******************************************/
+/**
+ * @route   DELETE /lists/{list_id}/games/{slug}
+ * @access  Protected
+ */
+#[utoipa::path(
+    delete,
+    path = "/api/v1/lists/{list_id}/games/{slug}",
+    tag = "lists",
+    security(("bearer_auth" = [])),
+    params(
+        ("list_id" = Uuid, Path, description = "Id of the list"),
+        ("slug" = String, Path, description = "Slug of the game")
+    ),
+    responses(
+        (status = 200, description = "Game removed from the list"),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "List doesn't exist, belongs to someone else or doesn't hold the game")
+    )
+)]
+#[instrument(name = "Remove game from list", skip(pool, req, redis_service, kafka_producer))]
+pub async fn remove_list_game(
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, String)>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+    let (list_id, slug) = path.into_inner();
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let list = owned_list(&mut conn, list_id, user).await?;
+    if !remove_game(&mut conn, list.id, &slug).await.map_err(DbError)? {
+        return Err(CustomError::DatabaseError {
+            msg: format!("Game {} isn't in list {}", slug, list.id),
+            resp: "The game isn't in this list".to_string(),
+            status_code: actix_web::http::StatusCode::NOT_FOUND,
+        });
+    }
+
+    push_list_event(&kafka_producer, user, list.id, list.kind, slug, false).await;
+
+    Ok(HttpResponse::Ok().json("Game removed from the list"))
+}
+
+/******************************************/
+// Reorder list Route
+/******************************************/
+/**
+ * @route   PUT /lists/{list_id}/games
+ * @access  Protected
+ */
+#[utoipa::path(
+    put,
+    path = "/api/v1/lists/{list_id}/games",
+    tag = "lists",
+    security(("bearer_auth" = [])),
+    params(("list_id" = Uuid, Path, description = "Id of the list")),
+    request_body = ReorderListRequest,
+    responses(
+        (status = 200, description = "Games of the list in the new order", body = GameListResponse),
+        (status = 400, description = "The new order doesn't name every game of the list exactly once"),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "List doesn't exist or belongs to someone else")
+    )
+)]
+#[instrument(name = "Reorder list", skip(pool, body, req, redis_service))]
+pub async fn reorder_list(
+    pool: web::Data<PgPool>,
+    list_id: web::Path<Uuid>,
+    body: web::Json<ReorderListRequest>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user = session_user(req.into_inner(), &redis_service).await?;
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let list = owned_list(&mut conn, list_id.into_inner(), user).await?;
+    reorder(&mut conn, list.id, body.into_inner().game_slugs).await?;
+
+    let games = list_games(&mut conn, list.id).await.map_err(DbError)?;
+    Ok(HttpResponse::Ok().json(GameListResponse { list, games }))
+}
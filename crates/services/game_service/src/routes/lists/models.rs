@@ -0,0 +1,77 @@
+use chrono::NaiveDateTime;
+use errors::CustomError;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::game_lists::{GameList, ListKind, ListVisibility, ListedGame, MAX_LIST_NAME_LENGTH};
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CreateListRequest {
+    pub name: String,
+    /// `Private` by default
+    pub visibility: Option<ListVisibility>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct UpdateListRequest {
+    /// Only custom lists can be renamed
+    pub name: Option<String>,
+    pub visibility: Option<ListVisibility>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct AddListGameRequest {
+    pub game_slug: String,
+    /// Zero based place in the list, the game is appended when missing
+    pub position: Option<i32>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ReorderListRequest {
+    /// Every game of the list, in the new order
+    pub game_slugs: Vec<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct GameListSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub kind: ListKind,
+    pub visibility: ListVisibility,
+    pub game_count: i64,
+    pub updated_at: NaiveDateTime,
+}
+
+impl From<(GameList, i64)> for GameListSummary {
+    fn from((list, game_count): (GameList, i64)) -> Self {
+        GameListSummary {
+            id: list.id,
+            name: list.name,
+            kind: list.kind,
+            visibility: list.visibility,
+            game_count,
+            updated_at: list.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct GameListResponse {
+    #[serde(flatten)]
+    pub list: GameList,
+    pub games: Vec<ListedGame>,
+}
+
+/// Trimmed list name, between 1 and `MAX_LIST_NAME_LENGTH` characters like the column
+pub fn list_name(name: &str) -> Result<String, CustomError> {
+    let name = name.trim();
+    let length = name.chars().count();
+    if length == 0 || length > MAX_LIST_NAME_LENGTH {
+        return Err(CustomError::ValidationError(format!(
+            "List names must be between 1 and {} characters.",
+            MAX_LIST_NAME_LENGTH
+        )));
+    }
+    Ok(name.to_string())
+}
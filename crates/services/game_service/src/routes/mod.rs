@@ -1,2 +1,5 @@
 pub mod health_check;
-pub mod game;
\ No newline at end of file
+pub mod game;
+pub mod admin;
+pub mod lists;
+pub mod openapi;
\ No newline at end of file
@@ -0,0 +1,38 @@
+use actix_web::HttpResponse;
+use helpers::openapi::BearerAuth;
+use utoipa::OpenApi;
+
+use crate::elasticsearch::ElasticsearchGame;
+use crate::routes::game::games;
+use crate::ratings::GameRating;
+use crate::routes::game::model::{EditRatingRequest, FlagReviewRequest, RateGameRequest, Review, ReviewSort, ReviewsResponse};
+use crate::routes::game::reviews;
+use crate::routes::admin::search as admin_search;
+use crate::reindex::{Drift, ReindexReport};
+use crate::routes::game::facets::{self, FacetsResponse, GenreFacet, RatingBucket};
+use crate::routes::game::search::{self, SearchHit, SearchResponse};
+use crate::routes::game::recommendations;
+use crate::recommendations::{RecommendationsResponse, RecommendedGame};
+use crate::game_lists::{GameList, ListKind, ListVisibility, ListedGame};
+use crate::routes::lists::lists;
+use crate::routes::lists::models::{AddListGameRequest, CreateListRequest, GameListResponse, GameListSummary, ReorderListRequest, UpdateListRequest};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Game Service"),
+    paths(games::rate, games::get_game, search::search_games, facets::game_facets, recommendations::game_recommendations, reviews::update_rating, reviews::remove_rating, reviews::get_reviews, reviews::flag_review, admin_search::reindex_games, admin_search::reconcile_index, lists::get_my_lists, lists::get_user_lists, lists::create_list, lists::get_list, lists::update_list, lists::delete_list, lists::add_list_game, lists::remove_list_game, lists::reorder_list),
+    components(schemas(RateGameRequest, ElasticsearchGame, SearchHit, SearchResponse, FacetsResponse, GenreFacet, RatingBucket, RecommendationsResponse, RecommendedGame, GameRating, EditRatingRequest, Review, ReviewSort, ReviewsResponse, FlagReviewRequest, ReindexReport, Drift, GameList, ListKind, ListVisibility, ListedGame, GameListSummary, GameListResponse, CreateListRequest, UpdateListRequest, AddListGameRequest, ReorderListRequest)),
+    modifiers(&BearerAuth)
+)]
+pub struct ApiDoc;
+
+/******************************************/
+// OpenAPI spec route
+/******************************************/
+/**
+ * @route   GET /api-docs/openapi.json
+ * @access  Public
+ */
+pub async fn openapi_spec() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
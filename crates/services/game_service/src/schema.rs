@@ -1,5 +1,46 @@
 // @generated automatically by Diesel CLI.
 
+pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "list_kind"))]
+    pub struct ListKind;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "list_visibility"))]
+    pub struct ListVisibility;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "review_status"))]
+    pub struct ReviewStatus;
+}
+
+diesel::table! {
+    game_list_items (list_id, game_slug) {
+        list_id -> Uuid,
+        #[max_length = 512]
+        game_slug -> Varchar,
+        position -> Int4,
+        added_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ListKind;
+    use super::sql_types::ListVisibility;
+
+    game_lists (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 100]
+        name -> Varchar,
+        kind -> ListKind,
+        visibility -> ListVisibility,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     games (slug) {
         #[max_length = 512]
@@ -14,10 +55,22 @@ diesel::table! {
         is_admin -> Nullable<Bool>,
         #[max_length = 255]
         genre -> Nullable<Varchar>,
+        average_rating -> Float4,
+        rating_count -> Int4,
+        rating_version -> Int8,
+        tags -> Array<Text>,
+        platforms -> Array<Text>,
+        release_date -> Nullable<Date>,
+        #[max_length = 255]
+        publisher -> Nullable<Varchar>,
+        cover_image_url -> Nullable<Text>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ReviewStatus;
+
     rate_game (id) {
         id -> Uuid,
         game_slug -> Varchar,
@@ -25,6 +78,18 @@ diesel::table! {
         rating -> Int4,
         review -> Nullable<Text>,
         created_at -> Timestamp,
+        updated_at -> Nullable<Timestamp>,
+        moderation_status -> ReviewStatus,
+    }
+}
+
+diesel::table! {
+    review_flags (id) {
+        id -> Uuid,
+        review_id -> Uuid,
+        flagged_by -> Uuid,
+        reason -> Nullable<Text>,
+        created_at -> Timestamp,
     }
 }
 
@@ -35,14 +100,23 @@ diesel::table! {
         email -> Varchar,
         created_at -> Nullable<Timestamp>,
         modified_at -> Nullable<Timestamp>,
+        deactivated_at -> Nullable<Timestamp>,
     }
 }
 
+diesel::joinable!(game_list_items -> game_lists (list_id));
+diesel::joinable!(game_list_items -> games (game_slug));
+diesel::joinable!(game_lists -> users (user_id));
 diesel::joinable!(rate_game -> games (game_slug));
 diesel::joinable!(rate_game -> users (user_id));
+diesel::joinable!(review_flags -> rate_game (review_id));
+diesel::joinable!(review_flags -> users (flagged_by));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    game_list_items,
+    game_lists,
     games,
     rate_game,
+    review_flags,
     users,
 );
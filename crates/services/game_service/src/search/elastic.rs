@@ -0,0 +1,540 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use elasticsearch::auth::Credentials;
+use elasticsearch::http::transport::{SingleNodeConnectionPool, TransportBuilder};
+use elasticsearch::http::Url;
+use elasticsearch::indices::{IndicesCreateParts, IndicesDeleteParts, IndicesExistsParts, IndicesGetAliasParts, IndicesRefreshParts};
+use elasticsearch::{BulkOperation, BulkOperations, BulkParts, DeleteParts, Elasticsearch, IndexParts, SearchParts, UpdateParts};
+use lib_config::config::configuration::SearchSettings;
+use serde_json::{json, Value};
+
+use crate::elasticsearch::ElasticsearchGame;
+use crate::ratings::GameRating;
+use crate::reindex::{GameDocument, IndexedGame, ReindexReport};
+use crate::routes::game::facets::{FacetsResponse, GenreFacet, RatingBucket, MAX_GENRE_FACETS, NEWEST_GAMES};
+use crate::routes::game::search::{SearchHit, SearchQuery};
+
+use super::{GameSearch, SearchPage};
+
+const BULK_SIZE: usize = 500;
+const SCAN_PAGE_SIZE: usize = 1000;
+const RATING_INTERVAL: f32 = 1.0;
+
+/// Games indexed in Elasticsearch behind the `index` alias
+pub struct ElasticGameSearch {
+    client: Elasticsearch,
+    index: String,
+}
+
+impl ElasticGameSearch {
+    pub fn new(settings: &SearchSettings) -> Result<Self, anyhow::Error> {
+        let url = Url::parse(&settings.url).context("Invalid search url")?;
+        let mut transport = TransportBuilder::new(SingleNodeConnectionPool::new(url));
+        if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+            transport = transport.auth(Credentials::Basic(username.clone(), password.clone()));
+        }
+
+        Ok(ElasticGameSearch {
+            client: Elasticsearch::new(transport.build().context("Failed to build Elasticsearch transport")?),
+            index: settings.index.clone(),
+        })
+    }
+
+    async fn send_bulk(&self, index: &str, ops: BulkOperations) -> Result<(), anyhow::Error> {
+        let response = self
+            .client
+            .bulk(BulkParts::Index(index))
+            .body(vec![ops])
+            .send()
+            .await?
+            .error_for_status_code()?
+            .json::<Value>()
+            .await?;
+
+        if response.get("errors").and_then(Value::as_bool).unwrap_or(false) {
+            anyhow::bail!("Bulk request to {} had failed items: {}", index, response["items"]);
+        }
+        Ok(())
+    }
+
+    async fn index_documents(&self, index: &str, documents: &[GameDocument]) -> Result<(), anyhow::Error> {
+        for chunk in documents.chunks(BULK_SIZE) {
+            let mut ops = BulkOperations::new();
+            for game in chunk {
+                ops.push(BulkOperation::index(game.source()).id(&game.slug))?;
+            }
+            self.send_bulk(index, ops).await?;
+        }
+        Ok(())
+    }
+
+    async fn search_json(&self, body: Value) -> Result<Value, anyhow::Error> {
+        self.client
+            .search(SearchParts::Index(&[&self.index]))
+            .body(body)
+            .send()
+            .await
+            .context("Failed to get response from elasticsearch")?
+            .error_for_status_code()?
+            .json::<Value>()
+            .await
+            .context("Error parsing response")
+    }
+
+    /// Indices currently behind the alias, and whether a concrete index uses the alias name
+    async fn alias_targets(&self) -> Result<(Vec<String>, bool), anyhow::Error> {
+        let response = self
+            .client
+            .indices()
+            .get_alias(IndicesGetAliasParts::Name(&[&self.index]))
+            .send()
+            .await?;
+
+        if response.status_code().is_success() {
+            let targets = response.json::<HashMap<String, Value>>().await?;
+            return Ok((targets.into_keys().collect(), false));
+        }
+
+        let concrete = self
+            .client
+            .indices()
+            .exists(IndicesExistsParts::Index(&[&self.index]))
+            .send()
+            .await?
+            .status_code()
+            .is_success();
+        Ok((Vec::new(), concrete))
+    }
+
+    /// Points the alias at `index` in one request, removing a concrete index that used the alias name
+    async fn swap_alias(&self, index: &str) -> Result<Vec<String>, anyhow::Error> {
+        let (targets, concrete) = self.alias_targets().await?;
+
+        let mut actions: Vec<Value> = targets
+            .iter()
+            .map(|old| json!({ "remove": { "index": old, "alias": self.index } }))
+            .collect();
+        if concrete {
+            actions.push(json!({ "remove_index": { "index": self.index } }));
+        }
+        actions.push(json!({ "add": { "index": index, "alias": self.index } }));
+
+        self.client
+            .indices()
+            .update_aliases()
+            .body(json!({ "actions": actions }))
+            .send()
+            .await?
+            .error_for_status_code()
+            .context("Failed to swap the games alias")?;
+
+        Ok(targets.into_iter().filter(|old| old != index).collect())
+    }
+}
+
+pub fn versioned_index_name(alias: &str, now: NaiveDateTime) -> String {
+    format!("{}_v{}", alias, now.format("%Y%m%d%H%M%S%3f"))
+}
+
+/// `genres` applies every filter but the genre one, `filtered` applies them all
+fn facets_body(search: &SearchQuery) -> Value {
+    let genre_scope = search.catalog_filters();
+
+    json!({
+        "size": 0,
+        "query": search.text_query(),
+        "aggs": {
+            "genres": {
+                "filter": { "bool": { "filter": genre_scope } },
+                "aggs": {
+                    "values": { "terms": { "field": "genre", "size": MAX_GENRE_FACETS } }
+                }
+            },
+            "filtered": {
+                "filter": { "bool": { "filter": search.filters() } },
+                "aggs": {
+                    "ratings": {
+                        "histogram": {
+                            "field": "average_rating",
+                            "interval": RATING_INTERVAL,
+                            "min_doc_count": 0,
+                            "extended_bounds": { "min": 0, "max": 5 }
+                        }
+                    },
+                    "newest": {
+                        "top_hits": {
+                            "size": NEWEST_GAMES,
+                            "sort": [{ "created_at": { "order": "desc", "unmapped_type": "date" } }]
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+fn buckets(aggregations: &Value, pointer: &str) -> Vec<Value> {
+    aggregations
+        .pointer(pointer)
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn parse_source(hit: &Value) -> Result<ElasticsearchGame, anyhow::Error> {
+    let source = hit
+        .get("_source")
+        .cloned()
+        .context("Failed to fetch \"_source\" field")?;
+    serde_json::from_value::<ElasticsearchGame>(source).context("Failed to deserialize \"_source\"")
+}
+
+fn parse_hit(hit: &Value) -> Result<SearchHit, anyhow::Error> {
+    let highlight = hit
+        .get("highlight")
+        .cloned()
+        .and_then(|highlight| serde_json::from_value(highlight).ok())
+        .unwrap_or_default();
+
+    Ok(SearchHit {
+        game: parse_source(hit)?,
+        score: hit.get("_score").and_then(Value::as_f64),
+        highlight,
+    })
+}
+
+#[async_trait]
+impl GameSearch for ElasticGameSearch {
+    /// Creates a versioned index behind the alias unless the alias or an index of that name exists
+    async fn prepare(&self) -> Result<(), anyhow::Error> {
+        let exists = self
+            .client
+            .indices()
+            .exists(IndicesExistsParts::Index(&[&self.index]))
+            .send()
+            .await?;
+
+        if exists.status_code().is_success() {
+            tracing::info!("Elasticsearch index {} already exists", self.index);
+            return Ok(());
+        }
+
+        let index = versioned_index_name(&self.index, Utc::now().naive_utc());
+        let mut aliases = serde_json::Map::new();
+        aliases.insert(self.index.clone(), json!({}));
+        let mut definition = ElasticsearchGame::index_definition();
+        definition["aliases"] = Value::Object(aliases);
+
+        self.client
+            .indices()
+            .create(IndicesCreateParts::Index(&index))
+            .body(definition)
+            .send()
+            .await?
+            .error_for_status_code()
+            .with_context(|| format!("Failed to create Elasticsearch index {}", index))?;
+
+        tracing::info!("Created Elasticsearch index {} behind alias {}", index, self.index);
+        Ok(())
+    }
+
+    fn is_persistent(&self) -> bool {
+        true
+    }
+
+    async fn index_game(&self, game: &ElasticsearchGame) -> Result<(), anyhow::Error> {
+        self.client
+            .index(IndexParts::IndexId(&self.index, &game.slug))
+            .body(json!({
+                "slug": game.slug,
+                "name": game.name,
+                "title": game.title,
+                "description": game.description,
+                "genre": game.genre,
+                "tags": game.tags,
+                "platforms": game.platforms,
+                "release_date": game.release_date,
+                "publisher": game.publisher,
+                "cover_image_url": game.cover_image_url,
+                "average_rating": 0,
+                "rating_count": 0,
+                "created_at": game.created_at
+            }))
+            .send()
+            .await?
+            .error_for_status_code()?;
+
+        tracing::info!("Indexed game: {} into Elasticsearch", game.slug);
+        Ok(())
+    }
+
+    async fn update_game(&self, game: &ElasticsearchGame) -> Result<(), anyhow::Error> {
+        self.client
+            .update(UpdateParts::IndexId(&self.index, &game.slug))
+            .body(json!({
+                "doc": {
+                    "name": game.name,
+                    "title": game.title,
+                    "description": game.description,
+                    "genre": game.genre,
+                    "tags": game.tags,
+                    "platforms": game.platforms,
+                    "release_date": game.release_date,
+                    "publisher": game.publisher,
+                    "cover_image_url": game.cover_image_url
+                }
+            }))
+            .send()
+            .await?
+            .error_for_status_code()?;
+
+        tracing::info!("Updated game: {} in Elasticsearch", game.slug);
+        Ok(())
+    }
+
+    async fn update_rating(&self, slug: &str, rating: &GameRating) -> Result<(), anyhow::Error> {
+        self.client
+            .update(UpdateParts::IndexId(&self.index, slug))
+            .retry_on_conflict(3)
+            .body(json!({
+                "script": {
+                    "lang": "painless",
+                    "source": "if (ctx._source.rating_version == null || ctx._source.rating_version < params.rating_version) { \
+                               ctx._source.average_rating = params.average_rating; \
+                               ctx._source.rating_count = params.rating_count; \
+                               ctx._source.rating_version = params.rating_version; \
+                               } else { ctx.op = 'noop'; }",
+                    "params": {
+                        "average_rating": rating.average_rating,
+                        "rating_count": rating.rating_count,
+                        "rating_version": rating.rating_version
+                    }
+                }
+            }))
+            .send()
+            .await?
+            .error_for_status_code()?;
+
+        tracing::info!("Updated rating of game: {} to version {} in Elasticsearch", slug, rating.rating_version);
+        Ok(())
+    }
+
+    async fn delete_game(&self, slug: &str) -> Result<(), anyhow::Error> {
+        self.client
+            .delete(DeleteParts::IndexId(&self.index, slug))
+            .send()
+            .await?
+            .error_for_status_code()?;
+
+        tracing::info!("Deleted game: {} from Elasticsearch", slug);
+        Ok(())
+    }
+
+    async fn top_rated(&self, from: i64, size: i64) -> Result<Vec<ElasticsearchGame>, anyhow::Error> {
+        let response = self
+            .search_json(json!({
+                "sort": [{ "average_rating": { "order": "desc", "unmapped_type": "float" } }],
+                "from": from,
+                "size": size
+            }))
+            .await?;
+
+        response
+            .pointer("/hits/hits")
+            .and_then(Value::as_array)
+            .context("Failed to fetch hits field")?
+            .iter()
+            .map(parse_source)
+            .collect()
+    }
+
+    async fn search(&self, query: &SearchQuery, from: i64, size: i64) -> Result<SearchPage, anyhow::Error> {
+        let sort = match query.text() {
+            Some(_) => json!(["_score", { "average_rating": { "order": "desc", "unmapped_type": "float" } }]),
+            None => json!([{ "average_rating": { "order": "desc", "unmapped_type": "float" } }]),
+        };
+
+        let response = self
+            .search_json(json!({
+                "query": query.query(),
+                "sort": sort,
+                "track_total_hits": true,
+                "from": from,
+                "size": size,
+                "highlight": {
+                    "pre_tags": ["<em>"],
+                    "post_tags": ["</em>"],
+                    "fields": {
+                        "name": {},
+                        "title": {},
+                        "description": { "fragment_size": 150, "number_of_fragments": 3 }
+                    }
+                }
+            }))
+            .await?;
+
+        let total = response
+            .pointer("/hits/total/value")
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+        let hits = response
+            .pointer("/hits/hits")
+            .and_then(Value::as_array)
+            .context("Failed to fetch hits field")?
+            .iter()
+            .map(parse_hit)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(SearchPage { total, hits })
+    }
+
+    async fn facets(&self, query: &SearchQuery) -> Result<FacetsResponse, anyhow::Error> {
+        let response = self.search_json(facets_body(query)).await?;
+        let aggregations = response
+            .get("aggregations")
+            .context("Failed to fetch aggregations field")?;
+
+        let genres = buckets(aggregations, "/genres/values/buckets")
+            .iter()
+            .filter_map(|bucket| {
+                Some(GenreFacet {
+                    genre: bucket.get("key")?.as_str()?.to_string(),
+                    count: bucket.get("doc_count")?.as_u64()?,
+                })
+            })
+            .collect();
+
+        let rating_histogram = buckets(aggregations, "/filtered/ratings/buckets")
+            .iter()
+            .filter_map(|bucket| {
+                Some(RatingBucket {
+                    rating: bucket.get("key")?.as_f64()? as f32,
+                    count: bucket.get("doc_count")?.as_u64()?,
+                })
+            })
+            .collect();
+
+        let newest = buckets(aggregations, "/filtered/newest/hits/hits")
+            .iter()
+            .map(parse_source)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let total = aggregations
+            .pointer("/filtered/doc_count")
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+
+        Ok(FacetsResponse {
+            total,
+            genres,
+            rating_histogram,
+            newest,
+        })
+    }
+
+    async fn indexed_games(&self) -> Result<Vec<IndexedGame>, anyhow::Error> {
+        let mut games = Vec::new();
+        let mut search_after: Option<Value> = None;
+
+        loop {
+            let mut body = json!({
+                "size": SCAN_PAGE_SIZE,
+                "_source": [
+                    "slug", "name", "title", "description", "genre", "tags", "platforms",
+                    "release_date", "publisher", "cover_image_url", "rating_version"
+                ],
+                "sort": [{ "slug": "asc" }],
+                "query": { "match_all": {} }
+            });
+            if let Some(after) = &search_after {
+                body["search_after"] = after.clone();
+            }
+
+            let response = self.search_json(body).await?;
+            let hits = response
+                .pointer("/hits/hits")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+
+            for hit in &hits {
+                let source = hit.get("_source").cloned().unwrap_or_default();
+                games.push(serde_json::from_value::<IndexedGame>(source).context("Failed to deserialize indexed game")?);
+            }
+
+            match hits.last().and_then(|hit| hit.get("sort")) {
+                Some(sort) if hits.len() == SCAN_PAGE_SIZE => search_after = Some(sort.clone()),
+                _ => break,
+            }
+        }
+
+        Ok(games)
+    }
+
+    async fn write_documents(&self, upserts: &[GameDocument], deletes: &[String]) -> Result<(), anyhow::Error> {
+        self.index_documents(&self.index, upserts).await?;
+
+        for chunk in deletes.chunks(BULK_SIZE) {
+            let mut ops = BulkOperations::new();
+            for slug in chunk {
+                ops.push(BulkOperation::<Value>::delete(slug))?;
+            }
+            self.send_bulk(&self.index, ops).await?;
+        }
+        Ok(())
+    }
+
+    /// Builds a new versioned index and atomically points the alias at it, the replaced indices are deleted
+    async fn rebuild(&self, documents: &[GameDocument]) -> Result<ReindexReport, anyhow::Error> {
+        let index = versioned_index_name(&self.index, Utc::now().naive_utc());
+
+        self.client
+            .indices()
+            .create(IndicesCreateParts::Index(&index))
+            .body(ElasticsearchGame::index_definition())
+            .send()
+            .await?
+            .error_for_status_code()
+            .with_context(|| format!("Failed to create index {}", index))?;
+
+        self.index_documents(&index, documents).await?;
+
+        self.client
+            .indices()
+            .refresh(IndicesRefreshParts::Index(&[&index]))
+            .send()
+            .await?
+            .error_for_status_code()?;
+
+        let replaced = self.swap_alias(&index).await?;
+
+        for old in &replaced {
+            if let Err(e) = self.client.indices().delete(IndicesDeleteParts::Index(&[old])).send().await {
+                tracing::error!("Failed to delete replaced index {}: {:?}", old, e);
+            }
+        }
+
+        Ok(ReindexReport {
+            index,
+            documents: documents.len(),
+            replaced,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::versioned_index_name;
+
+    #[test]
+    fn versioned_indices_sort_by_creation_time() {
+        let earlier = chrono::NaiveDate::from_ymd_opt(2026, 1, 2).unwrap().and_hms_opt(3, 4, 5).unwrap();
+        let later = earlier + chrono::Duration::seconds(1);
+
+        assert_eq!(versioned_index_name("rate", earlier), "rate_v20260102030405000");
+        assert!(versioned_index_name("rate", earlier) < versioned_index_name("rate", later));
+    }
+}
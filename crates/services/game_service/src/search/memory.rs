@@ -0,0 +1,500 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+
+use crate::elasticsearch::ElasticsearchGame;
+use crate::ratings::GameRating;
+use crate::reindex::{GameDocument, IndexedGame, ReindexReport};
+use crate::routes::game::facets::{FacetsResponse, GenreFacet, RatingBucket, MAX_GENRE_FACETS, NEWEST_GAMES};
+use crate::routes::game::search::{SearchHit, SearchQuery};
+
+use super::{GameSearch, SearchPage};
+
+/// Field weights of the Elasticsearch query, `name` also matches on word prefixes
+const NAME_WEIGHT: f64 = 3.0;
+const TITLE_WEIGHT: f64 = 2.0;
+const TEXT_WEIGHT: f64 = 1.0;
+
+struct StoredGame {
+    game: ElasticsearchGame,
+    rating_version: Option<i64>,
+}
+
+/// Games kept in a map inside game_service, searched with the same fields, fuzziness and filters as Elasticsearch
+#[derive(Default)]
+pub struct MemoryGameSearch {
+    games: RwLock<HashMap<String, StoredGame>>,
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Allowed typos per term like Elasticsearch `AUTO` fuzziness
+fn fuzziness(term: &str) -> usize {
+    match term.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Whether `term` matches `word`, the first letter has to be right like with `prefix_length: 1`
+fn term_matches(term: &str, word: &str, prefix: bool) -> bool {
+    if word == term || (prefix && term.chars().count() >= 2 && word.starts_with(term)) {
+        return true;
+    }
+    term.chars().next() == word.chars().next() && edit_distance(term, word) <= fuzziness(term)
+}
+
+fn field_matches(terms: &[String], text: Option<&str>, prefix: bool) -> bool {
+    let Some(text) = text else { return false };
+    let words = words(text);
+    terms.iter().any(|term| words.iter().any(|word| term_matches(term, word, prefix)))
+}
+
+/// Sum over query terms of the best weighted field each term matches, `None` when nothing matches
+fn score(game: &ElasticsearchGame, terms: &[String]) -> Option<f64> {
+    let tags = game.tags.join(" ");
+    let fields = [
+        (Some(game.name.as_str()), NAME_WEIGHT, true),
+        (game.title.as_deref(), TITLE_WEIGHT, false),
+        (game.description.as_deref(), TEXT_WEIGHT, false),
+        (game.genre.as_deref(), TEXT_WEIGHT, false),
+        (Some(tags.as_str()), TEXT_WEIGHT, false),
+        (game.publisher.as_deref(), TEXT_WEIGHT, false),
+    ];
+
+    let total: f64 = terms
+        .iter()
+        .map(|term| {
+            fields
+                .iter()
+                .filter(|(text, _, prefix)| field_matches(std::slice::from_ref(term), *text, *prefix))
+                .map(|(_, weight, _)| *weight)
+                .fold(0.0, f64::max)
+        })
+        .sum();
+    (total > 0.0).then_some(total)
+}
+
+/// The field with every matched word wrapped in `<em>` tags
+fn highlight(terms: &[String], text: &str, prefix: bool) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut word = String::new();
+    let mut matched = false;
+
+    let mut flush = |word: &mut String, out: &mut String| {
+        if word.is_empty() {
+            return;
+        }
+        let lower = word.to_lowercase();
+        if terms.iter().any(|term| term_matches(term, &lower, prefix)) {
+            out.push_str("<em>");
+            out.push_str(word);
+            out.push_str("</em>");
+            matched = true;
+        } else {
+            out.push_str(word);
+        }
+        word.clear();
+    };
+
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut word, &mut out);
+
+    matched.then_some(out)
+}
+
+fn highlights(game: &ElasticsearchGame, terms: &[String]) -> HashMap<String, Vec<String>> {
+    [
+        ("name", Some(game.name.as_str()), true),
+        ("title", game.title.as_deref(), false),
+        ("description", game.description.as_deref(), false),
+    ]
+    .into_iter()
+    .filter_map(|(field, text, prefix)| Some((field.to_string(), vec![highlight(terms, text?, prefix)?])))
+    .collect()
+}
+
+fn average(game: &ElasticsearchGame) -> f32 {
+    game.average_rating.unwrap_or(0.0)
+}
+
+fn in_genres(game: &ElasticsearchGame, genres: &[String]) -> bool {
+    genres.is_empty()
+        || game
+            .genre
+            .as_deref()
+            .is_some_and(|genre| genres.contains(&genre.to_lowercase()))
+}
+
+fn in_rating_range(game: &ElasticsearchGame, query: &SearchQuery) -> bool {
+    let rating = average(game);
+    query.min_rating.map_or(true, |min| rating >= min) && query.max_rating.map_or(true, |max| rating <= max)
+}
+
+fn in_release_range(game: &ElasticsearchGame, query: &SearchQuery) -> bool {
+    if query.released_after.is_none() && query.released_before.is_none() {
+        return true;
+    }
+    game.release_date.is_some_and(|date| {
+        query.released_after.map_or(true, |after| date >= after) && query.released_before.map_or(true, |before| date <= before)
+    })
+}
+
+fn has_label(labels: &[String], wanted: &str) -> bool {
+    labels.iter().any(|label| label.to_lowercase() == wanted)
+}
+
+/// Every filter but the genre one, like `SearchQuery::catalog_filters`
+fn in_catalog(game: &ElasticsearchGame, query: &SearchQuery) -> bool {
+    let platforms = query.platform_list();
+    let publisher = query.publisher_name();
+
+    query.tag_list().iter().all(|tag| has_label(&game.tags, tag))
+        && (platforms.is_empty() || platforms.iter().any(|platform| has_label(&game.platforms, platform)))
+        && publisher.map_or(true, |publisher| {
+            game.publisher.as_deref().is_some_and(|name| name.to_lowercase() == publisher)
+        })
+        && in_release_range(game, query)
+        && in_rating_range(game, query)
+}
+
+fn by_rating(a: &ElasticsearchGame, b: &ElasticsearchGame) -> Ordering {
+    average(b)
+        .partial_cmp(&average(a))
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| a.slug.cmp(&b.slug))
+}
+
+fn from_document(document: &GameDocument) -> StoredGame {
+    StoredGame {
+        game: document.game(),
+        rating_version: Some(document.rating_version),
+    }
+}
+
+impl MemoryGameSearch {
+    fn read(&self) -> Result<std::sync::RwLockReadGuard<'_, HashMap<String, StoredGame>>, anyhow::Error> {
+        self.games.read().map_err(|_| anyhow::anyhow!("Search index lock poisoned"))
+    }
+
+    fn write(&self) -> Result<std::sync::RwLockWriteGuard<'_, HashMap<String, StoredGame>>, anyhow::Error> {
+        self.games.write().map_err(|_| anyhow::anyhow!("Search index lock poisoned"))
+    }
+
+    /// Games matching the text of the query with their score, every game when there's no text
+    fn matching(&self, query: &SearchQuery) -> Result<Vec<(ElasticsearchGame, Option<f64>)>, anyhow::Error> {
+        let terms = query.text().map(words).unwrap_or_default();
+        let games = self.read()?;
+
+        Ok(games
+            .values()
+            .filter_map(|stored| match terms.is_empty() {
+                true => Some((stored.game.clone(), None)),
+                false => score(&stored.game, &terms).map(|score| (stored.game.clone(), Some(score))),
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl GameSearch for MemoryGameSearch {
+    async fn prepare(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    fn is_persistent(&self) -> bool {
+        false
+    }
+
+    async fn index_game(&self, game: &ElasticsearchGame) -> Result<(), anyhow::Error> {
+        let mut game = game.clone();
+        game.average_rating = Some(0.0);
+        game.rating_count = Some(0);
+        self.write()?.insert(game.slug.clone(), StoredGame { game, rating_version: None });
+        Ok(())
+    }
+
+    async fn update_game(&self, game: &ElasticsearchGame) -> Result<(), anyhow::Error> {
+        let mut games = self.write()?;
+        let stored = games
+            .get_mut(&game.slug)
+            .ok_or_else(|| anyhow::anyhow!("Game {} is not indexed", game.slug))?;
+
+        stored.game.name = game.name.clone();
+        stored.game.title = game.title.clone();
+        stored.game.description = game.description.clone();
+        stored.game.genre = game.genre.clone();
+        stored.game.tags = game.tags.clone();
+        stored.game.platforms = game.platforms.clone();
+        stored.game.release_date = game.release_date;
+        stored.game.publisher = game.publisher.clone();
+        stored.game.cover_image_url = game.cover_image_url.clone();
+        Ok(())
+    }
+
+    async fn update_rating(&self, slug: &str, rating: &GameRating) -> Result<(), anyhow::Error> {
+        let mut games = self.write()?;
+        let stored = games
+            .get_mut(slug)
+            .ok_or_else(|| anyhow::anyhow!("Game {} is not indexed", slug))?;
+
+        if stored.rating_version.map_or(true, |version| version < rating.rating_version) {
+            stored.game.average_rating = Some(rating.average_rating);
+            stored.game.rating_count = Some(rating.rating_count);
+            stored.rating_version = Some(rating.rating_version);
+        }
+        Ok(())
+    }
+
+    async fn delete_game(&self, slug: &str) -> Result<(), anyhow::Error> {
+        self.write()?.remove(slug);
+        Ok(())
+    }
+
+    async fn top_rated(&self, from: i64, size: i64) -> Result<Vec<ElasticsearchGame>, anyhow::Error> {
+        let mut games: Vec<ElasticsearchGame> = self.read()?.values().map(|stored| stored.game.clone()).collect();
+        games.sort_by(by_rating);
+        Ok(games.into_iter().skip(from as usize).take(size as usize).collect())
+    }
+
+    async fn search(&self, query: &SearchQuery, from: i64, size: i64) -> Result<SearchPage, anyhow::Error> {
+        let genres = query.genres();
+        let terms = query.text().map(words).unwrap_or_default();
+
+        let mut matches: Vec<(ElasticsearchGame, Option<f64>)> = self
+            .matching(query)?
+            .into_iter()
+            .filter(|(game, _)| in_genres(game, &genres) && in_catalog(game, query))
+            .collect();
+        matches.sort_by(|(a, a_score), (b, b_score)| {
+            b_score
+                .partial_cmp(a_score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| by_rating(a, b))
+        });
+
+        let total = matches.len() as u64;
+        let hits = matches
+            .into_iter()
+            .skip(from as usize)
+            .take(size as usize)
+            .map(|(game, score)| SearchHit {
+                highlight: highlights(&game, &terms),
+                game,
+                score,
+            })
+            .collect();
+
+        Ok(SearchPage { total, hits })
+    }
+
+    async fn facets(&self, query: &SearchQuery) -> Result<FacetsResponse, anyhow::Error> {
+        let genres = query.genres();
+        let matches: Vec<ElasticsearchGame> = self
+            .matching(query)?
+            .into_iter()
+            .map(|(game, _)| game)
+            .filter(|game| in_catalog(game, query))
+            .collect();
+
+        let mut genre_counts: BTreeMap<String, u64> = BTreeMap::new();
+        for genre in matches.iter().filter_map(|game| game.genre.as_deref()) {
+            *genre_counts.entry(genre.to_lowercase()).or_default() += 1;
+        }
+        let mut genre_facets: Vec<GenreFacet> = genre_counts
+            .into_iter()
+            .map(|(genre, count)| GenreFacet { genre, count })
+            .collect();
+        genre_facets.sort_by(|a, b| b.count.cmp(&a.count));
+        genre_facets.truncate(MAX_GENRE_FACETS);
+
+        let mut filtered: Vec<ElasticsearchGame> = matches.into_iter().filter(|game| in_genres(game, &genres)).collect();
+
+        let mut histogram = [0u64; 6];
+        for game in &filtered {
+            histogram[(average(game).floor() as usize).min(5)] += 1;
+        }
+
+        let total = filtered.len() as u64;
+        filtered.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        filtered.truncate(NEWEST_GAMES);
+
+        Ok(FacetsResponse {
+            total,
+            genres: genre_facets,
+            rating_histogram: histogram
+                .iter()
+                .enumerate()
+                .map(|(rating, count)| RatingBucket { rating: rating as f32, count: *count })
+                .collect(),
+            newest: filtered,
+        })
+    }
+
+    async fn indexed_games(&self) -> Result<Vec<IndexedGame>, anyhow::Error> {
+        Ok(self
+            .read()?
+            .values()
+            .map(|stored| IndexedGame {
+                slug: stored.game.slug.clone(),
+                name: stored.game.name.clone(),
+                title: stored.game.title.clone(),
+                description: stored.game.description.clone(),
+                genre: stored.game.genre.clone(),
+                tags: stored.game.tags.clone(),
+                platforms: stored.game.platforms.clone(),
+                release_date: stored.game.release_date,
+                publisher: stored.game.publisher.clone(),
+                cover_image_url: stored.game.cover_image_url.clone(),
+                rating_version: stored.rating_version,
+            })
+            .collect())
+    }
+
+    async fn write_documents(&self, upserts: &[GameDocument], deletes: &[String]) -> Result<(), anyhow::Error> {
+        let mut games = self.write()?;
+        for document in upserts {
+            games.insert(document.slug.clone(), from_document(document));
+        }
+        for slug in deletes {
+            games.remove(slug);
+        }
+        Ok(())
+    }
+
+    async fn rebuild(&self, documents: &[GameDocument]) -> Result<ReindexReport, anyhow::Error> {
+        let mut games = self.write()?;
+        *games = documents
+            .iter()
+            .map(|document| (document.slug.clone(), from_document(document)))
+            .collect();
+
+        Ok(ReindexReport {
+            index: "memory".to_string(),
+            documents: documents.len(),
+            replaced: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryGameSearch;
+    use crate::ratings::GameRating;
+    use crate::reindex::GameDocument;
+    use crate::routes::game::search::SearchQuery;
+    use crate::search::GameSearch;
+
+    fn document(slug: &str, name: &str, genre: &str, average_rating: f32) -> GameDocument {
+        GameDocument {
+            slug: slug.into(),
+            name: name.into(),
+            title: None,
+            description: Some(format!("{} is a {} game", name, genre)),
+            genre: Some(genre.into()),
+            created_at: None,
+            average_rating,
+            rating_count: 1,
+            rating_version: 1,
+            tags: vec![genre.to_lowercase(), "singleplayer".into()],
+            platforms: vec!["pc".into()],
+            release_date: None,
+            publisher: None,
+            cover_image_url: None,
+        }
+    }
+
+    fn query(q: Option<&str>, genre: Option<&str>) -> SearchQuery {
+        SearchQuery {
+            q: q.map(str::to_string),
+            genre: genre.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    async fn search() -> MemoryGameSearch {
+        let search = MemoryGameSearch::default();
+        search
+            .rebuild(&[
+                document("witcher", "The Witcher", "RPG", 4.5),
+                document("stardew", "Stardew Valley", "Simulation", 4.8),
+                document("skyrim", "Skyrim", "RPG", 4.0),
+            ])
+            .await
+            .unwrap();
+        search
+    }
+
+    #[tokio::test]
+    async fn search_is_fuzzy_filtered_and_highlighted() {
+        let search = search().await;
+
+        let page = search.search(&query(Some("witchr"), None), 0, 10).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.hits[0].highlight["name"], vec!["The <em>Witcher</em>".to_string()]);
+
+        let page = search.search(&query(None, Some("rpg")), 0, 10).await.unwrap();
+        let slugs: Vec<&str> = page.hits.iter().map(|hit| hit.game.slug.as_str()).collect();
+        assert_eq!(slugs, vec!["witcher", "skyrim"]);
+
+        let facets = search.facets(&query(None, Some("rpg"))).await.unwrap();
+        assert_eq!(facets.total, 2);
+        assert_eq!(facets.genres[0].genre, "rpg");
+        assert_eq!(facets.genres.len(), 2);
+
+        let mut tagged = query(None, None);
+        tagged.tags = Some("Singleplayer,simulation".into());
+        tagged.platforms = Some("switch, PC".into());
+        let page = search.search(&tagged, 0, 10).await.unwrap();
+        assert_eq!(page.hits.len(), 1);
+        assert_eq!(page.hits[0].game.slug, "stardew");
+    }
+
+    #[tokio::test]
+    async fn older_rating_versions_are_ignored() {
+        let search = search().await;
+        let rating = |average_rating, rating_version| GameRating {
+            average_rating,
+            rating_count: 2,
+            rating_version,
+        };
+
+        search.update_rating("skyrim", &rating(5.0, 3)).await.unwrap();
+        search.update_rating("skyrim", &rating(1.0, 2)).await.unwrap();
+
+        let top = search.top_rated(0, 1).await.unwrap();
+        assert_eq!(top[0].slug, "skyrim");
+        assert_eq!(top[0].average_rating, Some(5.0));
+        assert!(search.update_rating("missing", &rating(1.0, 1)).await.is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use lib_config::config::configuration::{SearchBackend, SearchSettings};
+
+use crate::elasticsearch::ElasticsearchGame;
+use crate::ratings::GameRating;
+use crate::reindex::{GameDocument, IndexedGame, ReindexReport};
+use crate::routes::game::facets::FacetsResponse;
+use crate::routes::game::search::{SearchHit, SearchQuery};
+
+pub mod elastic;
+pub mod memory;
+
+use elastic::ElasticGameSearch;
+use memory::MemoryGameSearch;
+
+/// One page of search hits, `total` counts every match
+pub struct SearchPage {
+    pub total: u64,
+    pub hits: Vec<SearchHit>,
+}
+
+/// Where games are indexed and searched, Postgres stays the source of truth
+#[async_trait]
+pub trait GameSearch: Send + Sync {
+    /// Creates the index when it doesn't exist yet
+    async fn prepare(&self) -> Result<(), anyhow::Error>;
+
+    /// Whether the index survives a restart, otherwise it is rebuilt from Postgres at startup
+    fn is_persistent(&self) -> bool;
+
+    async fn index_game(&self, game: &ElasticsearchGame) -> Result<(), anyhow::Error>;
+
+    /// Replaces the catalog fields of the game, ratings are left alone
+    async fn update_game(&self, game: &ElasticsearchGame) -> Result<(), anyhow::Error>;
+
+    /// Applies aggregates computed in Postgres unless the index already holds a newer `rating_version`
+    async fn update_rating(&self, slug: &str, rating: &GameRating) -> Result<(), anyhow::Error>;
+
+    async fn delete_game(&self, slug: &str) -> Result<(), anyhow::Error>;
+
+    /// Games sorted by average rating, best first
+    async fn top_rated(&self, from: i64, size: i64) -> Result<Vec<ElasticsearchGame>, anyhow::Error>;
+
+    async fn search(&self, query: &SearchQuery, from: i64, size: i64) -> Result<SearchPage, anyhow::Error>;
+
+    async fn facets(&self, query: &SearchQuery) -> Result<FacetsResponse, anyhow::Error>;
+
+    /// Every indexed game, for the drift check
+    async fn indexed_games(&self) -> Result<Vec<IndexedGame>, anyhow::Error>;
+
+    /// Writes whole documents and removes the `deletes` slugs
+    async fn write_documents(&self, upserts: &[GameDocument], deletes: &[String]) -> Result<(), anyhow::Error>;
+
+    /// Replaces the whole index with `documents`
+    async fn rebuild(&self, documents: &[GameDocument]) -> Result<ReindexReport, anyhow::Error>;
+}
+
+pub fn init_search(settings: &SearchSettings) -> Result<Arc<dyn GameSearch>, anyhow::Error> {
+    match settings.backend {
+        SearchBackend::Elasticsearch => Ok(Arc::new(ElasticGameSearch::new(settings)?)),
+        SearchBackend::Memory => Ok(Arc::new(MemoryGameSearch::default())),
+    }
+}
@@ -1,37 +1,34 @@
 use kafka::{channel::KafkaMessage, setup::{setup_kafka_sender, setup_kafka_receiver}};
 use lib_config::{config::configuration::Settings, db::db::PgPool};
-use crate::routes::{game::games::get_game, health_check::health_check};
+use crate::routes::{game::games::get_game, health_check::health_check, openapi::openapi_spec};
 use actix_web::cookie::Key;
 use actix_web::{dev::Server, web, App, HttpServer};
 use actix_web_lab::middleware::from_fn;
 use middleware::jwt::{jwt_auth_middleware, RoleRestrictor};
 use std::net::TcpListener;
+use std::time::Duration;
 use tracing_actix_web::TracingLogger;
+use utils::telemetry::RequestIdRootSpanBuilder;
 
 use lib_config::session::redis::RedisService;
 use crate::kafka_handler::process_kafka_game_message;
 use flume::Sender;
 use crate::routes::game::games::rate;
-use elasticsearch::{Elasticsearch};
-use elasticsearch::http::transport::Transport;
-use std::error::Error;
-use reqwest::ClientBuilder;
+use crate::routes::game::search::search_games;
+use crate::routes::game::facets::game_facets;
+use crate::routes::game::recommendations::game_recommendations;
+use crate::routes::lists::lists::{
+    add_list_game, create_list, delete_list, get_list, get_my_lists, get_user_lists, remove_list_game, reorder_list,
+    update_list,
+};
+use crate::routes::game::reviews::{flag_review, get_reviews, remove_rating, update_rating};
+use crate::moderation::WordFilter;
+use crate::reindex::{reindex, run_drift_check};
+use crate::routes::admin::search::{reconcile_index, reindex_games};
+use crate::search::{init_search, GameSearch};
+use std::sync::Arc;
 use helpers::auth_jwt::auth::Role;
 
-/******************************************/
-// Initializing Elastic client
-/******************************************/
-pub fn init_elasticsearch() -> Result<Elasticsearch, Box<dyn Error>> {
-    // let client = ClientBuilder::new()
-    // .danger_accept_invalid_certs(true)
-    // .build()?;
-    let transport = Transport::single_node("http://127.0.0.1:9200")?;
-
-    let es_client = Elasticsearch::new(transport);
-    Ok(es_client)
-}
-
-
 /**************************************************************/
 // Application State re reuse the same code in main and tests
 /***************************************************************/
@@ -54,18 +51,34 @@ impl Application {
         let tx = setup_kafka_sender(&config.kafka.game_url, &config.kafka.game_topics).await;
         let rx = setup_kafka_receiver(&config.kafka.game_url, &config.kafka.game_subscribe_topics, &config.kafka.game_consumer_group).await;
 
-        let elastic_client = init_elasticsearch().map_err(|e| {
-            eprintln!("Failed to create elastic search client: {:?}", e);
-            std::io::Error::new(std::io::ErrorKind::Other, "Redis connection failed")
+        let search_backend = init_search(&config.search).map_err(|e| {
+            eprintln!("Failed to create search backend: {:?}", e);
+            std::io::Error::new(std::io::ErrorKind::Other, "Search backend setup failed")
         })?;
 
+        if let Err(e) = search_backend.prepare().await {
+            tracing::error!("Failed to prepare the search index: {:?}", e);
+        }
+        if !search_backend.is_persistent() {
+            if let Err(e) = reindex(search_backend.as_ref(), &pool).await {
+                tracing::error!("Failed to fill the search index from Postgres: {:?}", e);
+            }
+        }
+
+        if config.search.drift_check_interval_secs > 0 {
+            let interval = Duration::from_secs(config.search.drift_check_interval_secs);
+            tokio::spawn(run_drift_check(search_backend.clone(), pool.clone(), interval));
+        }
+
         let pool_clone = pool.clone();
-        let es_clone= elastic_client.clone();
+        let search_clone = search_backend.clone();
+        let tx_clone = tx.clone();
         tokio::spawn(async move {
-            process_kafka_game_message(rx, pool_clone, es_clone).await;
+            process_kafka_game_message(rx, pool_clone, search_clone, tx_clone).await;
         });
 
-        let server = run_server(listener, pool.clone(), config.redis.uri.clone(), tx, elastic_client).await?;
+        let word_filter = WordFilter::new(&config.moderation);
+        let server = run_server(listener, pool.clone(), config.redis.uri.clone(), tx, search_backend, word_filter).await?;
 
         Ok(Self {
             port: actual_port,
@@ -88,30 +101,63 @@ pub async fn run_server(
     pool: PgPool,
     redis_uri: String,
     kafka_sender: Sender<KafkaMessage<String>>,
-    es_client: Elasticsearch
+    search_backend: Arc<dyn GameSearch>,
+    word_filter: WordFilter,
 ) -> Result<Server, std::io::Error> {
 
     let redis_service = RedisService::new(redis_uri).await;
 
     let server = HttpServer::new(move || {
         App::new()
-            .wrap(TracingLogger::default())
+            .wrap(TracingLogger::<RequestIdRootSpanBuilder>::new())
             .app_data(web::Data::new(redis_service.clone()))
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(kafka_sender.clone()))
-            .app_data(web::Data::new(es_client.clone()))
+            .app_data(web::Data::from(search_backend.clone()))
+            .app_data(web::Data::new(word_filter.clone()))
             .route("/health_check", web::get().to(health_check))
+            .route("/api-docs/openapi.json", web::get().to(openapi_spec))
+            .service(
+                web::scope("/api/v1/admin")
+                    .wrap(from_fn(jwt_auth_middleware::<AdminRoleRestrictor>))
+                    .route("/search/reindex", web::post().to(reindex_games))
+                    .route("/search/reconcile", web::post().to(reconcile_index))
+            )
             .service(
                 web::scope("/api/v1")
                     .wrap(from_fn(jwt_auth_middleware::<UserRoleRestrictor>))
                     .route("/rate", web::post().to(rate))
                     .route("/", web::get().to(get_game))
+                    .route("/games/search", web::get().to(search_games))
+                    .route("/games/facets", web::get().to(game_facets))
+                    .route("/games/recommendations", web::get().to(game_recommendations))
+                    .route("/games/{slug}/reviews", web::get().to(get_reviews))
+                    .route("/ratings/{slug}", web::put().to(update_rating))
+                    .route("/ratings/{slug}", web::delete().to(remove_rating))
+                    .route("/reviews/{review_id}/flag", web::post().to(flag_review))
+                    .route("/lists", web::get().to(get_my_lists))
+                    .route("/lists", web::post().to(create_list))
+                    .route("/lists/{list_id}", web::get().to(get_list))
+                    .route("/lists/{list_id}", web::patch().to(update_list))
+                    .route("/lists/{list_id}", web::delete().to(delete_list))
+                    .route("/lists/{list_id}/games", web::post().to(add_list_game))
+                    .route("/lists/{list_id}/games", web::put().to(reorder_list))
+                    .route("/lists/{list_id}/games/{slug}", web::delete().to(remove_list_game))
+                    .route("/users/{user_id}/lists", web::get().to(get_user_lists))
             )       
     })
     .listen(listener)?
     .run();
     Ok(server)
 }
+struct AdminRoleRestrictor();
+
+impl RoleRestrictor for AdminRoleRestrictor {
+    fn role_allowed() -> helpers::auth_jwt::auth::Role {
+        Role::Admin
+    }
+}
+
 struct UserRoleRestrictor();
 
 impl RoleRestrictor for UserRoleRestrictor {
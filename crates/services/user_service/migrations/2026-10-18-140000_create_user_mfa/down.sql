@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE user_recovery_codes;
+DROP TABLE user_mfa;
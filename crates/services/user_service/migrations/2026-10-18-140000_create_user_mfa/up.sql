@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE user_mfa (
+    user_id uuid PRIMARY KEY CONSTRAINT fk_mfa_user REFERENCES users (id) ON DELETE CASCADE,
+    secret VARCHAR(64) NOT NULL,
+    -- NULL until the first code is verified
+    enabled_at TIMESTAMP,
+    last_used_step BIGINT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE user_recovery_codes (
+    user_id uuid NOT NULL CONSTRAINT fk_recovery_code_user REFERENCES users (id) ON DELETE CASCADE,
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMP,
+    CONSTRAINT unique_recovery_code PRIMARY KEY (user_id, code_hash)
+);
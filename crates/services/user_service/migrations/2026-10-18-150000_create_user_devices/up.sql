@@ -0,0 +1,10 @@
+-- Your SQL goes here
+-- Devices and networks the user has signed in from, a sign-in from a new one is emailed
+CREATE TABLE user_devices (
+    user_id uuid NOT NULL CONSTRAINT fk_device_user REFERENCES users (id) ON DELETE CASCADE,
+    user_agent VARCHAR(255) NOT NULL,
+    network VARCHAR(64) NOT NULL,
+    first_seen_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT unique_user_device PRIMARY KEY (user_id, user_agent, network)
+);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE user_identities;
+
+-- Accounts without a password can't log in anymore
+DELETE FROM users WHERE password_hash IS NULL;
+ALTER TABLE users ALTER COLUMN password_hash SET NOT NULL;
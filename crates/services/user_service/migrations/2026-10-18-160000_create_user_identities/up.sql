@@ -0,0 +1,15 @@
+-- Your SQL goes here
+-- Accounts created through an identity provider have no password
+ALTER TABLE users ALTER COLUMN password_hash DROP NOT NULL;
+
+CREATE TABLE user_identities (
+    provider VARCHAR(64) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    user_id uuid NOT NULL CONSTRAINT fk_identity_user REFERENCES users (id) ON DELETE CASCADE,
+    email VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_login_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT unique_identity PRIMARY KEY (provider, subject)
+);
+
+CREATE INDEX idx_user_identities_user ON user_identities (user_id);
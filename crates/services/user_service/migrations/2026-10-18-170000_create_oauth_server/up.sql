@@ -0,0 +1,35 @@
+-- Your SQL goes here
+CREATE TABLE oauth_clients (
+    client_id VARCHAR(64) PRIMARY KEY,
+    -- NULL for public clients, they must use PKCE
+    client_secret_hash VARCHAR(64),
+    name VARCHAR(100) NOT NULL,
+    redirect_uris TEXT[] NOT NULL DEFAULT '{}',
+    scopes TEXT[] NOT NULL DEFAULT '{}',
+    -- Our own apps, users aren't asked for consent
+    first_party BOOLEAN NOT NULL DEFAULT FALSE,
+    owner_id uuid CONSTRAINT fk_client_owner REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE oauth_consents (
+    user_id uuid NOT NULL CONSTRAINT fk_consent_user REFERENCES users (id) ON DELETE CASCADE,
+    client_id VARCHAR(64) NOT NULL CONSTRAINT fk_consent_client REFERENCES oauth_clients (client_id) ON DELETE CASCADE,
+    scopes TEXT[] NOT NULL,
+    granted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT unique_consent PRIMARY KEY (user_id, client_id)
+);
+
+CREATE TABLE oauth_tokens (
+    token_hash VARCHAR(64) PRIMARY KEY,
+    kind VARCHAR(16) NOT NULL CONSTRAINT check_token_kind CHECK (kind IN ('access', 'refresh')),
+    client_id VARCHAR(64) NOT NULL CONSTRAINT fk_token_client REFERENCES oauth_clients (client_id) ON DELETE CASCADE,
+    -- NULL for client credentials tokens
+    user_id uuid CONSTRAINT fk_token_user REFERENCES users (id) ON DELETE CASCADE,
+    scopes TEXT[] NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP
+);
+
+CREATE INDEX idx_oauth_tokens_user_client ON oauth_tokens (user_id, client_id);
@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE erasure_confirmations;
+DROP TABLE erasure_requests;
+
+ALTER TABLE users
+    DROP COLUMN deletion_scheduled_at,
+    DROP COLUMN status_changed_at,
+    DROP COLUMN status;
+
+DROP TYPE account_status;
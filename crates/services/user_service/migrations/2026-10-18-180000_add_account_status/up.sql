@@ -0,0 +1,29 @@
+-- Your SQL goes here
+CREATE TYPE account_status AS ENUM ('active', 'deactivated', 'suspended', 'pending_deletion', 'erased');
+
+ALTER TABLE users
+    ADD COLUMN status account_status NOT NULL DEFAULT 'active',
+    ADD COLUMN status_changed_at TIMESTAMP,
+    -- Set while a deletion request waits out its grace period
+    ADD COLUMN deletion_scheduled_at TIMESTAMP;
+
+CREATE INDEX idx_users_deletion_scheduled_at ON users (deletion_scheduled_at) WHERE status = 'pending_deletion';
+
+-- One erasure saga per user, the users row stays behind anonymised
+CREATE TABLE erasure_requests (
+    id uuid PRIMARY KEY,
+    user_id uuid NOT NULL CONSTRAINT fk_erasure_user REFERENCES users (id),
+    requested_by VARCHAR(16) NOT NULL CONSTRAINT check_erasure_requester CHECK (requested_by IN ('user', 'admin')),
+    admin_id uuid,
+    started_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_sent_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMP,
+    CONSTRAINT unique_user_erasure UNIQUE (user_id)
+);
+
+CREATE TABLE erasure_confirmations (
+    erasure_id uuid NOT NULL CONSTRAINT fk_confirmation_erasure REFERENCES erasure_requests (id) ON DELETE CASCADE,
+    service VARCHAR(64) NOT NULL,
+    confirmed_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT unique_erasure_confirmation PRIMARY KEY (erasure_id, service)
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE data_export_parts;
+DROP TABLE data_exports;
@@ -0,0 +1,24 @@
+-- Your SQL goes here
+CREATE TABLE data_exports (
+    id uuid PRIMARY KEY,
+    user_id uuid NOT NULL CONSTRAINT fk_export_user REFERENCES users (id) ON DELETE CASCADE,
+    requested_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_sent_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMP,
+    -- ZIP archive, dropped once the download link expires
+    archive BYTEA,
+    -- SHA-256 of the emailed download token
+    download_token_hash VARCHAR(64),
+    expires_at TIMESTAMP
+);
+
+-- One export in progress per user
+CREATE UNIQUE INDEX unique_pending_export ON data_exports (user_id) WHERE completed_at IS NULL;
+
+CREATE TABLE data_export_parts (
+    export_id uuid NOT NULL CONSTRAINT fk_part_export REFERENCES data_exports (id) ON DELETE CASCADE,
+    service VARCHAR(64) NOT NULL,
+    data JSONB NOT NULL,
+    received_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT unique_export_part PRIMARY KEY (export_id, service)
+);
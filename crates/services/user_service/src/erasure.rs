@@ -0,0 +1,342 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use errors::CustomError;
+use flume::Sender;
+use kafka::channel::{push_to_broker, KafkaMessage};
+use kafka::models::{AccountStatus, ErasureEventsMessage, UserEventType, UserEventsMessage};
+use lib_config::config::configuration::AccountSettings;
+use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_errors::DbError;
+use crate::routes::user::model::DbAccountStatus;
+use crate::schema::{
+    data_exports, email_verifications, erasure_confirmations, erasure_requests, oauth_clients, oauth_consents, oauth_tokens,
+    user_devices, user_identities, user_mfa, user_recovery_codes, users,
+};
+
+/// Accounts erased per sweep, the rest wait for the next round
+const SWEEP_BATCH: i64 = 100;
+
+/// Who asked for the erasure
+#[derive(Debug, Clone, Copy)]
+pub enum ErasureRequester {
+    /// The grace period of the user's deletion request is over
+    User,
+    Admin(Uuid),
+}
+
+/// Admins can erase any account that isn't erased yet, the user's own request only once
+/// its grace period is over
+fn qualifies(
+    account: Option<(DbAccountStatus, Option<NaiveDateTime>)>,
+    requester: ErasureRequester,
+    now: NaiveDateTime,
+) -> bool {
+    match (account, requester) {
+        (Some((DbAccountStatus::Erased, _)) | None, _) => false,
+        (Some((DbAccountStatus::PendingDeletion, Some(scheduled_at))), ErasureRequester::User) => scheduled_at <= now,
+        (Some(_), ErasureRequester::User) => false,
+        (Some(_), ErasureRequester::Admin(_)) => true,
+    }
+}
+
+/// Anonymises the account in user_service and asks every other service to erase the user.
+/// The `users` row stays behind with an anonymous username and email so ids held elsewhere
+/// stay valid. Returns the erasure id, `None` when the account doesn't qualify (any more).
+/// Starting an erasure again resends the running one.
+#[instrument(name = "Start erasure", skip(conn, redis_service, kafka_producer))]
+pub async fn start_erasure(
+    conn: &mut AsyncPgConnection,
+    redis_service: &RedisService,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    user_id: Uuid,
+    requester: ErasureRequester,
+) -> Result<Option<Uuid>, CustomError> {
+    let existing = erasure_requests::table
+        .filter(erasure_requests::user_id.eq(user_id))
+        .select((erasure_requests::id, erasure_requests::completed_at.is_not_null()))
+        .first::<(Uuid, bool)>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    if let Some((erasure_id, completed)) = existing {
+        if !completed {
+            send_erasure(conn, kafka_producer, erasure_id, user_id).await?;
+        }
+        return Ok(Some(erasure_id));
+    }
+
+    let erasure_id = Uuid::new_v4();
+    let started = conn
+        .transaction::<_, DieselError, _>(|conn| {
+            async move {
+                let now = Utc::now().naive_utc();
+                // Locked so a login can't cancel the deletion halfway through
+                let account = users::table
+                    .find(user_id)
+                    .select((users::status, users::deletion_scheduled_at))
+                    .for_update()
+                    .first::<(DbAccountStatus, Option<NaiveDateTime>)>(conn)
+                    .await
+                    .optional()?;
+                if !qualifies(account, requester, now) {
+                    return Ok(false);
+                }
+
+                let anonymous = user_id.simple().to_string();
+                diesel::update(users::table.find(user_id))
+                    .set((
+                        users::username.eq(format!("deleted_{}", anonymous)),
+                        users::email.eq(format!("{}@erased.invalid", anonymous)),
+                        users::password_hash.eq(None::<String>),
+                        users::status.eq(DbAccountStatus::Erased),
+                        users::status_changed_at.eq(now),
+                        users::deletion_scheduled_at.eq(None::<NaiveDateTime>),
+                        users::modified_at.eq(now),
+                    ))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(data_exports::table.filter(data_exports::user_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(email_verifications::table.filter(email_verifications::user_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(user_identities::table.filter(user_identities::user_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(user_recovery_codes::table.filter(user_recovery_codes::user_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(user_mfa::table.filter(user_mfa::user_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(user_devices::table.filter(user_devices::user_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(oauth_tokens::table.filter(oauth_tokens::user_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+                diesel::delete(oauth_consents::table.filter(oauth_consents::user_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+                // Tokens and consents of the user's own clients go with them
+                diesel::delete(oauth_clients::table.filter(oauth_clients::owner_id.eq(user_id)))
+                    .execute(conn)
+                    .await?;
+
+                let (requested_by, admin_id) = match requester {
+                    ErasureRequester::User => ("user", None),
+                    ErasureRequester::Admin(admin_id) => ("admin", Some(admin_id)),
+                };
+                diesel::insert_into(erasure_requests::table)
+                    .values((
+                        erasure_requests::id.eq(erasure_id),
+                        erasure_requests::user_id.eq(user_id),
+                        erasure_requests::requested_by.eq(requested_by),
+                        erasure_requests::admin_id.eq(admin_id),
+                    ))
+                    .execute(conn)
+                    .await?;
+                Ok(true)
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(DbError)?;
+    if !started {
+        return Ok(None);
+    }
+
+    redis_service.delete_all_sessions(&user_id.to_string()).await?;
+    send_erasure(conn, kafka_producer, erasure_id, user_id).await?;
+    tracing::info!("Started erasure {} of user {}", erasure_id, user_id);
+    Ok(Some(erasure_id))
+}
+
+/// Publishes the erasure step for every service and remembers when
+async fn send_erasure(
+    conn: &mut AsyncPgConnection,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    erasure_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), CustomError> {
+    let now = Utc::now().naive_utc();
+    diesel::update(erasure_requests::table.find(erasure_id))
+        .set(erasure_requests::last_sent_at.eq(now))
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+
+    let message = UserEventsMessage {
+        user_id,
+        event_type: UserEventType::Erase { erasure_id, time: now },
+    };
+    push_to_broker(kafka_producer, &message)
+        .await
+        .context("Failed to send message to broker")?;
+    Ok(())
+}
+
+/// Records a service's confirmation and completes the erasure once every service in
+/// `erasure_services` has confirmed
+#[instrument(name = "Record erasure confirmation", skip(conn, kafka_producer, account_settings))]
+pub async fn record_confirmation(
+    conn: &mut AsyncPgConnection,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    account_settings: &AccountSettings,
+    message: ErasureEventsMessage,
+) -> Result<(), CustomError> {
+    let known = diesel::select(diesel::dsl::exists(erasure_requests::table.find(message.erasure_id)))
+        .get_result::<bool>(conn)
+        .await
+        .map_err(DbError)?;
+    if !known {
+        tracing::warn!("Confirmation from {} for unknown erasure {}", message.service, message.erasure_id);
+        return Ok(());
+    }
+
+    diesel::insert_into(erasure_confirmations::table)
+        .values((
+            erasure_confirmations::erasure_id.eq(message.erasure_id),
+            erasure_confirmations::service.eq(&message.service),
+            erasure_confirmations::confirmed_at.eq(message.time),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+
+    let confirmed: Vec<String> = erasure_confirmations::table
+        .filter(erasure_confirmations::erasure_id.eq(message.erasure_id))
+        .select(erasure_confirmations::service)
+        .load(conn)
+        .await
+        .map_err(DbError)?;
+    if !account_settings.erasure_services.iter().all(|service| confirmed.contains(service)) {
+        return Ok(());
+    }
+
+    let now = Utc::now().naive_utc();
+    let completed = diesel::update(
+        erasure_requests::table
+            .find(message.erasure_id)
+            .filter(erasure_requests::completed_at.is_null()),
+    )
+    .set(erasure_requests::completed_at.eq(now))
+    .returning(erasure_requests::user_id)
+    .get_result::<Uuid>(conn)
+    .await
+    .optional()
+    .map_err(DbError)?;
+
+    if let Some(user_id) = completed {
+        tracing::info!("Erasure {} of user {} confirmed by every service", message.erasure_id, user_id);
+        let message = UserEventsMessage {
+            user_id,
+            event_type: UserEventType::StatusChange { status: AccountStatus::Erased, time: now },
+        };
+        let _ = push_to_broker(kafka_producer, &message)
+            .await
+            .context("Failed to send message to broker");
+    }
+    Ok(())
+}
+
+/// Starts the erasures whose grace period is over and resends the unconfirmed ones
+async fn sweep(
+    pool: &PgPool,
+    redis_service: &RedisService,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    account_settings: &AccountSettings,
+) -> Result<(), CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+    let now = Utc::now().naive_utc();
+
+    let due: Vec<Uuid> = users::table
+        .filter(users::status.eq(DbAccountStatus::PendingDeletion))
+        .filter(users::deletion_scheduled_at.le(now))
+        .select(users::id)
+        .limit(SWEEP_BATCH)
+        .load(&mut conn)
+        .await
+        .map_err(DbError)?;
+    for user_id in due {
+        start_erasure(&mut conn, redis_service, kafka_producer, user_id, ErasureRequester::User).await?;
+    }
+
+    let retry_before = now - chrono::Duration::minutes(account_settings.erasure_retry_minutes);
+    let unconfirmed: Vec<(Uuid, Uuid)> = erasure_requests::table
+        .filter(erasure_requests::completed_at.is_null())
+        .filter(erasure_requests::last_sent_at.lt(retry_before))
+        .select((erasure_requests::id, erasure_requests::user_id))
+        .limit(SWEEP_BATCH)
+        .load(&mut conn)
+        .await
+        .map_err(DbError)?;
+    for (erasure_id, user_id) in unconfirmed {
+        tracing::warn!("Erasure {} of user {} still unconfirmed, sending it again", erasure_id, user_id);
+        send_erasure(&mut conn, kafka_producer, erasure_id, user_id).await?;
+    }
+    Ok(())
+}
+
+/// Runs `sweep` every `erasure_sweep_secs`
+pub async fn run_erasure_sweeper(
+    pool: PgPool,
+    redis_service: RedisService,
+    kafka_producer: Sender<KafkaMessage<String>>,
+    account_settings: AccountSettings,
+) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(account_settings.erasure_sweep_secs));
+
+    loop {
+        ticker.tick().await;
+        if let Err(e) = sweep(&pool, &redis_service, &kafka_producer, &account_settings).await {
+            tracing::error!("Erasure sweep failed: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{qualifies, ErasureRequester};
+    use crate::routes::user::model::DbAccountStatus;
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    #[test]
+    fn users_wait_for_the_grace_period() {
+        let now = Utc::now().naive_utc();
+        let user = ErasureRequester::User;
+
+        assert!(qualifies(Some((DbAccountStatus::PendingDeletion, Some(now - Duration::days(1)))), user, now));
+        assert!(qualifies(Some((DbAccountStatus::PendingDeletion, Some(now))), user, now));
+        assert!(!qualifies(Some((DbAccountStatus::PendingDeletion, Some(now + Duration::days(1)))), user, now));
+        assert!(!qualifies(Some((DbAccountStatus::PendingDeletion, None)), user, now));
+        assert!(!qualifies(Some((DbAccountStatus::Active, Some(now - Duration::days(1)))), user, now));
+    }
+
+    #[test]
+    fn admins_erase_any_account_once() {
+        let now = Utc::now().naive_utc();
+        let admin = ErasureRequester::Admin(Uuid::new_v4());
+
+        assert!(qualifies(Some((DbAccountStatus::Active, None)), admin, now));
+        assert!(qualifies(Some((DbAccountStatus::PendingDeletion, Some(now + Duration::days(1)))), admin, now));
+        assert!(!qualifies(Some((DbAccountStatus::Erased, None)), admin, now));
+        assert!(!qualifies(None, admin, now));
+    }
+}
@@ -0,0 +1,478 @@
+use std::io::{Cursor, Write};
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use errors::CustomError;
+use flume::Sender;
+use helpers::oidc::{hash_token, random_token};
+use kafka::channel::{push_to_broker, KafkaMessage};
+use kafka::models::{ExportEventsMessage, UserEventType, UserEventsMessage};
+use lib_config::config::configuration::AccountSettings;
+use lib_config::db::db::PgPool;
+use lib_config::send_mail::send::send_export_ready;
+use serde_json::{json, Value};
+use tracing::instrument;
+use utils::telemetry::spawn_blocking_with_tracing;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::db_errors::DbError;
+use crate::routes::user::model::{DbAccountStatus, StatusEnum};
+use crate::schema::{
+    data_export_parts, data_exports, email_verifications, oauth_clients, oauth_consents, user_devices, user_identities,
+    user_mfa, users,
+};
+
+/// Exports resent per sweep, the rest wait for the next round
+const SWEEP_BATCH: i64 = 100;
+
+/// Starts a data export of everything the services hold about the user, or resends the one
+/// already running. The archive is built once every service in `export_services` has answered.
+#[instrument(name = "Start data export", skip(conn, kafka_producer))]
+pub async fn start_export(
+    conn: &mut AsyncPgConnection,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    user_id: Uuid,
+) -> Result<Uuid, CustomError> {
+    let pending = data_exports::table
+        .filter(data_exports::user_id.eq(user_id))
+        .filter(data_exports::completed_at.is_null())
+        .select(data_exports::id)
+        .first::<Uuid>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    if let Some(export_id) = pending {
+        send_export(conn, kafka_producer, export_id, user_id).await?;
+        return Ok(export_id);
+    }
+
+    let export_id = Uuid::new_v4();
+    diesel::insert_into(data_exports::table)
+        .values((data_exports::id.eq(export_id), data_exports::user_id.eq(user_id)))
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+    send_export(conn, kafka_producer, export_id, user_id).await?;
+    tracing::info!("Started data export {} of user {}", export_id, user_id);
+    Ok(export_id)
+}
+
+/// Asks every service for its part and remembers when
+async fn send_export(
+    conn: &mut AsyncPgConnection,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    export_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), CustomError> {
+    let now = Utc::now().naive_utc();
+    diesel::update(data_exports::table.find(export_id))
+        .set(data_exports::last_sent_at.eq(now))
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+
+    let message = UserEventsMessage {
+        user_id,
+        event_type: UserEventType::Export { export_id, time: now },
+    };
+    push_to_broker(kafka_producer, &message)
+        .await
+        .context("Failed to send message to broker")?;
+    Ok(())
+}
+
+/// Stores a service's part and completes the export once every service has sent one
+#[instrument(name = "Record data export part", skip(conn, account_settings, message), fields(export_id = %message.export_id, service = %message.service))]
+pub async fn record_export_part(
+    conn: &mut AsyncPgConnection,
+    account_settings: &AccountSettings,
+    message: ExportEventsMessage,
+) -> Result<(), CustomError> {
+    let export = data_exports::table
+        .find(message.export_id)
+        .select((data_exports::user_id, data_exports::completed_at.is_not_null()))
+        .first::<(Uuid, bool)>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    let user_id = match export {
+        Some((user_id, false)) if user_id == message.user_id => user_id,
+        Some(_) => return Ok(()),
+        None => {
+            tracing::warn!("Part from {} for unknown export {}", message.service, message.export_id);
+            return Ok(());
+        }
+    };
+
+    diesel::insert_into(data_export_parts::table)
+        .values((
+            data_export_parts::export_id.eq(message.export_id),
+            data_export_parts::service.eq(&message.service),
+            data_export_parts::data.eq(&message.data),
+            data_export_parts::received_at.eq(message.time),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+
+    let received: Vec<String> = data_export_parts::table
+        .filter(data_export_parts::export_id.eq(message.export_id))
+        .select(data_export_parts::service)
+        .load(conn)
+        .await
+        .map_err(DbError)?;
+    if !account_settings.export_services.iter().all(|service| received.contains(service)) {
+        return Ok(());
+    }
+
+    complete_export(conn, account_settings, message.export_id, user_id).await
+}
+
+/// Builds the archive, keeps it until the link expires and emails the link
+async fn complete_export(
+    conn: &mut AsyncPgConnection,
+    account_settings: &AccountSettings,
+    export_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), CustomError> {
+    let profile = profile_data(conn, user_id).await?;
+    let parts: Vec<(String, Value)> = data_export_parts::table
+        .filter(data_export_parts::export_id.eq(export_id))
+        .select((data_export_parts::service, data_export_parts::data))
+        .order(data_export_parts::service.asc())
+        .load(conn)
+        .await
+        .map_err(DbError)?;
+
+    let now = Utc::now().naive_utc();
+    let mut files = vec![
+        (
+            "export.json".to_string(),
+            json!({
+                "export_id": export_id,
+                "user_id": user_id,
+                "generated_at": now,
+                "services": std::iter::once("user_service")
+                    .chain(parts.iter().map(|(service, _)| service.as_str()))
+                    .collect::<Vec<_>>(),
+            }),
+        ),
+        ("user_service.json".to_string(), profile),
+    ];
+    files.extend(parts.into_iter().map(|(service, data)| (format!("{}.json", service), data)));
+    let archive = spawn_blocking_with_tracing(move || build_archive(files))
+        .await
+        .context("Failed to due to JoinError")??;
+
+    let download_token = random_token();
+    let expires_at = now + chrono::Duration::hours(account_settings.export_link_hours);
+    let archive_size = archive.len();
+    let user_email = conn
+        .transaction::<_, DieselError, _>(|conn| {
+            async move {
+                let completed = diesel::update(
+                    data_exports::table
+                        .find(export_id)
+                        .filter(data_exports::completed_at.is_null()),
+                )
+                .set((
+                    data_exports::completed_at.eq(now),
+                    data_exports::archive.eq(archive),
+                    data_exports::download_token_hash.eq(hash_token(&download_token)),
+                    data_exports::expires_at.eq(expires_at),
+                ))
+                .execute(conn)
+                .await?;
+                if completed == 0 {
+                    return Ok(None);
+                }
+                // The archive holds them now
+                diesel::delete(data_export_parts::table.filter(data_export_parts::export_id.eq(export_id)))
+                    .execute(conn)
+                    .await?;
+                users::table
+                    .find(user_id)
+                    .select(users::email)
+                    .first::<String>(conn)
+                    .await
+                    .map(|email| Some((email, download_token)))
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(DbError)?;
+
+    if let Some((user_email, download_token)) = user_email {
+        tracing::info!("Data export {} of user {} ready, {} bytes", export_id, user_id, archive_size);
+        if let Err(e) = send_export_ready(
+            &user_email,
+            &export_id.to_string(),
+            &download_token,
+            account_settings.export_link_hours,
+        )
+        .await
+        {
+            tracing::error!("Failed to send the link of data export {}: {:?}", export_id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Everything user_service holds about the user, secrets and hashes left out
+async fn profile_data(conn: &mut AsyncPgConnection, user_id: Uuid) -> Result<Value, CustomError> {
+    let (username, email, has_password, created_at, modified_at, status, status_changed_at, deletion_scheduled_at) = users::table
+        .find(user_id)
+        .select((
+            users::username,
+            users::email,
+            users::password_hash.is_not_null(),
+            users::created_at,
+            users::modified_at,
+            users::status,
+            users::status_changed_at,
+            users::deletion_scheduled_at,
+        ))
+        .first::<(
+            String,
+            String,
+            bool,
+            Option<NaiveDateTime>,
+            Option<NaiveDateTime>,
+            DbAccountStatus,
+            Option<NaiveDateTime>,
+            Option<NaiveDateTime>,
+        )>(conn)
+        .await
+        .map_err(DbError)?;
+
+    let email_verification = email_verifications::table
+        .find(user_id)
+        .select(email_verifications::status)
+        .first::<StatusEnum>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    let two_factor_enabled_at = user_mfa::table
+        .find(user_id)
+        .select(user_mfa::enabled_at)
+        .first::<Option<NaiveDateTime>>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?
+        .flatten();
+
+    let identities = user_identities::table
+        .filter(user_identities::user_id.eq(user_id))
+        .select((
+            user_identities::provider,
+            user_identities::email,
+            user_identities::created_at,
+            user_identities::last_login_at,
+        ))
+        .load::<(String, Option<String>, NaiveDateTime, NaiveDateTime)>(conn)
+        .await
+        .map_err(DbError)?
+        .into_iter()
+        .map(|(provider, email, linked_at, last_login_at)| {
+            json!({ "provider": provider, "email": email, "linked_at": linked_at, "last_login_at": last_login_at })
+        })
+        .collect::<Vec<_>>();
+
+    let devices = user_devices::table
+        .filter(user_devices::user_id.eq(user_id))
+        .select((
+            user_devices::user_agent,
+            user_devices::network,
+            user_devices::first_seen_at,
+            user_devices::last_seen_at,
+        ))
+        .order(user_devices::last_seen_at.desc())
+        .load::<(String, String, NaiveDateTime, NaiveDateTime)>(conn)
+        .await
+        .map_err(DbError)?
+        .into_iter()
+        .map(|(user_agent, network, first_seen_at, last_seen_at)| {
+            json!({ "user_agent": user_agent, "network": network, "first_seen_at": first_seen_at, "last_seen_at": last_seen_at })
+        })
+        .collect::<Vec<_>>();
+
+    let consents = oauth_consents::table
+        .inner_join(oauth_clients::table)
+        .filter(oauth_consents::user_id.eq(user_id))
+        .select((oauth_clients::name, oauth_consents::scopes, oauth_consents::granted_at))
+        .load::<(String, Vec<String>, NaiveDateTime)>(conn)
+        .await
+        .map_err(DbError)?
+        .into_iter()
+        .map(|(client, scopes, granted_at)| json!({ "client": client, "scopes": scopes, "granted_at": granted_at }))
+        .collect::<Vec<_>>();
+
+    let clients = oauth_clients::table
+        .filter(oauth_clients::owner_id.eq(user_id))
+        .select((
+            oauth_clients::client_id,
+            oauth_clients::name,
+            oauth_clients::redirect_uris,
+            oauth_clients::scopes,
+            oauth_clients::created_at,
+        ))
+        .load::<(String, String, Vec<String>, Vec<String>, NaiveDateTime)>(conn)
+        .await
+        .map_err(DbError)?
+        .into_iter()
+        .map(|(client_id, name, redirect_uris, scopes, created_at)| {
+            json!({ "client_id": client_id, "name": name, "redirect_uris": redirect_uris, "scopes": scopes, "created_at": created_at })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json!({
+        "account": {
+            "id": user_id,
+            "username": username,
+            "email": email,
+            "email_verified": email_verification.map(|status| status == StatusEnum::Verified),
+            "has_password": has_password,
+            "two_factor_enabled_at": two_factor_enabled_at,
+            "status": status,
+            "status_changed_at": status_changed_at,
+            "deletion_scheduled_at": deletion_scheduled_at,
+            "created_at": created_at,
+            "modified_at": modified_at,
+        },
+        "identities": identities,
+        "devices": devices,
+        "oauth_consents": consents,
+        "oauth_clients": clients,
+    }))
+}
+
+/// One pretty-printed JSON file per entry
+fn build_archive(files: Vec<(String, Value)>) -> Result<Vec<u8>, CustomError> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, data) in files {
+        zip.start_file(name, options).context("Failed to add a file to the export archive")?;
+        let content = serde_json::to_vec_pretty(&data).context("Failed to serialize export data")?;
+        zip.write_all(&content).context("Failed to write the export archive")?;
+    }
+    let archive = zip.finish().context("Failed to finish the export archive")?;
+    Ok(archive.into_inner())
+}
+
+/// Resends the exports some service hasn't answered and drops the archives of expired links
+async fn sweep_exports(
+    pool: &PgPool,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    account_settings: &AccountSettings,
+) -> Result<(), CustomError> {
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+    let now = Utc::now().naive_utc();
+
+    let retry_before = now - chrono::Duration::minutes(account_settings.erasure_retry_minutes);
+    let unanswered: Vec<(Uuid, Uuid)> = data_exports::table
+        .filter(data_exports::completed_at.is_null())
+        .filter(data_exports::last_sent_at.lt(retry_before))
+        .select((data_exports::id, data_exports::user_id))
+        .limit(SWEEP_BATCH)
+        .load(&mut conn)
+        .await
+        .map_err(DbError)?;
+    for (export_id, user_id) in unanswered {
+        tracing::warn!("Data export {} of user {} still incomplete, sending it again", export_id, user_id);
+        send_export(&mut conn, kafka_producer, export_id, user_id).await?;
+    }
+
+    let expired = diesel::update(
+        data_exports::table
+            .filter(data_exports::expires_at.lt(now))
+            .filter(data_exports::archive.is_not_null()),
+    )
+    .set((
+        data_exports::archive.eq(None::<Vec<u8>>),
+        data_exports::download_token_hash.eq(None::<String>),
+    ))
+    .execute(&mut conn)
+    .await
+    .map_err(DbError)?;
+    if expired > 0 {
+        tracing::info!("Dropped {} expired data export archives", expired);
+    }
+    Ok(())
+}
+
+/// Runs `sweep_exports` every `erasure_sweep_secs`
+pub async fn run_export_sweeper(
+    pool: PgPool,
+    kafka_producer: Sender<KafkaMessage<String>>,
+    account_settings: AccountSettings,
+) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(account_settings.erasure_sweep_secs));
+
+    loop {
+        ticker.tick().await;
+        if let Err(e) = sweep_exports(&pool, &kafka_producer, &account_settings).await {
+            tracing::error!("Data export sweep failed: {:?}", e);
+        }
+    }
+}
+
+/// The archive of a finished export when the token matches and the link hasn't expired
+pub async fn export_archive(
+    conn: &mut AsyncPgConnection,
+    export_id: Uuid,
+    download_token: &str,
+) -> Result<Option<Vec<u8>>, CustomError> {
+    let archive = data_exports::table
+        .find(export_id)
+        .filter(data_exports::download_token_hash.eq(hash_token(download_token)))
+        .filter(data_exports::expires_at.gt(Utc::now().naive_utc()))
+        .select(data_exports::archive)
+        .first::<Option<Vec<u8>>>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?
+        .flatten();
+    Ok(archive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::build_archive;
+    use serde_json::{json, Value};
+    use std::io::{Cursor, Read};
+    use zip::ZipArchive;
+
+    #[test]
+    fn archive_holds_one_json_file_per_service() {
+        let files = vec![
+            ("user_service.json".to_string(), json!({ "profile": { "username": "player" } })),
+            ("game_service.json".to_string(), json!({ "ratings": [{ "game": "celeste", "rating": 5 }] })),
+        ];
+
+        let archive = build_archive(files.clone()).unwrap();
+        let mut zip = ZipArchive::new(Cursor::new(archive)).unwrap();
+        assert_eq!(zip.len(), 2);
+        for (name, data) in files {
+            let mut content = String::new();
+            zip.by_name(&name).unwrap().read_to_string(&mut content).unwrap();
+            assert_eq!(serde_json::from_str::<Value>(&content).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn empty_archive_is_still_a_zip() {
+        let archive = build_archive(Vec::new()).unwrap();
+        assert_eq!(ZipArchive::new(Cursor::new(archive)).unwrap().len(), 0);
+    }
+}
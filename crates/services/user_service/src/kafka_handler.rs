@@ -1,49 +1,86 @@
-use diesel::ExpressionMethods;
-use diesel_async::{AsyncPgConnection, RunQueryDsl};
-use flume::Receiver;
+use flume::{Receiver, Sender};
 use futures::StreamExt;
+use kafka::channel::KafkaMessage;
+use kafka::models::{AdminEventsMessage, AdminUserAction, ErasureEventsMessage, ExportEventsMessage};
+use lib_config::config::configuration::AccountSettings;
 use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
 use rdkafka::{message::OwnedMessage, Message};
-use serde::Deserialize;
 use tracing::instrument;
 
-#[derive(Deserialize, Debug)]
-struct DeleteUser {
-    id: uuid::Uuid
-}
+use crate::erasure::{record_confirmation, start_erasure, ErasureRequester};
+use crate::export::record_export_part;
+use crate::routes::user::account::change_status;
+use crate::routes::user::model::DbAccountStatus;
 
 pub async fn process_kafka_message(
     rx: Receiver<OwnedMessage>,
-    pool: PgPool
+    pool: PgPool,
+    redis_service: RedisService,
+    kafka_producer: Sender<KafkaMessage<String>>,
+    account_settings: AccountSettings
 ){
     rx.stream()
         .for_each_concurrent(10, |msg| {
             let pool_clone = pool.clone();
+            let redis_service = &redis_service;
+            let kafka_producer = &kafka_producer;
+            let account_settings = &account_settings;
             async move {
+                let payload = match msg.payload() {
+                    Some(p) => p,
+                    None => {
+                        tracing::error!(
+                            "No payload found in message. Topic: {}, Partition: {}, Offset: {}",
+                            msg.topic(),
+                            msg.partition(),
+                            msg.offset()
+                        );
+                        return
+                    }
+                };
+
                 if msg.topic() == "admin_events" {
-                    let payload = match msg.payload() {
-                        Some(p) => p,
-                        None => {
+                    match serde_json::from_slice::<AdminEventsMessage>(payload) {
+                        Ok(message) => {
+                            let mut conn = match pool_clone.get().await {
+                                Ok(conn) => conn,
+                                Err(e) => {
+                                    tracing::error!("Failed to fetch connection from pool: {:?}", e);
+                                    return
+                                }
+                            };
+                            apply_admin_action(message, &mut conn, redis_service, kafka_producer).await
+                        },
+                        Err(e) => {
                             tracing::error!(
-                                "No payload found in message. Topic: {}, Partition: {}, Offset: {}",
+                                "Failed to deserialize message to admin action
+                                Topic: {}, Partition: {}, Offset: {} | Error: {:?}",
                                 msg.topic(),
                                 msg.partition(),
-                                msg.offset()
+                                msg.offset(),
+                                e
                             );
-                            return
                         }
-                    };
-
-                    let user = serde_json::from_slice::<DeleteUser>(payload);
-
-                    match user {
-                        Ok(user) => {
-                            let mut conn = pool_clone.get().await.unwrap();
-                            delete_user_from_db(user, &mut conn).await
+                    }
+                } else if msg.topic() == "erasure_events" {
+                    match serde_json::from_slice::<ErasureEventsMessage>(payload) {
+                        Ok(message) => {
+                            let mut conn = match pool_clone.get().await {
+                                Ok(conn) => conn,
+                                Err(e) => {
+                                    tracing::error!("Failed to fetch connection from pool: {:?}", e);
+                                    return
+                                }
+                            };
+                            let erasure_id = message.erasure_id;
+                            if let Err(e) = record_confirmation(&mut conn, kafka_producer, account_settings, message).await {
+                                tracing::error!("Failed to record confirmation of erasure {}: {:?}", erasure_id, e);
+                            }
                         },
                         Err(e) => {
                             tracing::error!(
-                                "Failed to deserialize message to user
+                                "Failed to deserialize message to erasure confirmation
                                 Topic: {}, Partition: {}, Offset: {} | Error: {:?}",
                                 msg.topic(),
                                 msg.partition(),
@@ -52,25 +89,79 @@ pub async fn process_kafka_message(
                             );
                         }
                     }
+                } else if msg.topic() == "export_events" {
+                    match serde_json::from_slice::<ExportEventsMessage>(payload) {
+                        Ok(message) => {
+                            let mut conn = match pool_clone.get().await {
+                                Ok(conn) => conn,
+                                Err(e) => {
+                                    tracing::error!("Failed to fetch connection from pool: {:?}", e);
+                                    return
+                                }
+                            };
+                            let export_id = message.export_id;
+                            if let Err(e) = record_export_part(&mut conn, account_settings, message).await {
+                                tracing::error!("Failed to record part of data export {}: {:?}", export_id, e);
+                            }
+                        },
+                        Err(e) => {
+                            tracing::error!(
+                                "Failed to deserialize message to data export part
+                                Topic: {}, Partition: {}, Offset: {} | Error: {:?}",
+                                msg.topic(),
+                                msg.partition(),
+                                msg.offset(),
+                                e
+                            );
+                        }
+                    }
+                } else {
+                    tracing::error!("Handler for topic {} not found", msg.topic());
                 }
             }
         }).await;
 }
 
-#[instrument("Deleting user from db", skip(conn))]
-async fn delete_user_from_db(user: DeleteUser, conn: &mut AsyncPgConnection) {
-    use crate::schema::users;
-
-    let res = diesel::delete(users::table)
-        .filter(users::id.eq(&user.id))
-        .execute(conn)
-        .await;
+#[instrument("Applying admin action", skip(conn, redis_service, kafka_producer))]
+async fn apply_admin_action(
+    message: AdminEventsMessage,
+    conn: &mut diesel_async::AsyncPgConnection,
+    redis_service: &RedisService,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+) {
+    let user_id = message.user_id;
+    let res = match message.action {
+        AdminUserAction::Suspend => change_status(
+            conn,
+            redis_service,
+            kafka_producer,
+            user_id,
+            &[DbAccountStatus::Active, DbAccountStatus::Deactivated, DbAccountStatus::PendingDeletion],
+            DbAccountStatus::Suspended,
+            None,
+        )
+        .await
+        .map(|_| ()),
+        AdminUserAction::Reactivate => change_status(
+            conn,
+            redis_service,
+            kafka_producer,
+            user_id,
+            &[DbAccountStatus::Suspended, DbAccountStatus::Deactivated, DbAccountStatus::PendingDeletion],
+            DbAccountStatus::Active,
+            None,
+        )
+        .await
+        .map(|_| ()),
+        AdminUserAction::Erase => {
+            start_erasure(conn, redis_service, kafka_producer, user_id, ErasureRequester::Admin(message.admin_id))
+                .await
+                .map(|_| ())
+        }
+    };
 
     match res {
-        Ok(_) => tracing::info!("Deleted user: {:?} from db", user),
-        Err(e) => tracing::error!(
-            "Failed to delete user: {:?}",
-            e
-        )
+        Ok(_) => tracing::info!("Applied {:?} of admin {} to user {}", message.action, message.admin_id, user_id),
+        Err(e) => tracing::error!("Failed to apply {:?} to user {}: {:?}", message.action, user_id, e),
     };
 }
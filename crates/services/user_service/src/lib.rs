@@ -2,4 +2,6 @@ pub mod startup;
 pub mod routes;
 pub mod schema;
 pub mod kafka_handler;
-pub mod db_errors;
\ No newline at end of file
+pub mod db_errors;
+pub mod erasure;
+pub mod export;
\ No newline at end of file
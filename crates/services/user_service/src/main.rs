@@ -1,5 +1,8 @@
+use helpers::oidc::IdTokenSigner;
 use lib_config::config::configuration;
 use lib_config::db::db::establish_connection;
+use user_service::routes::oauth::clients::insert_client;
+use user_service::routes::oauth::models::{CreateClientBody, SUPPORTED_SCOPES};
 use user_service::startup::Application;
 use utils::telemetry::{get_subscriber, init_subscriber};
 
@@ -8,10 +11,48 @@ async fn main() -> std::io::Result<()> {
     let subscriber = get_subscriber("user_srv".into(), "info".into(), std::io::stdout);
     init_subscriber(subscriber);
 
+    let args: Vec<String> = std::env::args().collect();
+    // `user_service generate-signing-key` prints a key for `oauth.signing_key` and exits
+    if args.get(1).map(String::as_str) == Some("generate-signing-key") {
+        println!("{}", IdTokenSigner::new_pkcs8_base64());
+        return Ok(());
+    }
+
     let config = configuration::Settings::new().expect("Failed to load configurations");
     let pool = establish_connection(&config.databases.user_db_url).await;
     let port = config.service.user_service_port;
 
+    // `user_service register-client <name> <redirect_uri>...` registers a confidential
+    // first-party client with every scope, its users aren't asked for consent
+    if args.get(1).map(String::as_str) == Some("register-client") {
+        let body = match args.get(2) {
+            Some(name) => CreateClientBody {
+                name: name.clone(),
+                redirect_uris: args[3..].to_vec(),
+                scopes: SUPPORTED_SCOPES.iter().map(|scope| scope.to_string()).collect(),
+                confidential: true,
+            },
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Usage: user_service register-client <name> <redirect_uri>...",
+                ))
+            }
+        };
+        body.validate()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{:?}", e)))?;
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| std::io::Error::other(format!("{:?}", e)))?;
+        let created = insert_client(&mut conn, None, &body, true)
+            .await
+            .map_err(|e| std::io::Error::other(format!("{:?}", e)))?;
+        println!("client_id: {}", created.client.client_id);
+        println!("client_secret: {}", created.client_secret.unwrap_or_default());
+        return Ok(());
+    }
+
     let application = Application::build(pool, &config).await?;
     application.run_until_stopped().await?;
     Ok(())
@@ -1,2 +1,4 @@
 pub mod health_check;
-pub mod user;
\ No newline at end of file
+pub mod oauth;
+pub mod user;
+pub mod openapi;
\ No newline at end of file
@@ -0,0 +1,321 @@
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use errors::CustomError;
+use helpers::auth_jwt::auth::Claims;
+use helpers::oidc::{hash_token, random_token};
+use lib_config::config::configuration::OAuthSettings;
+use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
+use reqwest::Url;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_errors::DbError;
+use crate::routes::user::mfa::session_user;
+use crate::schema::{oauth_clients, oauth_consents};
+
+use super::models::{
+    parse_scopes, AuthorizeDecision, AuthorizeQuery, AuthorizeRedirect, ConsentPrompt, OAuthClient, PendingCode,
+};
+
+/// Client and redirect URI are checked before anything is sent back to the redirect URI,
+/// an unknown client or URI could otherwise turn the server into an open redirector
+async fn registered_client(conn: &mut AsyncPgConnection, request: &AuthorizeQuery) -> Result<OAuthClient, CustomError> {
+    let client = oauth_clients::table
+        .find(&request.client_id)
+        .select(OAuthClient::as_select())
+        .first(conn)
+        .await
+        .optional()
+        .map_err(DbError)?
+        .ok_or(CustomError::ValidationError("Unknown client_id.".to_string()))?;
+    if !client.redirect_uris.contains(&request.redirect_uri) {
+        return Err(CustomError::ValidationError(
+            "redirect_uri is not registered for this client.".to_string(),
+        ));
+    }
+    Ok(client)
+}
+
+/// Scopes of a valid request, or the RFC 6749 error code to redirect back with
+fn check_request(client: &OAuthClient, request: &AuthorizeQuery) -> Result<Vec<String>, &'static str> {
+    if request.response_type != "code" {
+        return Err("unsupported_response_type");
+    }
+    let scopes = parse_scopes(&request.scope);
+    if scopes.is_empty() || scopes.iter().any(|scope| !client.scopes.contains(scope)) {
+        return Err("invalid_scope");
+    }
+    match (&request.code_challenge, request.code_challenge_method.as_deref()) {
+        (Some(_), Some("S256")) => Ok(scopes),
+        // Plain challenges are as good as none
+        (Some(_), _) => Err("invalid_request"),
+        (None, _) if !client.is_confidential() => Err("invalid_request"),
+        (None, _) => Ok(scopes),
+    }
+}
+
+fn redirect_to(request: &AuthorizeQuery, params: &[(&str, &str)]) -> Result<HttpResponse, CustomError> {
+    let mut url = Url::parse(&request.redirect_uri).context("Invalid registered redirect URI")?;
+    {
+        let mut query = url.query_pairs_mut();
+        query.extend_pairs(params);
+        if let Some(state) = &request.state {
+            query.append_pair("state", state);
+        }
+    }
+    Ok(HttpResponse::Ok().json(AuthorizeRedirect {
+        redirect_to: url.to_string(),
+    }))
+}
+
+/// Code the client redeems at the token endpoint within `authorization_code_secs`
+async fn issue_code(
+    redis_service: &RedisService,
+    oauth_settings: &OAuthSettings,
+    request: &AuthorizeQuery,
+    user_id: Uuid,
+    scopes: Vec<String>,
+    auth_time: i64,
+) -> Result<HttpResponse, CustomError> {
+    let code = random_token();
+    let pending = PendingCode {
+        client_id: request.client_id.clone(),
+        user_id,
+        redirect_uri: request.redirect_uri.clone(),
+        scopes,
+        code_challenge: request.code_challenge.clone(),
+        nonce: request.nonce.clone(),
+        auth_time,
+    };
+    let pending = serde_json::to_string(&pending).context("Failed to serialize authorization code")?;
+    redis_service
+        .store_value(
+            &format!("oauth_code:{}", hash_token(&code)),
+            &pending,
+            oauth_settings.authorization_code_secs,
+        )
+        .await?;
+    redirect_to(request, &[("code", &code)])
+}
+
+/******************************************/
+// OAuth authorize Route
+/******************************************/
+/**
+ * @route   GET /user/protected/oauth/authorize
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/protected/oauth/authorize",
+    tag = "oauth",
+    security(("bearer_auth" = [])),
+    params(AuthorizeQuery),
+    responses(
+        (status = 200, description = "Where to redirect the browser with a code or an error, or the scopes the user has to consent to", body = AuthorizeRedirect),
+        (status = 400, description = "Unknown client or unregistered redirect URI"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "OAuth authorize", skip(pool, req, redis_service, oauth_settings))]
+pub async fn authorize(
+    query: web::Query<AuthorizeQuery>,
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+    oauth_settings: web::Data<OAuthSettings>,
+) -> Result<HttpResponse, CustomError> {
+    let claims = req.into_inner();
+    let auth_time = claims.iat as i64;
+    let user_id = session_user(claims, &redis_service).await?;
+    let request = query.into_inner();
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let client = registered_client(&mut conn, &request).await?;
+    let scopes = match check_request(&client, &request) {
+        Ok(scopes) => scopes,
+        Err(error) => return redirect_to(&request, &[("error", error)]),
+    };
+
+    if !client.first_party {
+        let granted = oauth_consents::table
+            .find((user_id, &client.client_id))
+            .select(oauth_consents::scopes)
+            .first::<Vec<String>>(&mut conn)
+            .await
+            .optional()
+            .map_err(DbError)?
+            .unwrap_or_default();
+        let missing: Vec<String> = scopes.iter().filter(|scope| !granted.contains(scope)).cloned().collect();
+        if !missing.is_empty() {
+            return Ok(HttpResponse::Ok().json(ConsentPrompt {
+                consent_required: true,
+                client_name: client.name,
+                scopes: missing,
+            }));
+        }
+    }
+    issue_code(&redis_service, &oauth_settings, &request, user_id, scopes, auth_time).await
+}
+
+/******************************************/
+// OAuth consent decision Route
+/******************************************/
+/**
+ * @route   POST /user/protected/oauth/authorize
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/protected/oauth/authorize",
+    tag = "oauth",
+    security(("bearer_auth" = [])),
+    request_body = AuthorizeDecision,
+    responses(
+        (status = 200, description = "Where to redirect the browser, with a code when approved or access_denied", body = AuthorizeRedirect),
+        (status = 400, description = "Unknown client or unregistered redirect URI"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "OAuth consent decision", skip(pool, req, decision, redis_service, oauth_settings))]
+pub async fn decide_consent(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    decision: web::Json<AuthorizeDecision>,
+    redis_service: web::Data<RedisService>,
+    oauth_settings: web::Data<OAuthSettings>,
+) -> Result<HttpResponse, CustomError> {
+    let claims = req.into_inner();
+    let auth_time = claims.iat as i64;
+    let user_id = session_user(claims, &redis_service).await?;
+    let AuthorizeDecision { request, approve } = decision.into_inner();
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let client = registered_client(&mut conn, &request).await?;
+    let scopes = match check_request(&client, &request) {
+        Ok(scopes) => scopes,
+        Err(error) => return redirect_to(&request, &[("error", error)]),
+    };
+    if !approve {
+        return redirect_to(&request, &[("error", "access_denied")]);
+    }
+
+    // Grants add up, a later request for fewer scopes doesn't take any back
+    let granted = oauth_consents::table
+        .find((user_id, &client.client_id))
+        .select(oauth_consents::scopes)
+        .first::<Vec<String>>(&mut conn)
+        .await
+        .optional()
+        .map_err(DbError)?
+        .unwrap_or_default();
+    let mut all_scopes = granted;
+    for scope in &scopes {
+        if !all_scopes.contains(scope) {
+            all_scopes.push(scope.clone());
+        }
+    }
+    diesel::insert_into(oauth_consents::table)
+        .values((
+            oauth_consents::user_id.eq(user_id),
+            oauth_consents::client_id.eq(&client.client_id),
+            oauth_consents::scopes.eq(&all_scopes),
+            oauth_consents::granted_at.eq(Utc::now().naive_utc()),
+        ))
+        .on_conflict((oauth_consents::user_id, oauth_consents::client_id))
+        .do_update()
+        .set((
+            oauth_consents::scopes.eq(excluded(oauth_consents::scopes)),
+            oauth_consents::granted_at.eq(excluded(oauth_consents::granted_at)),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    issue_code(&redis_service, &oauth_settings, &request, user_id, scopes, auth_time).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_request;
+    use crate::routes::oauth::models::{AuthorizeQuery, OAuthClient};
+
+    fn client(secret: Option<&str>) -> OAuthClient {
+        OAuthClient {
+            client_id: "client".to_string(),
+            client_secret_hash: secret.map(str::to_string),
+            name: "Client".to_string(),
+            redirect_uris: vec!["https://app.example/callback".to_string()],
+            scopes: vec!["openid".to_string(), "profile".to_string()],
+            first_party: false,
+            owner_id: None,
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    fn request(scope: &str, challenge: Option<&str>, method: Option<&str>) -> AuthorizeQuery {
+        AuthorizeQuery {
+            response_type: "code".to_string(),
+            client_id: "client".to_string(),
+            redirect_uri: "https://app.example/callback".to_string(),
+            scope: scope.to_string(),
+            state: None,
+            code_challenge: challenge.map(str::to_string),
+            code_challenge_method: method.map(str::to_string),
+            nonce: None,
+        }
+    }
+
+    #[test]
+    fn public_clients_must_use_s256_pkce() {
+        let public = client(None);
+
+        assert_eq!(check_request(&public, &request("openid", None, None)), Err("invalid_request"));
+        assert_eq!(
+            check_request(&public, &request("openid", Some("challenge"), Some("plain"))),
+            Err("invalid_request")
+        );
+        assert_eq!(
+            check_request(&public, &request("openid", Some("challenge"), None)),
+            Err("invalid_request")
+        );
+        assert_eq!(
+            check_request(&public, &request("openid profile", Some("challenge"), Some("S256"))),
+            Ok(vec!["openid".to_string(), "profile".to_string()])
+        );
+    }
+
+    #[test]
+    fn confidential_clients_may_skip_pkce() {
+        let confidential = client(Some("hash"));
+
+        assert_eq!(check_request(&confidential, &request("openid", None, None)), Ok(vec!["openid".to_string()]));
+        assert_eq!(
+            check_request(&confidential, &request("openid", Some("challenge"), Some("plain"))),
+            Err("invalid_request")
+        );
+    }
+
+    #[test]
+    fn scopes_and_response_type_are_checked() {
+        let confidential = client(Some("hash"));
+
+        assert_eq!(check_request(&confidential, &request("", None, None)), Err("invalid_scope"));
+        assert_eq!(check_request(&confidential, &request("openid email", None, None)), Err("invalid_scope"));
+
+        let mut token = request("openid", None, None);
+        token.response_type = "token".to_string();
+        assert_eq!(check_request(&confidential, &token), Err("unsupported_response_type"));
+    }
+}
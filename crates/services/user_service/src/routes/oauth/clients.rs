@@ -0,0 +1,302 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use errors::CustomError;
+use helpers::auth_jwt::auth::Claims;
+use helpers::oidc::{hash_token, random_token};
+use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_errors::DbError;
+use crate::routes::user::mfa::session_user;
+use crate::schema::{oauth_clients, oauth_consents, oauth_tokens};
+
+use super::models::{ConsentResponse, CreateClientBody, CreatedClient, OAuthClient, OAuthClientResponse};
+
+fn not_found(what: &str) -> CustomError {
+    CustomError::DatabaseError {
+        msg: format!("{} not found", what),
+        resp: format!("{} not found", what),
+        status_code: StatusCode::NOT_FOUND,
+    }
+}
+
+/// Registers a client, the secret is only returned here. Used by the routes and the
+/// `register-client` command, which creates first-party clients without an owner.
+pub async fn insert_client(
+    conn: &mut AsyncPgConnection,
+    owner: Option<Uuid>,
+    body: &CreateClientBody,
+    first_party: bool,
+) -> Result<CreatedClient, DbError> {
+    let client_secret = body.confidential.then(random_token);
+    let client = diesel::insert_into(oauth_clients::table)
+        .values((
+            oauth_clients::client_id.eq(&random_token()[..32]),
+            oauth_clients::client_secret_hash.eq(client_secret.as_deref().map(hash_token)),
+            oauth_clients::name.eq(body.name.trim()),
+            oauth_clients::redirect_uris.eq(&body.redirect_uris),
+            oauth_clients::scopes.eq(&body.scopes),
+            oauth_clients::first_party.eq(first_party),
+            oauth_clients::owner_id.eq(owner),
+        ))
+        .returning(OAuthClient::as_returning())
+        .get_result(conn)
+        .await
+        .map_err(DbError)?;
+    Ok(CreatedClient {
+        client: client.into(),
+        client_secret,
+    })
+}
+
+/******************************************/
+// Register OAuth client Route
+/******************************************/
+/**
+ * @route   POST /user/protected/oauth/clients
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/protected/oauth/clients",
+    tag = "oauth",
+    security(("bearer_auth" = [])),
+    request_body = CreateClientBody,
+    responses(
+        (status = 201, description = "Client registered, the secret is only shown once", body = CreatedClient),
+        (status = 400, description = "Invalid name, redirect URI or scope"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "Register OAuth client", skip(pool, req, body, redis_service))]
+pub async fn create_client(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    body: web::Json<CreateClientBody>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = session_user(req.into_inner(), &redis_service).await?;
+    let body = body.into_inner();
+    body.validate()?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let created = insert_client(&mut conn, Some(user_id), &body, false).await?;
+    Ok(HttpResponse::Created().json(created))
+}
+
+/******************************************/
+// List OAuth clients Route
+/******************************************/
+/**
+ * @route   GET /user/protected/oauth/clients
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/protected/oauth/clients",
+    tag = "oauth",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Clients registered by the user", body = [OAuthClientResponse]),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "List OAuth clients", skip(pool, req, redis_service))]
+pub async fn list_clients(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = session_user(req.into_inner(), &redis_service).await?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let clients = oauth_clients::table
+        .filter(oauth_clients::owner_id.eq(user_id))
+        .order(oauth_clients::created_at.desc())
+        .select(OAuthClient::as_select())
+        .load(&mut conn)
+        .await
+        .map_err(DbError)?;
+    let clients: Vec<OAuthClientResponse> = clients.into_iter().map(Into::into).collect();
+    Ok(HttpResponse::Ok().json(clients))
+}
+
+/******************************************/
+// Delete OAuth client Route
+/******************************************/
+/**
+ * @route   DELETE /user/protected/oauth/clients/{client_id}
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    delete,
+    path = "/api/v1/user/protected/oauth/clients/{client_id}",
+    tag = "oauth",
+    security(("bearer_auth" = [])),
+    params(("client_id" = String, Path, description = "Client to delete, with its consents and tokens")),
+    responses(
+        (status = 204, description = "Client deleted"),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "No client with this ID registered by the user")
+    )
+)]
+#[instrument(name = "Delete OAuth client", skip(pool, req, redis_service))]
+pub async fn delete_client(
+    client_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = session_user(req.into_inner(), &redis_service).await?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    // Consents and tokens go with it through the foreign keys
+    let deleted = diesel::delete(
+        oauth_clients::table
+            .find(client_id.as_str())
+            .filter(oauth_clients::owner_id.eq(user_id)),
+    )
+    .execute(&mut conn)
+    .await
+    .map_err(DbError)?;
+    if deleted == 0 {
+        return Err(not_found("Client"));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/******************************************/
+// List OAuth consents Route
+/******************************************/
+/**
+ * @route   GET /user/protected/oauth/consents
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/protected/oauth/consents",
+    tag = "oauth",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Applications the user has granted access to", body = [ConsentResponse]),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "List OAuth consents", skip(pool, req, redis_service))]
+pub async fn list_consents(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = session_user(req.into_inner(), &redis_service).await?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let consents = oauth_consents::table
+        .inner_join(oauth_clients::table)
+        .filter(oauth_consents::user_id.eq(user_id))
+        .order(oauth_consents::granted_at.desc())
+        .select((
+            oauth_consents::client_id,
+            oauth_clients::name,
+            oauth_consents::scopes,
+            oauth_consents::granted_at,
+        ))
+        .load::<(String, String, Vec<String>, chrono::NaiveDateTime)>(&mut conn)
+        .await
+        .map_err(DbError)?;
+    let consents: Vec<ConsentResponse> = consents
+        .into_iter()
+        .map(|(client_id, client_name, scopes, granted_at)| ConsentResponse {
+            client_id,
+            client_name,
+            scopes,
+            granted_at,
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(consents))
+}
+
+/******************************************/
+// Revoke OAuth consent Route
+/******************************************/
+/**
+ * @route   DELETE /user/protected/oauth/consents/{client_id}
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    delete,
+    path = "/api/v1/user/protected/oauth/consents/{client_id}",
+    tag = "oauth",
+    security(("bearer_auth" = [])),
+    params(("client_id" = String, Path, description = "Application to revoke access from")),
+    responses(
+        (status = 204, description = "Consent and the application's tokens revoked"),
+        (status = 401, description = "Invalid token or session"),
+        (status = 404, description = "The user hasn't granted this application access")
+    )
+)]
+#[instrument(name = "Revoke OAuth consent", skip(pool, req, redis_service))]
+pub async fn revoke_consent(
+    client_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = session_user(req.into_inner(), &redis_service).await?;
+    let client_id = client_id.into_inner();
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let deleted = conn
+        .transaction::<_, DieselError, _>(|conn| {
+            async move {
+                let deleted = diesel::delete(oauth_consents::table.find((user_id, &client_id)))
+                    .execute(conn)
+                    .await?;
+                revoke_user_tokens(conn, user_id, &client_id).await?;
+                Ok(deleted)
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(DbError)?;
+    if deleted == 0 {
+        return Err(not_found("Consent"));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Revokes every live token the client holds for the user
+pub async fn revoke_user_tokens(conn: &mut AsyncPgConnection, user_id: Uuid, client_id: &str) -> Result<usize, DieselError> {
+    diesel::update(
+        oauth_tokens::table
+            .filter(oauth_tokens::user_id.eq(user_id))
+            .filter(oauth_tokens::client_id.eq(client_id))
+            .filter(oauth_tokens::revoked_at.is_null()),
+    )
+    .set(oauth_tokens::revoked_at.eq(Utc::now().naive_utc()))
+    .execute(conn)
+    .await
+}
@@ -0,0 +1,69 @@
+use actix_web::http::header::CACHE_CONTROL;
+use actix_web::{web, HttpResponse};
+use helpers::oidc::IdTokenSigner;
+use lib_config::config::configuration::OAuthSettings;
+use serde_json::json;
+use tracing::instrument;
+
+use super::models::SUPPORTED_SCOPES;
+
+/******************************************/
+// OIDC discovery Route
+/******************************************/
+/**
+ * @route   GET /.well-known/openid-configuration
+ * @access  Public
+ */
+#[utoipa::path(
+    get,
+    path = "/.well-known/openid-configuration",
+    tag = "oauth",
+    responses(
+        (status = 200, description = "OpenID Connect discovery document")
+    )
+)]
+#[instrument(name = "OIDC discovery", skip(oauth_settings))]
+pub async fn openid_configuration(oauth_settings: web::Data<OAuthSettings>) -> HttpResponse {
+    let issuer = oauth_settings.issuer.trim_end_matches('/');
+    HttpResponse::Ok()
+        .insert_header((CACHE_CONTROL, "public, max-age=3600"))
+        .json(json!({
+            "issuer": issuer,
+            "authorization_endpoint": oauth_settings.authorization_page,
+            "token_endpoint": format!("{}/api/v1/oauth/token", issuer),
+            "introspection_endpoint": format!("{}/api/v1/oauth/introspect", issuer),
+            "revocation_endpoint": format!("{}/api/v1/oauth/revoke", issuer),
+            "userinfo_endpoint": format!("{}/api/v1/oauth/userinfo", issuer),
+            "jwks_uri": format!("{}/api/v1/oauth/jwks", issuer),
+            "scopes_supported": SUPPORTED_SCOPES,
+            "response_types_supported": ["code"],
+            "grant_types_supported": ["authorization_code", "refresh_token", "client_credentials"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["EdDSA"],
+            "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post", "none"],
+            "code_challenge_methods_supported": ["S256"],
+            "claims_supported": ["sub", "iss", "aud", "exp", "iat", "auth_time", "nonce", "email", "email_verified", "preferred_username"]
+        }))
+}
+
+/******************************************/
+// OAuth JWKS Route
+/******************************************/
+/**
+ * @route   GET /oauth/jwks
+ * @access  Public
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/oauth/jwks",
+    tag = "oauth",
+    responses(
+        (status = 200, description = "Public key ID tokens are signed with, as a JWK set")
+    )
+)]
+#[instrument(name = "OAuth JWKS", skip(signer))]
+pub async fn jwks(signer: web::Data<IdTokenSigner>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((CACHE_CONTROL, "public, max-age=3600"))
+        .json(json!({ "keys": [signer.public_jwk()] }))
+}
@@ -0,0 +1,5 @@
+pub mod authorize;
+pub mod clients;
+pub mod discovery;
+pub mod models;
+pub mod token;
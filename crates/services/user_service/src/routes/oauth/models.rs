@@ -0,0 +1,429 @@
+use std::fmt;
+
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use errors::CustomError;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::db_errors::DbError;
+
+/// Scopes clients can ask for; `openid` adds an ID token, `offline_access` a refresh token
+pub const SUPPORTED_SCOPES: [&str; 4] = ["openid", "profile", "email", "offline_access"];
+pub const ACCESS_TOKEN: &str = "access";
+pub const REFRESH_TOKEN: &str = "refresh";
+const MAX_REDIRECT_URIS: usize = 10;
+
+/// Space separated scopes, without duplicates
+pub fn parse_scopes(scope: &str) -> Vec<String> {
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in scope.split_whitespace() {
+        if !scopes.iter().any(|known| known == scope) {
+            scopes.push(scope.to_string());
+        }
+    }
+    scopes
+}
+
+/******************************************/
+// Clients
+/******************************************/
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::schema::oauth_clients)]
+pub struct OAuthClient {
+    pub client_id: String,
+    pub client_secret_hash: Option<String>,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub scopes: Vec<String>,
+    pub first_party: bool,
+    pub owner_id: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+}
+
+impl OAuthClient {
+    /// Confidential clients hold a secret, public ones (SPAs, mobile apps) must use PKCE
+    pub fn is_confidential(&self) -> bool {
+        self.client_secret_hash.is_some()
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct OAuthClientResponse {
+    pub client_id: String,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub scopes: Vec<String>,
+    pub confidential: bool,
+    pub first_party: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<OAuthClient> for OAuthClientResponse {
+    fn from(client: OAuthClient) -> Self {
+        Self {
+            confidential: client.is_confidential(),
+            client_id: client.client_id,
+            name: client.name,
+            redirect_uris: client.redirect_uris,
+            scopes: client.scopes,
+            first_party: client.first_party,
+            created_at: client.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct CreatedClient {
+    #[serde(flatten)]
+    pub client: OAuthClientResponse,
+    /// Only shown once, `None` for public clients
+    pub client_secret: Option<String>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CreateClientBody {
+    pub name: String,
+    /// Exact URIs the authorization response may be sent to, https or http on localhost
+    pub redirect_uris: Vec<String>,
+    /// Subset of `openid`, `profile`, `email` and `offline_access`
+    pub scopes: Vec<String>,
+    /// False for apps that can't keep a secret, they must use PKCE
+    pub confidential: bool,
+}
+
+impl CreateClientBody {
+    pub fn validate(&self) -> Result<(), CustomError> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > 100 {
+            return Err(CustomError::ValidationError("Client name must be 1 to 100 characters.".to_string()));
+        }
+        if self.redirect_uris.is_empty() || self.redirect_uris.len() > MAX_REDIRECT_URIS {
+            return Err(CustomError::ValidationError(format!(
+                "A client needs 1 to {} redirect URIs.",
+                MAX_REDIRECT_URIS
+            )));
+        }
+        if let Some(uri) = self.redirect_uris.iter().find(|uri| !is_valid_redirect_uri(uri)) {
+            return Err(CustomError::ValidationError(format!(
+                "Invalid redirect URI {}, use https or http on localhost without a fragment.",
+                uri
+            )));
+        }
+        if let Some(scope) = self.scopes.iter().find(|scope| !SUPPORTED_SCOPES.contains(&scope.as_str())) {
+            return Err(CustomError::ValidationError(format!("Unsupported scope {}.", scope)));
+        }
+        Ok(())
+    }
+}
+
+fn is_valid_redirect_uri(uri: &str) -> bool {
+    match Url::parse(uri) {
+        Ok(url) if url.fragment().is_none() => match url.scheme() {
+            "https" => true,
+            "http" => matches!(url.host_str(), Some("localhost" | "127.0.0.1")),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ConsentResponse {
+    pub client_id: String,
+    pub client_name: String,
+    pub scopes: Vec<String>,
+    pub granted_at: NaiveDateTime,
+}
+
+/******************************************/
+// Authorization endpoint
+/******************************************/
+#[derive(Deserialize, Serialize, Debug, Clone, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct AuthorizeQuery {
+    /// Only `code` is supported
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub state: Option<String>,
+    /// Required for public clients
+    pub code_challenge: Option<String>,
+    /// Only `S256` is supported
+    pub code_challenge_method: Option<String>,
+    pub nonce: Option<String>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct AuthorizeDecision {
+    #[serde(flatten)]
+    pub request: AuthorizeQuery,
+    pub approve: bool,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ConsentPrompt {
+    pub consent_required: bool,
+    pub client_name: String,
+    /// Scopes the user hasn't granted this client yet
+    pub scopes: Vec<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AuthorizeRedirect {
+    /// Where to send the browser, with `code` or `error` and the `state`
+    pub redirect_to: String,
+}
+
+/// Kept in Redis under the code hash until the client redeems it
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PendingCode {
+    pub client_id: String,
+    pub user_id: Uuid,
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    pub code_challenge: Option<String>,
+    pub nonce: Option<String>,
+    pub auth_time: i64,
+}
+
+/******************************************/
+// Token, introspection and revocation endpoints
+/******************************************/
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct TokenRequest {
+    /// `authorization_code`, `refresh_token` or `client_credentials`
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    pub scope: Option<String>,
+    /// Clients can authenticate with HTTP Basic instead
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    pub scope: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct TokenForm {
+    pub token: String,
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Serialize, Debug, Default, ToSchema)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+}
+
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::schema::oauth_tokens)]
+pub struct StoredToken {
+    pub kind: String,
+    pub client_id: String,
+    pub user_id: Option<Uuid>,
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl StoredToken {
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at > chrono::Utc::now().naive_utc()
+    }
+}
+
+/// Claims the userinfo endpoint and ID tokens share, depending on the granted scopes
+#[derive(Serialize, Debug, ToSchema)]
+pub struct UserInfoResponse {
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub user: UserInfoResponse,
+}
+
+/******************************************/
+// OAuth error responses
+/******************************************/
+/// RFC 6749 section 5.2 error, answered as `{"error", "error_description"}`
+#[derive(Debug)]
+pub struct OAuthError {
+    pub status_code: StatusCode,
+    pub error: &'static str,
+    pub description: String,
+}
+
+impl OAuthError {
+    pub fn invalid_request(description: impl Into<String>) -> Self {
+        Self { status_code: StatusCode::BAD_REQUEST, error: "invalid_request", description: description.into() }
+    }
+
+    pub fn invalid_client() -> Self {
+        Self {
+            status_code: StatusCode::UNAUTHORIZED,
+            error: "invalid_client",
+            description: "Client authentication failed".to_string(),
+        }
+    }
+
+    pub fn invalid_grant(description: impl Into<String>) -> Self {
+        Self { status_code: StatusCode::BAD_REQUEST, error: "invalid_grant", description: description.into() }
+    }
+
+    pub fn invalid_scope(description: impl Into<String>) -> Self {
+        Self { status_code: StatusCode::BAD_REQUEST, error: "invalid_scope", description: description.into() }
+    }
+
+    pub fn unauthorized_client(description: impl Into<String>) -> Self {
+        Self { status_code: StatusCode::BAD_REQUEST, error: "unauthorized_client", description: description.into() }
+    }
+
+    pub fn unsupported_grant_type() -> Self {
+        Self {
+            status_code: StatusCode::BAD_REQUEST,
+            error: "unsupported_grant_type",
+            description: "Supported grants are authorization_code, refresh_token and client_credentials".to_string(),
+        }
+    }
+
+    pub fn invalid_token() -> Self {
+        Self {
+            status_code: StatusCode::UNAUTHORIZED,
+            error: "invalid_token",
+            description: "The access token is invalid, expired or revoked".to_string(),
+        }
+    }
+
+    fn server_error() -> Self {
+        Self {
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            error: "server_error",
+            description: "Unexpected error".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.error, self.description)
+    }
+}
+
+impl ResponseError for OAuthError {
+    fn status_code(&self) -> StatusCode {
+        self.status_code
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code);
+        response.insert_header((header::CACHE_CONTROL, "no-store"));
+        match self.error {
+            "invalid_client" => {
+                response.insert_header((header::WWW_AUTHENTICATE, "Basic"));
+            }
+            "invalid_token" => {
+                response.insert_header((header::WWW_AUTHENTICATE, "Bearer error=\"invalid_token\""));
+            }
+            _ => {}
+        }
+        response.json(json!({
+            "error": self.error,
+            "error_description": self.description
+        }))
+    }
+}
+
+impl From<DbError> for OAuthError {
+    fn from(err: DbError) -> Self {
+        tracing::error!("Database error in OAuth endpoint: {:?}", err);
+        Self::server_error()
+    }
+}
+
+impl From<CustomError> for OAuthError {
+    fn from(err: CustomError) -> Self {
+        tracing::error!("Error in OAuth endpoint: {:?}", err);
+        Self::server_error()
+    }
+}
+
+impl From<anyhow::Error> for OAuthError {
+    fn from(err: anyhow::Error) -> Self {
+        tracing::error!("Error in OAuth endpoint: {:?}", err);
+        Self::server_error()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_valid_redirect_uri, parse_scopes};
+
+    #[test]
+    fn scopes_are_split_and_deduplicated() {
+        assert_eq!(parse_scopes("  openid profile\temail openid "), vec!["openid", "profile", "email"]);
+        assert!(parse_scopes("   ").is_empty());
+    }
+
+    #[test]
+    fn redirect_uris_need_https_or_localhost() {
+        assert!(is_valid_redirect_uri("https://app.example/callback?x=1"));
+        assert!(is_valid_redirect_uri("http://localhost:3000/callback"));
+        assert!(is_valid_redirect_uri("http://127.0.0.1/callback"));
+
+        assert!(!is_valid_redirect_uri("http://app.example/callback"));
+        assert!(!is_valid_redirect_uri("https://app.example/callback#token"));
+        assert!(!is_valid_redirect_uri("myapp://callback"));
+        assert!(!is_valid_redirect_uri("javascript:alert(1)"));
+        assert!(!is_valid_redirect_uri("/callback"));
+    }
+}
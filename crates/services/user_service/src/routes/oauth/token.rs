@@ -0,0 +1,589 @@
+use actix_web::http::header::{AUTHORIZATION, CACHE_CONTROL};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use helpers::oidc::{hash_token, parse_basic_auth, pkce_challenge, random_token, IdTokenSigner};
+use lib_config::config::configuration::OAuthSettings;
+use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_errors::DbError;
+use crate::routes::user::model::{DbAccountStatus, StatusEnum};
+use crate::schema::{email_verifications, oauth_clients, oauth_tokens, users};
+
+use super::clients::revoke_user_tokens;
+use super::models::{
+    parse_scopes, IdTokenClaims, IntrospectionResponse, OAuthClient, OAuthError, PendingCode, StoredToken,
+    TokenForm, TokenRequest, TokenResponse, UserInfoResponse, ACCESS_TOKEN, REFRESH_TOKEN,
+};
+
+/// What a grant hands out, whichever way it was obtained
+struct Grant {
+    user_id: Option<Uuid>,
+    scopes: Vec<String>,
+    nonce: Option<String>,
+    auth_time: Option<i64>,
+}
+
+/// Client from HTTP Basic or the form fields. Confidential clients must send their secret,
+/// public clients only identify themselves.
+async fn authenticate_client(
+    conn: &mut AsyncPgConnection,
+    req: &HttpRequest,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Result<OAuthClient, OAuthError> {
+    let basic = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_basic_auth);
+    let (client_id, client_secret) = match basic {
+        Some((client_id, client_secret)) => (client_id, Some(client_secret)),
+        None => (
+            client_id.ok_or_else(OAuthError::invalid_client)?.to_string(),
+            client_secret.map(str::to_string),
+        ),
+    };
+    let client_secret = client_secret.filter(|secret| !secret.is_empty());
+
+    let client = oauth_clients::table
+        .find(&client_id)
+        .select(OAuthClient::as_select())
+        .first(conn)
+        .await
+        .optional()
+        .map_err(DbError)?
+        .ok_or_else(OAuthError::invalid_client)?;
+    match (&client.client_secret_hash, client_secret) {
+        (Some(secret_hash), Some(secret)) if *secret_hash == hash_token(&secret) => Ok(client),
+        (None, None) => Ok(client),
+        _ => Err(OAuthError::invalid_client()),
+    }
+}
+
+async fn find_token(conn: &mut AsyncPgConnection, token: &str) -> Result<Option<StoredToken>, OAuthError> {
+    let stored = oauth_tokens::table
+        .find(hash_token(token))
+        .select(StoredToken::as_select())
+        .first(conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    Ok(stored)
+}
+
+/// Tokens of suspended, deactivated or deleted accounts stop working until the account is active again
+async fn user_is_active(conn: &mut AsyncPgConnection, user_id: Uuid) -> Result<bool, OAuthError> {
+    let status = users::table
+        .find(user_id)
+        .select(users::status)
+        .first::<DbAccountStatus>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    Ok(status == Some(DbAccountStatus::Active))
+}
+
+async fn store_token(
+    conn: &mut AsyncPgConnection,
+    kind: &str,
+    client_id: &str,
+    grant: &Grant,
+    expires_at: chrono::NaiveDateTime,
+) -> Result<String, OAuthError> {
+    let token = random_token();
+    diesel::insert_into(oauth_tokens::table)
+        .values((
+            oauth_tokens::token_hash.eq(hash_token(&token)),
+            oauth_tokens::kind.eq(kind),
+            oauth_tokens::client_id.eq(client_id),
+            oauth_tokens::user_id.eq(grant.user_id),
+            oauth_tokens::scopes.eq(&grant.scopes),
+            oauth_tokens::expires_at.eq(expires_at),
+        ))
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+    Ok(token)
+}
+
+/// Profile claims the scopes allow: the email with `email`, the username with `profile`
+async fn user_info(conn: &mut AsyncPgConnection, user_id: Uuid, scopes: &[String]) -> Result<UserInfoResponse, OAuthError> {
+    let (username, user_email) = users::table
+        .find(user_id)
+        .select((users::username, users::email))
+        .first::<(String, String)>(conn)
+        .await
+        .map_err(DbError)?;
+    let has_scope = |scope: &str| scopes.iter().any(|granted| granted == scope);
+
+    let mut info = UserInfoResponse {
+        sub: user_id.to_string(),
+        email: None,
+        email_verified: None,
+        preferred_username: has_scope("profile").then_some(username),
+    };
+    if has_scope("email") {
+        let verified = diesel::select(diesel::dsl::exists(
+            email_verifications::table
+                .filter(email_verifications::user_id.eq(user_id))
+                .filter(email_verifications::status.eq(StatusEnum::Verified)),
+        ))
+        .get_result::<bool>(conn)
+        .await
+        .map_err(DbError)?;
+        info.email = Some(user_email);
+        info.email_verified = Some(verified);
+    }
+    Ok(info)
+}
+
+/// Access token, a refresh token for `offline_access` and an ID token for `openid`
+async fn issue_tokens(
+    conn: &mut AsyncPgConnection,
+    oauth_settings: &OAuthSettings,
+    signer: &IdTokenSigner,
+    client: &OAuthClient,
+    grant: Grant,
+) -> Result<HttpResponse, OAuthError> {
+    let now = Utc::now();
+    let expires_in = Duration::minutes(oauth_settings.access_token_minutes);
+    let access_token = store_token(conn, ACCESS_TOKEN, &client.client_id, &grant, (now + expires_in).naive_utc()).await?;
+
+    let has_scope = |scope: &str| grant.scopes.iter().any(|granted| granted == scope);
+    let mut refresh_token = None;
+    let mut id_token = None;
+    if let Some(user_id) = grant.user_id {
+        if has_scope("offline_access") {
+            let expires_at = now + Duration::days(oauth_settings.refresh_token_days);
+            refresh_token = Some(store_token(conn, REFRESH_TOKEN, &client.client_id, &grant, expires_at.naive_utc()).await?);
+        }
+        if has_scope("openid") {
+            let claims = IdTokenClaims {
+                iss: oauth_settings.issuer.clone(),
+                aud: client.client_id.clone(),
+                exp: (now + expires_in).timestamp(),
+                iat: now.timestamp(),
+                auth_time: grant.auth_time,
+                nonce: grant.nonce.clone(),
+                user: user_info(conn, user_id, &grant.scopes).await?,
+            };
+            id_token = Some(signer.sign(&claims).context("Failed to sign the ID token")?);
+        }
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header((CACHE_CONTROL, "no-store"))
+        .json(TokenResponse {
+            access_token,
+            token_type: "Bearer",
+            expires_in: expires_in.num_seconds(),
+            scope: grant.scopes.join(" "),
+            refresh_token,
+            id_token,
+        }))
+}
+
+/******************************************/
+// OAuth token Route
+/******************************************/
+/**
+ * @route   POST /oauth/token
+ * @access  Client authentication
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/oauth/token",
+    tag = "oauth",
+    request_body(content = TokenRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Access token, with a refresh token for offline_access and an ID token for openid", body = TokenResponse),
+        (status = 400, description = "invalid_request, invalid_grant, invalid_scope, unauthorized_client or unsupported_grant_type"),
+        (status = 401, description = "invalid_client")
+    )
+)]
+#[instrument(name = "OAuth token", skip(req, form, pool, redis_service, oauth_settings, signer))]
+pub async fn token(
+    req: HttpRequest,
+    form: web::Form<TokenRequest>,
+    pool: web::Data<PgPool>,
+    redis_service: web::Data<RedisService>,
+    oauth_settings: web::Data<OAuthSettings>,
+    signer: web::Data<IdTokenSigner>,
+) -> Result<HttpResponse, OAuthError> {
+    let form = form.into_inner();
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+    let client = authenticate_client(&mut conn, &req, form.client_id.as_deref(), form.client_secret.as_deref()).await?;
+
+    let grant = match form.grant_type.as_str() {
+        "authorization_code" => authorization_code_grant(&redis_service, &client, &form).await?,
+        "refresh_token" => refresh_token_grant(&mut conn, &client, &form).await?,
+        "client_credentials" => client_credentials_grant(&client, &form)?,
+        _ => return Err(OAuthError::unsupported_grant_type()),
+    };
+    issue_tokens(&mut conn, &oauth_settings, &signer, &client, grant).await
+}
+
+async fn authorization_code_grant(
+    redis_service: &RedisService,
+    client: &OAuthClient,
+    form: &TokenRequest,
+) -> Result<Grant, OAuthError> {
+    let code = form.code.as_deref().ok_or_else(|| OAuthError::invalid_request("Missing code"))?;
+    // Codes are single use, a replayed one finds nothing
+    let pending: PendingCode = match redis_service.take_value(&format!("oauth_code:{}", hash_token(code))).await? {
+        Some(pending) => serde_json::from_str(&pending).context("Invalid stored authorization code")?,
+        None => return Err(OAuthError::invalid_grant("Unknown, expired or already used code")),
+    };
+    if pending.client_id != client.client_id {
+        return Err(OAuthError::invalid_grant("The code was issued to another client"));
+    }
+    if form.redirect_uri.as_deref() != Some(pending.redirect_uri.as_str()) {
+        return Err(OAuthError::invalid_grant("redirect_uri doesn't match the authorization request"));
+    }
+    if let Some(code_challenge) = &pending.code_challenge {
+        let verified = form
+            .code_verifier
+            .as_deref()
+            .is_some_and(|verifier| pkce_challenge(verifier) == *code_challenge);
+        if !verified {
+            return Err(OAuthError::invalid_grant("code_verifier doesn't match the code challenge"));
+        }
+    }
+    Ok(Grant {
+        user_id: Some(pending.user_id),
+        scopes: pending.scopes,
+        nonce: pending.nonce,
+        auth_time: Some(pending.auth_time),
+    })
+}
+
+async fn refresh_token_grant(
+    conn: &mut AsyncPgConnection,
+    client: &OAuthClient,
+    form: &TokenRequest,
+) -> Result<Grant, OAuthError> {
+    let refresh_token = form
+        .refresh_token
+        .as_deref()
+        .ok_or_else(|| OAuthError::invalid_request("Missing refresh_token"))?;
+    let stored = find_token(conn, refresh_token)
+        .await?
+        .filter(|stored| stored.kind == REFRESH_TOKEN && stored.client_id == client.client_id)
+        .ok_or_else(|| OAuthError::invalid_grant("Unknown refresh token"))?;
+    let user_id = stored.user_id.ok_or_else(|| OAuthError::invalid_grant("Unknown refresh token"))?;
+
+    if stored.revoked_at.is_some() {
+        // Refresh tokens rotate, a used one showing up again means it leaked
+        tracing::warn!("Revoked refresh token reused by client {} for user {}", client.client_id, user_id);
+        revoke_user_tokens(conn, user_id, &client.client_id).await.map_err(DbError)?;
+        return Err(OAuthError::invalid_grant("Refresh token revoked"));
+    }
+    if !stored.is_active() {
+        return Err(OAuthError::invalid_grant("Refresh token expired"));
+    }
+    if !user_is_active(conn, user_id).await? {
+        return Err(OAuthError::invalid_grant("Account is not active"));
+    }
+
+    let scopes = refreshed_scopes(form.scope.as_deref(), stored.scopes)?;
+
+    let rotated = diesel::update(
+        oauth_tokens::table
+            .find(hash_token(refresh_token))
+            .filter(oauth_tokens::revoked_at.is_null()),
+    )
+    .set(oauth_tokens::revoked_at.eq(Utc::now().naive_utc()))
+    .execute(conn)
+    .await
+    .map_err(DbError)?;
+    // Another request redeemed it first
+    if rotated == 0 {
+        return Err(OAuthError::invalid_grant("Refresh token revoked"));
+    }
+    Ok(Grant {
+        user_id: Some(user_id),
+        scopes,
+        nonce: None,
+        auth_time: None,
+    })
+}
+
+/// Scopes asked for on refresh, never more than the original grant
+fn refreshed_scopes(requested: Option<&str>, granted: Vec<String>) -> Result<Vec<String>, OAuthError> {
+    match requested {
+        Some(scope) => {
+            let scopes = parse_scopes(scope);
+            if scopes.iter().any(|scope| !granted.contains(scope)) {
+                return Err(OAuthError::invalid_scope("Scopes must be a subset of the original grant"));
+            }
+            Ok(scopes)
+        }
+        None => Ok(granted),
+    }
+}
+
+fn client_credentials_grant(client: &OAuthClient, form: &TokenRequest) -> Result<Grant, OAuthError> {
+    if !client.is_confidential() {
+        return Err(OAuthError::unauthorized_client("Public clients can't use client_credentials"));
+    }
+    // No user is involved, identity scopes make no sense here
+    let allowed: Vec<String> = client
+        .scopes
+        .iter()
+        .filter(|scope| !matches!(scope.as_str(), "openid" | "offline_access"))
+        .cloned()
+        .collect();
+    let scopes = match form.scope.as_deref() {
+        Some(scope) => parse_scopes(scope),
+        None => allowed.clone(),
+    };
+    if scopes.iter().any(|scope| !allowed.contains(scope)) {
+        return Err(OAuthError::invalid_scope("Scope not allowed for client_credentials"));
+    }
+    Ok(Grant {
+        user_id: None,
+        scopes,
+        nonce: None,
+        auth_time: None,
+    })
+}
+
+/******************************************/
+// OAuth introspection Route
+/******************************************/
+/**
+ * @route   POST /oauth/introspect
+ * @access  Confidential client authentication
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/oauth/introspect",
+    tag = "oauth",
+    request_body(content = TokenForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Token state, only `active: false` for unknown, expired or revoked tokens and tokens of inactive accounts", body = IntrospectionResponse),
+        (status = 400, description = "unauthorized_client, public clients can't introspect"),
+        (status = 401, description = "invalid_client")
+    )
+)]
+#[instrument(name = "OAuth introspect", skip(req, form, pool))]
+pub async fn introspect(
+    req: HttpRequest,
+    form: web::Form<TokenForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, OAuthError> {
+    let form = form.into_inner();
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+    let client = authenticate_client(&mut conn, &req, form.client_id.as_deref(), form.client_secret.as_deref()).await?;
+    if !client.is_confidential() {
+        return Err(OAuthError::unauthorized_client("Only confidential clients can introspect tokens"));
+    }
+
+    let stored = match find_token(&mut conn, &form.token).await?.filter(StoredToken::is_active) {
+        Some(stored) => match stored.user_id {
+            Some(user_id) if !user_is_active(&mut conn, user_id).await? => None,
+            _ => Some(stored),
+        },
+        None => None,
+    };
+    let response = match stored {
+        Some(stored) => IntrospectionResponse {
+            active: true,
+            scope: Some(stored.scopes.join(" ")),
+            client_id: Some(stored.client_id),
+            sub: stored.user_id.map(|user_id| user_id.to_string()),
+            token_type: Some(stored.kind),
+            exp: Some(stored.expires_at.and_utc().timestamp()),
+            iat: Some(stored.created_at.and_utc().timestamp()),
+        },
+        None => IntrospectionResponse::default(),
+    };
+    Ok(HttpResponse::Ok().insert_header((CACHE_CONTROL, "no-store")).json(response))
+}
+
+/******************************************/
+// OAuth revocation Route
+/******************************************/
+/**
+ * @route   POST /oauth/revoke
+ * @access  Client authentication
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/oauth/revoke",
+    tag = "oauth",
+    request_body(content = TokenForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Token revoked, also answered for unknown tokens"),
+        (status = 401, description = "invalid_client")
+    )
+)]
+#[instrument(name = "OAuth revoke", skip(req, form, pool))]
+pub async fn revoke(
+    req: HttpRequest,
+    form: web::Form<TokenForm>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, OAuthError> {
+    let form = form.into_inner();
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+    let client = authenticate_client(&mut conn, &req, form.client_id.as_deref(), form.client_secret.as_deref()).await?;
+
+    // Clients can only revoke their own tokens, anything else is ignored
+    let Some(stored) = find_token(&mut conn, &form.token)
+        .await?
+        .filter(|stored| stored.client_id == client.client_id)
+    else {
+        return Ok(HttpResponse::Ok().finish());
+    };
+    match (stored.kind.as_str(), stored.user_id) {
+        // Access tokens minted from the refresh token go with it
+        (REFRESH_TOKEN, Some(user_id)) => {
+            revoke_user_tokens(&mut conn, user_id, &client.client_id).await.map_err(DbError)?;
+        }
+        _ => {
+            diesel::update(
+                oauth_tokens::table
+                    .find(hash_token(&form.token))
+                    .filter(oauth_tokens::revoked_at.is_null()),
+            )
+            .set(oauth_tokens::revoked_at.eq(Utc::now().naive_utc()))
+            .execute(&mut conn)
+            .await
+            .map_err(DbError)?;
+        }
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+/******************************************/
+// OIDC userinfo Route
+/******************************************/
+/**
+ * @route   GET /oauth/userinfo
+ * @access  OAuth access token with the openid scope
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/oauth/userinfo",
+    tag = "oauth",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Claims of the token's user allowed by its scopes", body = UserInfoResponse),
+        (status = 401, description = "invalid_token, missing, expired, revoked, without the openid scope or of an inactive account")
+    )
+)]
+#[instrument(name = "OIDC userinfo", skip(req, pool))]
+pub async fn userinfo(req: HttpRequest, pool: web::Data<PgPool>) -> Result<HttpResponse, OAuthError> {
+    let access_token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(OAuthError::invalid_token)?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let stored = find_token(&mut conn, access_token)
+        .await?
+        .filter(|stored| stored.kind == ACCESS_TOKEN && stored.is_active())
+        .filter(|stored| stored.scopes.iter().any(|scope| scope == "openid"))
+        .ok_or_else(OAuthError::invalid_token)?;
+    let user_id = stored.user_id.ok_or_else(OAuthError::invalid_token)?;
+    if !user_is_active(&mut conn, user_id).await? {
+        return Err(OAuthError::invalid_token());
+    }
+
+    let info = user_info(&mut conn, user_id, &stored.scopes).await?;
+    Ok(HttpResponse::Ok().insert_header((CACHE_CONTROL, "no-store")).json(info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{client_credentials_grant, refreshed_scopes};
+    use crate::routes::oauth::models::{OAuthClient, TokenRequest};
+
+    fn client(secret: Option<&str>, scopes: &[&str]) -> OAuthClient {
+        OAuthClient {
+            client_id: "client".to_string(),
+            client_secret_hash: secret.map(str::to_string),
+            name: "Client".to_string(),
+            redirect_uris: vec!["https://app.example/callback".to_string()],
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+            first_party: false,
+            owner_id: None,
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    fn form(scope: Option<&str>) -> TokenRequest {
+        TokenRequest {
+            grant_type: "client_credentials".to_string(),
+            code: None,
+            redirect_uri: None,
+            code_verifier: None,
+            refresh_token: None,
+            scope: scope.map(str::to_string),
+            client_id: None,
+            client_secret: None,
+        }
+    }
+
+    fn scopes(scopes: &[&str]) -> Vec<String> {
+        scopes.iter().map(|scope| scope.to_string()).collect()
+    }
+
+    #[test]
+    fn client_credentials_drop_identity_scopes() {
+        let client = client(Some("hash"), &["openid", "profile", "email", "offline_access"]);
+
+        let grant = client_credentials_grant(&client, &form(None)).unwrap();
+        assert_eq!(grant.scopes, scopes(&["profile", "email"]));
+        assert!(grant.user_id.is_none());
+
+        let grant = client_credentials_grant(&client, &form(Some("email"))).unwrap();
+        assert_eq!(grant.scopes, scopes(&["email"]));
+
+        let err = client_credentials_grant(&client, &form(Some("email openid"))).err().unwrap();
+        assert_eq!(err.error, "invalid_scope");
+        let err = client_credentials_grant(&client, &form(Some("admin"))).err().unwrap();
+        assert_eq!(err.error, "invalid_scope");
+    }
+
+    #[test]
+    fn client_credentials_need_a_confidential_client() {
+        let client = client(None, &["profile"]);
+
+        let err = client_credentials_grant(&client, &form(None)).err().unwrap();
+        assert_eq!(err.error, "unauthorized_client");
+    }
+
+    #[test]
+    fn refreshed_scopes_stay_within_the_grant() {
+        let granted = scopes(&["openid", "profile", "offline_access"]);
+
+        assert_eq!(refreshed_scopes(None, granted.clone()).unwrap(), granted);
+        assert_eq!(
+            refreshed_scopes(Some("profile openid profile"), granted.clone()).unwrap(),
+            scopes(&["profile", "openid"])
+        );
+        let err = refreshed_scopes(Some("profile email"), granted).err().unwrap();
+        assert_eq!(err.error, "invalid_scope");
+    }
+}
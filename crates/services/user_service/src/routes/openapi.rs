@@ -0,0 +1,70 @@
+use actix_web::HttpResponse;
+use helpers::openapi::BearerAuth;
+use helpers::validations::validations::{CreateUserBody, LoginUserBody, UpdateUserBody};
+use utoipa::OpenApi;
+
+use crate::routes::oauth::{authorize, clients, discovery, token};
+use crate::routes::oauth::models::{
+    AuthorizeDecision, AuthorizeQuery, AuthorizeRedirect, ConsentPrompt, ConsentResponse, CreateClientBody,
+    CreatedClient, IntrospectionResponse, OAuthClientResponse, TokenForm, TokenRequest, TokenResponse, UserInfoResponse,
+};
+use crate::routes::user::{account, crud, export, mfa, oidc};
+use crate::routes::user::model::{DeleteAccountBody, DeletionScheduled, ExportRequested, MfaCodeBody, MfaEnrollment, MfaLoginBody, OidcAuthorization, RecoveryCodes};
+use crate::routes::user::response::UserResponse;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "User Service"),
+    paths(
+        crud::register_user,
+        crud::login_user,
+        crud::verify_email,
+        crud::view_user,
+        crud::logout_user,
+        crud::update_user,
+        crud::resend_verification_email,
+        mfa::enroll_mfa,
+        mfa::verify_mfa,
+        mfa::disable_mfa,
+        mfa::login_mfa,
+        account::deactivate_account,
+        account::request_deletion,
+        export::request_export,
+        export::download_export,
+        oidc::oidc_authorize,
+        oidc::oidc_callback,
+        clients::create_client,
+        clients::list_clients,
+        clients::delete_client,
+        clients::list_consents,
+        clients::revoke_consent,
+        authorize::authorize,
+        authorize::decide_consent,
+        token::token,
+        token::introspect,
+        token::revoke,
+        token::userinfo,
+        discovery::openid_configuration,
+        discovery::jwks
+    ),
+    components(schemas(
+        CreateUserBody, LoginUserBody, UpdateUserBody, UserResponse,
+        MfaCodeBody, MfaLoginBody, MfaEnrollment, RecoveryCodes, OidcAuthorization,
+        DeleteAccountBody, DeletionScheduled, ExportRequested,
+        CreateClientBody, CreatedClient, OAuthClientResponse, ConsentResponse, AuthorizeQuery, AuthorizeDecision,
+        ConsentPrompt, AuthorizeRedirect, TokenRequest, TokenResponse, TokenForm, IntrospectionResponse, UserInfoResponse
+    )),
+    modifiers(&BearerAuth)
+)]
+pub struct ApiDoc;
+
+/******************************************/
+// OpenAPI spec route
+/******************************************/
+/**
+ * @route   GET /api-docs/openapi.json
+ * @access  Public
+ */
+pub async fn openapi_spec() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
@@ -0,0 +1,234 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use errors::{AuthError, CustomError};
+use flume::Sender;
+use helpers::auth_jwt::auth::Claims;
+use kafka::channel::{push_to_broker, KafkaMessage};
+use kafka::models::{UserEventType, UserEventsMessage};
+use lib_config::config::configuration::AccountSettings;
+use lib_config::db::db::PgPool;
+use lib_config::send_mail::send::send_deletion_scheduled;
+use lib_config::session::redis::RedisService;
+use serde_json::json;
+use tracing::instrument;
+use utils::telemetry::spawn_blocking_with_tracing;
+use uuid::Uuid;
+
+use crate::db_errors::DbError;
+use crate::schema::{oauth_tokens, users};
+
+use super::mfa::session_user;
+use super::model::{DbAccountStatus, DeleteAccountBody, DeletionScheduled};
+use super::validate_user::verify_password;
+
+/// Moves the account from one of `from` to `to` and tells the other services. Leaving
+/// `Active` ends every session and revokes the OAuth tokens. False when the account wasn't
+/// in one of `from`.
+pub async fn change_status(
+    conn: &mut AsyncPgConnection,
+    redis_service: &RedisService,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    user_id: Uuid,
+    from: &[DbAccountStatus],
+    to: DbAccountStatus,
+    deletion_scheduled_at: Option<NaiveDateTime>,
+) -> Result<bool, CustomError> {
+    let now = Utc::now().naive_utc();
+    let changed = diesel::update(users::table.find(user_id).filter(users::status.eq_any(from)))
+        .set((
+            users::status.eq(to),
+            users::status_changed_at.eq(now),
+            users::deletion_scheduled_at.eq(deletion_scheduled_at),
+        ))
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+    if changed == 0 {
+        return Ok(false);
+    }
+
+    if to != DbAccountStatus::Active {
+        redis_service.delete_all_sessions(&user_id.to_string()).await?;
+        diesel::update(
+            oauth_tokens::table
+                .filter(oauth_tokens::user_id.eq(user_id))
+                .filter(oauth_tokens::revoked_at.is_null()),
+        )
+        .set(oauth_tokens::revoked_at.eq(now))
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+    }
+
+    let message = UserEventsMessage {
+        user_id,
+        event_type: UserEventType::StatusChange { status: to.into(), time: now },
+    };
+    let _ = push_to_broker(kafka_producer, &message)
+        .await
+        .context("Failed to send message to broker");
+    Ok(true)
+}
+
+/// Refuses logins of suspended and erased accounts, checked before any second factor
+pub async fn check_login_allowed(conn: &mut AsyncPgConnection, user_id: Uuid) -> Result<(), CustomError> {
+    let status = users::table
+        .find(user_id)
+        .select(users::status)
+        .first::<DbAccountStatus>(conn)
+        .await
+        .map_err(DbError)?;
+    match status {
+        DbAccountStatus::Suspended => Err(CustomError::DatabaseError {
+            msg: format!("Login refused for suspended user {}", user_id),
+            resp: "This account has been suspended".to_string(),
+            status_code: StatusCode::FORBIDDEN,
+        }),
+        DbAccountStatus::Erased => Err(AuthError::InvalidCredentials(anyhow::anyhow!("Login to an erased account")).into()),
+        _ => Ok(()),
+    }
+}
+
+/// Called right before a session is created: a deactivated account is reactivated and a
+/// pending deletion cancelled
+pub async fn admit_login(
+    conn: &mut AsyncPgConnection,
+    redis_service: &RedisService,
+    kafka_producer: &Sender<KafkaMessage<String>>,
+    user_id: Uuid,
+) -> Result<(), CustomError> {
+    check_login_allowed(conn, user_id).await?;
+    let reactivated = change_status(
+        conn,
+        redis_service,
+        kafka_producer,
+        user_id,
+        &[DbAccountStatus::Deactivated, DbAccountStatus::PendingDeletion],
+        DbAccountStatus::Active,
+        None,
+    )
+    .await?;
+    if reactivated {
+        tracing::info!("User {} reactivated by logging in", user_id);
+    }
+    Ok(())
+}
+
+/******************************************/
+// Deactivate account Route
+/******************************************/
+/**
+ * @route   POST /user/protected/deactivate
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/protected/deactivate",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Account deactivated and logged out everywhere, logging in reactivates it"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "Deactivate account", skip(pool, req, redis_service, kafka_producer))]
+pub async fn deactivate_account(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = session_user(req.into_inner(), &redis_service).await?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    change_status(
+        &mut conn,
+        &redis_service,
+        &kafka_producer,
+        user_id,
+        &[DbAccountStatus::Active],
+        DbAccountStatus::Deactivated,
+        None,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(json!({"message": "Account deactivated, log in again to reactivate it"})))
+}
+
+/******************************************/
+// Delete account Route
+/******************************************/
+/**
+ * @route   POST /user/protected/delete
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/protected/delete",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    request_body = DeleteAccountBody,
+    responses(
+        (status = 200, description = "Deletion scheduled after the grace period, logging in before cancels it", body = DeletionScheduled),
+        (status = 400, description = "Password missing"),
+        (status = 401, description = "Invalid token, session or password")
+    )
+)]
+#[instrument(name = "Request account deletion", skip(pool, req, body, redis_service, kafka_producer, account_settings))]
+pub async fn request_deletion(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    body: web::Json<DeleteAccountBody>,
+    redis_service: web::Data<RedisService>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    account_settings: web::Data<AccountSettings>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = session_user(req.into_inner(), &redis_service).await?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let (user_email, stored_hash) = users::table
+        .find(user_id)
+        .select((users::email, users::password_hash))
+        .first::<(String, Option<String>)>(&mut conn)
+        .await
+        .map_err(DbError)?;
+    // A stolen token alone must not be enough to delete an account with a password
+    if let Some(stored_hash) = stored_hash {
+        let password = body
+            .into_inner()
+            .password
+            .ok_or(CustomError::ValidationError("Enter your password to delete your account.".to_string()))?;
+        let is_valid = spawn_blocking_with_tracing(move || verify_password(&stored_hash, password))
+            .await
+            .context("Failed to due to JoinError")?;
+        if !is_valid {
+            return Err(AuthError::InvalidCredentials(anyhow::anyhow!("Wrong password for account deletion")).into());
+        }
+    }
+
+    let deletion_scheduled_at = (Utc::now() + Duration::days(account_settings.deletion_grace_days)).naive_utc();
+    change_status(
+        &mut conn,
+        &redis_service,
+        &kafka_producer,
+        user_id,
+        &[DbAccountStatus::Active, DbAccountStatus::Deactivated],
+        DbAccountStatus::PendingDeletion,
+        Some(deletion_scheduled_at),
+    )
+    .await?;
+
+    if let Err(e) = send_deletion_scheduled(&user_email, &deletion_scheduled_at.format("%Y-%m-%d %H:%M").to_string()).await {
+        tracing::warn!("Failed to send deletion confirmation to user {}: {:?}", user_id, e);
+    }
+    Ok(HttpResponse::Ok().json(DeletionScheduled { deletion_scheduled_at }))
+}
@@ -1,14 +1,19 @@
 use chrono::{NaiveDateTime, Utc};
-use helpers::auth_jwt::auth::{create_jwt, Claims, Role};
+use helpers::auth_jwt::auth::{create_jwt, create_mfa_challenge, mfa_challenge_key, Claims, Role, MFA_CHALLENGE_MINUTES};
 use flume::Sender;
 use kafka::channel::{push_to_broker, KafkaMessage};
 use kafka::models::{UserEventType, UserEventsMessage};
+use lib_config::config::configuration::AuthSettings;
 use lib_config::db::db::PgPool;
+use lib_config::session::lockout::LoginLockout;
 use errors::{AuthError, CustomError};
 use serde::Deserialize;
 use crate::db_errors;
 use crate::schema::users::dsl::*;
 use crate::routes::user::validate_user::validate_credentials;
+use crate::routes::user::mfa::mfa_enabled;
+use crate::routes::user::account::{admit_login, check_login_allowed};
+use crate::routes::user::devices::{remember_device, ClientInfo};
 use helpers::validations::validations::{CreateUserBody, LoginUserBody, generate_random_salt, UpdateUserBody, check_password_strength};
 use actix_web::{web, HttpResponse, HttpRequest};
 use argon2::{self, Argon2, PasswordHasher};
@@ -35,6 +40,16 @@ use crate::schema::email_verifications::dsl as email_verification_dsl;
  * @route   POST /register
  * @access  Public
  */
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/register",
+    tag = "users",
+    request_body = CreateUserBody,
+    responses(
+        (status = 200, description = "User created, returns a JWT token"),
+        (status = 400, description = "Invalid user data or user already exists")
+    )
+)]
 #[instrument(name = "Register a new user", skip(req_user, pool, redis_service), fields(username = %req_user.username, email = %req_user.email))]
 pub async fn register_user(
     pool: web::Data<PgPool>,
@@ -77,8 +92,9 @@ pub async fn register_user(
 
     let _ = push_to_broker(&kafka_producer, &result).await;
     
-    let (token, sid) = create_jwt(&user_id.to_string(), Role::User)?;
+    let (token, sid) = create_jwt(&user_id.to_string(), Role::User, Vec::new())?;
     let _= redis_service.set_session(&sid, &user_id.to_string(), false).await?;
+    redis_service.track_session(&user_id.to_string(), &sid).await?;
 
     let mail_token = generate_token();
     let expires_at = Utc::now() + Duration::hours(24); // 24 hours
@@ -111,19 +127,87 @@ pub async fn register_user(
  * @route   POST /login
  * @access  Public
  */
-#[instrument(name = "Login a customer", skip(req_login, pool, redis_service), fields(username = %req_login.email))]
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/login",
+    tag = "users",
+    request_body = LoginUserBody,
+    responses(
+        (status = 200, description = "Logged in, returns a JWT token, or a challenge token when 2FA is enabled"),
+        (status = 401, description = "Invalid credentials"),
+        (status = 429, description = "Too many failed logins for the account or IP, locked for a while")
+    )
+)]
+#[instrument(name = "Login a customer", skip(req, req_login, pool, redis_service, auth_settings), fields(username = %req_login.email))]
 
 pub async fn login_user(
+    req: HttpRequest,
     pool: web::Data<PgPool>,
     req_login: web::Json<LoginUserBody>,
     redis_service: web::Data<RedisService>,
-    kafka_producer: web::Data<Sender<KafkaMessage<String>>>
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    auth_settings: web::Data<AuthSettings>
 ) -> Result<HttpResponse, CustomError> {
-    let user_id = validate_credentials(&pool, &req_login.into_inner()).await?;
+    let req_login = req_login.into_inner();
+    let client = ClientInfo::from_request(&req, &auth_settings.trusted_proxies);
+    let lockout = LoginLockout::new(&redis_service, &auth_settings.lockout, "user");
+    lockout.ensure_not_locked(&req_login.email, client.ip.as_deref()).await?;
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+    let user_id = match validate_credentials(&pool, &req_login).await {
+        Ok(user_id) => user_id,
+        Err(err @ CustomError::AuthenticationError(_)) => {
+            let locked = lockout.record_failure(&req_login.email, client.ip.as_deref()).await?;
+            let account: Option<Uuid> = users
+                .filter(email.eq(&req_login.email))
+                .select(id)
+                .first(&mut conn)
+                .await
+                .optional()
+                .map_err(db_errors::DbError)?;
+            if let Some(user_id) = account {
+                let message = UserEventsMessage{
+                    user_id,
+                    event_type: UserEventType::LoginFailed {
+                        ip: client.ip,
+                        user_agent: client.user_agent,
+                        locked,
+                        time: Utc::now().naive_utc()
+                    }
+                };
+                let _ = push_to_broker(&kafka_producer, &message)
+                    .await
+                    .context("Failed to send message to broker");
+            }
+            return Err(err);
+        }
+        Err(err) => return Err(err),
+    };
+    check_login_allowed(&mut conn, user_id).await?;
+
+    // Failures are only forgotten once the second factor passed too
+    if mfa_enabled(&mut conn, user_id).await? {
+        // No session until the second factor is checked by /login/2fa
+        let (challenge_token, jti) = create_mfa_challenge(&user_id.to_string(), Role::User)?;
+        redis_service
+            .store_value(&mfa_challenge_key(&jti), &user_id.to_string(), (MFA_CHALLENGE_MINUTES * 60) as usize)
+            .await?;
+        return Ok(HttpResponse::Ok().json(json!({
+            "mfa_required": true,
+            "challenge_token": challenge_token
+        })));
+    }
+    lockout.clear_failures(&req_login.email).await?;
+    admit_login(&mut conn, &redis_service, &kafka_producer, user_id).await?;
+    remember_device(&mut conn, user_id, &client).await?;
 
-    let (token, sid) = create_jwt(&user_id.to_string(), Role::User)?;
+    let (token, sid) = create_jwt(&user_id.to_string(), Role::User, Vec::new())?;
     
     let _= redis_service.set_session(&sid, &user_id.to_string(), false).await?;
+    redis_service.track_session(&user_id.to_string(), &sid).await?;
 
     let message = UserEventsMessage{
         user_id,
@@ -144,6 +228,16 @@ pub async fn login_user(
  * @route   POST /user/protected/logout
  * @access  JWT Protected
  */
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/protected/logout",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Logout successful"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
 #[instrument(name = "Logout a user", skip(session, req))]
 pub async fn logout_user(
     session: web::Data<RedisService>,
@@ -186,6 +280,17 @@ pub async fn logout_user(
  * @route   Get /protected/view
  * @access  JWT Protected
  */
+#[utoipa::path(
+    get,
+    path = "/api/v1/user/protected/view_user",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Profile of the logged in user", body = UserResponse),
+        (status = 400, description = "Email not verified yet"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
 #[instrument(name = "Get user", skip(pool, req, redis_service))]
 pub async fn view_user(
     pool: web::Data<PgPool>,
@@ -205,13 +310,13 @@ pub async fn view_user(
         .get()
         .await
         .context("Failed to fetch connection from pool")?;
-    let status:StatusEnum = email_verification_dsl::email_verifications
+    let verification_status: StatusEnum = email_verification_dsl::email_verifications
         .filter(email_verification_dsl::user_id.eq(user_id.clone()))
         .select(email_verification_dsl::status)
         .first::<StatusEnum>(&mut conn)
         .await
         .map_err(|err| db_errors::DbError(err))?;
-    if(status == StatusEnum::Pending){
+    if(verification_status == StatusEnum::Pending){
     return Err(CustomError::ValidationError("Please verify your email before proceeding.".to_string()));
     }
 
@@ -233,6 +338,18 @@ pub async fn view_user(
  * @route   PUT /user/protected/update
  * @access  JWT Protected
  */
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/protected/update",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    request_body = UpdateUserBody,
+    responses(
+        (status = 200, description = "User updated successfully"),
+        (status = 400, description = "Invalid user data or email not verified yet"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
 #[instrument(name = "Update user", skip(req_update, pool, redis_service), fields(username = %req_update.username))]
 pub async fn update_user(
     pool: web::Data<PgPool>,
@@ -254,13 +371,13 @@ pub async fn update_user(
         .get()
         .await
         .context("Failed to fetch connection from pool")?;
-    let status:StatusEnum = email_verification_dsl::email_verifications
+    let verification_status: StatusEnum = email_verification_dsl::email_verifications
                                     .filter(email_verification_dsl::user_id.eq(user_id.clone()))
                                     .select(email_verification_dsl::status)
                                     .first::<StatusEnum>(&mut conn)
                                     .await
                                     .map_err(|err| db_errors::DbError(err))?;
-    if(status == StatusEnum::Pending){
+    if(verification_status == StatusEnum::Pending){
         return Err(CustomError::ValidationError("Please verify your email before proceeding.".to_string()));
     }
     let user_email = req_update.email.clone();
@@ -311,10 +428,21 @@ pub async fn update_user(
  * @route   POST /user/verify-email
  * @access  Public
  */
-#[instrument(name = "Verify user email", skip(pool, query))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/verify-email",
+    tag = "users",
+    params(MailQuery),
+    responses(
+        (status = 200, description = "Email successfully verified"),
+        (status = 400, description = "Invalid or expired token")
+    )
+)]
+#[instrument(name = "Verify user email", skip(pool, query, kafka_producer))]
 pub async fn verify_email(
     pool: web::Data<PgPool>,
-    query: web::Query<MailQuery>
+    query: web::Query<MailQuery>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>
 ) -> Result<HttpResponse, CustomError> {
     let query: MailQuery = query.into_inner();
     let token = query.token;
@@ -335,9 +463,10 @@ pub async fn verify_email(
         .optional()
         .map_err(|err| db_errors::DbError(err))?;
 
-    if verification_record.is_none() {
-        return Err(CustomError::ValidationError("Invalid or expired token".to_string()));
-    }
+    let verification_record = match verification_record {
+        Some(record) => record,
+        None => return Err(CustomError::ValidationError("Invalid or expired token".to_string())),
+    };
 
     let _ = diesel::update(email_verification_dsl::email_verifications)
         .filter(email_verification_dsl::token.eq(token))
@@ -346,6 +475,14 @@ pub async fn verify_email(
         .await
         .map_err(|err| db_errors::DbError(err))?;
 
+    let message = UserEventsMessage{
+        user_id: verification_record.user_id,
+        event_type: UserEventType::EmailVerified { time: Utc::now().naive_utc() }
+    };
+    let _ = push_to_broker(&kafka_producer, &message)
+        .await
+        .context("Failed to send message to broker");
+
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Email successfully verified"
     })))
@@ -358,6 +495,17 @@ pub async fn verify_email(
  * @route   POST /user/resend-verification
  * @access  JWT Protected
  */
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/protected/resend-verification",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Verification email resent"),
+        (status = 400, description = "Email already verified"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
 #[instrument(name = "Resend email verification", skip(pool, redis_service, req))]
 pub async fn resend_verification_email(
     pool: web::Data<PgPool>,
@@ -378,14 +526,14 @@ pub async fn resend_verification_email(
         .await
         .context("Failed to fetch connection from pool")?;
 
-    let status: StatusEnum = email_verification_dsl::email_verifications
+    let verification_status: StatusEnum = email_verification_dsl::email_verifications
         .filter(email_verification_dsl::user_id.eq(user_id.clone()))
         .select(email_verification_dsl::status)
         .first::<StatusEnum>(&mut conn)
         .await
         .map_err(|err| db_errors::DbError(err))?;
 
-    if status != StatusEnum::Pending {
+    if verification_status != StatusEnum::Pending {
         return Err(CustomError::ValidationError("Email is already verified or no pending verification".to_string()));
     }
 
@@ -0,0 +1,109 @@
+use std::net::IpAddr;
+
+use actix_web::http::header::USER_AGENT;
+use actix_web::HttpRequest;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use helpers::client_ip::client_ip;
+use lib_config::send_mail::send::send_login_alert;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_errors::DbError;
+use crate::schema::{user_devices, users};
+
+const MAX_USER_AGENT_LEN: usize = 255;
+
+/// Where a login comes from, the gateway forwards both through `X-Forwarded-For` and `User-Agent`
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn from_request(req: &HttpRequest, trusted_proxies: &[IpAddr]) -> Self {
+        let forwarded_for = req.headers().get("X-Forwarded-For").and_then(|value| value.to_str().ok());
+        let ip = client_ip(req.peer_addr().map(|addr| addr.ip()), forwarded_for, trusted_proxies);
+        let user_agent = req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LEN).collect());
+        Self { ip, user_agent }
+    }
+
+    /// Addresses of the same /24 (IPv4) or /48 (IPv6) count as one location
+    fn network(&self) -> String {
+        match self.ip.as_deref().map(|ip| ip.parse::<IpAddr>()) {
+            Some(Ok(IpAddr::V4(ip))) => {
+                let [a, b, c, _] = ip.octets();
+                format!("{}.{}.{}.0/24", a, b, c)
+            }
+            Some(Ok(IpAddr::V6(ip))) => {
+                let segments = ip.segments();
+                format!("{:x}:{:x}:{:x}::/48", segments[0], segments[1], segments[2])
+            }
+            Some(Err(_)) => self.ip.clone().unwrap_or_default(),
+            None => "unknown".to_string(),
+        }
+    }
+
+    fn device(&self) -> &str {
+        self.user_agent.as_deref().unwrap_or("unknown")
+    }
+}
+
+/// Records the device and network of a successful login and emails the user when either is
+/// new. The first login recorded for an account only learns it.
+#[instrument(name = "Remember login device", skip(conn, client))]
+pub async fn remember_device(conn: &mut AsyncPgConnection, user: Uuid, client: &ClientInfo) -> Result<(), DbError> {
+    let device = client.device();
+    let network = client.network();
+
+    let known: Vec<(String, String)> = user_devices::table
+        .filter(user_devices::user_id.eq(user))
+        .select((user_devices::user_agent, user_devices::network))
+        .load(conn)
+        .await
+        .map_err(DbError)?;
+    let new_device = !known.iter().any(|(known_device, _)| known_device == device);
+    let new_network = !known.iter().any(|(_, known_network)| *known_network == network);
+
+    let now = Utc::now().naive_utc();
+    diesel::insert_into(user_devices::table)
+        .values((
+            user_devices::user_id.eq(user),
+            user_devices::user_agent.eq(device),
+            user_devices::network.eq(&network),
+            user_devices::first_seen_at.eq(now),
+            user_devices::last_seen_at.eq(now),
+        ))
+        .on_conflict((user_devices::user_id, user_devices::user_agent, user_devices::network))
+        .do_update()
+        .set(user_devices::last_seen_at.eq(excluded(user_devices::last_seen_at)))
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+
+    if known.is_empty() || !(new_device || new_network) {
+        return Ok(());
+    }
+    let user_email = users::table
+        .find(user)
+        .select(users::email)
+        .first::<String>(conn)
+        .await
+        .map_err(DbError)?;
+    let device = device.to_string();
+    let ip = client.ip.clone().unwrap_or_else(|| "unknown".to_string());
+    // Sent in the background, a slow or failing mail server must not hold up or fail the login
+    tokio::spawn(async move {
+        if let Err(e) = send_login_alert(&user_email, &device, &ip, &now.format("%Y-%m-%d %H:%M").to_string()).await {
+            tracing::warn!("Failed to send new sign-in alert to user {}: {:?}", user, e);
+        }
+    });
+    Ok(())
+}
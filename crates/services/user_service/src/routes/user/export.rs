@@ -0,0 +1,116 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType, CACHE_CONTROL};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use errors::CustomError;
+use flume::Sender;
+use helpers::auth_jwt::auth::Claims;
+use kafka::channel::KafkaMessage;
+use lib_config::db::db::PgPool;
+use lib_config::session::redis::RedisService;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_errors::DbError;
+use crate::export::{export_archive, start_export};
+use crate::schema::email_verifications;
+
+use super::mfa::session_user;
+use super::model::{ExportDownloadQuery, ExportRequested, StatusEnum};
+
+/******************************************/
+// Request data export Route
+/******************************************/
+/**
+ * @route   POST /user/protected/export
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/protected/export",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 202, description = "Export started, a download link is emailed once every service has sent its data", body = ExportRequested),
+        (status = 400, description = "Email not verified"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "Request data export", skip(pool, req, redis_service, kafka_producer))]
+pub async fn request_export(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = session_user(req.into_inner(), &redis_service).await?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    // The link goes to the account email, it has to be the user's. Provider accounts get
+    // a verified row when the provider vouched for the email, so no row means unverified too
+    let verification = email_verifications::table
+        .find(user_id)
+        .select(email_verifications::status)
+        .first::<StatusEnum>(&mut conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    if verification != Some(StatusEnum::Verified) {
+        return Err(CustomError::ValidationError("Please verify your email before proceeding.".to_string()));
+    }
+
+    let export_id = start_export(&mut conn, &kafka_producer, user_id).await?;
+    Ok(HttpResponse::Accepted().json(ExportRequested { export_id }))
+}
+
+/******************************************/
+// Download data export Route
+/******************************************/
+/**
+ * @route   GET /users/exports/{export_id}
+ * @access  Public
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/exports/{export_id}",
+    tag = "users",
+    params(("export_id" = Uuid, Path, description = "Id of the export"), ExportDownloadQuery),
+    responses(
+        (status = 200, description = "ZIP archive with one JSON file per service", content_type = "application/zip"),
+        (status = 404, description = "Unknown export, wrong token or expired link")
+    )
+)]
+#[instrument(name = "Download data export", skip(pool, query))]
+pub async fn download_export(
+    pool: web::Data<PgPool>,
+    export_id: web::Path<Uuid>,
+    query: web::Query<ExportDownloadQuery>,
+) -> Result<HttpResponse, CustomError> {
+    let export_id = export_id.into_inner();
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let archive = export_archive(&mut conn, export_id, &query.token)
+        .await?
+        .ok_or(CustomError::DatabaseError {
+            msg: format!("No downloadable archive for export {}", export_id),
+            resp: "This download link is invalid or has expired".to_string(),
+            status_code: StatusCode::NOT_FOUND,
+        })?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!("export-{}.zip", export_id))],
+        })
+        .insert_header((CACHE_CONTROL, "no-store"))
+        .body(archive))
+}
@@ -0,0 +1,373 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use errors::{AuthError, CustomError};
+use flume::Sender;
+use helpers::auth_jwt::auth::{consume_mfa_challenge, create_jwt, verify_mfa_challenge, Claims, Role, MFA_CHALLENGE_MINUTES};
+use helpers::mfa::{generate_recovery_codes, generate_secret, hash_recovery_code, provisioning_uri, verify_totp};
+use kafka::channel::{push_to_broker, KafkaMessage};
+use kafka::models::{UserEventType, UserEventsMessage};
+use lib_config::config::configuration::AuthSettings;
+use lib_config::db::db::PgPool;
+use lib_config::session::lockout::LoginLockout;
+use lib_config::session::redis::RedisService;
+use serde_json::json;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_errors::DbError;
+use crate::schema::{user_mfa, user_recovery_codes, users};
+
+use super::account::admit_login;
+use super::devices::{remember_device, ClientInfo};
+use super::model::{MfaCodeBody, MfaEnrollment, MfaLoginBody, RecoveryCodes};
+
+/// Wrong codes allowed per login challenge before the password has to be entered again
+const MAX_CHALLENGE_ATTEMPTS: i64 = 5;
+
+pub async fn session_user(claims: Claims, redis_service: &RedisService) -> Result<Uuid, CustomError> {
+    let user_id = redis_service.get_user_from_session(&claims.sid).await?;
+    Uuid::parse_str(&user_id).map_err(|_| {
+        CustomError::AuthenticationError(AuthError::InvalidSession(anyhow::anyhow!("Invalid session ID")))
+    })
+}
+
+fn mfa_error(status_code: StatusCode, message: &str) -> CustomError {
+    CustomError::DatabaseError {
+        msg: message.to_string(),
+        resp: message.to_string(),
+        status_code,
+    }
+}
+
+/// True once the user has verified a code after enrolling
+pub async fn mfa_enabled(conn: &mut AsyncPgConnection, user: Uuid) -> Result<bool, DbError> {
+    user_mfa::table
+        .find(user)
+        .select(user_mfa::enabled_at.is_not_null())
+        .first::<bool>(conn)
+        .await
+        .optional()
+        .map(|enabled| enabled.unwrap_or(false))
+        .map_err(DbError)
+}
+
+/// Checks an authenticator code or consumes a recovery code of a user with 2FA enabled
+async fn check_second_factor(conn: &mut AsyncPgConnection, user: Uuid, code: &str) -> Result<bool, DbError> {
+    let stored = user_mfa::table
+        .find(user)
+        .filter(user_mfa::enabled_at.is_not_null())
+        .select((user_mfa::secret, user_mfa::last_used_step))
+        .first::<(String, Option<i64>)>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    let Some((secret, last_used_step)) = stored else {
+        return Ok(false);
+    };
+
+    let now = Utc::now();
+    if let Some(step) = verify_totp(&secret, code, now.timestamp() as u64, last_used_step) {
+        // Only one login can claim a time step, a replayed code updates nothing
+        let claimed = diesel::update(
+            user_mfa::table
+                .find(user)
+                .filter(user_mfa::last_used_step.is_null().or(user_mfa::last_used_step.lt(step))),
+        )
+        .set(user_mfa::last_used_step.eq(Some(step)))
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+        return Ok(claimed == 1);
+    }
+
+    let used = diesel::update(
+        user_recovery_codes::table
+            .find((user, hash_recovery_code(code)))
+            .filter(user_recovery_codes::used_at.is_null()),
+    )
+    .set(user_recovery_codes::used_at.eq(Some(now.naive_utc())))
+    .execute(conn)
+    .await
+    .map_err(DbError)?;
+    Ok(used == 1)
+}
+
+/******************************************/
+// Enrol 2FA Route
+/******************************************/
+/**
+ * @route   POST /user/protected/2fa/enroll
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/protected/2fa/enroll",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "New secret, 2FA is enabled once a code is verified", body = MfaEnrollment),
+        (status = 401, description = "Invalid token or session"),
+        (status = 409, description = "Two-factor authentication is already enabled")
+    )
+)]
+#[instrument(name = "Enrol 2FA", skip(pool, req, redis_service, auth_settings))]
+pub async fn enroll_mfa(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    redis_service: web::Data<RedisService>,
+    auth_settings: web::Data<AuthSettings>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = session_user(req.into_inner(), &redis_service).await?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    if mfa_enabled(&mut conn, user_id).await? {
+        return Err(mfa_error(StatusCode::CONFLICT, "Two-factor authentication is already enabled"));
+    }
+    let user_email = users::table
+        .find(user_id)
+        .select(users::email)
+        .first::<String>(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    let secret = generate_secret();
+    diesel::insert_into(user_mfa::table)
+        .values((user_mfa::user_id.eq(user_id), user_mfa::secret.eq(&secret)))
+        .on_conflict(user_mfa::user_id)
+        .do_update()
+        .set((
+            user_mfa::secret.eq(&secret),
+            user_mfa::last_used_step.eq(None::<i64>),
+            user_mfa::created_at.eq(Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(DbError)?;
+
+    Ok(HttpResponse::Ok().json(MfaEnrollment {
+        provisioning_uri: provisioning_uri(&auth_settings.mfa_issuer, &user_email, &secret),
+        secret,
+    }))
+}
+
+/******************************************/
+// Verify 2FA enrolment Route
+/******************************************/
+/**
+ * @route   POST /user/protected/2fa/verify
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/protected/2fa/verify",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    request_body = MfaCodeBody,
+    responses(
+        (status = 200, description = "2FA enabled, returns the recovery codes", body = RecoveryCodes),
+        (status = 400, description = "No enrolment started or wrong code"),
+        (status = 401, description = "Invalid token or session"),
+        (status = 409, description = "Two-factor authentication is already enabled")
+    )
+)]
+#[instrument(name = "Verify 2FA enrolment", skip(pool, req, req_code, redis_service))]
+pub async fn verify_mfa(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    req_code: web::Json<MfaCodeBody>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = session_user(req.into_inner(), &redis_service).await?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    let pending = user_mfa::table
+        .find(user_id)
+        .select((user_mfa::secret, user_mfa::enabled_at.is_not_null()))
+        .first::<(String, bool)>(&mut conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    let secret = match pending {
+        None => return Err(CustomError::ValidationError("Start the enrolment first.".to_string())),
+        Some((_, true)) => return Err(mfa_error(StatusCode::CONFLICT, "Two-factor authentication is already enabled")),
+        Some((secret, false)) => secret,
+    };
+    let step = verify_totp(&secret, &req_code.code, Utc::now().timestamp() as u64, None)
+        .ok_or(CustomError::ValidationError("Invalid code.".to_string()))?;
+
+    let recovery_codes = generate_recovery_codes();
+    let hashes: Vec<_> = recovery_codes
+        .iter()
+        .map(|code| {
+            (
+                user_recovery_codes::user_id.eq(user_id),
+                user_recovery_codes::code_hash.eq(hash_recovery_code(code)),
+            )
+        })
+        .collect();
+    conn.transaction::<_, DieselError, _>(|conn| {
+        async move {
+            diesel::update(user_mfa::table.find(user_id))
+                .set((
+                    user_mfa::enabled_at.eq(Some(Utc::now().naive_utc())),
+                    user_mfa::last_used_step.eq(Some(step)),
+                ))
+                .execute(conn)
+                .await?;
+            diesel::delete(user_recovery_codes::table.filter(user_recovery_codes::user_id.eq(user_id)))
+                .execute(conn)
+                .await?;
+            diesel::insert_into(user_recovery_codes::table)
+                .values(&hashes)
+                .execute(conn)
+                .await?;
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(DbError)?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodes { recovery_codes }))
+}
+
+/******************************************/
+// Disable 2FA Route
+/******************************************/
+/**
+ * @route   POST /user/protected/2fa/disable
+ * @access  JWT Protected
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/user/protected/2fa/disable",
+    tag = "users",
+    security(("bearer_auth" = [])),
+    request_body = MfaCodeBody,
+    responses(
+        (status = 200, description = "2FA disabled and recovery codes deleted"),
+        (status = 400, description = "2FA not enabled or wrong code"),
+        (status = 401, description = "Invalid token or session")
+    )
+)]
+#[instrument(name = "Disable 2FA", skip(pool, req, req_code, redis_service))]
+pub async fn disable_mfa(
+    pool: web::Data<PgPool>,
+    req: web::ReqData<Claims>,
+    req_code: web::Json<MfaCodeBody>,
+    redis_service: web::Data<RedisService>,
+) -> Result<HttpResponse, CustomError> {
+    let user_id = session_user(req.into_inner(), &redis_service).await?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+
+    if !check_second_factor(&mut conn, user_id, &req_code.code).await? {
+        return Err(CustomError::ValidationError("Two-factor authentication is not enabled or the code is invalid.".to_string()));
+    }
+    conn.transaction::<_, DieselError, _>(|conn| {
+        async move {
+            diesel::delete(user_recovery_codes::table.filter(user_recovery_codes::user_id.eq(user_id)))
+                .execute(conn)
+                .await?;
+            diesel::delete(user_mfa::table.find(user_id)).execute(conn).await?;
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(DbError)?;
+
+    Ok(HttpResponse::Ok().json(json!({"message": "Two-factor authentication disabled"})))
+}
+
+/******************************************/
+// Second factor login Route
+/******************************************/
+/**
+ * @route   POST /users/login/2fa
+ * @access  Public
+ */
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/login/2fa",
+    tag = "users",
+    request_body = MfaLoginBody,
+    responses(
+        (status = 200, description = "Logged in, returns a JWT token"),
+        (status = 401, description = "Invalid or expired challenge, wrong code or too many attempts"),
+        (status = 429, description = "Too many failed logins for the account or IP, locked for a while")
+    )
+)]
+#[instrument(name = "Login second factor", skip(req, pool, req_login, redis_service, kafka_producer, auth_settings))]
+pub async fn login_mfa(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    req_login: web::Json<MfaLoginBody>,
+    redis_service: web::Data<RedisService>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    auth_settings: web::Data<AuthSettings>,
+) -> Result<HttpResponse, CustomError> {
+    let req_login = req_login.into_inner();
+    let challenge = verify_mfa_challenge(&req_login.challenge_token, Role::User)
+        .map_err(|err| AuthError::InvalidCredentials(anyhow::anyhow!(err)))?;
+    let user_id = Uuid::parse_str(&challenge.sub)
+        .map_err(|err| AuthError::InvalidCredentials(anyhow::anyhow!(err)))?;
+
+    let attempts = redis_service
+        .increment_counter(&format!("mfa_attempts:{}", challenge.jti), (MFA_CHALLENGE_MINUTES * 60) as usize)
+        .await?;
+    if attempts > MAX_CHALLENGE_ATTEMPTS {
+        return Err(AuthError::InvalidCredentials(anyhow::anyhow!("Too many attempts, log in again")).into());
+    }
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+    // Wrong codes count against the account like wrong passwords, whatever challenge they came with
+    let user_email = users::table
+        .find(user_id)
+        .select(users::email)
+        .first::<String>(&mut conn)
+        .await
+        .map_err(DbError)?;
+    let client = ClientInfo::from_request(&req, &auth_settings.trusted_proxies);
+    let lockout = LoginLockout::new(&redis_service, &auth_settings.lockout, "user");
+    lockout.ensure_not_locked(&user_email, client.ip.as_deref()).await?;
+    if !check_second_factor(&mut conn, user_id, &req_login.code).await? {
+        lockout.record_failure(&user_email, client.ip.as_deref()).await?;
+        return Err(AuthError::InvalidCredentials(anyhow::anyhow!("Invalid second factor")).into());
+    }
+    consume_mfa_challenge(&redis_service, &challenge).await?;
+    lockout.clear_failures(&user_email).await?;
+    admit_login(&mut conn, &redis_service, &kafka_producer, user_id).await?;
+    remember_device(&mut conn, user_id, &client).await?;
+
+    let (token, sid) = create_jwt(&user_id.to_string(), Role::User, Vec::new())?;
+    redis_service.set_session(&sid, &user_id.to_string(), false).await?;
+    redis_service.track_session(&user_id.to_string(), &sid).await?;
+
+    let message = UserEventsMessage {
+        user_id,
+        event_type: UserEventType::Login { time: Utc::now().naive_utc() },
+    };
+    let _ = push_to_broker(&kafka_producer, &message)
+        .await
+        .context("Failed to send message to broker");
+
+    Ok(HttpResponse::Ok().json(json!({"token": token})))
+}
@@ -1,4 +1,9 @@
+pub mod account;
 pub mod crud;
+pub mod devices;
+pub mod export;
+pub mod mfa;
 pub mod model;
+pub mod oidc;
 pub mod validate_user;
 pub mod response;
\ No newline at end of file
@@ -1,19 +1,21 @@
 
 use chrono::{naive, NaiveDateTime};
 use diesel::{Queryable, Selectable};
-use kafka::models::{UserEventsMessage, UserEventType};
+use kafka::models::{AccountStatus, UserEventsMessage, UserEventType};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use helpers::validations::validations::UpdateUserBody;
 use diesel_derive_enum::DbEnum;
 use diesel::prelude::*;
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Queryable, Deserialize, Serialize, Debug, Selectable)]
 #[diesel(table_name = crate::schema::users)]
 pub struct User {
     pub id: Uuid,
     pub username: String,
-    pub password_hash: String,
+    /// `None` for accounts created through an identity provider
+    pub password_hash: Option<String>,
     pub email: String,
     pub created_at: Option<NaiveDateTime>,
     pub modified_at: Option<NaiveDateTime>,
@@ -38,6 +40,30 @@ pub enum StatusEnum {
     Verified,
     Expired,
 }
+/// Account lifecycle stored in `users.status`, published as `kafka::models::AccountStatus`
+#[derive(Debug, DbEnum, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, ToSchema)]
+#[ExistingTypePath = "crate::schema::sql_types::AccountStatus"]
+#[serde(rename_all = "snake_case")]
+pub enum DbAccountStatus {
+    Active,
+    Deactivated,
+    Suspended,
+    PendingDeletion,
+    Erased,
+}
+
+impl From<DbAccountStatus> for AccountStatus {
+    fn from(value: DbAccountStatus) -> Self {
+        match value {
+            DbAccountStatus::Active => AccountStatus::Active,
+            DbAccountStatus::Deactivated => AccountStatus::Deactivated,
+            DbAccountStatus::Suspended => AccountStatus::Suspended,
+            DbAccountStatus::PendingDeletion => AccountStatus::PendingDeletion,
+            DbAccountStatus::Erased => AccountStatus::Erased,
+        }
+    }
+}
+
 #[derive(Queryable, Deserialize, Serialize, Debug, Selectable)]
 #[diesel(table_name = crate::schema::email_verifications)]
 pub struct EmailVerification {
@@ -48,7 +74,75 @@ pub struct EmailVerification {
     pub status: StatusEnum,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct MailQuery{
     pub token: String
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct MfaCodeBody {
+    /// Current code of the authenticator app, or an unused recovery code
+    pub code: String,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct MfaLoginBody {
+    /// Token returned by the password login
+    pub challenge_token: String,
+    /// Current code of the authenticator app, or an unused recovery code
+    pub code: String,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct MfaEnrollment {
+    /// Base32 secret for apps that can't scan the QR code
+    pub secret: String,
+    /// `otpauth://` URI to show as a QR code
+    pub provisioning_uri: String,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct RecoveryCodes {
+    /// Shown only once, each code works a single time instead of an authenticator code
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OidcCallbackQuery {
+    pub state: String,
+    pub code: Option<String>,
+    /// Set by the provider instead of `code` when the user refused or the request was invalid
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct OidcAuthorization {
+    /// Provider page to send the browser to
+    pub authorization_url: String,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct DeleteAccountBody {
+    /// Current password, accounts created through an identity provider have none
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct DeletionScheduled {
+    /// When the account is erased unless the user logs in before
+    pub deletion_scheduled_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ExportRequested {
+    pub export_id: Uuid,
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportDownloadQuery {
+    /// Token from the emailed link
+    pub token: String,
+}
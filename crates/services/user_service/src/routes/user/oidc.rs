@@ -0,0 +1,512 @@
+use actix_web::cookie::time::Duration as CookieDuration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use diesel::result::Error as DieselError;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use errors::{AuthError, CustomError};
+use flume::Sender;
+use helpers::auth_jwt::auth::{create_jwt, create_mfa_challenge, mfa_challenge_key, Role, MFA_CHALLENGE_MINUTES};
+use helpers::oidc::{pkce_challenge, random_token, verify_id_token, IdTokenClaims, JwkSet};
+use helpers::validations::mail_token::generate_token;
+use kafka::channel::{push_to_broker, KafkaMessage};
+use kafka::models::{UserEventType, UserEventsMessage};
+use lib_config::config::configuration::{AuthSettings, OidcProviderSettings};
+use lib_config::db::db::PgPool;
+use lib_config::send_mail::send::send_email;
+use lib_config::session::redis::RedisService;
+use rand::Rng;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::db_errors::DbError;
+use crate::schema::{email_verifications, user_identities, users};
+
+use super::account::{admit_login, check_login_allowed};
+use super::devices::{remember_device, ClientInfo};
+use super::mfa::mfa_enabled;
+use super::model::{OidcAuthorization, OidcCallbackQuery, StatusEnum, User};
+
+/// Seconds the user has to come back from the provider
+const STATE_TTL_SECS: usize = 600;
+const MAX_USERNAME_LEN: usize = 32;
+/// Ties the `state` to the browser that started the login, so a callback URL with someone
+/// else's state can't log the victim into the attacker's account
+const BINDING_COOKIE: &str = "oidc_binding";
+
+/// Kept in Redis under the `state` between the two steps of the flow
+#[derive(Serialize, Deserialize)]
+struct PendingAuthorization {
+    provider: String,
+    nonce: String,
+    code_verifier: String,
+    browser_binding: String,
+}
+
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+    userinfo_endpoint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct UserInfo {
+    sub: String,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+}
+
+fn find_provider<'a>(auth_settings: &'a AuthSettings, name: &str) -> Result<&'a OidcProviderSettings, CustomError> {
+    auth_settings
+        .oidc_providers
+        .iter()
+        .find(|provider| provider.name == name)
+        .ok_or(CustomError::DatabaseError {
+            msg: format!("Identity provider {} is not configured", name),
+            resp: "Identity provider not found".to_string(),
+            status_code: StatusCode::NOT_FOUND,
+        })
+}
+
+async fn provider_metadata(client: &Client, provider: &OidcProviderSettings) -> Result<ProviderMetadata, CustomError> {
+    let url = format!("{}/.well-known/openid-configuration", provider.issuer.trim_end_matches('/'));
+    let metadata = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .context("Failed to fetch the identity provider configuration")?
+        .json()
+        .await
+        .context("Invalid identity provider configuration")?;
+    Ok(metadata)
+}
+
+fn rejected(reason: impl std::fmt::Display) -> CustomError {
+    AuthError::InvalidCredentials(anyhow::anyhow!("Identity provider login failed: {}", reason)).into()
+}
+
+/******************************************/
+// OIDC authorize Route
+/******************************************/
+/**
+ * @route   GET /users/oidc/{provider}/authorize
+ * @access  Public
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/oidc/{provider}/authorize",
+    tag = "users",
+    params(("provider" = String, Path, description = "Name of the configured identity provider")),
+    responses(
+        (status = 200, description = "Provider URL to send the browser to, with state, nonce and PKCE challenge, and a cookie binding the state to this browser", body = OidcAuthorization),
+        (status = 404, description = "Identity provider not found")
+    )
+)]
+#[instrument(name = "Start OIDC login", skip(redis_service, auth_settings))]
+pub async fn oidc_authorize(
+    provider: web::Path<String>,
+    redis_service: web::Data<RedisService>,
+    auth_settings: web::Data<AuthSettings>,
+) -> Result<HttpResponse, CustomError> {
+    let provider = find_provider(&auth_settings, &provider)?;
+    let metadata = provider_metadata(&Client::new(), provider).await?;
+
+    let state = random_token();
+    let pending = PendingAuthorization {
+        provider: provider.name.clone(),
+        nonce: random_token(),
+        code_verifier: random_token(),
+        browser_binding: random_token(),
+    };
+    let binding_cookie = Cookie::build(BINDING_COOKIE, pending.browser_binding.clone())
+        .path("/api/v1/users/oidc")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(CookieDuration::seconds(STATE_TTL_SECS as i64))
+        .finish();
+    let mut authorization_url = Url::parse(&metadata.authorization_endpoint)
+        .context("Invalid identity provider authorization endpoint")?;
+    authorization_url
+        .query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &provider.client_id)
+        .append_pair("redirect_uri", &provider.redirect_uri)
+        .append_pair("scope", &provider.scopes.join(" "))
+        .append_pair("state", &state)
+        .append_pair("nonce", &pending.nonce)
+        .append_pair("code_challenge", &pkce_challenge(&pending.code_verifier))
+        .append_pair("code_challenge_method", "S256");
+
+    let pending = serde_json::to_string(&pending).context("Failed to serialize authorization state")?;
+    redis_service
+        .store_value(&format!("oidc_state:{}", state), &pending, STATE_TTL_SECS)
+        .await?;
+
+    Ok(HttpResponse::Ok().cookie(binding_cookie).json(OidcAuthorization {
+        authorization_url: authorization_url.to_string(),
+    }))
+}
+
+/******************************************/
+// OIDC callback Route
+/******************************************/
+/**
+ * @route   GET /users/oidc/{provider}/callback
+ * @access  Public
+ */
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/oidc/{provider}/callback",
+    tag = "users",
+    params(
+        ("provider" = String, Path, description = "Name of the configured identity provider"),
+        OidcCallbackQuery
+    ),
+    responses(
+        (status = 200, description = "Logged in, returns a JWT token, or a challenge token when 2FA is enabled"),
+        (status = 400, description = "Unknown or expired state, state started in another browser, or the provider shared no email"),
+        (status = 401, description = "The provider refused the login or returned an invalid ID token"),
+        (status = 404, description = "Identity provider not found"),
+        (status = 409, description = "An account uses this email but it isn't verified, by the provider or by the account")
+    )
+)]
+#[instrument(name = "Finish OIDC login", skip(req, query, pool, redis_service, kafka_producer, auth_settings))]
+pub async fn oidc_callback(
+    req: HttpRequest,
+    provider: web::Path<String>,
+    query: web::Query<OidcCallbackQuery>,
+    pool: web::Data<PgPool>,
+    redis_service: web::Data<RedisService>,
+    kafka_producer: web::Data<Sender<KafkaMessage<String>>>,
+    auth_settings: web::Data<AuthSettings>,
+) -> Result<HttpResponse, CustomError> {
+    let query = query.into_inner();
+    let provider = find_provider(&auth_settings, &provider)?;
+
+    // The state is single use, a replayed callback finds nothing
+    let pending: PendingAuthorization = match redis_service.take_value(&format!("oidc_state:{}", query.state)).await? {
+        Some(pending) => serde_json::from_str(&pending).context("Invalid authorization state")?,
+        None => return Err(CustomError::ValidationError("Unknown or expired login state.".to_string())),
+    };
+    if pending.provider != provider.name {
+        return Err(CustomError::ValidationError("Login state belongs to another provider.".to_string()));
+    }
+    let binding = req.cookie(BINDING_COOKIE);
+    if binding.as_ref().map(|cookie| cookie.value()) != Some(pending.browser_binding.as_str()) {
+        return Err(CustomError::ValidationError("Login state was started in another browser.".to_string()));
+    }
+    if let Some(error) = query.error {
+        return Err(rejected(error));
+    }
+    let code = query.code.ok_or(CustomError::ValidationError("Missing authorization code.".to_string()))?;
+
+    let client = Client::new();
+    let metadata = provider_metadata(&client, provider).await?;
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("redirect_uri", provider.redirect_uri.as_str()),
+        ("client_id", provider.client_id.as_str()),
+        ("code_verifier", pending.code_verifier.as_str()),
+    ];
+    if !provider.client_secret.is_empty() {
+        form.push(("client_secret", provider.client_secret.as_str()));
+    }
+    let tokens: TokenResponse = client
+        .post(&metadata.token_endpoint)
+        .form(&form)
+        .send()
+        .await
+        .context("Failed to reach the identity provider")?
+        .error_for_status()
+        .map_err(rejected)?
+        .json()
+        .await
+        .map_err(rejected)?;
+
+    let jwks: JwkSet = client
+        .get(&metadata.jwks_uri)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .context("Failed to fetch the identity provider keys")?
+        .json()
+        .await
+        .context("Invalid identity provider keys")?;
+    let mut claims = verify_id_token(
+        &tokens.id_token,
+        &jwks,
+        provider.issuer.trim_end_matches('/'),
+        &provider.client_id,
+        &pending.nonce,
+    )
+    .map_err(rejected)?;
+
+    // Some providers only return the email from the userinfo endpoint
+    if claims.email.is_none() {
+        if let Some(userinfo_endpoint) = &metadata.userinfo_endpoint {
+            let userinfo: UserInfo = client
+                .get(userinfo_endpoint)
+                .bearer_auth(&tokens.access_token)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status())
+                .context("Failed to fetch the identity provider userinfo")?
+                .json()
+                .await
+                .context("Invalid identity provider userinfo")?;
+            // Userinfo has to describe the user the ID token was issued for
+            if userinfo.sub != claims.sub {
+                return Err(rejected("userinfo subject doesn't match the ID token"));
+            }
+            claims.email = userinfo.email;
+            claims.email_verified = userinfo.email_verified;
+        }
+    }
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to fetch connection from pool")?;
+    let (user_id, resolved) = resolve_identity(&mut conn, &provider.name, &claims).await?;
+
+    if let ResolvedIdentity::Linked = resolved {
+        // Sessions opened with the cleared password end as well
+        redis_service.delete_all_sessions(&user_id.to_string()).await?;
+    }
+    if let ResolvedIdentity::Created(user) = resolved {
+        let user_email = user.email.clone();
+        let message: UserEventsMessage = user.into();
+        let _ = push_to_broker(&kafka_producer, &message).await;
+        if claims.email_verified {
+            let message = UserEventsMessage {
+                user_id,
+                event_type: UserEventType::EmailVerified { time: Utc::now().naive_utc() },
+            };
+            let _ = push_to_broker(&kafka_producer, &message)
+                .await
+                .context("Failed to send message to broker");
+        } else {
+            start_email_verification(&mut conn, user_id, &user_email).await?;
+        }
+    }
+
+    check_login_allowed(&mut conn, user_id).await?;
+
+    if mfa_enabled(&mut conn, user_id).await? {
+        // Same second step as a password login, through /login/2fa
+        let (challenge_token, jti) = create_mfa_challenge(&user_id.to_string(), Role::User)?;
+        redis_service
+            .store_value(&mfa_challenge_key(&jti), &user_id.to_string(), (MFA_CHALLENGE_MINUTES * 60) as usize)
+            .await?;
+        return Ok(HttpResponse::Ok().json(json!({
+            "mfa_required": true,
+            "challenge_token": challenge_token
+        })));
+    }
+    admit_login(&mut conn, &redis_service, &kafka_producer, user_id).await?;
+    remember_device(&mut conn, user_id, &ClientInfo::from_request(&req, &auth_settings.trusted_proxies)).await?;
+
+    let (token, sid) = create_jwt(&user_id.to_string(), Role::User, Vec::new())?;
+    redis_service.set_session(&sid, &user_id.to_string(), false).await?;
+    redis_service.track_session(&user_id.to_string(), &sid).await?;
+
+    let message = UserEventsMessage {
+        user_id,
+        event_type: UserEventType::Login { time: Utc::now().naive_utc() },
+    };
+    let _ = push_to_broker(&kafka_producer, &message)
+        .await
+        .context("Failed to send message to broker");
+
+    Ok(HttpResponse::Ok().json(json!({"token": token})))
+}
+
+/// How the provider identity was matched to an account
+enum ResolvedIdentity {
+    /// Signed in before
+    Known,
+    /// First sign-in, linked to the account with the same verified email
+    Linked,
+    /// First sign-in, a new password-less account
+    Created(User),
+}
+
+/// User signed in by the provider identity: the already linked one, an existing account
+/// with the same email verified on both sides, or a new password-less account
+async fn resolve_identity(
+    conn: &mut AsyncPgConnection,
+    provider: &str,
+    claims: &IdTokenClaims,
+) -> Result<(Uuid, ResolvedIdentity), CustomError> {
+    let now = Utc::now().naive_utc();
+    let linked = diesel::update(user_identities::table.find((provider, &claims.sub)))
+        .set(user_identities::last_login_at.eq(now))
+        .returning(user_identities::user_id)
+        .get_result::<Uuid>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    if let Some(user_id) = linked {
+        return Ok((user_id, ResolvedIdentity::Known));
+    }
+
+    let user_email = claims
+        .email
+        .as_deref()
+        .ok_or(CustomError::ValidationError("The identity provider didn't share an email address.".to_string()))?;
+    let existing = users::table
+        .filter(users::email.eq(user_email))
+        .select(users::id)
+        .first::<Uuid>(conn)
+        .await
+        .optional()
+        .map_err(DbError)?;
+    if existing.is_some() && !claims.email_verified {
+        return Err(CustomError::DatabaseError {
+            msg: format!("Unverified provider email {} matches an account", user_email),
+            resp: "An account already uses this email, log in with your password".to_string(),
+            status_code: StatusCode::CONFLICT,
+        });
+    }
+    // Whoever registered an address they never verified may not own it, linking would hand
+    // the provider user an account whose password someone else knows
+    if let Some(user_id) = existing {
+        let locally_verified = diesel::select(diesel::dsl::exists(
+            email_verifications::table
+                .filter(email_verifications::user_id.eq(user_id))
+                .filter(email_verifications::status.eq(StatusEnum::Verified)),
+        ))
+        .get_result::<bool>(conn)
+        .await
+        .map_err(DbError)?;
+        if !locally_verified {
+            return Err(CustomError::DatabaseError {
+                msg: format!("Provider email {} matches unverified account {}", user_email, user_id),
+                resp: "An account already uses this email, log in with your password and verify your email first".to_string(),
+                status_code: StatusCode::CONFLICT,
+            });
+        }
+    }
+    let username = match existing {
+        Some(_) => String::new(),
+        None => available_username(conn, claims, user_email).await?,
+    };
+
+    let provider = provider.to_string();
+    let subject = claims.sub.clone();
+    let user_email = user_email.to_string();
+    let email_verified = claims.email_verified;
+    conn.transaction::<_, DieselError, _>(|conn| {
+        async move {
+            let (user_id, resolved) = match existing {
+                Some(user_id) => {
+                    // From now on the account signs in through the provider only
+                    diesel::update(users::table.find(user_id))
+                        .set(users::password_hash.eq(None::<String>))
+                        .execute(conn)
+                        .await?;
+                    (user_id, ResolvedIdentity::Linked)
+                }
+                None => {
+                    let user = diesel::insert_into(users::table)
+                        .values((
+                            users::id.eq(Uuid::new_v4()),
+                            users::username.eq(&username),
+                            users::password_hash.eq(None::<String>),
+                            users::email.eq(&user_email),
+                        ))
+                        .returning(User::as_returning())
+                        .get_result(conn)
+                        .await?;
+                    if email_verified {
+                        diesel::insert_into(email_verifications::table)
+                            .values((
+                                email_verifications::token.eq(generate_token()),
+                                email_verifications::user_id.eq(user.id),
+                                email_verifications::expires_at.eq(now),
+                                email_verifications::status.eq(StatusEnum::Verified),
+                            ))
+                            .execute(conn)
+                            .await?;
+                    }
+                    (user.id, ResolvedIdentity::Created(user))
+                }
+            };
+            diesel::insert_into(user_identities::table)
+                .values((
+                    user_identities::provider.eq(&provider),
+                    user_identities::subject.eq(&subject),
+                    user_identities::user_id.eq(user_id),
+                    user_identities::email.eq(&user_email),
+                ))
+                .execute(conn)
+                .await?;
+            Ok((user_id, resolved))
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(|err| DbError(err).into())
+}
+
+/// Username from the provider profile or the email, with a random suffix when it's taken
+async fn available_username(conn: &mut AsyncPgConnection, claims: &IdTokenClaims, user_email: &str) -> Result<String, DbError> {
+    let forbidden_characters = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
+    let base: String = claims
+        .preferred_username
+        .as_deref()
+        .or(claims.name.as_deref())
+        .unwrap_or_else(|| user_email.split('@').next().unwrap_or_default())
+        .chars()
+        .filter(|c| !forbidden_characters.contains(c) && !c.is_whitespace())
+        .take(MAX_USERNAME_LEN)
+        .collect();
+    let base = if base.is_empty() { "user".to_string() } else { base };
+
+    let mut candidate = base.clone();
+    loop {
+        let taken = diesel::select(diesel::dsl::exists(users::table.filter(users::username.eq(&candidate))))
+            .get_result::<bool>(conn)
+            .await
+            .map_err(DbError)?;
+        if !taken {
+            return Ok(candidate);
+        }
+        candidate = format!("{}_{:04}", base, rand::thread_rng().gen_range(0..10_000));
+    }
+}
+
+/// Same verification mail as a password registration, for emails the provider didn't verify
+async fn start_email_verification(conn: &mut AsyncPgConnection, user_id: Uuid, user_email: &str) -> Result<(), CustomError> {
+    let mail_token = generate_token();
+    diesel::insert_into(email_verifications::table)
+        .values((
+            email_verifications::token.eq(&mail_token),
+            email_verifications::user_id.eq(user_id),
+            email_verifications::expires_at.eq((Utc::now() + Duration::hours(24)).naive_utc()),
+            email_verifications::status.eq(StatusEnum::Pending),
+        ))
+        .execute(conn)
+        .await
+        .map_err(DbError)?;
+    send_email(user_email, mail_token).await
+}
@@ -2,8 +2,9 @@ use chrono::NaiveDateTime;
 use diesel::{Queryable, Selectable};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use utoipa::ToSchema;
 
-#[derive(Queryable, Deserialize, Serialize, Debug, Selectable)]
+#[derive(Queryable, Deserialize, Serialize, Debug, Selectable, ToSchema)]
 #[diesel(table_name = crate::schema::users)]
 pub struct UserResponse {
     pub id: Uuid,
@@ -21,16 +21,17 @@ async fn get_stored_credentials(
         .await
         .context("Failed to get connection from pool")?;
 
-    let row: Result<Option<Vec<(String, Uuid)>>, diesel::result::Error> = users
+    let row = users
         .filter(email.eq(user_email))
         .select((password_hash, id))
-        .load::<(String, Uuid)>(&mut conn)
+        .load::<(Option<String>, Uuid)>(&mut conn)
         .await
         .optional();
 
     let (id_user, expected_hash_password) = match row {
         Ok(Some(vec)) => {
-            if let Some((hash_password, id_user)) = vec.into_iter().next() {
+            // Accounts without a password only sign in through their identity provider
+            if let Some((Some(hash_password), id_user)) = vec.into_iter().next() {
                 (id_user, hash_password)
             } else {
                 return Err(CustomError::AuthenticationError(
@@ -55,7 +56,7 @@ async fn get_stored_credentials(
 }
 
 #[instrument(name = "Verify password", skip(expected_hash, candidate))]
-fn verify_password(expected_hash: &str, candidate: String) -> bool {
+pub fn verify_password(expected_hash: &str, candidate: String) -> bool {
     let argon2 = Argon2::default();
     let password_hashed = PasswordHash::new(expected_hash).expect("Failed to parse password hash");
 
@@ -1,11 +1,39 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "account_status"))]
+    pub struct AccountStatus;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "status_enum"))]
     pub struct StatusEnum;
 }
 
+diesel::table! {
+    data_export_parts (export_id, service) {
+        export_id -> Uuid,
+        #[max_length = 64]
+        service -> Varchar,
+        data -> Jsonb,
+        received_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    data_exports (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        requested_at -> Timestamp,
+        last_sent_at -> Timestamp,
+        completed_at -> Nullable<Timestamp>,
+        archive -> Nullable<Bytea>,
+        #[max_length = 64]
+        download_token_hash -> Nullable<Varchar>,
+        expires_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::StatusEnum;
@@ -20,19 +48,158 @@ diesel::table! {
 }
 
 diesel::table! {
+    erasure_confirmations (erasure_id, service) {
+        erasure_id -> Uuid,
+        #[max_length = 64]
+        service -> Varchar,
+        confirmed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    erasure_requests (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 16]
+        requested_by -> Varchar,
+        admin_id -> Nullable<Uuid>,
+        started_at -> Timestamp,
+        last_sent_at -> Timestamp,
+        completed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    oauth_clients (client_id) {
+        #[max_length = 64]
+        client_id -> Varchar,
+        #[max_length = 64]
+        client_secret_hash -> Nullable<Varchar>,
+        #[max_length = 100]
+        name -> Varchar,
+        redirect_uris -> Array<Text>,
+        scopes -> Array<Text>,
+        first_party -> Bool,
+        owner_id -> Nullable<Uuid>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    oauth_consents (user_id, client_id) {
+        user_id -> Uuid,
+        #[max_length = 64]
+        client_id -> Varchar,
+        scopes -> Array<Text>,
+        granted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    oauth_tokens (token_hash) {
+        #[max_length = 64]
+        token_hash -> Varchar,
+        #[max_length = 16]
+        kind -> Varchar,
+        #[max_length = 64]
+        client_id -> Varchar,
+        user_id -> Nullable<Uuid>,
+        scopes -> Array<Text>,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    user_devices (user_id, user_agent, network) {
+        user_id -> Uuid,
+        #[max_length = 255]
+        user_agent -> Varchar,
+        #[max_length = 64]
+        network -> Varchar,
+        first_seen_at -> Timestamp,
+        last_seen_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    user_identities (provider, subject) {
+        #[max_length = 64]
+        provider -> Varchar,
+        #[max_length = 255]
+        subject -> Varchar,
+        user_id -> Uuid,
+        email -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        last_login_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    user_mfa (user_id) {
+        user_id -> Uuid,
+        #[max_length = 64]
+        secret -> Varchar,
+        enabled_at -> Nullable<Timestamp>,
+        last_used_step -> Nullable<Int8>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    user_recovery_codes (user_id, code_hash) {
+        user_id -> Uuid,
+        #[max_length = 64]
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AccountStatus;
+
     users (id) {
         id -> Uuid,
         username -> Varchar,
-        password_hash -> Varchar,
+        password_hash -> Nullable<Varchar>,
         email -> Varchar,
         created_at -> Nullable<Timestamp>,
         modified_at -> Nullable<Timestamp>,
+        status -> AccountStatus,
+        status_changed_at -> Nullable<Timestamp>,
+        deletion_scheduled_at -> Nullable<Timestamp>,
     }
 }
 
+diesel::joinable!(data_export_parts -> data_exports (export_id));
+diesel::joinable!(data_exports -> users (user_id));
 diesel::joinable!(email_verifications -> users (user_id));
+diesel::joinable!(erasure_confirmations -> erasure_requests (erasure_id));
+diesel::joinable!(erasure_requests -> users (user_id));
+diesel::joinable!(oauth_clients -> users (owner_id));
+diesel::joinable!(oauth_consents -> oauth_clients (client_id));
+diesel::joinable!(oauth_consents -> users (user_id));
+diesel::joinable!(oauth_tokens -> oauth_clients (client_id));
+diesel::joinable!(oauth_tokens -> users (user_id));
+diesel::joinable!(user_devices -> users (user_id));
+diesel::joinable!(user_identities -> users (user_id));
+diesel::joinable!(user_mfa -> users (user_id));
+diesel::joinable!(user_recovery_codes -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    data_export_parts,
+    data_exports,
     email_verifications,
+    erasure_confirmations,
+    erasure_requests,
+    oauth_clients,
+    oauth_consents,
+    oauth_tokens,
+    user_devices,
+    user_identities,
+    user_mfa,
+    user_recovery_codes,
     users,
 );
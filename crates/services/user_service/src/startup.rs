@@ -1,16 +1,27 @@
 use helpers::auth_jwt::auth::Role;
 use kafka::{channel::KafkaMessage, setup::{setup_kafka_receiver, setup_kafka_sender}};
-use lib_config::{config::configuration::Settings, db::db::PgPool};
+use helpers::oidc::IdTokenSigner;
+use lib_config::{config::configuration::{AccountSettings, AuthSettings, OAuthSettings, Settings}, db::db::PgPool};
 // use crate::middleware::jwt_auth_middleware;
-use crate::{kafka_handler::process_kafka_message, routes::{
+use crate::{erasure::run_erasure_sweeper, export::run_export_sweeper, kafka_handler::process_kafka_message, routes::{
     health_check::health_check,
-    user::crud::{login_user, logout_user, register_user, view_user, update_user, verify_email, resend_verification_email}
+    openapi::openapi_spec,
+    user::account::{deactivate_account, request_deletion},
+    user::export::{download_export, request_export},
+    user::crud::{login_user, logout_user, register_user, view_user, update_user, verify_email, resend_verification_email},
+    user::mfa::{disable_mfa, enroll_mfa, login_mfa, verify_mfa},
+    user::oidc::{oidc_authorize, oidc_callback},
+    oauth::authorize::{authorize, decide_consent},
+    oauth::clients::{create_client, delete_client, list_clients, list_consents, revoke_consent},
+    oauth::discovery::{jwks, openid_configuration},
+    oauth::token::{introspect, revoke, token, userinfo}
 }};
 use actix_web::{dev::Server, web, App, HttpServer};
 use actix_web_lab::middleware::from_fn;
 use middleware::jwt::{jwt_auth_middleware, RoleRestrictor};
 use std::net::TcpListener;
 use tracing_actix_web::TracingLogger;
+use utils::telemetry::RequestIdRootSpanBuilder;
 
 use lib_config::session::redis::RedisService;
 
@@ -43,12 +54,41 @@ impl Application {
             &consumer_group
         ).await;
 
+        let redis_service = RedisService::new(config.redis.uri.clone()).await;
         let pool_clone = pool.clone();
+        let redis_clone = redis_service.clone();
+        let tx_clone = tx.clone();
+        let account_settings = config.account.clone();
         tokio::spawn(async move {
-            process_kafka_message(rx, pool_clone).await;
+            process_kafka_message(rx, pool_clone, redis_clone, tx_clone, account_settings).await;
         });
 
-        let server = run_server(listener, pool.clone(), config.redis.uri.clone(), tx).await?;
+        // Erases accounts at the end of their grace period and retries unconfirmed erasures
+        let pool_clone = pool.clone();
+        let redis_clone = redis_service.clone();
+        let tx_clone = tx.clone();
+        let account_settings = config.account.clone();
+        tokio::spawn(async move {
+            run_erasure_sweeper(pool_clone, redis_clone, tx_clone, account_settings).await;
+        });
+
+        // Resends unanswered data exports and drops expired archives
+        let pool_clone = pool.clone();
+        let tx_clone = tx.clone();
+        let account_settings = config.account.clone();
+        tokio::spawn(async move {
+            run_export_sweeper(pool_clone, tx_clone, account_settings).await;
+        });
+
+        let server = run_server(
+            listener,
+            pool.clone(),
+            redis_service,
+            tx,
+            config.auth.clone(),
+            config.oauth.clone(),
+            config.account.clone()
+        ).await?;
         Ok(Self {
             port: actual_port,
             server,
@@ -68,26 +108,55 @@ impl Application {
 pub async fn run_server(
     listener: TcpListener,
     pool: PgPool,
-    redis_uri: String,
-    kafka_sender: Sender<KafkaMessage<String>>
+    redis_service: RedisService,
+    kafka_sender: Sender<KafkaMessage<String>>,
+    auth_settings: AuthSettings,
+    oauth_settings: OAuthSettings,
+    account_settings: AccountSettings
 ) -> Result<Server, std::io::Error> {
 
-    let redis_service = RedisService::new(redis_uri).await;
+    let signer = match &oauth_settings.signing_key {
+        Some(key) => IdTokenSigner::from_pkcs8_base64(key)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+        None => {
+            tracing::warn!("oauth.signing_key is not set, ID tokens are signed with a key that changes on restart");
+            IdTokenSigner::generate()
+        }
+    };
+    let signer = web::Data::new(signer);
 
     let server = HttpServer::new(move || {
         App::new()
-            .wrap(TracingLogger::default())
+            .wrap(TracingLogger::<RequestIdRootSpanBuilder>::new())
             .app_data(web::Data::new(redis_service.clone()))
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(kafka_sender.clone()))
+            .app_data(web::Data::new(auth_settings.clone()))
+            .app_data(web::Data::new(oauth_settings.clone()))
+            .app_data(web::Data::new(account_settings.clone()))
+            .app_data(signer.clone())
             .route("/health_check", web::get().to(health_check))
+            .route("/.well-known/openid-configuration", web::get().to(openid_configuration))
+            .route("/api-docs/openapi.json", web::get().to(openapi_spec))
             .service(
                 web::scope("/api/v1")
                 .service(
                     web::scope("/users")
                                 .route("/register", web::post().to(register_user))
                                 .route("/login", web::post().to(login_user))
+                                .route("/login/2fa", web::post().to(login_mfa))
+                                .route("/oidc/{provider}/authorize", web::get().to(oidc_authorize))
+                                .route("/oidc/{provider}/callback", web::get().to(oidc_callback))
                                 .route("/verify-email", web::get().to(verify_email))
+                                .route("/exports/{export_id}", web::get().to(download_export))
+                )
+                .service(
+                    web::scope("/oauth")
+                                .route("/token", web::post().to(token))
+                                .route("/introspect", web::post().to(introspect))
+                                .route("/revoke", web::post().to(revoke))
+                                .route("/userinfo", web::get().to(userinfo))
+                                .route("/jwks", web::get().to(jwks))
                 )
                 .service(
                     web::scope("/user/protected")
@@ -96,6 +165,19 @@ pub async fn run_server(
                     .route("/logout", web::post().to(logout_user))
                     .route("/update", web::post().to(update_user))
                     .route("/resend-verification", web::post().to(resend_verification_email))
+                    .route("/2fa/enroll", web::post().to(enroll_mfa))
+                    .route("/2fa/verify", web::post().to(verify_mfa))
+                    .route("/2fa/disable", web::post().to(disable_mfa))
+                    .route("/deactivate", web::post().to(deactivate_account))
+                    .route("/delete", web::post().to(request_deletion))
+                    .route("/export", web::post().to(request_export))
+                    .route("/oauth/authorize", web::get().to(authorize))
+                    .route("/oauth/authorize", web::post().to(decide_consent))
+                    .route("/oauth/clients", web::post().to(create_client))
+                    .route("/oauth/clients", web::get().to(list_clients))
+                    .route("/oauth/clients/{client_id}", web::delete().to(delete_client))
+                    .route("/oauth/consents", web::get().to(list_consents))
+                    .route("/oauth/consents/{client_id}", web::delete().to(revoke_consent))
                 )
             )       
     })